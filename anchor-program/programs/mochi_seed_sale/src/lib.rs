@@ -1,5 +1,10 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token::{self, InitializeAccount3, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+};
+use anchor_spl::token_interface::{self, TokenInterface, TransferChecked};
 
 // Program ID
 declare_id!("2mt9FhkfhrkC5RL29MVPfMGVzpFR3eupGCMqKVYssiue");
@@ -22,6 +27,7 @@ pub mod mochi_seed_sale {
         price_tokens_per_sol: u64,
         token_cap: u64,
         sol_cap_lamports: u64,
+        absorb_fee: bool,
     ) -> Result<()> {
         require!(end_ts > start_ts, SeedError::InvalidWindow);
         let sale = &mut ctx.accounts.sale;
@@ -41,17 +47,110 @@ pub mod mochi_seed_sale {
         sale.bump = ctx.bumps.sale;
         sale.vault_bump = ctx.bumps.vault_authority;
         sale.vault_token_bump = ctx.bumps.seed_vault;
+        sale.absorb_fee = absorb_fee;
+        sale.transfer_fees = 0;
+        sale.payment_mint = None;
+        sale.token_treasury = Pubkey::default();
+        sale.price_tokens_per_stable = 0;
+        sale.raised_stable = 0;
+        sale.funded = false;
         Ok(())
     }
 
-    pub fn contribute(ctx: Context<Contribute>, lamports: u64) -> Result<()> {
+    /// Opens the sale to contributions once `seed_vault` holds enough tokens to cover
+    /// `token_cap` (or any tokens at all for an uncapped sale). Also grows legacy sales.
+    pub fn mark_funded(ctx: Context<MarkFunded>) -> Result<()> {
+        let sale_info = ctx.accounts.sale.to_account_info();
+        let mut sale = load_sale_for_update(
+            &ctx.accounts.authority.to_account_info(),
+            &sale_info,
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.program_id,
+        )?;
+        require_keys_eq!(
+            ctx.accounts.seed_vault.key(),
+            sale.seed_vault,
+            SeedError::MintMismatch
+        );
+        let balance = ctx.accounts.seed_vault.amount;
+        require!(
+            balance > 0 && balance >= sale.token_cap,
+            SeedError::NotFunded
+        );
+        sale.funded = true;
+        let mut data = sale_info.try_borrow_mut_data()?;
+        let mut cursor = std::io::Cursor::new(&mut data[..]);
+        sale.try_serialize(&mut cursor)?;
+        Ok(())
+    }
+
+    /// Choose who bears a Token-2022 transfer fee on claim. Also grows sales created
+    /// before the fee fields existed.
+    pub fn set_absorb_fee(ctx: Context<SetAbsorbFee>, absorb_fee: bool) -> Result<()> {
+        let sale_info = ctx.accounts.sale.to_account_info();
+        let mut sale = load_sale_for_update(
+            &ctx.accounts.authority.to_account_info(),
+            &sale_info,
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.program_id,
+        )?;
+        sale.absorb_fee = absorb_fee;
+        let mut data = sale_info.try_borrow_mut_data()?;
+        let mut cursor = std::io::Cursor::new(&mut data[..]);
+        sale.try_serialize(&mut cursor)?;
+        Ok(())
+    }
+
+    /// Accept `payment_mint` (e.g. USDC) alongside SOL, paid into `token_treasury` and
+    /// credited at `price_tokens_per_stable`. `None` turns stablecoin contributions off.
+    pub fn set_payment_mint(
+        ctx: Context<SetPaymentMint>,
+        payment_mint: Option<Pubkey>,
+        token_treasury: Pubkey,
+        price_tokens_per_stable: u64,
+    ) -> Result<()> {
+        let sale_info = ctx.accounts.sale.to_account_info();
+        let mut sale = load_sale_for_update(
+            &ctx.accounts.authority.to_account_info(),
+            &sale_info,
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.program_id,
+        )?;
+        if payment_mint.is_some() {
+            require!(price_tokens_per_stable > 0, SeedError::InvalidPrice);
+        }
+        sale.payment_mint = payment_mint;
+        sale.token_treasury = token_treasury;
+        sale.price_tokens_per_stable = price_tokens_per_stable;
+        let mut data = sale_info.try_borrow_mut_data()?;
+        let mut cursor = std::io::Cursor::new(&mut data[..]);
+        sale.try_serialize(&mut cursor)?;
+        Ok(())
+    }
+
+    /// With `partial_fill`, a contribution straddling a cap is trimmed to what still fits;
+    /// only the trimmed amount leaves the buyer, the rest stays in their wallet.
+    pub fn contribute(ctx: Context<Contribute>, lamports: u64, partial_fill: bool) -> Result<()> {
         let clock = Clock::get()?;
-        let sale = &mut ctx.accounts.sale;
+        let sale_info = ctx.accounts.sale.to_account_info();
+        let mut sale = load_sale(&sale_info)?;
         require!(!sale.is_canceled, SeedError::Canceled);
+        require!(sale.funded, SeedError::NotFunded);
         require!(clock.unix_timestamp >= sale.start_ts, SeedError::NotStarted);
         require!(clock.unix_timestamp <= sale.end_ts, SeedError::Ended);
         require!(lamports > 0, SeedError::InvalidContribution);
 
+        let lamports = if partial_fill {
+            let fitted = fit_to_caps(&sale, lamports)?;
+            require!(fitted > 0, SeedError::CapReached);
+            if fitted < lamports {
+                msg!("partial fill: accepted {} of {} lamports", fitted, lamports);
+            }
+            fitted
+        } else {
+            lamports
+        };
+
         let potential_raise = sale
             .raised_lamports
             .checked_add(lamports)
@@ -89,7 +188,7 @@ pub mod mochi_seed_sale {
         )?;
 
         let contrib = &mut ctx.accounts.contribution;
-        contrib.sale = sale.key();
+        contrib.sale = sale_info.key();
         contrib.buyer = ctx.accounts.buyer.key();
         contrib.contributed_lamports = contrib
             .contributed_lamports
@@ -100,27 +199,107 @@ pub mod mochi_seed_sale {
             .checked_add(tokens_owed)
             .ok_or(SeedError::Overflow)?;
         contrib.claimed = false;
+        contrib.bump = ctx.bumps.contribution;
         sale.raised_lamports = potential_raise;
         sale.sold_tokens = potential_sold;
+        store_sale(
+            &sale,
+            &sale_info,
+            &ctx.accounts.buyer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    pub fn contribute_token(ctx: Context<ContributeToken>, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let sale = &mut ctx.accounts.sale;
+        require!(!sale.is_canceled, SeedError::Canceled);
+        require!(sale.funded, SeedError::NotFunded);
+        require!(clock.unix_timestamp >= sale.start_ts, SeedError::NotStarted);
+        require!(clock.unix_timestamp <= sale.end_ts, SeedError::Ended);
+        require!(amount > 0, SeedError::InvalidContribution);
+
+        let mint = sale.payment_mint.ok_or(SeedError::CurrencyNotAccepted)?;
+        let buyer_token = &ctx.accounts.buyer_token;
+        let treasury_token = &ctx.accounts.treasury_token;
+        require_keys_eq!(buyer_token.mint, mint, SeedError::MintMismatch);
+        require_keys_eq!(
+            buyer_token.owner,
+            ctx.accounts.buyer.key(),
+            SeedError::Unauthorized
+        );
+        require_keys_eq!(
+            treasury_token.key(),
+            sale.token_treasury,
+            SeedError::TreasuryMismatch
+        );
+        require_keys_eq!(treasury_token.mint, mint, SeedError::MintMismatch);
+
+        let tokens_owed = amount
+            .checked_mul(sale.price_tokens_per_stable)
+            .ok_or(SeedError::Overflow)?;
+        let potential_sold = sale
+            .sold_tokens
+            .checked_add(tokens_owed)
+            .ok_or(SeedError::Overflow)?;
+        if sale.token_cap > 0 {
+            require!(potential_sold <= sale.token_cap, SeedError::CapReached);
+        }
+
+        let cpi_accounts = Transfer {
+            from: buyer_token.to_account_info(),
+            to: treasury_token.to_account_info(),
+            authority: ctx.accounts.buyer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        let contrib = &mut ctx.accounts.contribution;
+        contrib.sale = sale.key();
+        contrib.buyer = ctx.accounts.buyer.key();
+        contrib.tokens_owed = contrib
+            .tokens_owed
+            .checked_add(tokens_owed)
+            .ok_or(SeedError::Overflow)?;
+        contrib.claimed = false;
+        contrib.bump = ctx.bumps.contribution;
+        sale.raised_stable = sale
+            .raised_stable
+            .checked_add(amount)
+            .ok_or(SeedError::Overflow)?;
+        sale.sold_tokens = potential_sold;
         Ok(())
     }
 
     pub fn claim(ctx: Context<Claim>) -> Result<()> {
         let clock = Clock::get()?;
-        let sale = &mut ctx.accounts.sale;
+        let sale_info = ctx.accounts.sale.to_account_info();
+        let mut sale = load_sale(&sale_info)?;
+        require_keys_eq!(ctx.accounts.mint.key(), sale.mint, SeedError::MintMismatch);
         require!(!sale.is_canceled, SeedError::Canceled);
         require!(clock.unix_timestamp > sale.end_ts, SeedError::NotEnded);
 
         let contrib = &mut ctx.accounts.contribution;
         require!(!contrib.claimed, SeedError::AlreadyClaimed);
-        let amount = contrib.tokens_owed;
-        require!(amount > 0, SeedError::NothingToClaim);
+        let owed = contrib.tokens_owed;
+        require!(owed > 0, SeedError::NothingToClaim);
+
+        // With absorb_fee the vault grosses up so the buyer nets `owed`; otherwise the
+        // buyer eats the fee. Either way the fee is tracked outside sold_tokens.
+        let mint_info = ctx.accounts.mint.to_account_info();
+        let fee = transfer_fee(&mint_info, owed, sale.absorb_fee)?;
+        let amount = if sale.absorb_fee {
+            owed.checked_add(fee).ok_or(SeedError::Overflow)?
+        } else {
+            owed
+        };
 
-        let sale_key = sale.key();
+        let sale_key = sale_info.key();
         let seeds = &[VAULT_AUTH_SEED, sale_key.as_ref(), &[sale.vault_bump]];
         let signer = &[&seeds[..]];
-        let cpi_accounts = Transfer {
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.seed_vault.to_account_info(),
+            mint: mint_info,
             to: ctx.accounts.user_ata.to_account_info(),
             authority: ctx.accounts.vault_authority.to_account_info(),
         };
@@ -129,9 +308,56 @@ pub mod mochi_seed_sale {
             cpi_accounts,
             signer,
         );
-        token::transfer(cpi_ctx, amount)?;
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
 
+        sale.transfer_fees = sale
+            .transfer_fees
+            .checked_add(fee)
+            .ok_or(SeedError::Overflow)?;
         contrib.claimed = true;
+        store_sale(
+            &sale,
+            &sale_info,
+            &ctx.accounts.buyer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    /// Read-only: hands a buyer's entitlement back as return data so a wallet can check it
+    /// without trusting our UI. `expected_tokens_at_current_price` only reprices the SOL
+    /// leg; stable contributions are folded into `tokens_owed` but not tracked separately.
+    pub fn verify_contribution(ctx: Context<VerifyContribution>) -> Result<ContributionSummary> {
+        let sale = load_sale(&ctx.accounts.sale)?;
+        let contrib = &ctx.accounts.contribution;
+        let expected_tokens_at_current_price = contrib
+            .contributed_lamports
+            .checked_mul(sale.price_tokens_per_sol)
+            .ok_or(SeedError::Overflow)?;
+        Ok(ContributionSummary {
+            contributed_lamports: contrib.contributed_lamports,
+            tokens_owed: contrib.tokens_owed,
+            claimed: contrib.claimed,
+            expected_tokens_at_current_price,
+        })
+    }
+
+    /// Permissionless repair for existing Contribution PDAs: grows them to the current
+    /// `Contribution::LEN` (payer funds the rent delta; new fields read as defaults) and
+    /// records the canonical bump, which `contribute` did not always store.
+    pub fn migrate_contribution(ctx: Context<MigrateContribution>) -> Result<()> {
+        let contrib_info = ctx.accounts.contribution.to_account_info();
+        grow_account(
+            &ctx.accounts.payer.to_account_info(),
+            &contrib_info,
+            8 + Contribution::LEN,
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.program_id,
+        )?;
+        let mut contrib = Contribution::try_deserialize(&mut &contrib_info.data.borrow()[..])?;
+        contrib.bump = ctx.bumps.contribution;
+        let mut data = contrib_info.try_borrow_mut_data()?;
+        let mut cursor = std::io::Cursor::new(&mut data[..]);
+        contrib.try_serialize(&mut cursor)?;
         Ok(())
     }
 
@@ -145,14 +371,66 @@ pub mod mochi_seed_sale {
         Ok(())
     }
 
+    /// Push out the close of a live sale; an ended or canceled sale stays closed.
+    pub fn extend_sale(ctx: Context<ExtendSale>, new_end_ts: i64) -> Result<()> {
+        let clock = Clock::get()?;
+        let sale = &mut ctx.accounts.sale;
+        require!(
+            ctx.accounts.authority.key() == sale.authority,
+            SeedError::Unauthorized
+        );
+        require!(!sale.is_canceled, SeedError::Canceled);
+        require!(clock.unix_timestamp < sale.end_ts, SeedError::Ended);
+        require!(new_end_ts > sale.end_ts, SeedError::InvalidWindow);
+        let old_end_ts = sale.end_ts;
+        sale.end_ts = new_end_ts;
+        emit!(SaleExtended {
+            sale: sale.key(),
+            old_end_ts,
+            new_end_ts,
+        });
+        Ok(())
+    }
+
+    /// Reprice a sale that has not opened yet; once `start_ts` passes the price is fixed.
+    pub fn update_sale_price(ctx: Context<UpdateSalePrice>, new_price: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let sale = &mut ctx.accounts.sale;
+        require!(
+            ctx.accounts.authority.key() == sale.authority,
+            SeedError::Unauthorized
+        );
+        require!(!sale.is_canceled, SeedError::Canceled);
+        require!(
+            clock.unix_timestamp < sale.start_ts,
+            SeedError::AlreadyStarted
+        );
+        require!(new_price > 0, SeedError::InvalidPrice);
+        let old_price = sale.price_tokens_per_sol;
+        sale.price_tokens_per_sol = new_price;
+        emit!(PriceUpdated {
+            sale: sale.key(),
+            old_price,
+            new_price,
+        });
+        Ok(())
+    }
+
     pub fn init_vesting(
         ctx: Context<InitVesting>,
         start_ts: i64,
         cliff_ts: i64,
         end_ts: i64,
         total_amount: u64,
+        curve: VestingCurve,
+        cliff_unlock_bps: u16,
     ) -> Result<()> {
         require!(start_ts < end_ts, SeedError::InvalidWindow);
+        require!(cliff_unlock_bps <= 10_000, SeedError::InvalidBps);
+        require!(
+            cliff_unlock_bps == 0 || cliff_ts < end_ts,
+            SeedError::InvalidWindow
+        );
         let vest = &mut ctx.accounts.vesting;
         vest.authority = ctx.accounts.authority.key();
         vest.beneficiary = ctx.accounts.beneficiary.key();
@@ -166,12 +444,220 @@ pub mod mochi_seed_sale {
         vest.bump = ctx.bumps.vesting;
         vest.vault_bump = ctx.bumps.vest_vault_authority;
         vest.vault_token_bump = ctx.bumps.vest_vault;
+        vest.max_claim_per_tx = 0;
+        vest.curve = curve;
+        vest.min_claim_interval_seconds = 0;
+        vest.last_claim_ts = 0;
+        vest.cliff_unlock_bps = cliff_unlock_bps;
+        Ok(())
+    }
+
+    /// Backs a schedule with tokens: moves `amount` from the authority's token account into
+    /// the vest vault that `init_vesting` created empty. May be called repeatedly.
+    pub fn fund_vesting(ctx: Context<FundVesting>, amount: u64) -> Result<()> {
+        require!(amount > 0, SeedError::InvalidContribution);
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.authority_ata.to_account_info(),
+            to: ctx.accounts.vest_vault.to_account_info(),
+            authority: ctx.accounts.authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
         Ok(())
     }
 
+    /// Authority-only restructuring: carves `splits` out of the grant's unvested remainder
+    /// into new Vesting accounts with the same mint, schedule, curve and claim limits, moving
+    /// the tokens out of the original vest vault. Vesting is linear in `total_amount`, so the
+    /// combined vested amount is unchanged (up to rounding).
+    /// remaining_accounts, per split: [beneficiary, vesting PDA, vest_vault PDA], all unused.
+    pub fn split_vesting<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SplitVesting<'info>>,
+        splits: Vec<SplitAllocation>,
+    ) -> Result<()> {
+        require!(
+            !splits.is_empty() && ctx.remaining_accounts.len() == splits.len() * 3,
+            SeedError::InvalidSplit
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let vest = &mut ctx.accounts.vesting;
+        let mut split_total: u64 = 0;
+        for split in splits.iter() {
+            require!(split.amount > 0, SeedError::InvalidSplit);
+            split_total = split_total
+                .checked_add(split.amount)
+                .ok_or(SeedError::Overflow)?;
+        }
+        let unvested = vest
+            .total_amount
+            .checked_sub(vested_amount(vest, now)?)
+            .ok_or(SeedError::Overflow)?;
+        require!(split_total <= unvested, SeedError::InvalidSplit);
+        require!(
+            ctx.accounts.vest_vault.amount >= split_total,
+            SeedError::VaultUnderfunded
+        );
+        vest.total_amount -= split_total;
+        // The shrunken grant must still cover what its beneficiary already took.
+        require!(
+            vested_amount(vest, now)? >= vest.claimed_amount,
+            SeedError::InvalidSplit
+        );
+
+        let payer = ctx.accounts.authority.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let source_seeds = &[VESTING_SEED, vest.beneficiary.as_ref(), &[vest.bump]];
+        let source_signer = &[&source_seeds[..]];
+        for (split, group) in splits.iter().zip(ctx.remaining_accounts.chunks(3)) {
+            let (beneficiary, vesting_info, vault_info) = (&group[0], &group[1], &group[2]);
+            require_keys_eq!(
+                beneficiary.key(),
+                split.beneficiary,
+                SeedError::InvalidSplit
+            );
+            let (vesting_key, bump) = Pubkey::find_program_address(
+                &[VESTING_SEED, split.beneficiary.as_ref()],
+                ctx.program_id,
+            );
+            let (vault_key, vault_token_bump) = Pubkey::find_program_address(
+                &[VEST_VAULT_TOKEN_SEED, split.beneficiary.as_ref()],
+                ctx.program_id,
+            );
+            require_keys_eq!(vesting_info.key(), vesting_key, SeedError::InvalidSplit);
+            require_keys_eq!(vault_info.key(), vault_key, SeedError::InvalidSplit);
+
+            create_pda_account(
+                &payer,
+                vesting_info,
+                8 + Vesting::LEN,
+                ctx.program_id,
+                &[VESTING_SEED, split.beneficiary.as_ref(), &[bump]],
+                &system_program,
+            )?;
+            create_pda_account(
+                &payer,
+                vault_info,
+                TokenAccount::LEN,
+                &token::ID,
+                &[
+                    VEST_VAULT_TOKEN_SEED,
+                    split.beneficiary.as_ref(),
+                    &[vault_token_bump],
+                ],
+                &system_program,
+            )?;
+            token::initialize_account3(CpiContext::new(
+                token_program.clone(),
+                InitializeAccount3 {
+                    account: vault_info.clone(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    authority: vesting_info.clone(),
+                },
+            ))?;
+
+            let new_vest = Vesting {
+                authority: vest.authority,
+                beneficiary: split.beneficiary,
+                mint: vest.mint,
+                vault: vault_key,
+                start_ts: vest.start_ts,
+                cliff_ts: vest.cliff_ts,
+                end_ts: vest.end_ts,
+                total_amount: split.amount,
+                claimed_amount: 0,
+                bump,
+                vault_bump: bump,
+                vault_token_bump,
+                max_claim_per_tx: vest.max_claim_per_tx,
+                curve: vest.curve,
+                min_claim_interval_seconds: vest.min_claim_interval_seconds,
+                last_claim_ts: 0,
+                cliff_unlock_bps: vest.cliff_unlock_bps,
+            };
+            {
+                let mut data = vesting_info.try_borrow_mut_data()?;
+                let mut cursor = std::io::Cursor::new(&mut data[..]);
+                new_vest.try_serialize(&mut cursor)?;
+            }
+
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.vest_vault.to_account_info(),
+                to: vault_info.clone(),
+                authority: ctx.accounts.vest_vault_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new_with_signer(token_program.clone(), cpi_accounts, source_signer),
+                split.amount,
+            )?;
+            emit!(VestingSplit {
+                source: vest.key(),
+                beneficiary: split.beneficiary,
+                amount: split.amount,
+            });
+        }
+        Ok(())
+    }
+
+    /// Cap each `claim_vesting` transfer so large unlocks go out in chunks; 0 is
+    /// unlimited. Also grows vestings created before the cap existed.
+    pub fn set_max_claim_per_tx(
+        ctx: Context<SetMaxClaimPerTx>,
+        max_claim_per_tx: u64,
+    ) -> Result<()> {
+        let vest_info = ctx.accounts.vesting.to_account_info();
+        grow_account(
+            &ctx.accounts.authority.to_account_info(),
+            &vest_info,
+            8 + Vesting::LEN,
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.program_id,
+        )?;
+        let mut vest = Vesting::try_deserialize(&mut &vest_info.data.borrow()[..])?;
+        require!(
+            ctx.accounts.authority.key() == vest.authority,
+            SeedError::Unauthorized
+        );
+        vest.max_claim_per_tx = max_claim_per_tx;
+        let mut data = vest_info.try_borrow_mut_data()?;
+        let mut cursor = std::io::Cursor::new(&mut data[..]);
+        vest.try_serialize(&mut cursor)?;
+        Ok(())
+    }
+
+    /// Minimum seconds between `claim_vesting` calls; 0 disables the throttle. Also
+    /// grows vestings created before the throttle existed.
+    pub fn set_min_claim_interval(
+        ctx: Context<SetMinClaimInterval>,
+        min_claim_interval_seconds: i64,
+    ) -> Result<()> {
+        require!(min_claim_interval_seconds >= 0, SeedError::InvalidWindow);
+        let vest_info = ctx.accounts.vesting.to_account_info();
+        grow_account(
+            &ctx.accounts.authority.to_account_info(),
+            &vest_info,
+            8 + Vesting::LEN,
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.program_id,
+        )?;
+        let mut vest = Vesting::try_deserialize(&mut &vest_info.data.borrow()[..])?;
+        require!(
+            ctx.accounts.authority.key() == vest.authority,
+            SeedError::Unauthorized
+        );
+        vest.min_claim_interval_seconds = min_claim_interval_seconds;
+        let mut data = vest_info.try_borrow_mut_data()?;
+        let mut cursor = std::io::Cursor::new(&mut data[..]);
+        vest.try_serialize(&mut cursor)?;
+        Ok(())
+    }
+
+    /// Vestings created before the latest fields are grown (rent paid by the beneficiary)
+    /// and read with those fields zeroed.
     pub fn claim_vesting(ctx: Context<ClaimVesting>) -> Result<()> {
         let clock = Clock::get()?;
-        let vest = &mut ctx.accounts.vesting;
+        let vest_info = ctx.accounts.vesting.to_account_info();
+        let vest = &mut load_vesting(&vest_info)?;
         require!(
             clock.unix_timestamp >= vest.cliff_ts,
             SeedError::CliffNotReached
@@ -180,12 +666,30 @@ pub mod mochi_seed_sale {
             vest.total_amount > vest.claimed_amount,
             SeedError::NothingToClaim
         );
+        if vest.min_claim_interval_seconds > 0 && vest.last_claim_ts > 0 {
+            let next_claim_ts = vest
+                .last_claim_ts
+                .checked_add(vest.min_claim_interval_seconds)
+                .ok_or(SeedError::Overflow)?;
+            require!(
+                clock.unix_timestamp >= next_claim_ts,
+                SeedError::ClaimTooSoon
+            );
+        }
 
         let vested = vested_amount(vest, clock.unix_timestamp)?;
-        let claimable = vested
+        let mut claimable = vested
             .checked_sub(vest.claimed_amount)
             .ok_or(SeedError::Overflow)?;
         require!(claimable > 0, SeedError::NothingToClaim);
+        // Anything above the cap stays vested-but-unclaimed for the next call.
+        if vest.max_claim_per_tx > 0 {
+            claimable = claimable.min(vest.max_claim_per_tx);
+        }
+        require!(
+            ctx.accounts.vest_vault.amount >= claimable,
+            SeedError::VaultUnderfunded
+        );
 
         let seeds = &[VESTING_SEED, vest.beneficiary.as_ref(), &[vest.bump]];
         let signer = &[&seeds[..]];
@@ -205,32 +709,339 @@ pub mod mochi_seed_sale {
             .claimed_amount
             .checked_add(claimable)
             .ok_or(SeedError::Overflow)?;
-        Ok(())
+        vest.last_claim_ts = clock.unix_timestamp;
+        store_vesting(
+            vest,
+            &vest_info,
+            &ctx.accounts.beneficiary.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    /// Read-only dashboard view of a vesting as return data. `claimable_now` is what
+    /// `claim_vesting` would transfer right now (0 before the cliff or while throttled).
+    /// The next unlock is the cliff before it is reached, then `end_ts` while the curve
+    /// runs (curves release continuously), and 0 once everything has vested.
+    pub fn vesting_details(ctx: Context<ViewVesting>) -> Result<VestingDetails> {
+        let now = Clock::get()?.unix_timestamp;
+        let vest = &load_vesting(&ctx.accounts.vesting)?;
+        let vested_now = vested_amount(vest, now)?;
+        let unclaimed = vested_now.saturating_sub(vest.claimed_amount);
+        let throttled = vest.min_claim_interval_seconds > 0
+            && vest.last_claim_ts > 0
+            && now
+                < vest
+                    .last_claim_ts
+                    .saturating_add(vest.min_claim_interval_seconds);
+        let claimable_now = if now < vest.cliff_ts || throttled {
+            0
+        } else if vest.max_claim_per_tx > 0 {
+            unclaimed.min(vest.max_claim_per_tx)
+        } else {
+            unclaimed
+        };
+        let next_unlock_ts = if now < vest.cliff_ts {
+            vest.cliff_ts
+        } else if now < vest.end_ts {
+            vest.end_ts
+        } else {
+            0
+        };
+        let next_unlock_amount = if next_unlock_ts == 0 {
+            0
+        } else {
+            vested_amount(vest, next_unlock_ts)?.saturating_sub(vested_now)
+        };
+        Ok(VestingDetails {
+            vested_now,
+            claimable_now,
+            total: vest.total_amount,
+            next_unlock_ts,
+            next_unlock_amount,
+        })
+    }
+
+    /// End-of-schedule sweep: once `end_ts` has passed, transfers everything still unclaimed
+    /// in one call, bypassing the curve math and the per-tx cap.
+    pub fn claim_all_vesting(ctx: Context<ClaimVesting>) -> Result<()> {
+        let clock = Clock::get()?;
+        let vest_info = ctx.accounts.vesting.to_account_info();
+        let vest = &mut load_vesting(&vest_info)?;
+        require!(
+            clock.unix_timestamp >= vest.end_ts,
+            SeedError::VestingNotComplete
+        );
+        let remaining = vest
+            .total_amount
+            .checked_sub(vest.claimed_amount)
+            .ok_or(SeedError::Overflow)?;
+        require!(remaining > 0, SeedError::NothingToClaim);
+        require!(
+            ctx.accounts.vest_vault.amount >= remaining,
+            SeedError::VaultUnderfunded
+        );
+
+        let seeds = &[VESTING_SEED, vest.beneficiary.as_ref(), &[vest.bump]];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vest_vault.to_account_info(),
+            to: ctx.accounts.beneficiary_ata.to_account_info(),
+            authority: ctx.accounts.vest_vault_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        token::transfer(cpi_ctx, remaining)?;
+
+        vest.claimed_amount = vest.total_amount;
+        vest.last_claim_ts = clock.unix_timestamp;
+        store_vesting(
+            vest,
+            &vest_info,
+            &ctx.accounts.beneficiary.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
     }
 }
 
-fn vested_amount(vest: &Vesting, now: i64) -> Result<u64> {
-    if now <= vest.start_ts {
+/// Transfer fee charged by a Token-2022 mint for `amount`; zero for classic SPL mints.
+/// With `gross_up`, `amount` is what the recipient must net rather than what is sent.
+fn transfer_fee(mint: &AccountInfo, amount: u64, gross_up: bool) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
         return Ok(0);
     }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let Ok(config) = state.get_extension::<TransferFeeConfig>() else {
+        return Ok(0);
+    };
+    let epoch = Clock::get()?.epoch;
+    let fee = if gross_up {
+        config.calculate_inverse_epoch_fee(epoch, amount)
+    } else {
+        config.calculate_epoch_fee(epoch, amount)
+    };
+    Ok(fee.ok_or(SeedError::Overflow)?)
+}
+
+/// Grow a program account created before its latest fields to `target_len`,
+/// topping up rent from `payer`. New bytes are zeroed.
+fn grow_account<'info>(
+    payer: &AccountInfo<'info>,
+    target: &AccountInfo<'info>,
+    target_len: usize,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<()> {
+    require!(target.owner == program_id, SeedError::Unauthorized);
+    if target.data_len() >= target_len {
+        return Ok(());
+    }
+    let required_lamports = Rent::get()?.minimum_balance(target_len);
+    if target.lamports() < required_lamports {
+        let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
+            payer.key,
+            target.key,
+            required_lamports - target.lamports(),
+        );
+        anchor_lang::solana_program::program::invoke(
+            &transfer_ix,
+            &[payer.clone(), target.clone(), system_program.clone()],
+        )?;
+    }
+    target.realloc(target_len, true)?;
+    Ok(())
+}
+
+/// Reads a Vesting, zero-filling fields an account created before them doesn't have yet.
+fn load_vesting(vest_info: &AccountInfo) -> Result<Vesting> {
+    require!(vest_info.owner == &crate::ID, SeedError::Unauthorized);
+    let mut data = vest_info.try_borrow_data()?.to_vec();
+    if data.len() < 8 + Vesting::LEN {
+        data.resize(8 + Vesting::LEN, 0);
+    }
+    Vesting::try_deserialize(&mut &data[..])
+}
+
+/// Writes `vest` back, first growing a legacy account to the current layout.
+fn store_vesting<'info>(
+    vest: &Vesting,
+    vest_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    grow_account(
+        payer,
+        vest_info,
+        8 + Vesting::LEN,
+        system_program,
+        &crate::ID,
+    )?;
+    let mut data = vest_info.try_borrow_mut_data()?;
+    let mut cursor = std::io::Cursor::new(&mut data[..]);
+    vest.try_serialize(&mut cursor)?;
+    Ok(())
+}
+
+/// Create the program-signed PDA `target` with `space` bytes owned by `owner`, rent paid by
+/// `payer`. Fails if the account is already in use.
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    target: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[u8]],
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    require!(
+        target.lamports() == 0 && target.data_is_empty(),
+        SeedError::InvalidSplit
+    );
+    let lamports = Rent::get()?.minimum_balance(space);
+    anchor_lang::solana_program::program::invoke_signed(
+        &anchor_lang::solana_program::system_instruction::create_account(
+            payer.key,
+            target.key,
+            lamports,
+            space as u64,
+            owner,
+        ),
+        &[payer.clone(), target.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    Ok(())
+}
+
+/// Reads a SeedSale, zero-filling fields a sale created before them doesn't have yet.
+fn load_sale(sale_info: &AccountInfo) -> Result<SeedSale> {
+    require!(sale_info.owner == &crate::ID, SeedError::Unauthorized);
+    let mut data = sale_info.try_borrow_data()?.to_vec();
+    if data.len() < 8 + SeedSale::LEN {
+        data.resize(8 + SeedSale::LEN, 0);
+    }
+    SeedSale::try_deserialize(&mut &data[..])
+}
+
+/// Writes `sale` back, first growing a legacy account to the current layout.
+fn store_sale<'info>(
+    sale: &SeedSale,
+    sale_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    grow_account(
+        payer,
+        sale_info,
+        8 + SeedSale::LEN,
+        system_program,
+        &crate::ID,
+    )?;
+    let mut data = sale_info.try_borrow_mut_data()?;
+    let mut cursor = std::io::Cursor::new(&mut data[..]);
+    sale.try_serialize(&mut cursor)?;
+    Ok(())
+}
+
+/// Grow a sale created before the latest fields and load it for an authority-only update.
+fn load_sale_for_update<'info>(
+    payer: &AccountInfo<'info>,
+    sale_info: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<SeedSale> {
+    grow_account(
+        payer,
+        sale_info,
+        8 + SeedSale::LEN,
+        system_program,
+        program_id,
+    )?;
+    let sale = SeedSale::try_deserialize(&mut &sale_info.data.borrow()[..])?;
+    require!(payer.key() == sale.authority, SeedError::Unauthorized);
+    Ok(sale)
+}
+
+/// Largest contribution up to `lamports` that stays within both the SOL and token caps.
+fn fit_to_caps(sale: &SeedSale, lamports: u64) -> Result<u64> {
+    let mut fitted = lamports;
+    if sale.sol_cap_lamports > 0 {
+        let remaining = sale.sol_cap_lamports.saturating_sub(sale.raised_lamports);
+        fitted = fitted.min(remaining);
+    }
+    if sale.token_cap > 0 && sale.price_tokens_per_sol > 0 {
+        let remaining_tokens = sale.token_cap.saturating_sub(sale.sold_tokens);
+        let max_lamports = remaining_tokens
+            .checked_div(sale.price_tokens_per_sol)
+            .ok_or(SeedError::Overflow)?;
+        fitted = fitted.min(max_lamports);
+    }
+    Ok(fitted)
+}
+
+fn vested_amount(vest: &Vesting, now: i64) -> Result<u64> {
     if now >= vest.end_ts {
         return Ok(vest.total_amount);
     }
+    if vest.cliff_unlock_bps > 0 {
+        // Lump sum at the cliff, then the rest follows the curve from the cliff to the end.
+        if now < vest.cliff_ts {
+            return Ok(0);
+        }
+        let total = vest.total_amount as u128;
+        let bonus = mul_div(total, vest.cliff_unlock_bps as u128, 10_000)?;
+        let curve_start = vest.cliff_ts.max(vest.start_ts);
+        let rest = if now <= curve_start {
+            0
+        } else {
+            curve_vested(
+                vest.curve,
+                total - bonus,
+                (now - curve_start) as u128,
+                (vest.end_ts - curve_start) as u128,
+            )?
+        };
+        return Ok((bonus + rest) as u64);
+    }
+    if now <= vest.start_ts {
+        return Ok(0);
+    }
     let elapsed = (now - vest.start_ts) as u128;
     let duration = (vest.end_ts - vest.start_ts) as u128;
-    let vested = (vest.total_amount as u128)
-        .checked_mul(elapsed)
+    Ok(curve_vested(vest.curve, vest.total_amount as u128, elapsed, duration)? as u64)
+}
+
+/// Portion of `total` released `elapsed` into a `duration`-long curve (elapsed < duration).
+fn curve_vested(curve: VestingCurve, total: u128, elapsed: u128, duration: u128) -> Result<u128> {
+    let vested = match curve {
+        VestingCurve::Linear => mul_div(total, elapsed, duration)?,
+        // total * (elapsed/duration)^2, scaled in two steps so it cannot overflow u128.
+        VestingCurve::BackLoaded => mul_div(mul_div(total, elapsed, duration)?, elapsed, duration)?,
+        // Mirror of BackLoaded: whatever would still be locked under the back-loaded curve
+        // at the remaining time is what stays locked here.
+        VestingCurve::FrontLoaded => {
+            let remaining = duration - elapsed;
+            let locked = mul_div(mul_div(total, remaining, duration)?, remaining, duration)?;
+            total - locked
+        }
+    };
+    Ok(vested)
+}
+
+fn mul_div(value: u128, numerator: u128, denominator: u128) -> Result<u128> {
+    Ok(value
+        .checked_mul(numerator)
         .ok_or(SeedError::Overflow)?
-        .checked_div(duration)
-        .ok_or(SeedError::Overflow)?;
-    Ok(vested as u64)
+        .checked_div(denominator)
+        .ok_or(SeedError::Overflow)?)
 }
 
 #[derive(Accounts)]
 pub struct InitSale<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
-    pub mint: Account<'info, Mint>,
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
     /// CHECK: treasury can be any system account
     #[account(mut)]
     pub treasury: UncheckedAccount<'info>,
@@ -252,9 +1063,10 @@ pub struct InitSale<'info> {
         bump,
         token::mint = mint,
         token::authority = vault_authority,
+        token::token_program = token_program,
     )]
-    pub seed_vault: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub seed_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
@@ -263,8 +1075,9 @@ pub struct InitSale<'info> {
 pub struct Contribute<'info> {
     #[account(mut)]
     pub buyer: Signer<'info>,
+    /// CHECK: sale PDA (may predate the latest fields); read via `load_sale`, grown on write.
     #[account(mut)]
-    pub sale: Account<'info, SeedSale>,
+    pub sale: UncheckedAccount<'info>,
     /// CHECK: treasury system account
     #[account(mut)]
     pub treasury: UncheckedAccount<'info>,
@@ -280,20 +1093,92 @@ pub struct Contribute<'info> {
 }
 
 #[derive(Accounts)]
-pub struct Claim<'info> {
+pub struct ContributeToken<'info> {
+    #[account(mut)]
     pub buyer: Signer<'info>,
+    // Typed: stable contributions need `set_payment_mint`, which already grew the sale.
     #[account(mut)]
     pub sale: Account<'info, SeedSale>,
-    #[account(mut, seeds = [b"contrib", sale.key().as_ref(), buyer.key().as_ref()], bump = contribution.bump)]
+    #[account(mut)]
+    pub buyer_token: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub treasury_token: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        seeds = [b"contrib", sale.key().as_ref(), buyer.key().as_ref()],
+        bump,
+        space = 8 + Contribution::LEN,
+    )]
     pub contribution: Account<'info, Contribution>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    /// Pays the rent to grow a legacy sale on its first claim.
     #[account(mut)]
-    pub seed_vault: Account<'info, TokenAccount>,
+    pub buyer: Signer<'info>,
+    /// CHECK: sale PDA (may predate the latest fields); read via `load_sale`, grown on write.
+    #[account(mut)]
+    pub sale: UncheckedAccount<'info>,
+    // Canonical bump rather than `contribution.bump`: contributions made before the bump was
+    // stored hold 0 there and must still be claimable without a migration.
+    #[account(mut, seeds = [b"contrib", sale.key().as_ref(), buyer.key().as_ref()], bump)]
+    pub contribution: Account<'info, Contribution>,
+    #[account(mut)]
+    pub seed_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
     /// CHECK: PDA authority
-    #[account(seeds = [VAULT_AUTH_SEED, sale.key().as_ref()], bump = sale.vault_bump)]
+    #[account(seeds = [VAULT_AUTH_SEED, sale.key().as_ref()], bump)]
     pub vault_authority: UncheckedAccount<'info>,
     #[account(mut)]
-    pub user_ata: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    pub user_ata: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Checked against `sale.mint` in the handler.
+    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyContribution<'info> {
+    /// CHECK: only used to derive the contribution PDA; anyone may verify.
+    pub buyer: UncheckedAccount<'info>,
+    /// CHECK: sale PDA (may predate the latest fields); read via `load_sale`.
+    pub sale: UncheckedAccount<'info>,
+    #[account(seeds = [b"contrib", sale.key().as_ref(), buyer.key().as_ref()], bump)]
+    pub contribution: Account<'info, Contribution>,
+}
+
+#[derive(Accounts)]
+pub struct SetAbsorbFee<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: sale PDA (may predate the fee fields); authority checked in handler.
+    #[account(mut)]
+    pub sale: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MarkFunded<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: sale PDA (may predate the funded flag); authority checked in handler.
+    #[account(mut)]
+    pub sale: UncheckedAccount<'info>,
+    pub seed_vault: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaymentMint<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: sale PDA (may predate the payment fields); authority checked in handler.
+    #[account(mut)]
+    pub sale: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
@@ -303,6 +1188,20 @@ pub struct CancelSale<'info> {
     pub sale: Account<'info, SeedSale>,
 }
 
+#[derive(Accounts)]
+pub struct ExtendSale<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub sale: Account<'info, SeedSale>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSalePrice<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub sale: Account<'info, SeedSale>,
+}
+
 #[derive(Accounts)]
 pub struct InitVesting<'info> {
     #[account(mut)]
@@ -335,19 +1234,96 @@ pub struct InitVesting<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+pub struct FundVesting<'info> {
+    pub authority: Signer<'info>,
+    #[account(has_one = authority)]
+    pub vesting: Account<'info, Vesting>,
+    #[account(mut, address = vesting.vault)]
+    pub vest_vault: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = vesting.mint, token::authority = authority)]
+    pub authority_ata: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SplitVesting<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: only used to derive the source vesting PDA.
+    pub beneficiary: UncheckedAccount<'info>,
+    #[account(mut, has_one = authority, seeds = [VESTING_SEED, beneficiary.key().as_ref()], bump = vesting.bump)]
+    pub vesting: Account<'info, Vesting>,
+    #[account(mut, address = vesting.vault)]
+    pub vest_vault: Account<'info, TokenAccount>,
+    /// CHECK: PDA authority of the source vest vault
+    #[account(seeds = [VESTING_SEED, beneficiary.key().as_ref()], bump = vesting.vault_bump)]
+    pub vest_vault_authority: UncheckedAccount<'info>,
+    #[account(address = vesting.mint)]
+    pub mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxClaimPerTx<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: vesting PDA (may predate the cap); authority checked in handler.
+    #[account(mut)]
+    pub vesting: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateContribution<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: only used to derive the contribution PDA.
+    pub sale: UncheckedAccount<'info>,
+    /// CHECK: only used to derive the contribution PDA.
+    pub buyer: UncheckedAccount<'info>,
+    /// CHECK: contribution PDA (may predate the current layout); owner checked on grow.
+    #[account(mut, seeds = [b"contrib", sale.key().as_ref(), buyer.key().as_ref()], bump)]
+    pub contribution: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinClaimInterval<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: vesting PDA (may predate the throttle); authority checked in handler.
+    #[account(mut)]
+    pub vesting: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct ClaimVesting<'info> {
+    #[account(mut)]
     pub beneficiary: Signer<'info>,
-    #[account(mut, seeds = [VESTING_SEED, beneficiary.key().as_ref()], bump = vesting.bump)]
-    pub vesting: Account<'info, Vesting>,
+    /// CHECK: vesting PDA (may predate the latest fields); read via `load_vesting`.
+    #[account(mut, seeds = [VESTING_SEED, beneficiary.key().as_ref()], bump)]
+    pub vesting: UncheckedAccount<'info>,
     #[account(mut)]
     pub vest_vault: Account<'info, TokenAccount>,
     /// CHECK: PDA authority
-    #[account(seeds = [VESTING_SEED, beneficiary.key().as_ref()], bump = vesting.vault_bump)]
+    #[account(seeds = [VESTING_SEED, beneficiary.key().as_ref()], bump)]
     pub vest_vault_authority: UncheckedAccount<'info>,
     #[account(mut)]
     pub beneficiary_ata: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ViewVesting<'info> {
+    /// CHECK: only used to derive the vesting PDA; anyone may read a schedule.
+    pub beneficiary: UncheckedAccount<'info>,
+    /// CHECK: vesting PDA (may predate the latest fields); read via `load_vesting`.
+    #[account(seeds = [VESTING_SEED, beneficiary.key().as_ref()], bump)]
+    pub vesting: UncheckedAccount<'info>,
 }
 
 #[account]
@@ -368,10 +1344,20 @@ pub struct SeedSale {
     pub bump: u8,
     pub vault_bump: u8,
     pub vault_token_bump: u8,
+    pub absorb_fee: bool,
+    pub transfer_fees: u64,
+    pub payment_mint: Option<Pubkey>,
+    pub token_treasury: Pubkey,
+    pub price_tokens_per_stable: u64,
+    pub raised_stable: u64,
+    pub funded: bool,
 }
 impl SeedSale {
     // 5 pubkeys (5*32) + 2 i64 (start/end) + 5 u64 (price, caps, totals) + 4 u8/bool
-    pub const LEN: usize = 32 * 5 + 8 * 7 + 4; // 220 bytes (data), +8 discriminator at init
+    // + absorb_fee (1) + transfer_fees (8)
+    // + payment_mint Option (1 + 32) + token_treasury (32) + stable price/raised (8 * 2)
+    // + funded (1)
+    pub const LEN: usize = 32 * 5 + 8 * 7 + 4 + 1 + 8 + 33 + 32 + 8 * 2 + 1; // 311 bytes (data), +8 discriminator at init
 }
 
 #[account]
@@ -387,6 +1373,14 @@ impl Contribution {
     pub const LEN: usize = 32 * 2 + 8 * 2 + 1 + 1;
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ContributionSummary {
+    pub contributed_lamports: u64,
+    pub tokens_owed: u64,
+    pub claimed: bool,
+    pub expected_tokens_at_current_price: u64,
+}
+
 #[account]
 pub struct Vesting {
     pub authority: Pubkey,
@@ -401,9 +1395,62 @@ pub struct Vesting {
     pub bump: u8,
     pub vault_bump: u8,
     pub vault_token_bump: u8,
+    pub max_claim_per_tx: u64,
+    pub curve: VestingCurve,
+    pub min_claim_interval_seconds: i64,
+    pub last_claim_ts: i64,
+    /// Share of `total_amount` (bps) released at `cliff_ts`; the rest vests from the cliff.
+    pub cliff_unlock_bps: u16,
 }
 impl Vesting {
-    pub const LEN: usize = 32 * 4 + 8 * 5 + 1 + 1 + 1;
+    pub const LEN: usize = 32 * 4 + 8 * 5 + 1 + 1 + 1 + 8 + 1 + 8 * 2 + 2;
+}
+
+/// One new beneficiary of `split_vesting` and the amount carved out for them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SplitAllocation {
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct VestingDetails {
+    pub vested_now: u64,
+    pub claimable_now: u64,
+    pub total: u64,
+    pub next_unlock_ts: i64,
+    pub next_unlock_amount: u64,
+}
+
+/// Release shape between `start_ts` and `end_ts`. Every curve is 0 at the start and
+/// `total_amount` at the end; vestings grown from the legacy layout read as `Linear`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VestingCurve {
+    #[default]
+    Linear,
+    FrontLoaded,
+    BackLoaded,
+}
+
+#[event]
+pub struct SaleExtended {
+    pub sale: Pubkey,
+    pub old_end_ts: i64,
+    pub new_end_ts: i64,
+}
+
+#[event]
+pub struct PriceUpdated {
+    pub sale: Pubkey,
+    pub old_price: u64,
+    pub new_price: u64,
+}
+
+#[event]
+pub struct VestingSplit {
+    pub source: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
 }
 
 #[error_code]
@@ -432,4 +1479,114 @@ pub enum SeedError {
     NothingToClaim,
     #[msg("Cliff not reached")]
     CliffNotReached,
+    #[msg("Vesting schedule not complete")]
+    VestingNotComplete,
+    #[msg("Sale does not accept this currency")]
+    CurrencyNotAccepted,
+    #[msg("Mint mismatch")]
+    MintMismatch,
+    #[msg("Treasury mismatch")]
+    TreasuryMismatch,
+    #[msg("Invalid price")]
+    InvalidPrice,
+    #[msg("Sale already started")]
+    AlreadyStarted,
+    #[msg("Seed vault does not cover the token cap")]
+    NotFunded,
+    #[msg("Claim interval has not elapsed")]
+    ClaimTooSoon,
+    #[msg("Basis points above 10000")]
+    InvalidBps,
+    #[msg("Vesting vault does not hold enough tokens")]
+    VaultUnderfunded,
+    #[msg("Vesting split is invalid or exceeds the unvested remainder")]
+    InvalidSplit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOTAL: u64 = 1_000_000_007;
+
+    fn vesting(curve: VestingCurve, cliff_ts: i64, cliff_unlock_bps: u16) -> Vesting {
+        Vesting {
+            authority: Pubkey::default(),
+            beneficiary: Pubkey::default(),
+            mint: Pubkey::default(),
+            vault: Pubkey::default(),
+            start_ts: 1_000,
+            cliff_ts,
+            end_ts: 11_000,
+            total_amount: TOTAL,
+            claimed_amount: 0,
+            bump: 0,
+            vault_bump: 0,
+            vault_token_bump: 0,
+            max_claim_per_tx: 0,
+            curve,
+            min_claim_interval_seconds: 0,
+            last_claim_ts: 0,
+            cliff_unlock_bps,
+        }
+    }
+
+    const CURVES: [VestingCurve; 3] = [
+        VestingCurve::Linear,
+        VestingCurve::FrontLoaded,
+        VestingCurve::BackLoaded,
+    ];
+
+    #[test]
+    fn curves_hit_both_endpoints() {
+        for curve in CURVES {
+            let vest = vesting(curve, 1_000, 0);
+            assert_eq!(vested_amount(&vest, 0).unwrap(), 0);
+            assert_eq!(vested_amount(&vest, vest.start_ts).unwrap(), 0);
+            assert_eq!(vested_amount(&vest, vest.end_ts).unwrap(), TOTAL);
+            assert_eq!(vested_amount(&vest, vest.end_ts + 1).unwrap(), TOTAL);
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic() {
+        for curve in CURVES {
+            let vest = vesting(curve, 1_000, 0);
+            let mut previous = 0;
+            for now in (vest.start_ts..=vest.end_ts).step_by(7) {
+                let vested = vested_amount(&vest, now).unwrap();
+                assert!(vested >= previous);
+                assert!(vested <= TOTAL);
+                previous = vested;
+            }
+        }
+    }
+
+    #[test]
+    fn curves_order_at_midpoint() {
+        let mid = |curve| vested_amount(&vesting(curve, 1_000, 0), 6_000).unwrap();
+        assert_eq!(mid(VestingCurve::Linear), TOTAL / 2);
+        assert_eq!(mid(VestingCurve::BackLoaded), TOTAL / 4);
+        assert_eq!(mid(VestingCurve::FrontLoaded), TOTAL - TOTAL / 4);
+    }
+
+    #[test]
+    fn curve_vested_survives_large_totals() {
+        let total = u64::MAX as u128;
+        for curve in CURVES {
+            let vested = curve_vested(curve, total, 999, 1_000).unwrap();
+            assert!(vested <= total);
+        }
+    }
+
+    #[test]
+    fn cliff_unlock_releases_lump_then_follows_curve() {
+        let vest = vesting(VestingCurve::Linear, 3_000, 2_500);
+        assert_eq!(vested_amount(&vest, 2_999).unwrap(), 0);
+        assert_eq!(vested_amount(&vest, 3_000).unwrap(), TOTAL / 4);
+        let bonus = TOTAL as u128 * 2_500 / 10_000;
+        let rest = (TOTAL as u128 - bonus) / 2;
+        assert_eq!(vested_amount(&vest, 7_000).unwrap(), (bonus + rest) as u64);
+        assert_eq!(vested_amount(&vest, vest.end_ts).unwrap(), TOTAL);
+    }
 }
@@ -1,22 +1,77 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{
-    program::invoke, program::invoke_signed, program_option::COption, system_instruction,
+    ed25519_program,
+    hash::{hash, hashv},
+    program::invoke,
+    program::invoke_signed,
+    program_option::COption,
+    system_instruction,
+    sysvar::{instructions as ix_sysvar, slot_hashes},
 };
 use anchor_lang::Discriminator;
-use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount, Transfer};
-use mpl_core::instructions::{BurnV1CpiBuilder, TransferV1CpiBuilder};
+use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
+use mpl_core::instructions::{
+    AddPluginV1CpiBuilder, BurnV1CpiBuilder, RemovePluginV1CpiBuilder, TransferV1CpiBuilder,
+    UpdatePluginV1CpiBuilder,
+};
+use mpl_core::types::{FreezeDelegate, Plugin, PluginType, UpdateAuthority};
 use std::io::Write;
 
 declare_id!("Gc7u33eCs81jPcfzgX4nh6xsiEtRYuZUyHKFjmf5asfx");
 
 const PACK_CARD_COUNT: usize = 11;
 const MAX_RARE_CARDS: usize = 3;
+const MAX_FEE_RECIPIENTS: usize = 4;
+// Byte offset of `VaultState.escrowed_lamports` (discriminator included), for raw migrations.
+const ESCROWED_LAMPORTS_OFFSET: usize = 8 + 332;
+// Byte length of the original VaultState fields (through `treasury`), discriminator included.
+const VAULT_STATE_BASE_LEN: usize = 8 + 247;
+// Number of `Rarity` variants.
+const RARITY_COUNT: usize = 9;
+// Gacha vaults live at [GACHA_VAULT_SEED, gacha_vault_seed(vault_id)]; see `gacha_vault_seed`.
 const GACHA_VAULT_SEED: &[u8] = b"vault_state";
 const GACHA_VAULT_AUTHORITY_SEED: &[u8] = b"vault_authority";
 const MARKETPLACE_VAULT_SEED: &[u8] = b"market_vault_state";
 const MARKETPLACE_VAULT_AUTHORITY_SEED: &[u8] = b"market_vault_authority";
 const LISTING_SEED: &[u8] = b"listing";
 const CARD_RECORD_SEED: &[u8] = b"card_record";
+const FEE_SPLIT_SEED: &[u8] = b"fee_split";
+const FILL_COMMIT_SEED: &[u8] = b"fill_commit";
+const SELLER_INDEX_SEED: &[u8] = b"seller_index";
+const CARD_RESERVATION_SEED: &[u8] = b"card_reservation";
+const PROVENANCE_SEED: &[u8] = b"provenance";
+const TEMPLATE_STATS_SEED: &[u8] = b"template_stats";
+const AIRDROP_BITMAP_SEED: &[u8] = b"airdrop_bitmap";
+const DISCOUNT_CODE_SEED: &[u8] = b"discount_code";
+const RARITY_INVENTORY_SEED: &[u8] = b"rarity_inventory";
+const FORCE_CLOSE_REQUEST_SEED: &[u8] = b"force_close_request";
+const TEMPLATE_SET_SEED: &[u8] = b"template_set";
+const SET_BONUS_CLAIM_SEED: &[u8] = b"set_bonus_claim";
+const FUSION_RECIPE_SEED: &[u8] = b"fusion_recipe";
+const USER_STATS_SEED: &[u8] = b"user_stats";
+
+/// `AdminSessionAction.action` values.
+const ADMIN_ACTION_FORCE_CLOSE: u8 = 0;
+const ADMIN_ACTION_RESET: u8 = 1;
+const ADMIN_ACTION_EXPIRE: u8 = 2;
+const MAX_SELLER_LISTINGS: usize = 32;
+const MAX_CURRENCY_MINTS: usize = 4;
+const MAX_AIRDROP_LEAVES: usize = 8192;
+const MAX_ADMINS: usize = 4;
+const MAX_MIGRATE_BATCH: usize = 10;
+const MAX_RELAYER_BATCH: usize = 4;
+const MAX_SET_TEMPLATES: usize = 16;
+const MAX_FUSION_INPUTS: usize = 8;
+// Hard cap on templates per pack manifest. Only the hash is stored on-chain, so this bounds
+// the `verify_manifest` argument and the slot count of a session, not any account size.
+const MAX_MANIFEST_LEN: usize = 32;
+const MAX_ROYALTY_COLLECTIONS: usize = 4;
+const MAX_TREASURY_LIST_BATCH: usize = 5;
+// Base units per whole SOL and USDC, for `RewardMode::Proportional` conversion rates.
+const SOL_UNIT: u128 = 1_000_000_000;
+const USDC_UNIT: u128 = 1_000_000;
+// Wait before a looser self-imposed spend limit takes effect.
+const SPEND_LIMIT_RAISE_COOLDOWN: i64 = 86_400;
 
 #[program]
 mod mochi_v2_vault {
@@ -24,16 +79,27 @@ mod mochi_v2_vault {
 
     pub fn initialize_vault(
         ctx: Context<InitializeVault>,
-        pack_price_sol: u64,
-        pack_price_usdc: u64,
-        buyback_bps: u16,
-        claim_window_seconds: i64,
-        marketplace_fee_bps: u16,
-        core_collection: Option<Pubkey>,
-        usdc_mint: Option<Pubkey>,
-        mochi_mint: Option<Pubkey>,
-        reward_per_pack: u64,
+        args: InitializeVaultArgs,
     ) -> Result<()> {
+        let InitializeVaultArgs {
+            pack_price_sol,
+            pack_price_usdc,
+            buyback_bps,
+            claim_window_seconds,
+            marketplace_fee_bps,
+            core_collection,
+            usdc_mint,
+            mochi_mint,
+            reward_per_pack,
+            treasury,
+            vault_id,
+        } = args;
+        // A non-positive window expires every session on open, stranding paid packs.
+        require!(claim_window_seconds > 0, MochiError::InvalidWindow);
+        require!(treasury != Pubkey::default(), MochiError::VaultMismatch);
+        if let Some(collection_info) = ctx.accounts.core_collection_account.as_ref() {
+            verify_core_collection(collection_info, core_collection)?;
+        }
         let vault_state = &mut ctx.accounts.vault_state;
         vault_state.admin = ctx.accounts.admin.key();
         vault_state.vault_authority = ctx.accounts.vault_authority.key();
@@ -47,7 +113,9 @@ mod mochi_v2_vault {
         vault_state.usdc_mint = usdc_mint;
         vault_state.mochi_mint = mochi_mint;
         vault_state.reward_per_pack = reward_per_pack;
-        Ok(())
+        vault_state.treasury = treasury;
+        vault_state.vault_id = vault_id;
+        vault_state.check_pack_prices()
     }
 
     pub fn initialize_marketplace_vault(
@@ -55,6 +123,7 @@ mod mochi_v2_vault {
         marketplace_fee_bps: u16,
         core_collection: Option<Pubkey>,
         usdc_mint: Option<Pubkey>,
+        treasury: Pubkey,
     ) -> Result<()> {
         let vault_state = &mut ctx.accounts.vault_state;
         vault_state.admin = ctx.accounts.admin.key();
@@ -69,6 +138,46 @@ mod mochi_v2_vault {
         vault_state.usdc_mint = usdc_mint;
         vault_state.mochi_mint = None;
         vault_state.reward_per_pack = 0;
+        vault_state.treasury = treasury;
+        Ok(())
+    }
+
+    /// Primary admin grants a secondary admin; secondaries pass every `is_admin` check but
+    /// cannot change the admin set themselves. Works on gacha and marketplace vaults alike.
+    pub fn add_admin(ctx: Context<ManageAdmins>, new_admin: Pubkey) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        require_keys_eq!(
+            ctx.accounts.admin.key(),
+            vault_state.admin,
+            MochiError::Unauthorized
+        );
+        require!(new_admin != Pubkey::default(), MochiError::Unauthorized);
+        if vault_state.is_admin(&new_admin) {
+            return Ok(());
+        }
+        let slot = vault_state
+            .admins
+            .iter_mut()
+            .find(|a| **a == Pubkey::default())
+            .ok_or(MochiError::AdminSetFull)?;
+        *slot = new_admin;
+        Ok(())
+    }
+
+    pub fn remove_admin(ctx: Context<ManageAdmins>, old_admin: Pubkey) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        require_keys_eq!(
+            ctx.accounts.admin.key(),
+            vault_state.admin,
+            MochiError::Unauthorized
+        );
+        require!(old_admin != Pubkey::default(), MochiError::AdminNotFound);
+        let slot = vault_state
+            .admins
+            .iter_mut()
+            .find(|a| **a == old_admin)
+            .ok_or(MochiError::AdminNotFound)?;
+        *slot = Pubkey::default();
         Ok(())
     }
 
@@ -78,9 +187,8 @@ mod mochi_v2_vault {
         mochi_mint: Pubkey,
         reward_per_pack: u64,
     ) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
             MochiError::Unauthorized
         );
         let vault_state = &mut ctx.accounts.vault_state;
@@ -89,2454 +197,10699 @@ mod mochi_v2_vault {
         Ok(())
     }
 
-    /// One-time migration to grow the VaultState account to the new size that includes MOCHI rewards.
-    pub fn migrate_vault_state(
-        ctx: Context<MigrateVaultState>,
-        pack_price_sol: u64,
-        pack_price_usdc: u64,
-        buyback_bps: u16,
-        claim_window_seconds: i64,
-        marketplace_fee_bps: u16,
-        usdc_mint: Option<Pubkey>,
-        mochi_mint: Option<Pubkey>,
-        reward_per_pack: u64,
+    /// Start a decaying emission schedule from now; `reward_start = 0` reverts to `reward_per_pack`.
+    pub fn set_reward_schedule(
+        ctx: Context<SetVaultConfig>,
+        reward_start: u64,
+        reward_decay_bps_per_epoch: u16,
+        epoch_seconds: i64,
     ) -> Result<()> {
-        let admin_key = ctx.accounts.admin.key();
-        let vault_key = ctx.accounts.vault_state.key();
-        let (expected_vault_auth, vault_bump) = Pubkey::find_program_address(
-            &[GACHA_VAULT_AUTHORITY_SEED, vault_key.as_ref()],
-            ctx.program_id,
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
+        require!(
+            reward_start == 0 || epoch_seconds > 0,
+            MochiError::InvalidWindow
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.reward_start = reward_start;
+        vault_state.reward_decay_bps_per_epoch = reward_decay_bps_per_epoch.min(10_000);
+        vault_state.epoch_seconds = epoch_seconds;
+        vault_state.reward_schedule_start = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
 
-        // Ensure account is large enough and rent-exempt for the expanded struct.
-        let target_len: usize = 8 + VaultState::SIZE;
-        let rent = Rent::get()?;
-        let required_lamports = rent.minimum_balance(target_len);
-        let vault_info = ctx.accounts.vault_state.to_account_info();
+    /// How open rewards are sized. `Proportional` pays `reward_rate_bps` of the price paid,
+    /// valued in MOCHI at `mochi_per_sol` / `mochi_per_usdc` raw units per whole SOL / USDC
+    /// (MOCHI packs count at face value). `max_reward_per_pack` caps the final reward in both
+    /// modes (0 = uncapped).
+    pub fn set_reward_mode(
+        ctx: Context<SetVaultConfig>,
+        reward_mode: RewardMode,
+        reward_rate_bps: u16,
+        mochi_per_sol: u64,
+        mochi_per_usdc: u64,
+        max_reward_per_pack: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            reward_mode == RewardMode::Flat || (1..=10_000).contains(&reward_rate_bps),
+            MochiError::InvalidRewardConfig
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.reward_mode = reward_mode;
+        vault_state.reward_rate_bps = reward_rate_bps;
+        vault_state.reward_mochi_per_sol = mochi_per_sol;
+        vault_state.reward_mochi_per_usdc = mochi_per_usdc;
+        vault_state.max_reward_per_pack = max_reward_per_pack;
+        Ok(())
+    }
 
-        if vault_info.lamports() < required_lamports {
-            let diff = required_lamports
-                .checked_sub(vault_info.lamports())
-                .ok_or(MochiError::MathOverflow)?;
-            invoke(
-                &system_instruction::transfer(&ctx.accounts.admin.key(), vault_info.key, diff),
-                &[
-                    ctx.accounts.admin.to_account_info(),
-                    vault_info.clone(),
-                    ctx.accounts.system_program.to_account_info(),
-                ],
-            )?;
-        }
+    /// Pity guarantee: after `pity_threshold` V2 opens in a row without a Rare+, the next open
+    /// must reserve at least one (0 disables). Tracked in each purchaser's UserStats.
+    pub fn set_pity_threshold(ctx: Context<SetVaultConfig>, pity_threshold: u16) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.pity_threshold = pity_threshold;
+        Ok(())
+    }
 
-        vault_info.realloc(target_len, false)?;
+    /// MOCHI minted to a user per card burned through `redeem_burn` (raw units; 0 disables),
+    /// capped by `max_reward_per_pack`.
+    pub fn set_burn_reward(ctx: Context<SetVaultConfig>, burn_reward_per_card: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.burn_reward_per_card = burn_reward_per_card;
+        Ok(())
+    }
 
-        // Manually write the struct to guarantee deterministic layout and overwrite any legacy bytes.
-        let mut data = vault_info.try_borrow_mut_data()?;
-        data.fill(0);
-        // Discriminator
-        data[..8].copy_from_slice(&VaultState::discriminator());
-        let mut offset = 8;
+    /// MOCHI minted to a user who completes a template set through `claim_set_bonus`
+    /// (raw units; 0 disables).
+    pub fn set_completion_reward(
+        ctx: Context<SetVaultConfig>,
+        set_completion_reward: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.set_completion_reward = set_completion_reward;
+        Ok(())
+    }
 
-        // admin
-        data[offset..offset + 32].copy_from_slice(admin_key.as_ref());
-        offset += 32;
-        // vault_authority
-        data[offset..offset + 32].copy_from_slice(expected_vault_auth.as_ref());
-        offset += 32;
-        // pack_price_sol
-        data[offset..offset + 8].copy_from_slice(&pack_price_sol.to_le_bytes());
-        offset += 8;
-        // pack_price_usdc
-        data[offset..offset + 8].copy_from_slice(&pack_price_usdc.to_le_bytes());
-        offset += 8;
-        // buyback_bps (u16)
-        data[offset..offset + 2].copy_from_slice(&buyback_bps.to_le_bytes());
-        offset += 2;
-        // claim_window_seconds (i64)
-        data[offset..offset + 8].copy_from_slice(&claim_window_seconds.to_le_bytes());
-        offset += 8;
-        // marketplace_fee_bps (u16)
-        data[offset..offset + 2].copy_from_slice(&marketplace_fee_bps.to_le_bytes());
-        offset += 2;
+    /// Backend key whose ed25519 signature every open must carry (None disables the gate).
+    pub fn set_open_gate_authority(
+        ctx: Context<SetVaultConfig>,
+        open_gate_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.open_gate_authority = open_gate_authority;
+        Ok(())
+    }
 
-        // core_collection: None => flag 0
-        data[offset] = 0;
-        offset += 1 + 32; // keep layout consistent with SIZE even though value is None.
+    /// Lamports charged to the claimer on each Keep claim, paid to the treasury (0 disables).
+    pub fn set_claim_fee(ctx: Context<SetVaultConfig>, claim_fee_lamports: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.claim_fee_lamports = claim_fee_lamports;
+        Ok(())
+    }
 
-        // usdc_mint option
-        match usdc_mint {
-            Some(pk) => {
-                data[offset] = 1;
-                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
-            }
-            None => data[offset] = 0,
-        }
-        offset += 1 + 32;
+    /// USDC charged alongside `pack_price_sol` for `Currency::Both` packs (0 disables).
+    pub fn set_combo_price(ctx: Context<SetVaultConfig>, combo_price_usdc: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.combo_price_usdc = combo_price_usdc;
+        Ok(())
+    }
 
-        // mochi_mint option
-        match mochi_mint {
-            Some(pk) => {
-                data[offset] = 1;
-                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
+    /// Price of a `Currency::Mochi` pack in raw MOCHI units (0 leaves the SKU unpriced).
+    pub fn set_mochi_price(ctx: Context<SetVaultConfig>, pack_price_mochi: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.pack_price_mochi = pack_price_mochi;
+        Ok(())
+    }
+
+    /// Scale the V2 sellback rate with rarity stock (needs the rarity inventory): scarce
+    /// rarities buy back above `buyback_bps`, abundant ones below, within
+    /// [`min_buyback_bps`, `max_buyback_bps`]. `target_count == 0` restores the flat rate.
+    pub fn set_dynamic_buyback(
+        ctx: Context<SetVaultConfig>,
+        target_count: u32,
+        step_bps: u16,
+        min_buyback_bps: u16,
+        max_buyback_bps: u16,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            min_buyback_bps <= max_buyback_bps && max_buyback_bps <= 10_000,
+            MochiError::InvalidPrice
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.buyback_target_count = target_count;
+        vault_state.buyback_step_bps = step_bps;
+        vault_state.min_buyback_bps = min_buyback_bps;
+        vault_state.max_buyback_bps = max_buyback_bps;
+        Ok(())
+    }
+
+    /// Switch the accepted stablecoin. Refused while any `usdc_mint` session is pending, since
+    /// its sellback would refund from the new mint's account. Passing the vault's old token
+    /// account and a destination sweeps the old balance there.
+    pub fn rotate_payment_mint(ctx: Context<RotatePaymentMint>, new_mint: Pubkey) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(new_mint != Pubkey::default(), MochiError::MintMismatch);
+        require!(
+            ctx.accounts.vault_state.pending_token_sessions == 0,
+            MochiError::TokenSessionsPending
+        );
+        let old_mint = ctx.accounts.vault_state.usdc_mint;
+        let mut swept = 0;
+        match (
+            ctx.accounts.old_vault_token.as_ref(),
+            ctx.accounts.sweep_destination.as_ref(),
+        ) {
+            (Some(source), Some(destination)) => {
+                require_keys_eq!(
+                    source.owner,
+                    ctx.accounts.vault_authority.key(),
+                    MochiError::VaultMismatch
+                );
+                if let Some(mint) = old_mint {
+                    require_keys_eq!(source.mint, mint, MochiError::MintMismatch);
+                }
+                require_keys_eq!(destination.mint, source.mint, MochiError::MintMismatch);
+                swept = source.amount;
+                if swept > 0 {
+                    let vault_key = ctx.accounts.vault_state.key();
+                    let seeds = &[
+                        GACHA_VAULT_AUTHORITY_SEED,
+                        vault_key.as_ref(),
+                        &[ctx.bumps.vault_authority],
+                    ];
+                    let signer = &[&seeds[..]];
+                    let cpi_accounts = Transfer {
+                        from: source.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: ctx.accounts.vault_authority.to_account_info(),
+                    };
+                    token::transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            cpi_accounts,
+                            signer,
+                        ),
+                        swept,
+                    )?;
+                }
             }
-            None => data[offset] = 0,
+            (None, None) => {}
+            _ => return err!(MochiError::MissingTokenAccount),
         }
-        offset += 1 + 32;
-
-        // reward_per_pack
-        data[offset..offset + 8].copy_from_slice(&reward_per_pack.to_le_bytes());
-        offset += 8;
+        ctx.accounts.vault_state.usdc_mint = Some(new_mint);
+        emit!(PaymentMintRotated {
+            admin: ctx.accounts.admin.key(),
+            old_mint,
+            new_mint,
+            swept,
+        });
+        Ok(())
+    }
 
-        // vault_authority_bump
-        data[offset] = vault_bump;
-        offset += 1;
+    /// MOCHI buyback, burn variant: burns `amount` of the MOCHI the vault authority holds
+    /// (MOCHI-priced pack revenue) to cut circulating supply. A DEX swap of SOL revenue into
+    /// MOCHI is left to an off-chain step that deposits into the same account.
+    pub fn burn_vault_mochi(ctx: Context<BurnVaultMochi>, amount: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(amount > 0, MochiError::InvalidPrice);
+        let mochi_mint = ctx
+            .accounts
+            .vault_state
+            .mochi_mint
+            .ok_or(MochiError::MintMismatch)?;
+        require_keys_eq!(
+            ctx.accounts.mochi_mint.key(),
+            mochi_mint,
+            MochiError::MintMismatch
+        );
+        require_keys_eq!(
+            ctx.accounts.vault_mochi.mint,
+            mochi_mint,
+            MochiError::MintMismatch
+        );
+        require_keys_eq!(
+            ctx.accounts.vault_mochi.owner,
+            ctx.accounts.vault_authority.key(),
+            MochiError::VaultMismatch
+        );
+        require!(
+            ctx.accounts.vault_mochi.amount >= amount,
+            MochiError::InsufficientFunds
+        );
+        let vault_key = ctx.accounts.vault_state.key();
+        let seeds = &[
+            GACHA_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.mochi_mint.to_account_info(),
+            from: ctx.accounts.vault_mochi.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        token::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer,
+            ),
+            amount,
+        )?;
+        emit!(MochiBurned {
+            admin: ctx.accounts.admin.key(),
+            amount,
+        });
+        Ok(())
+    }
 
-        // padding (7 bytes already zeroed)
-        // offset now should equal target_len
+    /// Caps sessions in PendingDecision at once so reservations cannot lock the whole
+    /// inventory; 0 removes the cap.
+    pub fn set_max_active_sessions(
+        ctx: Context<SetVaultConfig>,
+        max_active_sessions: u32,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.max_active_sessions = max_active_sessions;
         Ok(())
     }
 
-    /// One-time migration to grow the marketplace VaultState PDA to the expanded size.
-    pub fn migrate_marketplace_vault(
-        ctx: Context<MigrateMarketplaceVault>,
-        marketplace_fee_bps: u16,
-        core_collection: Option<Pubkey>,
-        usdc_mint: Option<Pubkey>,
-        mochi_mint: Option<Pubkey>,
+    /// Balance below which opens and V2 sellbacks emit LowTreasuryWarning; 0 disables it.
+    pub fn set_min_authority_balance(
+        ctx: Context<SetVaultConfig>,
+        min_authority_balance: u64,
     ) -> Result<()> {
-        let admin_key = ctx.accounts.admin.key();
-        let vault_key = ctx.accounts.vault_state.key();
-        let (expected_vault_auth, vault_bump) = Pubkey::find_program_address(
-            &[MARKETPLACE_VAULT_AUTHORITY_SEED, vault_key.as_ref()],
-            ctx.program_id,
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
+        ctx.accounts.vault_state.min_authority_balance = min_authority_balance;
+        Ok(())
+    }
 
-        let target_len: usize = 8 + VaultState::SIZE;
-        let rent = Rent::get()?;
-        let required_lamports = rent.minimum_balance(target_len);
-        let vault_info = ctx.accounts.vault_state.to_account_info();
+    /// Deployments that must be commit-to-keep pass `allowed = false`; sessions revealed via
+    /// `reveal_pack_v2` afterwards refuse sellback with SellbackLocked.
+    pub fn set_sellback_after_reveal(ctx: Context<SetVaultConfig>, allowed: bool) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.sellback_locked_after_reveal = !allowed;
+        Ok(())
+    }
 
-        require!(vault_info.owner == ctx.program_id, MochiError::Unauthorized);
+    /// Halts every sellback with SellbackPaused while set, leaving opens and claims running.
+    pub fn set_sellback_paused(ctx: Context<SetVaultConfig>, paused: bool) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.sellback_paused = paused;
+        Ok(())
+    }
 
-        if vault_info.lamports() < required_lamports {
-            let diff = required_lamports
-                .checked_sub(vault_info.lamports())
-                .ok_or(MochiError::MathOverflow)?;
-            invoke(
-                &system_instruction::transfer(&admin_key, vault_info.key, diff),
-                &[
-                    ctx.accounts.admin.to_account_info(),
-                    vault_info.clone(),
-                    ctx.accounts.system_program.to_account_info(),
-                ],
-            )?;
-        }
+    /// Processing fee on sellbacks, in bps of the already-discounted payout; 0 disables it.
+    pub fn set_sellback_fee(ctx: Context<SetVaultConfig>, sellback_fee_bps: u16) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(sellback_fee_bps <= 10_000, MochiError::InvalidPrice);
+        ctx.accounts.vault_state.sellback_fee_bps = sellback_fee_bps;
+        Ok(())
+    }
 
-        // Grow account to the new size and zero-fill.
-        vault_info.realloc(target_len, false)?;
-        let mut data = vault_info.try_borrow_mut_data()?;
-        data.fill(0);
-        data[..8].copy_from_slice(&VaultState::discriminator());
-        let mut offset = 8;
+    /// Share of the pro-rated price refunded when a V1 session expires after `claim_pack_batch`
+    /// moved only some of its cards (bps; 0 refunds nothing).
+    pub fn set_partial_claim_refund(
+        ctx: Context<SetVaultConfig>,
+        partial_claim_refund_bps: u16,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(partial_claim_refund_bps <= 10_000, MochiError::InvalidPrice);
+        ctx.accounts.vault_state.partial_claim_refund_bps = partial_claim_refund_bps;
+        Ok(())
+    }
 
-        // admin
-        data[offset..offset + 32].copy_from_slice(admin_key.as_ref());
-        offset += 32;
-        // vault_authority
-        data[offset..offset + 32].copy_from_slice(expected_vault_auth.as_ref());
-        offset += 32;
-        // pack_price_sol
-        data[offset..offset + 8].copy_from_slice(&0u64.to_le_bytes());
-        offset += 8;
-        // pack_price_usdc
-        data[offset..offset + 8].copy_from_slice(&0u64.to_le_bytes());
-        offset += 8;
-        // buyback_bps (u16)
-        data[offset..offset + 2].copy_from_slice(&0u16.to_le_bytes());
-        offset += 2;
-        // claim_window_seconds (i64)
-        data[offset..offset + 8].copy_from_slice(&0i64.to_le_bytes());
-        offset += 8;
-        // marketplace_fee_bps (u16)
-        data[offset..offset + 2].copy_from_slice(&marketplace_fee_bps.to_le_bytes());
-        offset += 2;
+    /// Caps the templates a pack manifest may cover (and so the slots a session may open
+    /// with). 0 falls back to `MAX_MANIFEST_LEN`; a cap below the current pack size would
+    /// refuse every open and is rejected.
+    pub fn set_max_manifest_len(ctx: Context<SetVaultConfig>, max_manifest_len: u16) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            max_manifest_len == 0
+                || (PACK_CARD_COUNT..=MAX_MANIFEST_LEN).contains(&(max_manifest_len as usize)),
+            MochiError::ManifestTooLong
+        );
+        ctx.accounts.vault_state.max_manifest_len = max_manifest_len;
+        Ok(())
+    }
 
-        // core_collection option
-        match core_collection {
-            Some(pk) => {
-                data[offset] = 1;
-                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
-            }
-            None => data[offset] = 0,
-        }
-        offset += 1 + 32;
+    /// Opens by holders of a Core asset in `membership_collection` earn
+    /// `multiplier_bps / 10_000` times the usual MOCHI reward. `None` or 0 disables the boost.
+    pub fn set_membership_reward(
+        ctx: Context<SetVaultConfig>,
+        membership_collection: Option<Pubkey>,
+        multiplier_bps: u16,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.membership_collection = membership_collection;
+        vault_state.membership_reward_multiplier_bps = multiplier_bps;
+        Ok(())
+    }
 
-        // usdc_mint option
-        match usdc_mint {
-            Some(pk) => {
-                data[offset] = 1;
-                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
-            }
-            None => data[offset] = 0,
-        }
-        offset += 1 + 32;
+    /// Marketplace allowlist of SPL mints listings may be priced in (max 4; empty = SOL only).
+    pub fn set_allowed_currency_mints(
+        ctx: Context<SetMarketplaceConfig>,
+        mints: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            mints.len() <= MAX_CURRENCY_MINTS,
+            MochiError::CurrencyNotAccepted
+        );
+        let mut allowed = [Pubkey::default(); MAX_CURRENCY_MINTS];
+        allowed[..mints.len()].copy_from_slice(&mints);
+        ctx.accounts.vault_state.allowed_currency_mints = allowed;
+        Ok(())
+    }
 
-        // mochi_mint option
-        match mochi_mint {
-            Some(pk) => {
-                data[offset] = 1;
-                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
-            }
-            None => data[offset] = 0,
+    /// Marketplace allowlist of verified Core collections whose creator royalties fills
+    /// enforce (max 4; empty = royalty-optional everywhere). Assets outside the set sell
+    /// without a royalty.
+    pub fn set_royalty_collections(
+        ctx: Context<SetMarketplaceConfig>,
+        collections: Vec<VerifiedCollection>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            collections.len() <= MAX_ROYALTY_COLLECTIONS,
+            MochiError::InvalidRoyaltyConfig
+        );
+        let mut keys = [Pubkey::default(); MAX_ROYALTY_COLLECTIONS];
+        let mut bps = [0u16; MAX_ROYALTY_COLLECTIONS];
+        let mut recipients = [Pubkey::default(); MAX_ROYALTY_COLLECTIONS];
+        for (i, entry) in collections.iter().enumerate() {
+            require!(
+                entry.collection != Pubkey::default()
+                    && entry.recipient != Pubkey::default()
+                    && entry.royalty_bps <= 10_000
+                    && !keys[..i].contains(&entry.collection),
+                MochiError::InvalidRoyaltyConfig
+            );
+            keys[i] = entry.collection;
+            bps[i] = entry.royalty_bps;
+            recipients[i] = entry.recipient;
         }
-        offset += 1 + 32;
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.royalty_collections = keys;
+        vault_state.royalty_bps = bps;
+        vault_state.royalty_recipients = recipients;
+        Ok(())
+    }
 
-        // reward_per_pack
-        data[offset..offset + 8].copy_from_slice(&0u64.to_le_bytes());
-        offset += 8;
+    /// Marketplace fee rounding: true ceils `price * fee_bps / 10000`, false truncates (legacy).
+    pub fn set_fee_rounding(ctx: Context<SetMarketplaceConfig>, round_fees_up: bool) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.round_fees_up = round_fees_up;
+        Ok(())
+    }
 
-        // vault_authority_bump
-        data[offset] = vault_bump;
-        // padding already zeroed
+    /// Fills priced at or above `cosign_threshold_lamports` must also be signed by
+    /// `cosigner`. `None` disables the gate.
+    pub fn set_fill_cosigner(
+        ctx: Context<SetMarketplaceConfig>,
+        cosigner: Option<Pubkey>,
+        cosign_threshold_lamports: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.cosigner = cosigner;
+        vault_state.cosign_threshold_lamports = cosign_threshold_lamports;
         Ok(())
     }
 
-    pub fn deposit_card(ctx: Context<DepositCard>, template_id: u32, rarity: Rarity) -> Result<()> {
+    /// Maker-taker fees: `maker_fee_bps` comes out of the seller's proceeds and
+    /// `taker_fee_bps` is added on top of the price for the buyer. They replace
+    /// `marketplace_fee_bps` while either is non-zero; (0, 0) returns to the single fee.
+    pub fn set_maker_taker_fees(
+        ctx: Context<SetMarketplaceConfig>,
+        maker_fee_bps: u16,
+        taker_fee_bps: u16,
+    ) -> Result<()> {
         require!(
-            ctx.accounts.admin.key() == ctx.accounts.vault_state.admin,
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
             MochiError::Unauthorized
         );
+        require!(
+            maker_fee_bps <= 10_000 && taker_fee_bps <= 10_000,
+            MochiError::InvalidPrice
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.maker_fee_bps = maker_fee_bps;
+        vault_state.taker_fee_bps = taker_fee_bps;
+        Ok(())
+    }
 
-        let record = &mut ctx.accounts.card_record;
-        record.vault_state = ctx.accounts.vault_state.key();
-        record.core_asset = ctx.accounts.core_asset.key();
-        record.template_id = template_id;
-        record.rarity = rarity;
-        record.status = CardStatus::Available;
-        record.owner = ctx.accounts.vault_authority.key();
+    /// Listing price floors against dust listings: `min_lamports` for SOL listings and
+    /// `min_token` (raw units) for mint-denominated ones. Zero disables either floor.
+    pub fn set_min_listing_price(
+        ctx: Context<SetMarketplaceConfig>,
+        min_lamports: u64,
+        min_token: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.min_listing_price_lamports = min_lamports;
+        vault_state.min_listing_price_token = min_token;
+        Ok(())
+    }
 
-        // NOTE: Real implementation should CPI-transfer Metaplex Core asset into the vault_authority PDA escrow.
-        // Placeholder until Core CPI wiring is finalized.
+    /// Admin-configurable upper bound for `open_pack_long` window overrides (0 disables overrides).
+    pub fn set_max_window_override(
+        ctx: Context<SetVaultConfig>,
+        max_window_override: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(max_window_override >= 0, MochiError::InvalidWindow);
+        ctx.accounts.vault_state.max_window_override = max_window_override;
         Ok(())
     }
 
-    /// New lightweight open: only Rare+ CardRecords are reserved on-chain (max 3).
-    /// remaining_accounts: [rare_card_records...]
-    pub fn open_pack<'info>(
-        ctx: Context<'_, '_, 'info, 'info, OpenPackV2<'info>>,
-        currency: Currency,
-        client_seed_hash: [u8; 32],
-        rare_templates: Vec<u32>,
+    /// Admin-configurable keep-only rarities: bit `1 << rank` set means that rarity cannot be sold back.
+    pub fn set_no_buyback_rarities(
+        ctx: Context<SetVaultConfig>,
+        no_buyback_rarities: u16,
     ) -> Result<()> {
-        let vault_state = &ctx.accounts.vault_state;
-        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.no_buyback_rarities = no_buyback_rarities;
+        Ok(())
+    }
 
-        let rare_count = rare_templates.len();
-        require!(rare_count <= MAX_RARE_CARDS, MochiError::TooManyRareCards);
+    /// Admin-configurable currency acceptance: bit `1 << Currency rank` (0 accepts all).
+    pub fn set_accepted_currencies(
+        ctx: Context<SetVaultConfig>,
+        accepted_currencies: u8,
+    ) -> Result<()> {
         require!(
-            ctx.remaining_accounts.len() >= rare_count,
-            MochiError::InvalidCardCount
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
-        msg!(
-            "reward cfg amount {} mint {:?}",
-            vault_state.reward_per_pack,
-            vault_state.mochi_mint
+        ctx.accounts.vault_state.accepted_currencies = accepted_currencies;
+        Ok(())
+    }
+
+    /// Admin-configurable minimum session age before a V2 sellback is allowed (0 disables).
+    pub fn set_sellback_cooldown(
+        ctx: Context<SetVaultConfig>,
+        sellback_cooldown_seconds: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
+        require!(sellback_cooldown_seconds >= 0, MochiError::InvalidWindow);
+        ctx.accounts.vault_state.sellback_cooldown_seconds = sellback_cooldown_seconds;
+        Ok(())
+    }
 
-        // Fail fast if an active session already exists.
-        let session = &mut ctx.accounts.pack_session;
-        if session.state == PackState::PendingDecision && now <= session.expires_at {
-            return err!(MochiError::SessionExists);
+    /// Admin rare ceiling per pack SKU, capped by MAX_RARE_CARDS (0 keeps MAX_RARE_CARDS).
+    pub fn set_max_rares_per_pack(
+        ctx: Context<SetVaultConfig>,
+        max_rares_per_pack: u8,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            max_rares_per_pack as usize <= MAX_RARE_CARDS,
+            MochiError::TooManyRareCards
+        );
+        ctx.accounts.vault_state.max_rares_per_pack = max_rares_per_pack;
+        Ok(())
+    }
+
+    /// One-time admin guardrail against mispriced packs (0 leaves that currency unbounded).
+    pub fn set_max_pack_prices(
+        ctx: Context<SetVaultConfig>,
+        max_pack_price_sol: u64,
+        max_pack_price_usdc: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        require!(
+            vault_state.max_pack_price_sol == 0 && vault_state.max_pack_price_usdc == 0,
+            MochiError::PriceBoundsLocked
+        );
+        vault_state.max_pack_price_sol = max_pack_price_sol;
+        vault_state.max_pack_price_usdc = max_pack_price_usdc;
+        vault_state.check_pack_prices()
+    }
+
+    /// Admin pack pricing, bounded by `max_pack_price_*` when set.
+    pub fn set_pack_prices(
+        ctx: Context<SetVaultConfig>,
+        pack_price_sol: u64,
+        pack_price_usdc: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.pack_price_sol = pack_price_sol;
+        vault_state.pack_price_usdc = pack_price_usdc;
+        vault_state.check_pack_prices()
+    }
+
+    /// Route this vault's fees across up to four recipients; an empty list restores the
+    /// single-treasury behaviour.
+    pub fn set_fee_split(ctx: Context<SetFeeSplit>, recipients: Vec<FeeRecipient>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            recipients.len() <= MAX_FEE_RECIPIENTS,
+            MochiError::InvalidFeeSplit
+        );
+        if !recipients.is_empty() {
+            let total_bps: u32 = recipients.iter().map(|r| r.bps as u32).sum();
+            require!(total_bps == 10_000, MochiError::InvalidFeeSplit);
         }
+        ctx.accounts.vault_state.fee_split_enabled = !recipients.is_empty();
+        let fee_split = &mut ctx.accounts.fee_split;
+        fee_split.vault_state = ctx.accounts.vault_state.key();
+        fee_split.recipients = recipients;
+        fee_split.bump = ctx.bumps.fee_split;
+        Ok(())
+    }
 
-        // Process payment first.
-        let paid_amount = match currency {
-            Currency::Sol => {
-                let price = vault_state.pack_price_sol;
-                require!(price > 0, MochiError::InvalidPrice);
-                invoke(
-                    &system_instruction::transfer(
-                        &ctx.accounts.user.key(),
-                        &ctx.accounts.vault_treasury.key(),
-                        price,
-                    ),
+    /// Seed (or resync) the per-rarity count of Available cards from an off-chain tally.
+    /// Once set, the tracked paths (deposit, batch registration, reserve_for/unreserve,
+    /// airdrops, V1 and V2 opens, layaway reserve, sellback, expiry, cancel_layaway, the admin
+    /// session closes and resets, card resets, deprecation and migration) must be passed the
+    /// inventory account. Other admin paths don't adjust it, so re-run this after using them.
+    pub fn set_rarity_inventory(
+        ctx: Context<SetRarityInventory>,
+        available_count: [u32; RARITY_COUNT],
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.rarity_inventory_enabled = true;
+        let inventory = &mut ctx.accounts.rarity_inventory;
+        inventory.vault_state = ctx.accounts.vault_state.key();
+        inventory.available_count = available_count;
+        inventory.bump = ctx.bumps.rarity_inventory;
+        Ok(())
+    }
+
+    /// Rebuilds the session and card counters (and the rarity inventory, when passed) from
+    /// the records themselves, e.g. after `migrate_vault_state` zeroed them. `reset` zeroes
+    /// the counters first; later calls add their accounts on top, so a vault too large for
+    /// one transaction is summed across several. Every live CardRecord and PackSessionV2 of
+    /// the vault must be passed exactly once over the run; lock the vault while it spans
+    /// transactions. Sales and fee totals have no ground truth on chain and are left alone.
+    /// remaining_accounts: [card_records and pack_sessions, in any order]
+    pub fn recompute_vault_counters<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RecomputeVaultCounters<'info>>,
+        reset: bool,
+    ) -> Result<()> {
+        let vault_key = ctx.accounts.vault_state.key();
+        let vault_state = &mut ctx.accounts.vault_state;
+        require!(
+            vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        if reset {
+            vault_state.active_session_count = 0;
+            vault_state.pending_token_sessions = 0;
+            vault_state.total_cards_deposited = 0;
+            if let Some(inventory) = ctx.accounts.rarity_inventory.as_mut() {
+                inventory.available_count = [0; RARITY_COUNT];
+            }
+        }
+
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+        let (mut records, mut sessions) = (0u32, 0u32);
+        for info in ctx.remaining_accounts.iter() {
+            require!(!seen.contains(info.key), MochiError::DuplicateCard);
+            seen.push(info.key());
+            require_keys_eq!(*info.owner, crate::ID, MochiError::InvalidPda);
+            let data = info.try_borrow_data()?;
+            require!(data.len() >= 8, MochiError::InvalidPda);
+            if data[..8] == CardRecord::discriminator() {
+                let record = CardRecord::try_deserialize(&mut &data[..])?;
+                require_keys_eq!(record.vault_state, vault_key, MochiError::VaultMismatch);
+                if !matches!(record.status, CardStatus::Burned | CardStatus::Deprecated) {
+                    vault_state.total_cards_deposited = vault_state
+                        .total_cards_deposited
+                        .checked_add(1)
+                        .ok_or(MochiError::MathOverflow)?;
+                }
+                if record.status == CardStatus::Available {
+                    if let Some(inventory) = ctx.accounts.rarity_inventory.as_mut() {
+                        inventory.add(&record.rarity)?;
+                    }
+                }
+                records += 1;
+            } else if data[..8] == PackSessionV2::discriminator() {
+                let session = PackSessionV2::try_deserialize(&mut &data[..])?;
+                let expected = Pubkey::create_program_address(
                     &[
-                        ctx.accounts.user.to_account_info(),
-                        ctx.accounts.vault_treasury.to_account_info(),
-                        ctx.accounts.system_program.to_account_info(),
+                        b"pack_session_v2",
+                        vault_key.as_ref(),
+                        session.user.as_ref(),
+                        &[session.bump],
                     ],
-                )?;
-                price
+                    &crate::ID,
+                )
+                .map_err(|_| MochiError::InvalidPda)?;
+                require_keys_eq!(info.key(), expected, MochiError::InvalidPda);
+                if session.state == PackState::PendingDecision {
+                    // Counted without the max_active_sessions cap: this restores, not opens.
+                    vault_state.active_session_count =
+                        vault_state.active_session_count.saturating_add(1);
+                    if matches!(session.currency, Currency::Token | Currency::Both) {
+                        vault_state.pending_token_sessions =
+                            vault_state.pending_token_sessions.saturating_add(1);
+                    }
+                }
+                sessions += 1;
+            } else {
+                return err!(MochiError::InvalidPda);
             }
-            Currency::Token => {
-                let price = vault_state.pack_price_usdc;
-                require!(price > 0, MochiError::InvalidPrice);
-                require!(
-                    ctx.remaining_accounts.len() >= rare_count + 2,
-                    MochiError::MissingTokenAccount
-                );
-                let token_accounts = &ctx.remaining_accounts[rare_count..];
-                let user_token: Account<TokenAccount> = Account::try_from(&token_accounts[0])?;
-                let vault_token: Account<TokenAccount> = Account::try_from(&token_accounts[1])?;
-                if let Some(mint) = vault_state.usdc_mint {
-                    require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
-                    require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
-                }
-                let cpi_accounts = Transfer {
-                    from: user_token.to_account_info(),
-                    to: vault_token.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                };
-                let cpi_ctx =
-                    CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-                token::transfer(cpi_ctx, price)?;
-                price
-            }
-        };
-
-        // Reserve Rare+ CardRecords only.
-        let mut rare_keys: Vec<Pubkey> = Vec::with_capacity(rare_count);
-        for (idx, acc_info) in ctx.remaining_accounts.iter().take(rare_count).enumerate() {
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require_keys_eq!(
-                card_record.vault_state,
-                vault_state.key(),
-                MochiError::VaultMismatch
-            );
-            require!(
-                card_record.status == CardStatus::Available,
-                MochiError::CardNotAvailable
-            );
-            require!(
-                is_rare_or_above(&card_record.rarity),
-                MochiError::CardTooCommon
-            );
-            require!(
-                card_record.template_id == rare_templates[idx],
-                MochiError::TemplateMismatch
-            );
-            card_record.status = CardStatus::Reserved;
-            card_record.owner = ctx.accounts.user.key();
-            rare_keys.push(acc_info.key());
-            persist_card_record(&card_record, acc_info)?;
         }
 
-        // Write session state
-        session.user = ctx.accounts.user.key();
-        session.currency = currency;
-        session.paid_amount = paid_amount;
-        session.created_at = now;
-        session.expires_at = now + vault_state.claim_window_seconds;
-        session.state = PackState::PendingDecision;
-        session.client_seed_hash = client_seed_hash;
-        session.rare_card_keys = rare_keys;
-        session.rare_templates = rare_templates;
-        session.total_slots = PACK_CARD_COUNT as u8;
-        session.bump = ctx.bumps.pack_session;
-        // Atomic MOCHI reward: transfer from PDA-owned vault, or mint if PDA holds mint authority.
-        let reward_amount = vault_state.reward_per_pack;
-        require!(reward_amount > 0, MochiError::RewardDisabled);
-        let mochi_mint = vault_state.mochi_mint.ok_or(MochiError::MintMismatch)?;
-        require_keys_eq!(
-            ctx.accounts.reward_mint.key(),
-            mochi_mint,
-            MochiError::MintMismatch
-        );
-        require_keys_eq!(
-            ctx.accounts.user_token_account.mint,
-            mochi_mint,
-            MochiError::MintMismatch
-        );
-        require_keys_eq!(
-            ctx.accounts.reward_vault.mint,
-            mochi_mint,
-            MochiError::MintMismatch
-        );
-        require_keys_eq!(
-            ctx.accounts.user_token_account.owner,
-            ctx.accounts.user.key(),
+        emit!(CountersRecomputed {
+            vault_state: vault_key,
+            records,
+            sessions,
+            active_session_count: vault_state.active_session_count,
+            pending_token_sessions: vault_state.pending_token_sessions,
+            total_cards_deposited: vault_state.total_cards_deposited,
+        });
+        Ok(())
+    }
+
+    /// Rotate where this vault's fees and pack payments land. When the current treasury
+    /// is the vault authority PDA, its balance must be swept out first so nothing is
+    /// left behind that only the old routing knew about.
+    pub fn set_treasury(ctx: Context<SetTreasury>, new_treasury: Pubkey) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
+        require!(
+            vault_state.is_admin(&ctx.accounts.admin.key()),
             MochiError::Unauthorized
         );
-        let vault_key = vault_state.key();
-        let seeds = &[
-            GACHA_VAULT_AUTHORITY_SEED,
-            vault_key.as_ref(),
-            &[ctx.bumps.vault_authority],
-        ];
-        let signer = &[&seeds[..]];
-        let mut rewarded = false;
-        if ctx.accounts.reward_vault.owner == ctx.accounts.vault_authority.key() {
-            require!(
-                ctx.accounts.reward_vault.amount >= reward_amount,
-                MochiError::InsufficientFunds
-            );
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.reward_vault.to_account_info(),
-                to: ctx.accounts.user_token_account.to_account_info(),
-                authority: ctx.accounts.vault_authority.to_account_info(),
-            };
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
-                signer,
-            );
-            token::transfer(cpi_ctx, reward_amount)?;
-            rewarded = true;
-        }
-        if !rewarded
-            && ctx.accounts.reward_mint.mint_authority
-                == COption::Some(ctx.accounts.vault_authority.key())
-        {
-            let cpi_accounts = MintTo {
-                mint: ctx.accounts.reward_mint.to_account_info(),
-                to: ctx.accounts.user_token_account.to_account_info(),
-                authority: ctx.accounts.vault_authority.to_account_info(),
-            };
-            let cpi_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                cpi_accounts,
-                signer,
-            );
-            token::mint_to(cpi_ctx, reward_amount)?;
-            rewarded = true;
+        require!(new_treasury != Pubkey::default(), MochiError::VaultMismatch);
+        let current = &ctx.accounts.current_treasury;
+        if current.key() == vault_state.vault_authority {
+            let floor = Rent::get()?.minimum_balance(current.data_len());
+            require!(current.lamports() <= floor, MochiError::TreasuryNotEmpty);
         }
-        require!(rewarded, MochiError::Unauthorized);
-        emit!(RewardMinted {
-            user: ctx.accounts.user.key(),
-            ata: ctx.accounts.user_token_account.key(),
-            mint: mochi_mint,
-            amount: reward_amount,
+        let old_treasury = vault_state.treasury;
+        vault_state.treasury = new_treasury;
+        emit!(TreasuryChanged {
+            vault_state: vault_state.key(),
+            old_treasury,
+            new_treasury,
         });
-        msg!("reward delivered");
         Ok(())
     }
 
-    /// Tx2 Keep path – transfers only the Rare+ assets listed in the PackSessionV2.
-    /// remaining_accounts: [rare_card_records...][core_assets...]
-    pub fn claim_pack_v2<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+    /// Admin publishes the hash of the off-chain odds table (see `odds_table_hash`).
+    pub fn set_odds_commitment(
+        ctx: Context<SetVaultConfig>,
+        odds_commitment: [u8; 32],
     ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let now = Clock::get()?.unix_timestamp;
         require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
-        require!(now <= session.expires_at, MochiError::SessionExpired);
-
-        let rare_count = session.rare_card_keys.len();
-        let (card_accounts, asset_accounts, _) =
-            split_rare_accounts(&ctx.remaining_accounts, rare_count)?;
         require!(
-            asset_accounts.len() == rare_count,
-            MochiError::InvalidCardCount
+            Clock::get()?.unix_timestamp >= ctx.accounts.vault_state.odds_locked_until,
+            MochiError::OddsLocked
         );
-
-        for i in 0..rare_count {
-            let acc_info: &AccountInfo<'info> = &card_accounts[i];
-            require_keys_eq!(
-                acc_info.key(),
-                session.rare_card_keys[i],
-                MochiError::CardKeyMismatch
-            );
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require!(
-                card_record.status == CardStatus::Reserved,
-                MochiError::CardNotReserved
-            );
-            require_keys_eq!(
-                card_record.owner,
-                ctx.accounts.user.key(),
-                MochiError::Unauthorized
-            );
-            let asset_info: &AccountInfo<'info> = &asset_accounts[i];
-            transfer_core_asset(
-                asset_info,
-                &ctx.accounts.vault_authority,
-                &ctx.accounts.vault_authority,
-                &ctx.accounts.user.to_account_info(),
-                &ctx.accounts.vault_state.key(),
-                ctx.bumps.vault_authority,
-                GACHA_VAULT_AUTHORITY_SEED,
-                &ctx.accounts.system_program.to_account_info(),
-                &ctx.accounts.mpl_core_program.to_account_info(),
-            )?;
-            card_record.status = CardStatus::UserOwned;
-            card_record.owner = ctx.accounts.user.key();
-            persist_card_record(&card_record, acc_info)?;
-        }
-
-        session.state = PackState::Accepted;
+        ctx.accounts.vault_state.odds_commitment = odds_commitment;
         Ok(())
     }
 
-    /// Tx2 Sellback path – frees Rare+ reservations and pays the refund.
-    /// remaining_accounts: [rare_card_records...][core_assets...][optional token accounts]
-    pub fn sellback_pack_v2<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+    /// Absolute cap on session lifetime so a misconfigured window can't pin inventory (0 disables).
+    pub fn set_max_session_seconds(
+        ctx: Context<SetVaultConfig>,
+        max_session_seconds: i64,
     ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let vault_state = &ctx.accounts.vault_state;
-        let now = Clock::get()?.unix_timestamp;
         require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
-        require!(now <= session.expires_at, MochiError::SessionExpired);
-
-        let payout = session
-            .paid_amount
-            .checked_mul(vault_state.buyback_bps as u64)
-            .and_then(|v| v.checked_div(10_000))
-            .ok_or(MochiError::MathOverflow)?;
-
-        let rare_count = session.rare_card_keys.len();
-        let (card_accounts, _asset_accounts, extras) =
-            split_rare_accounts(&ctx.remaining_accounts, rare_count)?;
-
-        // Pay refund
-        match session.currency {
-            Currency::Sol => {
-                let vault_key = vault_state.key();
-                let seeds = &[
-                    GACHA_VAULT_AUTHORITY_SEED,
-                    vault_key.as_ref(),
-                    &[ctx.bumps.vault_authority],
-                ];
-                let signer = &[&seeds[..]];
-                invoke_signed(
-                    &system_instruction::transfer(
-                        &ctx.accounts.vault_authority.key(),
-                        &ctx.accounts.user.key(),
-                        payout,
-                    ),
-                    &[
-                        ctx.accounts.vault_authority.to_account_info(),
-                        ctx.accounts.user.to_account_info(),
-                        ctx.accounts.system_program.to_account_info(),
-                    ],
-                    signer,
-                )?;
-            }
-            Currency::Token => {
-                require!(extras.len() >= 2, MochiError::MissingTokenAccount);
-                let user_token: Account<TokenAccount> = Account::try_from(&extras[0])?;
-                let vault_token: Account<TokenAccount> = Account::try_from(&extras[1])?;
-                if let Some(mint) = vault_state.usdc_mint {
-                    require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
-                    require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
-                }
-                let vault_key = vault_state.key();
-                let seeds = &[
-                    GACHA_VAULT_AUTHORITY_SEED,
-                    vault_key.as_ref(),
-                    &[ctx.bumps.vault_authority],
-                ];
-                let signer = &[&seeds[..]];
-                let cpi_accounts = Transfer {
-                    from: vault_token.to_account_info(),
-                    to: user_token.to_account_info(),
-                    authority: ctx.accounts.vault_authority.to_account_info(),
-                };
-                let cpi_ctx = CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    cpi_accounts,
-                    signer,
-                );
-                token::transfer(cpi_ctx, payout)?;
-            }
-        }
-
-        for (idx, acc_info) in card_accounts.iter().enumerate() {
-            require_keys_eq!(
-                acc_info.key(),
-                session.rare_card_keys[idx],
-                MochiError::CardKeyMismatch
-            );
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require!(
-                card_record.status == CardStatus::Reserved,
-                MochiError::CardNotReserved
-            );
-            require_keys_eq!(
-                card_record.owner,
-                ctx.accounts.user.key(),
-                MochiError::Unauthorized
-            );
-            card_record.status = CardStatus::Available;
-            card_record.owner = ctx.accounts.vault_authority.key();
-            persist_card_record(&card_record, acc_info)?;
-        }
-
-        session.state = PackState::Rejected;
+        require!(max_session_seconds >= 0, MochiError::InvalidWindow);
+        ctx.accounts.vault_state.max_session_seconds = max_session_seconds;
         Ok(())
     }
 
-    /// Post-window cleanup – frees Rare+ reservations without payout.
-    pub fn expire_session_v2<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+    /// Layaway (`reserve_pack`): how long a part-paid reservation may stay open and the
+    /// minimum deposit in bps of `pack_price_sol`. A zero window disables layaway.
+    pub fn set_layaway_config(
+        ctx: Context<SetVaultConfig>,
+        layaway_window_seconds: i64,
+        layaway_min_deposit_bps: u16,
     ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let now = Clock::get()?.unix_timestamp;
         require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
-        require!(now > session.expires_at, MochiError::SessionNotExpired);
+        require!(layaway_window_seconds >= 0, MochiError::InvalidWindow);
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.layaway_window_seconds = layaway_window_seconds;
+        vault_state.layaway_min_deposit_bps = layaway_min_deposit_bps.min(10_000);
+        Ok(())
+    }
 
-        let rare_count = session.rare_card_keys.len();
-        let (card_accounts, _assets, _) = split_rare_accounts(&ctx.remaining_accounts, rare_count)?;
-        for (idx, acc_info) in card_accounts.iter().enumerate() {
-            require_keys_eq!(
-                acc_info.key(),
-                session.rare_card_keys[idx],
-                MochiError::CardKeyMismatch
-            );
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require!(
-                card_record.status == CardStatus::Reserved,
-                MochiError::CardNotReserved
-            );
-            card_record.status = CardStatus::Available;
-            card_record.owner = ctx.accounts.vault_authority.key();
-            persist_card_record(&card_record, acc_info)?;
-        }
-
-        session.state = PackState::Expired;
+    /// Caps how many cards of one template can ever be deposited (0 = unlimited).
+    pub fn set_max_per_template(ctx: Context<SetVaultConfig>, max_per_template: u32) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        ctx.accounts.vault_state.max_per_template = max_per_template;
         Ok(())
     }
 
-    /// Admin-only hard reset for V2 sessions; frees any passed Rare+ CardRecords.
-    pub fn admin_force_close_v2<'info>(
-        ctx: Context<'_, '_, 'info, 'info, AdminForceCloseV2<'info>>,
-    ) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
+    /// Caps the live cards across all templates (0 = unlimited). Existing cards are counted
+    /// only from the deposits and burns made after the counter was introduced.
+    pub fn set_max_total_cards(ctx: Context<SetVaultConfig>, max_total_cards: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
             MochiError::Unauthorized
         );
-        let session = &mut ctx.accounts.pack_session;
-        let rare_count = session.rare_card_keys.len();
-        let (card_accounts, _, _) = split_rare_accounts(&ctx.remaining_accounts, rare_count)?;
-        for acc_info in card_accounts.iter() {
-            if let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) {
-                if card_record.vault_state == ctx.accounts.vault_state.key() {
-                    card_record.status = CardStatus::Available;
-                    card_record.owner = ctx.accounts.vault_authority.key();
-                    persist_card_record(&card_record, acc_info)?;
-                }
-            }
-        }
-
-        // Zero session but keep account alive for the user; they can reuse it on next open.
-        session.state = PackState::Uninitialized;
-        session.paid_amount = 0;
-        session.created_at = 0;
-        session.expires_at = 0;
-        session.currency = Currency::Sol;
-        session.rare_card_keys.clear();
-        session.rare_templates.clear();
-        session.total_slots = PACK_CARD_COUNT as u8;
+        ctx.accounts.vault_state.max_total_cards = max_total_cards;
         Ok(())
     }
 
-    pub fn open_pack_start<'info>(
-        ctx: Context<'_, '_, 'info, 'info, OpenPackStart<'info>>,
-        currency: Currency,
-        client_seed_hash: [u8; 32],
-        rarity_prices: Vec<u64>,
-    ) -> Result<()> {
-        let vault_state = &ctx.accounts.vault_state;
-        let now = Clock::get()?.unix_timestamp;
-
-        let (card_accounts, _asset_accounts, extra_accounts) =
-            partition_pack_accounts(&ctx.remaining_accounts)?;
-        msg!("open_pack_start rem len {}", ctx.remaining_accounts.len());
-        for (i, ai) in ctx.remaining_accounts.iter().enumerate() {
-            msg!("  rem[{}] = {}", i, ai.key);
-        }
-        let mut user_token: Option<Account<'info, TokenAccount>> = None;
-        let mut vault_token: Option<Account<'info, TokenAccount>> = None;
-        if currency == Currency::Token {
-            require!(extra_accounts.len() >= 2, MochiError::MissingTokenAccount);
-            user_token = Some(Account::try_from(&extra_accounts[0])?);
-            vault_token = Some(Account::try_from(&extra_accounts[1])?);
-        }
-
-        // Payment handling (simplified). For SOL we move lamports; for tokens we debit from user token account.
-        match currency {
-            Currency::Sol => {
-                let price = vault_state.pack_price_sol;
-                require!(price > 0, MochiError::InvalidPrice);
-                require!(
-                    ctx.accounts.user.lamports() >= price,
-                    MochiError::InsufficientFunds
-                );
-                invoke(
-                    &system_instruction::transfer(
-                        &ctx.accounts.user.key(),
-                        &ctx.accounts.vault_treasury.key(),
-                        price,
-                    ),
-                    &[
-                        ctx.accounts.user.to_account_info(),
-                        ctx.accounts.vault_treasury.to_account_info(),
-                        ctx.accounts.system_program.to_account_info(),
-                    ],
-                )?;
-            }
-            Currency::Token => {
-                let price = vault_state.pack_price_usdc;
-                require!(price > 0, MochiError::InvalidPrice);
-                let user_token = user_token.as_ref().ok_or(MochiError::MissingTokenAccount)?;
-                let vault_token = vault_token
-                    .as_ref()
-                    .ok_or(MochiError::MissingTokenAccount)?;
-                if let Some(mint) = vault_state.usdc_mint {
-                    require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
-                    require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
-                }
-                let price = vault_state.pack_price_usdc;
-                let cpi_accounts = Transfer {
-                    from: user_token.to_account_info(),
-                    to: vault_token.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                };
-                let cpi_ctx =
-                    CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-                token::transfer(cpi_ctx, price)?;
-            }
-        }
-
-        let mut card_record_keys: [Pubkey; PACK_CARD_COUNT] = [Pubkey::default(); PACK_CARD_COUNT];
-
-        let session = &mut ctx.accounts.pack_session;
+    /// Freezes the current odds commitment until `until_ts` for a limited-time event.
+    /// A lock can be extended but never shortened, so an advertised window always holds.
+    pub fn lock_odds(ctx: Context<SetVaultConfig>, until_ts: i64) -> Result<()> {
+        let vault_state = &mut ctx.accounts.vault_state;
         require!(
-            matches!(
-                session.state,
-                PackState::Uninitialized
-                    | PackState::Accepted
-                    | PackState::Rejected
-                    | PackState::Expired
-            ),
-            MochiError::SessionExists
+            vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
-        session.user = ctx.accounts.user.key();
-        session.currency = currency.clone();
-        session.paid_amount = match currency {
-            Currency::Sol => vault_state.pack_price_sol,
-            Currency::Token => vault_state.pack_price_usdc,
-        };
-        session.created_at = now;
-        session.expires_at = now + vault_state.claim_window_seconds;
-        session.state = PackState::PendingDecision;
-        session.client_seed_hash = client_seed_hash;
-        session.rarity_prices = rarity_prices;
-
-        // Validate + Reserve CardRecords in one pass
-        for (idx, acc_info) in card_accounts.iter().enumerate() {
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require_keys_eq!(
-                card_record.vault_state,
-                ctx.accounts.vault_state.key(),
-                MochiError::VaultMismatch
-            );
-            require!(
-                card_record.status == CardStatus::Available,
-                MochiError::CardNotAvailable
-            );
-            card_record_keys[idx] = acc_info.key();
-            card_record.status = CardStatus::Reserved;
-            card_record.owner = ctx.accounts.user.key();
-            // Manually serialize because we constructed Account<T> from raw AccountInfo
-            let mut data = acc_info.try_borrow_mut_data()?;
-            let mut cursor = std::io::Cursor::new(&mut data[..]);
-            card_record.try_serialize(&mut cursor)?;
-        }
-        session.card_record_keys = card_record_keys;
+        require!(
+            until_ts > Clock::get()?.unix_timestamp,
+            MochiError::InvalidWindow
+        );
+        require!(
+            until_ts >= vault_state.odds_locked_until,
+            MochiError::OddsLocked
+        );
+        vault_state.odds_locked_until = until_ts;
         Ok(())
     }
 
-    pub fn claim_pack<'info>(ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let now = Clock::get()?.unix_timestamp;
+    /// Permissionless check that a published odds table matches the on-chain commitment.
+    pub fn verify_odds(ctx: Context<VerifyOdds>, odds_table: Vec<u16>) -> Result<()> {
         require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
+            odds_table_hash(&odds_table) == ctx.accounts.vault_state.odds_commitment,
+            MochiError::OddsMismatch
         );
-        require!(now <= session.expires_at, MochiError::SessionExpired);
+        Ok(())
+    }
 
-        let (card_accounts, asset_accounts, _extras) =
-            partition_pack_accounts(&ctx.remaining_accounts)?;
-        msg!(
-            "claim_pack: cards {} assets {} rarity_prices_len {} state {:?}",
-            card_accounts.len(),
-            asset_accounts.len(),
-            session.rarity_prices.len(),
-            session.state
+    /// Permissionless check that the stored vault authority and its bump still match the
+    /// canonical PDA. Signing instructions rederive the bump through `ctx.bumps`, so a stored
+    /// bump left wrong by a migration would otherwise go unnoticed. Works on gacha and
+    /// marketplace vaults; fails with VaultMismatch on drift.
+    pub fn verify_vault_authority_bump(ctx: Context<VerifyVaultAuthorityBump>) -> Result<()> {
+        let vault_state = &ctx.accounts.vault_state;
+        let vault_key = vault_state.key();
+        let (gacha_authority, gacha_bump) = Pubkey::find_program_address(
+            &[GACHA_VAULT_AUTHORITY_SEED, vault_key.as_ref()],
+            ctx.program_id,
+        );
+        let (canonical, canonical_bump) = if vault_state.vault_authority == gacha_authority {
+            (gacha_authority, gacha_bump)
+        } else {
+            Pubkey::find_program_address(
+                &[MARKETPLACE_VAULT_AUTHORITY_SEED, vault_key.as_ref()],
+                ctx.program_id,
+            )
+        };
+        require_keys_eq!(
+            vault_state.vault_authority,
+            canonical,
+            MochiError::VaultMismatch
         );
         require!(
-            asset_accounts.len() == PACK_CARD_COUNT,
-            MochiError::InvalidCardCount
+            vault_state.vault_authority_bump == canonical_bump,
+            MochiError::VaultMismatch
         );
-        // Defensive: ensure rarity_prices never allocates huge vec on deserialize
-        if session.rarity_prices.len() > PACK_CARD_COUNT {
-            session.rarity_prices.truncate(PACK_CARD_COUNT);
-        }
-        for i in 0..PACK_CARD_COUNT {
-            let acc_info: &AccountInfo<'info> = &card_accounts[i];
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require!(
-                card_record.status == CardStatus::Reserved,
-                MochiError::CardNotReserved
-            );
-            require_keys_eq!(
-                card_record.owner,
-                ctx.accounts.user.key(),
-                MochiError::Unauthorized
-            );
-            msg!("claim idx {} card {}", i, acc_info.key());
-            card_record.status = CardStatus::UserOwned;
-            card_record.owner = ctx.accounts.user.key();
-            // Transfer Core asset to user
-            let asset_info: &AccountInfo<'info> = &asset_accounts[i];
-            msg!("claim transfer asset {}", asset_info.key());
-            transfer_core_asset(
-                &asset_info,
-                &ctx.accounts.vault_authority,
-                &ctx.accounts.vault_authority, // payer = vault authority
-                &ctx.accounts.user.to_account_info(),
-                &ctx.accounts.vault_state.key(),
-                ctx.bumps.vault_authority,
-                GACHA_VAULT_AUTHORITY_SEED,
-                &ctx.accounts.system_program.to_account_info(),
-                &ctx.accounts.mpl_core_program.to_account_info(),
-            )?;
-            msg!("claim transfer done {}", asset_info.key());
-            // Persist card_record changes
-            let mut data = acc_info.try_borrow_mut_data()?;
-            let mut cursor = std::io::Cursor::new(&mut data[..]);
-            card_record.try_serialize(&mut cursor)?;
-        }
-
-        session.state = PackState::Accepted;
         Ok(())
     }
 
-    /// New: claim selected cards in smaller batches to reduce heap/CU pressure.
-    /// remaining_accounts = [card_records..., core_assets...] with equal lengths >0.
-    pub fn claim_pack_batch<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
+    /// One-time migration to grow the VaultState account to the new size that includes MOCHI rewards.
+    pub fn migrate_vault_state(
+        ctx: Context<MigrateVaultState>,
+        args: MigrateVaultStateArgs,
     ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let now = Clock::get()?.unix_timestamp;
-        require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
+        let MigrateVaultStateArgs {
+            pack_price_sol,
+            pack_price_usdc,
+            buyback_bps,
+            claim_window_seconds,
+            marketplace_fee_bps,
+            usdc_mint,
+            mochi_mint,
+            reward_per_pack,
+            treasury,
+        } = args;
+        require!(claim_window_seconds > 0, MochiError::InvalidWindow);
+        let admin_key = ctx.accounts.admin.key();
+        let vault_key = ctx.accounts.vault_state.key();
+        let (expected_vault_auth, vault_bump) = Pubkey::find_program_address(
+            &[GACHA_VAULT_AUTHORITY_SEED, vault_key.as_ref()],
+            ctx.program_id,
         );
-        require!(now <= session.expires_at, MochiError::SessionExpired);
 
-        let (card_accounts, asset_accounts, _extras) =
-            partition_half_accounts(&ctx.remaining_accounts)?;
-        // Restrict batch size to 1 or 2 to avoid heap blowups.
-        require!(
-            card_accounts.len() > 0 && card_accounts.len() <= 2,
-            MochiError::InvalidCardCount
-        );
-        for i in 0..card_accounts.len() {
-            let acc_info: &AccountInfo<'info> = &card_accounts[i];
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require!(
-                card_record.status == CardStatus::Reserved,
-                MochiError::CardNotReserved
-            );
-            require_keys_eq!(
-                card_record.owner,
-                ctx.accounts.user.key(),
-                MochiError::Unauthorized
-            );
-            card_record.status = CardStatus::UserOwned;
-            card_record.owner = ctx.accounts.user.key();
-            let asset_info: &AccountInfo<'info> = &asset_accounts[i];
-            transfer_core_asset(
-                &asset_info,
-                &ctx.accounts.vault_authority,
-                &ctx.accounts.vault_authority,
-                &ctx.accounts.user.to_account_info(),
-                &ctx.accounts.vault_state.key(),
-                ctx.bumps.vault_authority,
-                GACHA_VAULT_AUTHORITY_SEED,
-                &ctx.accounts.system_program.to_account_info(),
-                &ctx.accounts.mpl_core_program.to_account_info(),
-            )?;
-            let mut data = acc_info.try_borrow_mut_data()?;
-            let mut cursor = std::io::Cursor::new(&mut data[..]);
-            card_record.try_serialize(&mut cursor)?;
-        }
-        // Keep session pending; frontend/backend should call finalize_claim when all cards processed.
-        Ok(())
-    }
+        // Ensure account is large enough and rent-exempt for the expanded struct.
+        let target_len: usize = 8 + VaultState::SIZE;
+        let rent = Rent::get()?;
+        let required_lamports = rent.minimum_balance(target_len);
+        let vault_info = ctx.accounts.vault_state.to_account_info();
 
-    /// Test helper: claim exactly 3 cards in one ix (for benchmarking); minimal logging.
-    pub fn claim_pack_batch3<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
-    ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let now = Clock::get()?.unix_timestamp;
-        require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
-        );
-        require!(now <= session.expires_at, MochiError::SessionExpired);
+        require!(vault_info.owner == ctx.program_id, MochiError::Unauthorized);
 
-        let (card_accounts, asset_accounts, _extras) =
-            partition_half_accounts(&ctx.remaining_accounts)?;
-        require!(card_accounts.len() == 3, MochiError::InvalidCardCount);
-        for i in 0..card_accounts.len() {
-            let acc_info: &AccountInfo<'info> = &card_accounts[i];
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+        // Only the stored admin may migrate, and every field appended after `treasury`
+        // (escrowed layaway lamports included) must survive the rewrite.
+        let appended = {
+            let data = vault_info.try_borrow_data()?;
+            require!(data.len() >= 8 + 32, MochiError::Unauthorized);
             require!(
-                card_record.status == CardStatus::Reserved,
-                MochiError::CardNotReserved
-            );
-            require_keys_eq!(
-                card_record.owner,
-                ctx.accounts.user.key(),
+                data[8..8 + 32] == admin_key.to_bytes(),
                 MochiError::Unauthorized
             );
-            card_record.status = CardStatus::UserOwned;
-            card_record.owner = ctx.accounts.user.key();
-            let asset_info: &AccountInfo<'info> = &asset_accounts[i];
-            transfer_core_asset(
-                &asset_info,
-                &ctx.accounts.vault_authority,
-                &ctx.accounts.vault_authority,
-                &ctx.accounts.user.to_account_info(),
-                &ctx.accounts.vault_state.key(),
-                ctx.bumps.vault_authority,
-                GACHA_VAULT_AUTHORITY_SEED,
-                &ctx.accounts.system_program.to_account_info(),
-                &ctx.accounts.mpl_core_program.to_account_info(),
+            let end = data.len().min(target_len);
+            data.get(VAULT_STATE_BASE_LEN..end)
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default()
+        };
+
+        if vault_info.lamports() < required_lamports {
+            let diff = required_lamports
+                .checked_sub(vault_info.lamports())
+                .ok_or(MochiError::MathOverflow)?;
+            invoke(
+                &system_instruction::transfer(&ctx.accounts.admin.key(), vault_info.key, diff),
+                &[
+                    ctx.accounts.admin.to_account_info(),
+                    vault_info.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
             )?;
-            let mut data = acc_info.try_borrow_mut_data()?;
-            let mut cursor = std::io::Cursor::new(&mut data[..]);
-            card_record.try_serialize(&mut cursor)?;
         }
-        Ok(())
-    }
 
-    /// New: finalize after all cards are user-owned; sets state = Accepted.
-    /// remaining_accounts should include all card_record PDAs for verification.
-    pub fn finalize_claim<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
-    ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let now = Clock::get()?.unix_timestamp;
-        require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
-        );
-        require!(now <= session.expires_at, MochiError::SessionExpired);
-        for acc_info in ctx.remaining_accounts.iter() {
-            let card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            require!(
-                card_record.status == CardStatus::UserOwned,
-                MochiError::CardNotReserved
-            );
-            require_keys_eq!(
-                card_record.owner,
-                ctx.accounts.user.key(),
-                MochiError::Unauthorized
-            );
-        }
-        session.state = PackState::Accepted;
-        Ok(())
-    }
+        vault_info.realloc(target_len, false)?;
 
-    pub fn sellback_pack<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
-    ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let vault_state = &ctx.accounts.vault_state;
-        let now = Clock::get()?.unix_timestamp;
-        require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
-        );
-        require!(now <= session.expires_at, MochiError::SessionExpired);
+        // Manually write the struct to guarantee deterministic layout and overwrite any legacy bytes.
+        let mut data = vault_info.try_borrow_mut_data()?;
+        data.fill(0);
+        // Discriminator
+        data[..8].copy_from_slice(&VaultState::discriminator());
+        let mut offset = 8;
 
-        let total_value: u64 = session.rarity_prices.iter().copied().sum();
-        let payout = total_value
-            .checked_mul(vault_state.buyback_bps as u64)
-            .and_then(|x| x.checked_div(10_000))
-            .ok_or(MochiError::MathOverflow)?;
+        // admin
+        data[offset..offset + 32].copy_from_slice(admin_key.as_ref());
+        offset += 32;
+        // vault_authority
+        data[offset..offset + 32].copy_from_slice(expected_vault_auth.as_ref());
+        offset += 32;
+        // pack_price_sol
+        data[offset..offset + 8].copy_from_slice(&pack_price_sol.to_le_bytes());
+        offset += 8;
+        // pack_price_usdc
+        data[offset..offset + 8].copy_from_slice(&pack_price_usdc.to_le_bytes());
+        offset += 8;
+        // buyback_bps (u16)
+        data[offset..offset + 2].copy_from_slice(&buyback_bps.to_le_bytes());
+        offset += 2;
+        // claim_window_seconds (i64)
+        data[offset..offset + 8].copy_from_slice(&claim_window_seconds.to_le_bytes());
+        offset += 8;
+        // marketplace_fee_bps (u16)
+        data[offset..offset + 2].copy_from_slice(&marketplace_fee_bps.to_le_bytes());
+        offset += 2;
 
-        let (card_accounts, asset_accounts, extra_accounts) =
-            partition_pack_accounts(&ctx.remaining_accounts)?;
-        require!(
-            asset_accounts.len() == PACK_CARD_COUNT,
-            MochiError::InvalidCardCount
-        );
+        // core_collection: None => flag 0
+        data[offset] = 0;
+        offset += 1 + 32; // keep layout consistent with SIZE even though value is None.
 
-        match session.currency {
-            Currency::Sol => {
-                invoke(
-                    &system_instruction::transfer(
-                        &ctx.accounts.vault_treasury.key(),
-                        &ctx.accounts.user.key(),
-                        payout,
-                    ),
-                    &[
-                        ctx.accounts.vault_treasury.to_account_info(),
-                        ctx.accounts.user.to_account_info(),
-                        ctx.accounts.system_program.to_account_info(),
-                    ],
-                )?;
-            }
-            Currency::Token => {
-                require!(extra_accounts.len() >= 2, MochiError::MissingTokenAccount);
-                let user_token: Account<TokenAccount> = Account::try_from(&extra_accounts[0])?;
-                let vault_token: Account<TokenAccount> = Account::try_from(&extra_accounts[1])?;
-                if let Some(mint) = vault_state.usdc_mint {
-                    require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
-                    require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
-                }
-                let cpi_accounts = Transfer {
-                    from: vault_token.to_account_info(),
-                    to: user_token.to_account_info(),
-                    authority: ctx.accounts.vault_authority.to_account_info(),
-                };
-                let vault_key = vault_state.key();
-                let seeds = &[
-                    GACHA_VAULT_AUTHORITY_SEED,
-                    vault_key.as_ref(),
-                    &[ctx.bumps.vault_authority],
-                ];
-                let signer = &[&seeds[..]];
-                let cpi_ctx = CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    cpi_accounts,
-                    signer,
-                );
-                token::transfer(cpi_ctx, payout)?;
+        // usdc_mint option
+        match usdc_mint {
+            Some(pk) => {
+                data[offset] = 1;
+                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
             }
+            None => data[offset] = 0,
         }
+        offset += 1 + 32;
 
-        for acc_info in card_accounts.iter() {
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            card_record.status = CardStatus::Available;
-            card_record.owner = ctx.accounts.vault_authority.key();
-            // Assets remain in vault authority escrow; no transfer needed
+        // mochi_mint option
+        match mochi_mint {
+            Some(pk) => {
+                data[offset] = 1;
+                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
+            }
+            None => data[offset] = 0,
         }
+        offset += 1 + 32;
 
-        session.state = PackState::Rejected;
-        Ok(())
-    }
+        // reward_per_pack
+        data[offset..offset + 8].copy_from_slice(&reward_per_pack.to_le_bytes());
+        offset += 8;
 
-    pub fn expire_session<'info>(
-        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
-    ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
-        let now = Clock::get()?.unix_timestamp;
-        require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
-        );
-        require!(now > session.expires_at, MochiError::SessionNotExpired);
+        // vault_authority_bump
+        data[offset] = vault_bump;
+        offset += 1;
 
-        let (card_accounts, _asset_accounts, _extras) =
-            partition_pack_accounts(&ctx.remaining_accounts)?;
-        for acc_info in card_accounts.iter() {
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            card_record.status = CardStatus::Available;
-            card_record.owner = ctx.accounts.vault_authority.key();
-        }
+        // padding (7 bytes) + max_window_override (0)
+        offset += 7 + 8;
 
-        session.state = PackState::Expired;
+        // treasury
+        data[offset..offset + 32].copy_from_slice(treasury.as_ref());
+
+        // Appended fields keep their prior values; bytes past the old length stay zeroed.
+        data[VAULT_STATE_BASE_LEN..VAULT_STATE_BASE_LEN + appended.len()]
+            .copy_from_slice(&appended);
         Ok(())
     }
 
-    pub fn admin_force_expire<'info>(
-        ctx: Context<'_, '_, 'info, 'info, AdminForceExpire<'info>>,
+    /// One-time migration to grow the marketplace VaultState PDA to the expanded size.
+    pub fn migrate_marketplace_vault(
+        ctx: Context<MigrateMarketplaceVault>,
+        marketplace_fee_bps: u16,
+        core_collection: Option<Pubkey>,
+        usdc_mint: Option<Pubkey>,
+        mochi_mint: Option<Pubkey>,
+        treasury: Pubkey,
     ) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
-            MochiError::Unauthorized
-        );
-        let session = &mut ctx.accounts.pack_session;
-        require!(
-            session.state == PackState::PendingDecision,
-            MochiError::InvalidSessionState
+        let admin_key = ctx.accounts.admin.key();
+        let vault_key = ctx.accounts.vault_state.key();
+        let (expected_vault_auth, vault_bump) = Pubkey::find_program_address(
+            &[MARKETPLACE_VAULT_AUTHORITY_SEED, vault_key.as_ref()],
+            ctx.program_id,
         );
 
-        let (card_accounts, _asset_accounts, _extras) =
-            partition_pack_accounts(&ctx.remaining_accounts)?;
-        for acc_info in card_accounts.iter() {
-            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
-            card_record.status = CardStatus::Available;
-            card_record.owner = ctx.accounts.vault_authority.key();
+        let target_len: usize = 8 + VaultState::SIZE;
+        let rent = Rent::get()?;
+        let required_lamports = rent.minimum_balance(target_len);
+        let vault_info = ctx.accounts.vault_state.to_account_info();
+
+        require!(vault_info.owner == ctx.program_id, MochiError::Unauthorized);
+
+        // Buyer lamports already held for pending fill commits must survive the rewrite,
+        // otherwise those commits could never be refunded or filled.
+        let escrowed_lamports = {
+            let data = vault_info.try_borrow_data()?;
+            if data.len() >= ESCROWED_LAMPORTS_OFFSET + 8 {
+                u64::from_le_bytes(
+                    data[ESCROWED_LAMPORTS_OFFSET..ESCROWED_LAMPORTS_OFFSET + 8]
+                        .try_into()
+                        .unwrap(),
+                )
+            } else {
+                0
+            }
+        };
+
+        if vault_info.lamports() < required_lamports {
+            let diff = required_lamports
+                .checked_sub(vault_info.lamports())
+                .ok_or(MochiError::MathOverflow)?;
+            invoke(
+                &system_instruction::transfer(&admin_key, vault_info.key, diff),
+                &[
+                    ctx.accounts.admin.to_account_info(),
+                    vault_info.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
         }
 
-        session.state = PackState::Expired;
-        Ok(())
-    }
+        // Grow account to the new size and zero-fill.
+        vault_info.realloc(target_len, false)?;
+        let mut data = vault_info.try_borrow_mut_data()?;
+        data.fill(0);
+        data[..8].copy_from_slice(&VaultState::discriminator());
+        data[ESCROWED_LAMPORTS_OFFSET..ESCROWED_LAMPORTS_OFFSET + 8]
+            .copy_from_slice(&escrowed_lamports.to_le_bytes());
+        let mut offset = 8;
 
-    pub fn admin_reset_session<'info>(
-        ctx: Context<'_, '_, 'info, 'info, AdminResetSession<'info>>,
-    ) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
-            MochiError::Unauthorized
-        );
+        // admin
+        data[offset..offset + 32].copy_from_slice(admin_key.as_ref());
+        offset += 32;
+        // vault_authority
+        data[offset..offset + 32].copy_from_slice(expected_vault_auth.as_ref());
+        offset += 32;
+        // pack_price_sol
+        data[offset..offset + 8].copy_from_slice(&0u64.to_le_bytes());
+        offset += 8;
+        // pack_price_usdc
+        data[offset..offset + 8].copy_from_slice(&0u64.to_le_bytes());
+        offset += 8;
+        // buyback_bps (u16)
+        data[offset..offset + 2].copy_from_slice(&0u16.to_le_bytes());
+        offset += 2;
+        // claim_window_seconds (i64)
+        data[offset..offset + 8].copy_from_slice(&0i64.to_le_bytes());
+        offset += 8;
+        // marketplace_fee_bps (u16)
+        data[offset..offset + 2].copy_from_slice(&marketplace_fee_bps.to_le_bytes());
+        offset += 2;
 
-        // Optionally free any card records passed in remaining accounts.
-        for acc_info in ctx.remaining_accounts.iter() {
-            if let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) {
-                if card_record.vault_state == ctx.accounts.vault_state.key() {
-                    card_record.status = CardStatus::Available;
-                    card_record.owner = ctx.accounts.vault_authority.key();
-                }
+        // core_collection option
+        match core_collection {
+            Some(pk) => {
+                data[offset] = 1;
+                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
             }
+            None => data[offset] = 0,
         }
+        offset += 1 + 32;
 
-        let session = &mut ctx.accounts.pack_session;
-        require!(
-            session.state != PackState::PendingDecision,
-            MochiError::InvalidSessionState
-        );
-        session.state = PackState::Uninitialized;
-        session.paid_amount = 0;
-        session.created_at = 0;
-        session.expires_at = 0;
-        session.currency = Currency::Sol;
-        session.card_record_keys = [Pubkey::default(); PACK_CARD_COUNT];
-        session.client_seed_hash = [0u8; 32];
-        session.rarity_prices = Vec::new();
+        // usdc_mint option
+        match usdc_mint {
+            Some(pk) => {
+                data[offset] = 1;
+                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
+            }
+            None => data[offset] = 0,
+        }
+        offset += 1 + 32;
+
+        // mochi_mint option
+        match mochi_mint {
+            Some(pk) => {
+                data[offset] = 1;
+                data[offset + 1..offset + 33].copy_from_slice(pk.as_ref());
+            }
+            None => data[offset] = 0,
+        }
+        offset += 1 + 32;
+
+        // reward_per_pack
+        data[offset..offset + 8].copy_from_slice(&0u64.to_le_bytes());
+        offset += 8;
+
+        // vault_authority_bump
+        data[offset] = vault_bump;
+        offset += 1;
+
+        // padding + max_window_override already zeroed
+        offset += 7 + 8;
+
+        // treasury
+        data[offset..offset + 32].copy_from_slice(treasury.as_ref());
         Ok(())
     }
 
-    pub fn user_reset_session<'info>(
-        ctx: Context<'_, '_, 'info, 'info, UserResetSession<'info>>,
-    ) -> Result<()> {
-        let session = &mut ctx.accounts.pack_session;
+    pub fn deposit_card(ctx: Context<DepositCard>, template_id: u32, rarity: Rarity) -> Result<()> {
         require!(
-            session.state != PackState::PendingDecision,
-            MochiError::InvalidSessionState
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
         );
 
-        for acc_info in ctx.remaining_accounts.iter() {
-            if let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) {
-                if card_record.vault_state == ctx.accounts.vault_state.key() {
-                    card_record.status = CardStatus::Available;
-                    card_record.owner = ctx.accounts.vault_authority.key();
-                }
-            }
+        let max_per_template = ctx.accounts.vault_state.max_per_template;
+        let stats = &mut ctx.accounts.template_stats;
+        if stats.vault_state == Pubkey::default() {
+            stats.vault_state = ctx.accounts.vault_state.key();
+            stats.template_id = template_id;
+            stats.bump = ctx.bumps.template_stats;
         }
-        // Account will be closed to user via `close = user` attribute.
-        Ok(())
-    }
-
-    pub fn list_card(
-        ctx: Context<ListCard>,
-        price_lamports: u64,
-        currency_mint: Option<Pubkey>,
-        template_id: u32,
-        rarity: Rarity,
-    ) -> Result<()> {
-        // Enforce canonical marketplace vault PDA so listings cannot target a bogus vault.
-        let (expected_vault, _) =
-            Pubkey::find_program_address(&[MARKETPLACE_VAULT_SEED], ctx.program_id);
-        require_keys_eq!(
-            ctx.accounts.vault_state.key(),
-            expected_vault,
-            MochiError::VaultMismatch
+        require!(
+            max_per_template == 0 || stats.deposited_count < max_per_template,
+            MochiError::TemplateCapReached
         );
+        stats.deposited_count = stats
+            .deposited_count
+            .checked_add(1)
+            .ok_or(MochiError::MathOverflow)?;
+        ctx.accounts.vault_state.count_deposit()?;
 
-        let vault_key = ctx.accounts.vault_state.key();
-        let core_key = ctx.accounts.core_asset.key();
-        let seller_key = ctx.accounts.seller.key();
+        if let Some(inventory) = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )? {
+            inventory.add(&rarity)?;
+        }
 
-        // Load or initialize the CardRecord with the canonical marketplace seeds.
         let record = &mut ctx.accounts.card_record;
-        let is_uninitialized = record.vault_state == Pubkey::default();
-        if is_uninitialized {
-            record.vault_state = vault_key;
-            record.core_asset = core_key;
-            record.template_id = template_id;
-            record.rarity = rarity.clone();
-            record.status = CardStatus::UserOwned;
-            record.owner = seller_key;
-        } else {
-            require_keys_eq!(record.vault_state, vault_key, MochiError::VaultMismatch);
-            require_keys_eq!(record.core_asset, core_key, MochiError::AssetMismatch);
-            require!(
-                record.template_id == template_id,
-                MochiError::TemplateMismatch
-            );
-            require!(record.rarity == rarity, MochiError::RarityMismatch);
-        }
+        record.vault_state = ctx.accounts.vault_state.key();
+        record.core_asset = ctx.accounts.core_asset.key();
+        record.template_id = template_id;
+        record.rarity = rarity;
+        record.status = CardStatus::Available;
+        record.owner = ctx.accounts.vault_authority.key();
+
+        // NOTE: Real implementation should CPI-transfer Metaplex Core asset into the vault_authority PDA escrow.
+        // Placeholder until Core CPI wiring is finalized.
+        Ok(())
+    }
 
+    /// Hold an Available card for `buyer` (pre-sales / VIP allocations). The card is Reserved,
+    /// so packs can't pull it; only `buyer` can claim it, until the admin unreserves it.
+    pub fn reserve_for(ctx: Context<ReserveFor>, buyer: Pubkey) -> Result<()> {
         require!(
-            record.owner == seller_key || record.owner == ctx.accounts.vault_authority.key(),
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
             MochiError::Unauthorized
         );
+        let record = &mut ctx.accounts.card_record;
         require!(
-            record.status == CardStatus::UserOwned || record.status == CardStatus::Available,
+            record.status == CardStatus::Available,
             MochiError::CardNotAvailable
         );
-
-        // Move custody into the marketplace vault if the seller still holds the asset.
-        if record.owner != ctx.accounts.vault_authority.key() {
-            transfer_core_asset_user(
-                &ctx.accounts.core_asset,
-                &ctx.accounts.seller.to_account_info(),
-                &ctx.accounts.seller.to_account_info(),
-                &ctx.accounts.vault_authority.to_account_info(),
-                &ctx.accounts.system_program.to_account_info(),
-                &ctx.accounts.mpl_core_program.to_account_info(),
-            )?;
-        }
-
         record.status = CardStatus::Reserved;
-        record.owner = ctx.accounts.vault_authority.key();
+        record.owner = buyer;
+        if let Some(inventory) = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )? {
+            inventory.remove(&record.rarity);
+        }
 
-        // Write the Listing account directly; anchor will serialize on exit.
-        let listing = &mut ctx.accounts.listing;
-        listing.vault_state = vault_key;
-        listing.seller = seller_key;
-        listing.core_asset = record.core_asset;
-        listing.price_lamports = price_lamports;
-        listing.currency_mint = currency_mint;
-        listing.status = ListingStatus::Active;
+        let reservation = &mut ctx.accounts.reservation;
+        reservation.card_record = record.key();
+        reservation.buyer = buyer;
+        reservation.created_at = Clock::get()?.unix_timestamp;
+        reservation.bump = ctx.bumps.reservation;
         Ok(())
     }
 
-    pub fn cancel_listing(ctx: Context<CancelListing>) -> Result<()> {
-        let listing = &mut ctx.accounts.listing;
+    /// Buyer takes a card reserved for them via `reserve_for`.
+    pub fn claim_reserved(ctx: Context<ClaimReserved>) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        let buyer_key = ctx.accounts.buyer.key();
+        let record = &mut ctx.accounts.card_record;
         require!(
-            listing.status == ListingStatus::Active,
-            MochiError::InvalidListingState
-        );
-        require_keys_eq!(
-            listing.seller,
-            ctx.accounts.seller.key(),
-            MochiError::Unauthorized
+            record.status == CardStatus::Reserved && record.owner == buyer_key,
+            MochiError::CardNotReserved
         );
-
-        // Defensive: recover or rebuild card_record even if prior data drifted.
-        let mut record =
-            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
-                .or_else(|_| {
-                    CardRecord::try_deserialize_unchecked(
-                        &mut &ctx.accounts.card_record.data.borrow()[..],
-                    )
-                })
-                .unwrap_or(CardRecord {
-                    vault_state: ctx.accounts.vault_state.key(),
-                    core_asset: listing.core_asset,
-                    template_id: 0,
-                    rarity: Rarity::Common,
-                    status: CardStatus::Reserved,
-                    owner: ctx.accounts.vault_authority.key(),
-                });
-        record.vault_state = ctx.accounts.vault_state.key();
-        record.core_asset = listing.core_asset;
-        record.status = CardStatus::UserOwned;
-        record.owner = ctx.accounts.seller.key();
-
         transfer_core_asset(
             &ctx.accounts.core_asset,
             &ctx.accounts.vault_authority,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.buyer.to_account_info(),
+            &ctx.accounts.buyer.to_account_info(),
             &ctx.accounts.vault_state.key(),
             ctx.bumps.vault_authority,
-            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            GACHA_VAULT_AUTHORITY_SEED,
             &ctx.accounts.system_program.to_account_info(),
             &ctx.accounts.mpl_core_program.to_account_info(),
         )?;
+        record.status = CardStatus::UserOwned;
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
 
-        // Persist repaired record.
-        {
-            let mut data = ctx.accounts.card_record.try_borrow_mut_data()?;
-            let mut cursor = std::io::Cursor::new(data.as_mut());
-            record.try_serialize(&mut cursor)?;
+    /// Admin releases a `reserve_for` hold back to the pool.
+    pub fn unreserve(ctx: Context<Unreserve>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let record = &mut ctx.accounts.card_record;
+        require!(
+            record.status == CardStatus::Reserved && record.owner == ctx.accounts.reservation.buyer,
+            MochiError::CardNotReserved
+        );
+        record.status = CardStatus::Available;
+        record.owner = ctx.accounts.vault_authority.key();
+        if let Some(inventory) = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )? {
+            inventory.add(&record.rarity)?;
         }
+        Ok(())
+    }
 
-        listing.status = ListingStatus::Cancelled;
+    /// Permissionless per-card recovery: frees a Reserved card that nothing holds any more,
+    /// i.e. neither its owner's V1/V2 session (pending with this card in it) nor a
+    /// `reserve_for` hold. Cards of an expired but still pending session unwind through
+    /// `expire_session_v2` / `admin_force_close_v2` instead.
+    pub fn release_orphaned_card(ctx: Context<ReleaseOrphanedCard>) -> Result<()> {
+        let card_key = ctx.accounts.card_record.key();
+        require!(
+            ctx.accounts.card_record.status == CardStatus::Reserved,
+            MochiError::CardNotReserved
+        );
+        require!(
+            ctx.accounts.reservation.data_is_empty(),
+            MochiError::CardStillHeld
+        );
+        let session_info = ctx.accounts.pack_session.to_account_info();
+        if session_info.owner == ctx.program_id && !session_info.data_is_empty() {
+            let session = PackSessionV2::try_deserialize(&mut &session_info.data.borrow()[..])?;
+            require!(
+                session.state != PackState::PendingDecision
+                    || !session.rare_card_keys.contains(&card_key),
+                MochiError::CardStillHeld
+            );
+        }
+        let legacy_info = ctx.accounts.legacy_session.to_account_info();
+        if legacy_info.owner == ctx.program_id && !legacy_info.data_is_empty() {
+            let session = PackSession::try_deserialize(&mut &legacy_info.data.borrow()[..])?;
+            require!(
+                session.state != PackState::PendingDecision
+                    || !session.card_record_keys.contains(&card_key),
+                MochiError::CardStillHeld
+            );
+        }
+
+        if let Some(inventory) = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )? {
+            inventory.add(&ctx.accounts.card_record.rarity)?;
+        }
+        let record = &mut ctx.accounts.card_record;
+        record.status = CardStatus::Available;
+        record.owner = ctx.accounts.vault_authority.key();
         Ok(())
     }
 
-    pub fn fill_listing(ctx: Context<FillListing>) -> Result<()> {
+    /// Register CardRecords for Core assets already custodied by the vault authority, counted
+    /// against `max_per_template` like `deposit_card`.
+    /// remaining_accounts: [card_records...][core_assets...][template_stats...]; already-registered
+    /// records are skipped.
+    pub fn register_existing_cards_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterExistingCards<'info>>,
+        templates: Vec<u32>,
+        rarities: Vec<Rarity>,
+    ) -> Result<()> {
         require!(
-            ctx.accounts.listing.status == ListingStatus::Active,
-            MochiError::InvalidListingState
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let count = templates.len();
+        require!(
+            count > 0 && rarities.len() == count && ctx.remaining_accounts.len() == count * 3,
+            MochiError::InvalidCardCount
         );
-        let core_key = ctx.accounts.card_record.core_asset;
+        let (card_accounts, rest) = ctx.remaining_accounts.split_at(count);
+        let (asset_accounts, stats_accounts) = rest.split_at(count);
 
-        let fee_bps = ctx.accounts.vault_state.marketplace_fee_bps as u64;
-        let price = ctx.accounts.listing.price_lamports;
-        let fee = price
-            .checked_mul(fee_bps)
-            .and_then(|v| v.checked_div(10_000))
-            .ok_or(MochiError::MathOverflow)?;
-        let seller_amount = price.checked_sub(fee).ok_or(MochiError::MathOverflow)?;
-        // Direct pay: buyer -> treasury (fee) and buyer -> seller (net). No escrow on listing PDA.
-        if fee > 0 {
-            invoke(
-                &system_instruction::transfer(
-                    &ctx.accounts.buyer.key(),
-                    &ctx.accounts.vault_treasury.key(),
-                    fee,
+        let vault_key = ctx.accounts.vault_state.key();
+        let max_per_template = ctx.accounts.vault_state.max_per_template;
+        let admin = ctx.accounts.admin.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let space = 8 + CardRecord::SIZE;
+        let lamports = Rent::get()?.minimum_balance(space);
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        for (idx, (record_info, asset_info)) in
+            card_accounts.iter().zip(asset_accounts.iter()).enumerate()
+        {
+            require_keys_eq!(*asset_info.owner, mpl_core::ID, MochiError::AssetMismatch);
+            let asset = mpl_core::accounts::BaseAssetV1::from_bytes(&asset_info.data.borrow())
+                .map_err(|_| error!(MochiError::AssetMismatch))?;
+            require_keys_eq!(asset.owner, vault_authority, MochiError::AssetMismatch);
+
+            let (expected, bump) = Pubkey::find_program_address(
+                &[
+                    CARD_RECORD_SEED,
+                    vault_key.as_ref(),
+                    asset_info.key.as_ref(),
+                ],
+                ctx.program_id,
+            );
+            require_keys_eq!(record_info.key(), expected, MochiError::CardKeyMismatch);
+            if !record_info.data_is_empty() {
+                continue;
+            }
+            count_template_deposit(
+                &stats_accounts[idx],
+                &vault_key,
+                templates[idx],
+                max_per_template,
+                &admin,
+                &system_program,
+                ctx.program_id,
+            )?;
+            ctx.accounts.vault_state.count_deposit()?;
+
+            invoke_signed(
+                &system_instruction::create_account(
+                    &ctx.accounts.admin.key(),
+                    record_info.key,
+                    lamports,
+                    space as u64,
+                    ctx.program_id,
                 ),
                 &[
-                    ctx.accounts.buyer.to_account_info(),
-                    ctx.accounts.vault_treasury.to_account_info(),
+                    ctx.accounts.admin.to_account_info(),
+                    record_info.clone(),
                     ctx.accounts.system_program.to_account_info(),
                 ],
+                &[&[
+                    CARD_RECORD_SEED,
+                    vault_key.as_ref(),
+                    asset_info.key.as_ref(),
+                    &[bump],
+                ]],
             )?;
+            let record = CardRecord {
+                vault_state: vault_key,
+                core_asset: asset_info.key(),
+                template_id: templates[idx],
+                rarity: rarities[idx].clone(),
+                status: CardStatus::Available,
+                owner: vault_authority,
+            };
+            persist_card_record(&record, record_info)?;
+            if let Some(inventory) = inventory.as_deref_mut() {
+                inventory.add(&record.rarity)?;
+            }
         }
-        invoke(
-            &system_instruction::transfer(
-                &ctx.accounts.buyer.key(),
-                &ctx.accounts.seller.key(),
-                seller_amount,
-            ),
-            &[
-                ctx.accounts.buyer.to_account_info(),
-                ctx.accounts.seller.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-        )?;
-
-        let record = &mut ctx.accounts.card_record;
-        require_keys_eq!(record.core_asset, core_key, MochiError::AssetMismatch);
-        record.status = CardStatus::UserOwned;
-        record.owner = ctx.accounts.buyer.key();
-        transfer_core_asset(
-            &ctx.accounts.core_asset,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.buyer.to_account_info(),
-            &ctx.accounts.vault_state.key(),
-            ctx.bumps.vault_authority,
-            MARKETPLACE_VAULT_AUTHORITY_SEED,
-            &ctx.accounts.system_program.to_account_info(),
-            &ctx.accounts.mpl_core_program.to_account_info(),
-        )?;
-
-        let listing = &mut ctx.accounts.listing;
-        listing.status = ListingStatus::Filled;
         Ok(())
     }
 
-    pub fn redeem_burn(ctx: Context<RedeemBurn>) -> Result<()> {
-        let record = &mut ctx.accounts.card_record;
-        require_keys_eq!(
-            record.owner,
-            ctx.accounts.user.key(),
-            MochiError::Unauthorized
-        );
-        burn_core_asset(
-            &ctx.accounts.core_asset,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.vault_state.key(),
-            ctx.bumps.vault_authority,
-            GACHA_VAULT_AUTHORITY_SEED,
-            &ctx.accounts.system_program.to_account_info(),
-            &ctx.accounts.mpl_core_program.to_account_info(),
-        )?;
-        record.status = CardStatus::Burned;
-        Ok(())
+    /// New lightweight open: only Rare+ CardRecords are reserved on-chain (max 3).
+    /// remaining_accounts: [rare_card_records...]
+    pub fn open_pack<'info>(
+        ctx: Context<'_, '_, 'info, 'info, OpenPackV2<'info>>,
+        currency: Currency,
+        client_seed_hash: [u8; 32],
+        rare_templates: Vec<u32>,
+        manifest_hash: [u8; 32],
+        recipient: Option<Pubkey>,
+    ) -> Result<()> {
+        let claim_window_seconds = ctx.accounts.vault_state.claim_window_seconds;
+        let remaining_accounts = ctx.remaining_accounts;
+        open_pack_v2_inner(
+            ctx,
+            remaining_accounts,
+            PackOpen {
+                currency,
+                client_seed_hash,
+                rare_templates,
+                claim_window_seconds,
+                manifest_hash,
+                recipient,
+                discount_bps: 0,
+                random_draw: None,
+            },
+        )
     }
 
-    pub fn admin_migrate_asset(ctx: Context<AdminMigrateAsset>) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
-            MochiError::Unauthorized
+    /// open_pack with a caller-chosen claim window for slow (mobile) clients.
+    /// Zero falls back to `claim_window_seconds`; anything else must be <= `max_window_override`.
+    pub fn open_pack_long<'info>(
+        ctx: Context<'_, '_, 'info, 'info, OpenPackV2<'info>>,
+        currency: Currency,
+        client_seed_hash: [u8; 32],
+        rare_templates: Vec<u32>,
+        window_override_seconds: i64,
+        manifest_hash: [u8; 32],
+    ) -> Result<()> {
+        let vault_state = &ctx.accounts.vault_state;
+        let claim_window_seconds = if window_override_seconds == 0 {
+            vault_state.claim_window_seconds
+        } else {
+            require!(
+                window_override_seconds > 0
+                    && window_override_seconds <= vault_state.max_window_override,
+                MochiError::InvalidWindow
+            );
+            window_override_seconds
+        };
+        let remaining_accounts = ctx.remaining_accounts;
+        open_pack_v2_inner(
+            ctx,
+            remaining_accounts,
+            PackOpen {
+                currency,
+                client_seed_hash,
+                rare_templates,
+                claim_window_seconds,
+                manifest_hash,
+                recipient: None,
+                discount_bps: 0,
+                random_draw: None,
+            },
+        )
+    }
+
+    /// open_pack for "choose your hero" promos: at least one of the reserved rares must be
+    /// exactly `guaranteed_rarity`, otherwise the open fails before any funds move.
+    /// remaining_accounts: [rare_card_records...]
+    pub fn open_targeted_pack<'info>(
+        ctx: Context<'_, '_, 'info, 'info, OpenPackV2<'info>>,
+        currency: Currency,
+        client_seed_hash: [u8; 32],
+        rare_templates: Vec<u32>,
+        manifest_hash: [u8; 32],
+        guaranteed_rarity: Rarity,
+    ) -> Result<()> {
+        require!(
+            is_rare_or_above(&guaranteed_rarity),
+            MochiError::CardTooCommon
         );
-        let record = &mut ctx.accounts.card_record;
-        transfer_core_asset(
-            &ctx.accounts.core_asset,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.destination.to_account_info(),
-            &ctx.accounts.vault_state.key(),
-            ctx.bumps.vault_authority,
-            GACHA_VAULT_AUTHORITY_SEED,
-            &ctx.accounts.system_program.to_account_info(),
-            &ctx.accounts.mpl_core_program.to_account_info(),
-        )?;
-        record.owner = ctx.accounts.destination.key();
-        record.status = CardStatus::Deprecated;
-        Ok(())
+        require!(
+            ctx.remaining_accounts.len() >= rare_templates.len(),
+            MochiError::InvalidCardCount
+        );
+        let mut matched = false;
+        for acc_info in &ctx.remaining_accounts[..rare_templates.len()] {
+            let record: Account<CardRecord> = Account::try_from(acc_info)?;
+            if record.rarity == guaranteed_rarity {
+                matched = true;
+                break;
+            }
+        }
+        require!(matched, MochiError::RarityMismatch);
+        let claim_window_seconds = ctx.accounts.vault_state.claim_window_seconds;
+        let remaining_accounts = ctx.remaining_accounts;
+        open_pack_v2_inner(
+            ctx,
+            remaining_accounts,
+            PackOpen {
+                currency,
+                client_seed_hash,
+                rare_templates,
+                claim_window_seconds,
+                manifest_hash,
+                recipient: None,
+                discount_bps: 0,
+                random_draw: None,
+            },
+        )
     }
 
-    /// Admin-only prune for malformed listings that point to a wrong/nonexistent vault_state.
-    /// This does NOT move any assets; it simply marks the listing as Cancelled to hide it.
-    pub fn admin_prune_listing(ctx: Context<AdminPruneListing>) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
+    /// Registers a bounded-use discount code issued to one wallet. Only `code_hash`
+    /// (sha256 of the code followed by the redeemer's key) goes on-chain, so the code
+    /// revealed by a redemption is useless to any other wallet.
+    pub fn create_discount_code(
+        ctx: Context<CreateDiscountCode>,
+        code_hash: [u8; 32],
+        discount_bps: u16,
+        max_uses: u32,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
             MochiError::Unauthorized
         );
-        // Overwrite the listing account regardless of prior contents to mark it Cancelled.
-        let listing = Listing {
-            vault_state: ctx.accounts.vault_state.key(),
-            seller: Pubkey::default(),
-            core_asset: Pubkey::default(),
-            price_lamports: 0,
-            currency_mint: None,
-            status: ListingStatus::Cancelled,
-        };
-        let mut data = ctx.accounts.listing.try_borrow_mut_data()?;
-        let mut cursor = std::io::Cursor::new(&mut data[..]);
-        // AccountSerialize already writes the discriminator; avoid writing it twice.
-        listing.try_serialize(&mut cursor)?;
+        require!(
+            discount_bps > 0 && discount_bps < 10_000 && max_uses > 0,
+            MochiError::InvalidDiscount
+        );
+        let discount = &mut ctx.accounts.discount_code;
+        discount.vault_state = ctx.accounts.vault_state.key();
+        discount.code_hash = code_hash;
+        discount.discount_bps = discount_bps;
+        discount.max_uses = max_uses;
+        discount.uses = 0;
+        discount.bump = ctx.bumps.discount_code;
         Ok(())
     }
 
-    /// Admin-only escape hatch to repair/cancel corrupted listings.
-    /// Returns NFT to seller and marks listing + card_record accordingly.
-    pub fn admin_force_cancel_listing(ctx: Context<AdminForceCancel>) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
-            MochiError::Unauthorized
-        );
-        let listing = &mut ctx.accounts.listing;
+    /// open_pack at a discounted price. `code` together with the opening user's key must
+    /// hash to the DiscountCode's `code_hash`, and the code must have uses left; each call
+    /// consumes one.
+    /// remaining_accounts: [discount_code, ...open_pack remaining_accounts]
+    pub fn open_pack_discounted<'info>(
+        ctx: Context<'_, '_, 'info, 'info, OpenPackV2<'info>>,
+        currency: Currency,
+        client_seed_hash: [u8; 32],
+        rare_templates: Vec<u32>,
+        manifest_hash: [u8; 32],
+        code: Vec<u8>,
+    ) -> Result<()> {
+        let (discount_info, rest) = ctx
+            .remaining_accounts
+            .split_first()
+            .ok_or(MochiError::InvalidCardCount)?;
+        let mut discount: Account<DiscountCode> = Account::try_from(discount_info)?;
         require_keys_eq!(
-            listing.vault_state,
+            discount.vault_state,
             ctx.accounts.vault_state.key(),
             MochiError::VaultMismatch
         );
-        require_keys_eq!(
-            listing.seller,
-            ctx.accounts.seller.key(),
-            MochiError::Unauthorized
+        require!(
+            hashv(&[&code, ctx.accounts.user.key().as_ref()]).to_bytes() == discount.code_hash,
+            MochiError::InvalidDiscount
         );
+        require!(
+            discount.uses < discount.max_uses,
+            MochiError::DiscountExhausted
+        );
+        discount.uses += 1;
+        write_account(&*discount, discount_info)?;
+
+        let discount_bps = discount.discount_bps;
+        let claim_window_seconds = ctx.accounts.vault_state.claim_window_seconds;
+        open_pack_v2_inner(
+            ctx,
+            rest,
+            PackOpen {
+                currency,
+                client_seed_hash,
+                rare_templates,
+                claim_window_seconds,
+                manifest_hash,
+                recipient: None,
+                discount_bps,
+                random_draw: None,
+            },
+        )
+    }
 
-        // Defensive: recover card_record even if drifted.
-        let mut record =
-            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
-                .or_else(|_| {
-                    CardRecord::try_deserialize_unchecked(
-                        &mut &ctx.accounts.card_record.data.borrow()[..],
-                    )
-                })
-                .unwrap_or(CardRecord {
-                    vault_state: ctx.accounts.vault_state.key(),
-                    core_asset: listing.core_asset,
-                    template_id: 0,
-                    rarity: Rarity::Common,
-                    status: CardStatus::Reserved,
-                    owner: ctx.accounts.vault_authority.key(),
-                });
-        record.vault_state = ctx.accounts.vault_state.key();
-        record.core_asset = listing.core_asset;
-        record.status = CardStatus::UserOwned;
-        record.owner = listing.seller;
-
-        // Return NFT to seller.
-        transfer_core_asset(
-            &ctx.accounts.core_asset,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.seller.to_account_info(),
-            &ctx.accounts.vault_state.key(),
-            ctx.bumps.vault_authority,
-            MARKETPLACE_VAULT_AUTHORITY_SEED,
-            &ctx.accounts.system_program.to_account_info(),
-            &ctx.accounts.mpl_core_program.to_account_info(),
-        )?;
-
-        // Persist repaired card_record
+    /// open_pack where the program, not the backend, picks which rares are reserved:
+    /// `num_rares` records are drawn from the candidate pool using the most recent slot
+    /// hash, the user key and `client_seed_hash` as entropy.
+    ///
+    /// The candidate pool must be every Available Rare+ record of the vault in ascending key
+    /// order, checked against the RarityInventory, so the caller cannot narrow or reorder
+    /// it. A slot leader can still grind the slot hash; pair it with a commit-reveal of the
+    /// seed where stronger guarantees are needed. No manifest is committed because the
+    /// rares are not known until execution.
+    /// remaining_accounts: [slot_hashes sysvar, candidate_records (num_candidates)...,
+    /// ...open_pack payment accounts]
+    pub fn open_pack_random<'info>(
+        ctx: Context<'_, '_, 'info, 'info, OpenPackV2<'info>>,
+        currency: Currency,
+        client_seed_hash: [u8; 32],
+        num_candidates: u8,
+        num_rares: u8,
+    ) -> Result<()> {
+        let (sysvar_info, rest) = ctx
+            .remaining_accounts
+            .split_first()
+            .ok_or(MochiError::InvalidCardCount)?;
+        require_keys_eq!(sysvar_info.key(), slot_hashes::ID, MochiError::InvalidPda);
+        let pool_len = num_candidates as usize;
+        require!(
+            num_rares as usize <= pool_len && pool_len <= rest.len(),
+            MochiError::InvalidCardCount
+        );
+        let vault_key = ctx.accounts.vault_state.key();
+        let inventory = ctx
+            .accounts
+            .rarity_inventory
+            .as_deref()
+            .filter(|_| ctx.accounts.vault_state.rarity_inventory_enabled)
+            .ok_or(MochiError::MissingRarityInventory)?;
+        require!(
+            pool_len as u64 == inventory.rare_stock(),
+            MochiError::IncompleteCandidatePool
+        );
+        let mut pool_templates = Vec::with_capacity(pool_len);
+        for (idx, acc_info) in rest[..pool_len].iter().enumerate() {
+            require!(
+                idx == 0 || rest[idx - 1].key < acc_info.key,
+                MochiError::IncompleteCandidatePool
+            );
+            let record = load_card_record(acc_info)?;
+            require_keys_eq!(record.vault_state, vault_key, MochiError::VaultMismatch);
+            require!(
+                record.status == CardStatus::Available,
+                MochiError::CardNotAvailable
+            );
+            require!(is_rare_or_above(&record.rarity), MochiError::CardTooCommon);
+            pool_templates.push(record.template_id);
+        }
+        // SlotHashes data: u64 count, then (u64 slot, [u8; 32] hash) newest first.
+        let mut recent_hash = [0u8; 32];
         {
-            let mut data = ctx.accounts.card_record.try_borrow_mut_data()?;
-            let mut cursor = std::io::Cursor::new(&mut data[..]);
-            cursor.write_all(&CardRecord::discriminator())?;
-            record.try_serialize(&mut cursor)?;
+            let data = sysvar_info.try_borrow_data()?;
+            require!(data.len() >= 48, MochiError::InvalidPda);
+            recent_hash.copy_from_slice(&data[16..48]);
         }
-
-        listing.status = ListingStatus::Cancelled;
-        Ok(())
+        let entropy = hashv(&[
+            &recent_hash,
+            ctx.accounts.user.key().as_ref(),
+            &client_seed_hash,
+        ])
+        .to_bytes();
+
+        let pool_hash = candidate_pool_hash(&rest[..pool_len]);
+        let picks = pick_random_indices(entropy, pool_len, num_rares as usize);
+        // The chosen rares must lead the accounts open_pack_v2_inner reserves from.
+        let mut reordered: Vec<AccountInfo<'info>> = Vec::with_capacity(rest.len());
+        let mut rare_templates = Vec::with_capacity(picks.len());
+        for &idx in &picks {
+            rare_templates.push(pool_templates[idx]);
+            reordered.push(rest[idx].clone());
+        }
+        reordered.extend(rest[pool_len..].iter().cloned());
+
+        let claim_window_seconds = ctx.accounts.vault_state.claim_window_seconds;
+        open_pack_v2_inner(
+            ctx,
+            &reordered,
+            PackOpen {
+                currency,
+                client_seed_hash,
+                rare_templates,
+                claim_window_seconds,
+                manifest_hash: [0u8; 32],
+                recipient: None,
+                discount_bps: 0,
+                random_draw: Some((entropy, pool_hash)),
+            },
+        )
     }
 
-    /// Admin-only guardrail to return a stuck listing's asset to its original seller.
-    /// Destination is fixed to listing.seller; admin cannot redirect funds.
-    pub fn emergency_return_asset(ctx: Context<EmergencyReturnAsset>) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
-            MochiError::Unauthorized
+    /// Layaway open: reserves the rares against a SOL deposit held in escrow by the vault
+    /// authority. The session stays unclaimable until `add_payment` brings `paid_amount` up to
+    /// `full_price` (`pack_price_sol` at reservation); `cancel_layaway` refunds the deposits.
+    /// Layaway opens carry no MOCHI open reward.
+    /// remaining_accounts: [rare_card_records...]
+    pub fn reserve_pack<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReservePack<'info>>,
+        client_seed_hash: [u8; 32],
+        rare_templates: Vec<u32>,
+        manifest_hash: [u8; 32],
+        deposit: u64,
+    ) -> Result<()> {
+        verify_open_gate(
+            &ctx.accounts.vault_state,
+            ctx.accounts.instructions_sysvar.as_ref(),
+            &ctx.accounts.user.key(),
+            ctx.accounts.pack_session.created_at,
+        )?;
+        // Counted up front; any failure below reverts it with the rest of the open.
+        let previous = &ctx.accounts.pack_session;
+        let replaced = (previous.state == PackState::PendingDecision).then_some(&previous.currency);
+        ctx.accounts
+            .vault_state
+            .open_session(replaced, &Currency::Sol)?;
+        let nonce = ctx.accounts.vault_state.next_session_nonce()?;
+        let vault_state = &ctx.accounts.vault_state;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            vault_state.layaway_window_seconds > 0,
+            MochiError::LayawayDisabled
         );
-        let listing = &mut ctx.accounts.listing;
-        require_keys_eq!(
-            listing.vault_state,
-            ctx.accounts.vault_state.key(),
-            MochiError::VaultMismatch
+        require!(
+            vault_state.accepts_currency(&Currency::Sol),
+            MochiError::CurrencyNotAccepted
         );
-        require_keys_eq!(
-            listing.seller,
-            ctx.accounts.seller.key(),
-            MochiError::Unauthorized
+        let full_price = vault_state.pack_price_sol;
+        let min_deposit =
+            (full_price as u128 * vault_state.layaway_min_deposit_bps as u128 / 10_000) as u64;
+        require!(
+            full_price > 0 && deposit > 0 && deposit >= min_deposit && deposit < full_price,
+            MochiError::InvalidPrice
+        );
+        require!(
+            rare_templates.len() <= vault_state.rare_ceiling(),
+            MochiError::TooManyRareCards
         );
 
-        let mut record =
-            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
-                .or_else(|_| {
-                    CardRecord::try_deserialize_unchecked(
-                        &mut &ctx.accounts.card_record.data.borrow()[..],
-                    )
-                })
-                .unwrap_or(CardRecord {
-                    vault_state: ctx.accounts.vault_state.key(),
-                    core_asset: listing.core_asset,
-                    template_id: 0,
-                    rarity: Rarity::Common,
-                    status: CardStatus::Reserved,
-                    owner: ctx.accounts.vault_authority.key(),
-                });
-        record.vault_state = ctx.accounts.vault_state.key();
-        record.core_asset = listing.core_asset;
-        record.status = CardStatus::UserOwned;
-        record.owner = listing.seller;
+        let session = &mut ctx.accounts.pack_session;
+        if session.state == PackState::PendingDecision
+            && (now <= session.expires_at || !session.is_paid())
+        {
+            return err!(MochiError::SessionExists);
+        }
+        session.reset_for_open();
 
-        transfer_core_asset(
-            &ctx.accounts.core_asset,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.vault_authority,
-            &ctx.accounts.seller.to_account_info(),
-            &ctx.accounts.vault_state.key(),
-            ctx.bumps.vault_authority,
-            MARKETPLACE_VAULT_AUTHORITY_SEED,
+        invoke(
+            &system_instruction::transfer(
+                &ctx.accounts.user.key(),
+                &ctx.accounts.vault_authority.key(),
+                deposit,
+            ),
+            &[
+                ctx.accounts.user.to_account_info(),
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+
+        let rare_keys = reserve_rare_records(
+            &vault_state.key(),
+            &ctx.accounts.user.key(),
+            ctx.remaining_accounts,
+            &rare_templates,
+            tracked_inventory(vault_state, &mut ctx.accounts.rarity_inventory)?,
+        )?;
+        // The whole price is committed at reservation, so it counts against the limit now.
+        charge_spend_limit(
+            &ctx.accounts.user_stats,
+            &ctx.accounts.user.to_account_info(),
             &ctx.accounts.system_program.to_account_info(),
-            &ctx.accounts.mpl_core_program.to_account_info(),
+            full_price,
+            now,
+        )?;
+        track_pity(
+            &ctx.accounts.user_stats,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            vault_state,
+            ctx.bumps.user_stats,
+            !rare_keys.is_empty(),
         )?;
 
-        {
-            let mut data = ctx.accounts.card_record.try_borrow_mut_data()?;
-            let mut cursor = std::io::Cursor::new(&mut data[..]);
-            cursor.write_all(&CardRecord::discriminator())?;
-            record.try_serialize(&mut cursor)?;
-        }
+        session.user = ctx.accounts.user.key();
+        session.currency = Currency::Sol;
+        session.paid_amount = deposit;
+        session.paid_sol_amount = 0;
+        session.full_price = full_price;
+        session.entropy = [0u8; 32];
+        session.candidate_pool_hash = [0u8; 32];
+        session.nonce = nonce;
+        session.created_at = now;
+        session.expires_at = now
+            .checked_add(vault_state.capped_window(vault_state.layaway_window_seconds))
+            .ok_or(MochiError::MathOverflow)?;
+        session.state = PackState::PendingDecision;
+        session.client_seed_hash = client_seed_hash;
+        session.manifest_hash = manifest_hash;
+        session.rare_card_keys = rare_keys;
+        session.rare_templates = rare_templates;
+        require!(
+            PACK_CARD_COUNT <= vault_state.manifest_ceiling(),
+            MochiError::ManifestTooLong
+        );
+        session.total_slots = PACK_CARD_COUNT as u8;
+        session.bump = ctx.bumps.pack_session;
+        session.recipient = Pubkey::default();
+        session.reward_minted = false;
+
+        emit!(PackOpened {
+            vault_state: vault_state.key(),
+            user: session.user,
+            pack_session: session.key(),
+            paid_amount: deposit,
+            rarity_counts: rarity_counts(ctx.remaining_accounts, session.rare_card_keys.len())?,
+        });
 
-        listing.status = ListingStatus::Cancelled;
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.escrowed_lamports = vault_state
+            .escrowed_lamports
+            .checked_add(deposit)
+            .ok_or(MochiError::MathOverflow)?;
         Ok(())
     }
 
-    /// Admin-only rescue for legacy listings anchored to an old/non-canonical vault_state PDA.
-    /// Returns the asset to the original seller and marks the listing cancelled.
-    pub fn admin_rescue_legacy_listing(ctx: Context<AdminRescueLegacyListing>) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.marketplace_vault_state.admin,
-            MochiError::Unauthorized
+    /// Adds an installment to a layaway session (capped at what is still owed). The payment
+    /// that completes it moves the escrow to the treasury and opens the normal claim window.
+    /// remaining_accounts: [fee_split, recipients...] when the vault has a fee split.
+    pub fn add_payment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AddPayment<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
         );
-        let listing = &mut ctx.accounts.listing;
+        require!(now <= session.expires_at, MochiError::SessionExpired);
+        require!(!session.is_paid(), MochiError::InvalidSessionState);
         require_keys_eq!(
-            listing.vault_state,
-            ctx.accounts.legacy_vault_state.key(),
+            ctx.accounts.vault_treasury.key(),
+            ctx.accounts.vault_state.treasury,
             MochiError::VaultMismatch
         );
-        require_keys_eq!(
-            listing.seller,
-            ctx.accounts.seller.key(),
-            MochiError::Unauthorized
-        );
 
-        let (market_auth, market_bump) = Pubkey::find_program_address(
-            &[
-                MARKETPLACE_VAULT_AUTHORITY_SEED,
-                ctx.accounts.legacy_vault_state.key().as_ref(),
-            ],
-            ctx.program_id,
-        );
-        let (gacha_auth, gacha_bump) = Pubkey::find_program_address(
+        let owed = session.full_price - session.paid_amount;
+        let amount = amount.min(owed);
+        require!(amount > 0, MochiError::InvalidPrice);
+        invoke(
+            &system_instruction::transfer(
+                &ctx.accounts.user.key(),
+                &ctx.accounts.vault_authority.key(),
+                amount,
+            ),
             &[
-                GACHA_VAULT_AUTHORITY_SEED,
-                ctx.accounts.legacy_vault_state.key().as_ref(),
+                ctx.accounts.user.to_account_info(),
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
             ],
-            ctx.program_id,
-        );
-        let (authority_seed, authority_bump) =
-            if market_auth == ctx.accounts.legacy_vault_authority.key() {
-                (MARKETPLACE_VAULT_AUTHORITY_SEED, market_bump)
-            } else {
-                require_keys_eq!(
-                    gacha_auth,
-                    ctx.accounts.legacy_vault_authority.key(),
-                    MochiError::VaultMismatch
-                );
-                (GACHA_VAULT_AUTHORITY_SEED, gacha_bump)
-            };
-
-        let mut record =
-            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
-                .or_else(|_| {
-                    CardRecord::try_deserialize_unchecked(
-                        &mut &ctx.accounts.card_record.data.borrow()[..],
-                    )
-                })
-                .unwrap_or(CardRecord {
-                    vault_state: listing.vault_state,
-                    core_asset: listing.core_asset,
-                    template_id: 0,
-                    rarity: Rarity::Common,
-                    status: CardStatus::Reserved,
-                    owner: ctx.accounts.legacy_vault_authority.key(),
-                });
-        record.vault_state = listing.vault_state;
-        record.core_asset = listing.core_asset;
-        record.status = CardStatus::UserOwned;
-        record.owner = listing.seller;
-
-        let should_transfer = record.owner == ctx.accounts.legacy_vault_authority.key();
-        if should_transfer {
-            transfer_core_asset(
-                &ctx.accounts.core_asset,
-                &ctx.accounts.legacy_vault_authority,
-                &ctx.accounts.legacy_vault_authority,
-                &ctx.accounts.seller.to_account_info(),
-                &ctx.accounts.legacy_vault_state.key(),
-                authority_bump,
-                authority_seed,
-                &ctx.accounts.system_program.to_account_info(),
-                &ctx.accounts.mpl_core_program.to_account_info(),
-            )?;
-        } else if record.owner != listing.seller {
-            // If the asset is already with the seller, no transfer is needed; otherwise fail.
-            return err!(MochiError::Unauthorized);
-        }
+        )?;
+        session.paid_amount += amount;
 
-        // Best-effort persist; if the legacy card_record is missing or too small, skip persistence.
-        if let Ok(mut data) = ctx.accounts.card_record.try_borrow_mut_data() {
-            if data.len() >= 8 + CardRecord::SIZE {
-                let mut cursor = std::io::Cursor::new(&mut data[..]);
-                let _ = cursor.write_all(&CardRecord::discriminator());
-                let _ = record.try_serialize(&mut cursor);
-            }
+        let vault_state = &mut ctx.accounts.vault_state;
+        if !session.is_paid() {
+            vault_state.escrowed_lamports = vault_state
+                .escrowed_lamports
+                .checked_add(amount)
+                .ok_or(MochiError::MathOverflow)?;
+            return Ok(());
         }
 
-        listing.status = ListingStatus::Cancelled;
+        // Fully paid: release the earlier installments from escrow and settle the whole price.
+        vault_state.escrowed_lamports = vault_state
+            .escrowed_lamports
+            .checked_sub(session.full_price - amount)
+            .ok_or(MochiError::MathOverflow)?;
+        let vault_key = vault_state.key();
+        let seeds = &[
+            GACHA_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        pay_fee(
+            vault_state,
+            &ctx.accounts.vault_authority.to_account_info(),
+            &ctx.accounts.vault_treasury.to_account_info(),
+            ctx.remaining_accounts,
+            session.full_price,
+            &ctx.accounts.system_program.to_account_info(),
+            &[&seeds[..]],
+        )?;
+        session.expires_at = now
+            .checked_add(vault_state.capped_window(vault_state.claim_window_seconds))
+            .ok_or(MochiError::MathOverflow)?;
         Ok(())
     }
 
-    pub fn deprecate_card(ctx: Context<DeprecateCard>) -> Result<()> {
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
-            MochiError::Unauthorized
+    /// Abandons an unpaid layaway: refunds every installment from escrow and frees the rares.
+    /// remaining_accounts: [rare_card_records...]
+    pub fn cancel_layaway<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelLayaway<'info>>,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            session.state == PackState::PendingDecision && !session.is_paid(),
+            MochiError::InvalidSessionState
         );
-        let record = &mut ctx.accounts.card_record;
-        record.status = CardStatus::Deprecated;
+        let vault_key = ctx.accounts.vault_state.key();
+        let seeds = &[
+            GACHA_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        refund_layaway(
+            &mut ctx.accounts.vault_state,
+            session,
+            &ctx.accounts.vault_authority.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &[&seeds[..]],
+        )?;
+        free_reserved_rares(
+            session,
+            &ctx.accounts.vault_authority.key(),
+            tracked_inventory(
+                &ctx.accounts.vault_state,
+                &mut ctx.accounts.rarity_inventory,
+            )?,
+            ctx.remaining_accounts,
+        )?;
+
+        session.state = PackState::Rejected;
+        ctx.accounts.vault_state.settle_session(&session.currency);
         Ok(())
     }
 
-    pub fn admin_force_close_session<'info>(
-        ctx: Context<'_, '_, 'info, 'info, AdminForceClose<'info>>,
+    /// Permissionless unwind of a layaway whose `expires_at` passed before it was paid off:
+    /// refunds every installment to the user and frees the rares, like `cancel_layaway`.
+    pub fn expire_layaway<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExpireLayaway<'info>>,
     ) -> Result<()> {
-        // Admin-only override: closes pack_session regardless of state and frees card records.
-        require_keys_eq!(
-            ctx.accounts.admin.key(),
-            ctx.accounts.vault_state.admin,
-            MochiError::Unauthorized
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            session.state == PackState::PendingDecision && !session.is_paid(),
+            MochiError::InvalidSessionState
         );
-
-        // Reset card records passed in remaining accounts (best-effort)
-        for acc_info in ctx.remaining_accounts.iter() {
-            if let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) {
-                if card_record.vault_state == ctx.accounts.vault_state.key() {
-                    card_record.status = CardStatus::Available;
-                    card_record.owner = ctx.accounts.vault_authority.key();
-                }
-            }
+        let now = Clock::get()?.unix_timestamp;
+        require!(now > session.expires_at, MochiError::SessionNotExpired);
+        let vault_key = ctx.accounts.vault_state.key();
+        let seeds = &[
+            GACHA_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        refund_layaway(
+            &mut ctx.accounts.vault_state,
+            session,
+            &ctx.accounts.vault_authority.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &[&seeds[..]],
+        )?;
+        free_reserved_rares(
+            session,
+            &ctx.accounts.vault_authority.key(),
+            tracked_inventory(
+                &ctx.accounts.vault_state,
+                &mut ctx.accounts.rarity_inventory,
+            )?,
+            ctx.remaining_accounts,
+        )?;
+
+        session.state = PackState::Expired;
+        ctx.accounts.vault_state.settle_session(&session.currency);
+        Ok(())
+    }
+
+    /// Permissionless check that `manifest` (all template ids of the pack, in slot order)
+    /// matches what the server committed to when the session was opened. Works on both
+    /// PackSession and PackSessionV2. The manifest itself is never stored, so sessions stay
+    /// fixed-size; it must cover exactly the session's slots.
+    pub fn verify_manifest(ctx: Context<VerifyManifest>, manifest: Vec<u32>) -> Result<()> {
+        require!(
+            manifest.len() <= MAX_MANIFEST_LEN,
+            MochiError::ManifestTooLong
+        );
+        let session_info = ctx.accounts.pack_session.to_account_info();
+        require_keys_eq!(
+            *session_info.owner,
+            *ctx.program_id,
+            MochiError::InvalidSessionState
+        );
+        let data = session_info.try_borrow_data()?;
+        let (committed, slots) = if let Ok(session) = PackSessionV2::try_deserialize(&mut &data[..])
+        {
+            (session.manifest_hash, session.total_slots as usize)
+        } else {
+            let session = PackSession::try_deserialize(&mut &data[..])?;
+            (session.manifest_hash, PACK_CARD_COUNT)
+        };
+        require!(manifest.len() == slots, MochiError::ManifestMismatch);
+        require!(
+            manifest_hash(&manifest) == committed,
+            MochiError::ManifestMismatch
+        );
+        Ok(())
+    }
+
+    /// Permissionless audit of an `open_pack_random` session: replays the draw from the
+    /// stored entropy over the committed candidate pool and checks it reserved exactly the
+    /// records whose templates are `claimed_templates`, in session order.
+    /// remaining_accounts: [candidate_records...] in ascending key order, as open_pack_random took them
+    pub fn verify_pack_fairness<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PeekPack<'info>>,
+        claimed_templates: Vec<u32>,
+    ) -> Result<()> {
+        let session = &ctx.accounts.pack_session;
+        require!(session.entropy != [0u8; 32], MochiError::NotRandomDraw);
+        require!(
+            candidate_pool_hash(ctx.remaining_accounts) == session.candidate_pool_hash,
+            MochiError::FairnessMismatch
+        );
+        let rare_count = session.rare_card_keys.len();
+        require!(
+            claimed_templates.len() == rare_count,
+            MochiError::FairnessMismatch
+        );
+        let picks = pick_random_indices(session.entropy, ctx.remaining_accounts.len(), rare_count);
+        for (slot, &idx) in picks.iter().enumerate() {
+            let record_info = &ctx.remaining_accounts[idx];
+            require_keys_eq!(
+                record_info.key(),
+                session.rare_card_keys[slot],
+                MochiError::FairnessMismatch
+            );
+            let record: Account<CardRecord> = Account::try_from(record_info)?;
+            require!(
+                record.template_id == claimed_templates[slot]
+                    && record.template_id == session.rare_templates[slot],
+                MochiError::FairnessMismatch
+            );
         }
+        Ok(())
+    }
 
-        // Zero out the pack_session; account will be closed to admin via the context.
+    /// Tx2 Keep path – transfers only the Rare+ assets listed in the PackSessionV2.
+    /// remaining_accounts: [rare_card_records...][core_assets...]
+    /// Gift packs deliver to `session.recipient`, passed as the first account after the assets.
+    pub fn claim_pack_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        charge_claim_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.vault_treasury,
+            &ctx.accounts.system_program,
+        )?;
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        let rare_count = session.rare_card_keys.len();
+        let (card_accounts, asset_accounts, extras) =
+            split_rare_accounts(ctx.remaining_accounts, rare_count)?;
+        // A short asset list would otherwise shift into `extras` and surface as a bad recipient.
+        require!(
+            card_accounts.len() == rare_count && asset_accounts.len() == rare_count,
+            MochiError::InvalidCardCount
+        );
+        let user_info = ctx.accounts.user.to_account_info();
+        let new_owner = if session.recipient == Pubkey::default() {
+            &user_info
+        } else {
+            let recipient_info = extras.first().ok_or(MochiError::Unauthorized)?;
+            require_keys_eq!(
+                recipient_info.key(),
+                session.recipient,
+                MochiError::Unauthorized
+            );
+            recipient_info
+        };
+        claim_session_rares(
+            session,
+            card_accounts,
+            asset_accounts,
+            new_owner,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &user_info,
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: GACHA_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        ctx.accounts
+            .vault_state
+            .settle_session(&ctx.accounts.pack_session.currency);
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// Gift recipient claims the rares of a pack someone else paid for.
+    /// remaining_accounts: [rare_card_records...][core_assets...]
+    pub fn claim_gift_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimGiftV2<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        require_keys_eq!(
+            session.recipient,
+            ctx.accounts.recipient.key(),
+            MochiError::Unauthorized
+        );
+        charge_claim_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.recipient.to_account_info(),
+            &ctx.accounts.vault_treasury,
+            &ctx.accounts.system_program,
+        )?;
+        let (card_accounts, asset_accounts, _) =
+            split_rare_accounts(ctx.remaining_accounts, session.rare_card_keys.len())?;
+        claim_session_rares(
+            session,
+            card_accounts,
+            asset_accounts,
+            &ctx.accounts.recipient.to_account_info(),
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.recipient.to_account_info(),
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: GACHA_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        ctx.accounts
+            .vault_state
+            .settle_session(&ctx.accounts.pack_session.currency);
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// Claims every rare the session holds, however many (up to MAX_RARE_CARDS), in one ix.
+    /// remaining_accounts must be exactly [rare_card_records...][core_assets...] in session order.
+    /// Gift sessions go through `claim_gift_v2`.
+    pub fn claim_all_rares_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        charge_claim_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.vault_treasury,
+            &ctx.accounts.system_program,
+        )?;
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        require_keys_eq!(
+            session.recipient,
+            Pubkey::default(),
+            MochiError::Unauthorized
+        );
+        let rare_count = session.rare_card_keys.len();
+        require!(
+            rare_count <= MAX_RARE_CARDS && ctx.remaining_accounts.len() == rare_count * 2,
+            MochiError::InvalidCardCount
+        );
+        let (card_accounts, asset_accounts, _) =
+            split_rare_accounts(ctx.remaining_accounts, rare_count)?;
+        let user_info = ctx.accounts.user.to_account_info();
+        claim_session_rares(
+            session,
+            card_accounts,
+            asset_accounts,
+            &user_info,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &user_info,
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: GACHA_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        ctx.accounts
+            .vault_state
+            .settle_session(&ctx.accounts.pack_session.currency);
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// `claim_pack_v2` with explicit (card_record, core_asset) pairs; accounts are looked up by
+    /// key, so remaining_accounts may come in any order (gift recipient included).
+    pub fn claim_pack_v2_tagged<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+        pairs: Vec<CardAssetPair>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        charge_claim_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.vault_treasury,
+            &ctx.accounts.system_program,
+        )?;
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        let (card_accounts, asset_accounts) =
+            resolve_card_pairs(ctx.remaining_accounts, &session.rare_card_keys, &pairs)?;
+        let user_info = ctx.accounts.user.to_account_info();
+        let new_owner = if session.recipient == Pubkey::default() {
+            &user_info
+        } else {
+            find_account(ctx.remaining_accounts, &session.recipient)
+                .ok_or(MochiError::Unauthorized)?
+        };
+        claim_session_rares(
+            session,
+            &card_accounts,
+            &asset_accounts,
+            new_owner,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &user_info,
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: GACHA_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        ctx.accounts
+            .vault_state
+            .settle_session(&ctx.accounts.pack_session.currency);
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// `claim_gift_v2` with explicit (card_record, core_asset) pairs; see `claim_pack_v2_tagged`.
+    pub fn claim_gift_v2_tagged<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimGiftV2<'info>>,
+        pairs: Vec<CardAssetPair>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        require_keys_eq!(
+            session.recipient,
+            ctx.accounts.recipient.key(),
+            MochiError::Unauthorized
+        );
+        charge_claim_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.recipient.to_account_info(),
+            &ctx.accounts.vault_treasury,
+            &ctx.accounts.system_program,
+        )?;
+        let (card_accounts, asset_accounts) =
+            resolve_card_pairs(ctx.remaining_accounts, &session.rare_card_keys, &pairs)?;
+        claim_session_rares(
+            session,
+            &card_accounts,
+            &asset_accounts,
+            &ctx.accounts.recipient.to_account_info(),
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.recipient.to_account_info(),
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: GACHA_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        ctx.accounts
+            .vault_state
+            .settle_session(&ctx.accounts.pack_session.currency);
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// Relayer-signed claims for up to MAX_RELAYER_BATCH sessions in one transaction. The
+    /// relayer pays fees, rent and any claim fee, but each session's rares only ever go to
+    /// that session's beneficiary (the gift recipient, else the purchaser). Sessions must
+    /// still be inside their claim window; expired ones go through the expiry paths.
+    /// remaining_accounts, per session: [pack_session, beneficiary, rare_card_records...,
+    /// core_assets...] with the rare count taken from the session.
+    pub fn relayer_claim_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RelayerClaimBatch<'info>>,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        let vault_key = ctx.accounts.vault_state.key();
+        let relayer = ctx.accounts.relayer.to_account_info();
+        let mut rest = ctx.remaining_accounts;
+        let mut claimed = 0usize;
+        while !rest.is_empty() {
+            require!(claimed < MAX_RELAYER_BATCH, MochiError::InvalidCardCount);
+            let session_info = &rest[0];
+            require_keys_eq!(
+                *session_info.owner,
+                crate::ID,
+                MochiError::InvalidSessionState
+            );
+            let mut session = PackSessionV2::try_deserialize(&mut &session_info.data.borrow()[..])?;
+            // Ties the session to this vault; the bump alone is not enough.
+            let expected = Pubkey::create_program_address(
+                &[
+                    b"pack_session_v2",
+                    vault_key.as_ref(),
+                    session.user.as_ref(),
+                    &[session.bump],
+                ],
+                &crate::ID,
+            )
+            .map_err(|_| MochiError::InvalidPda)?;
+            require_keys_eq!(session_info.key(), expected, MochiError::InvalidPda);
+
+            let rare_count = session.rare_card_keys.len();
+            let group_len = 2 + rare_count * 2;
+            require!(rest.len() >= group_len, MochiError::InvalidCardCount);
+            let beneficiary = &rest[1];
+            require_keys_eq!(
+                beneficiary.key(),
+                session.beneficiary(),
+                MochiError::Unauthorized
+            );
+            charge_claim_fee(
+                &ctx.accounts.vault_state,
+                &relayer,
+                &ctx.accounts.vault_treasury,
+                &ctx.accounts.system_program,
+            )?;
+            claim_session_rares(
+                &mut session,
+                &rest[2..2 + rare_count],
+                &rest[2 + rare_count..group_len],
+                beneficiary,
+                &CoreCpi {
+                    authority: &ctx.accounts.vault_authority,
+                    payer: &relayer,
+                    vault_state: &vault_key,
+                    vault_bump: ctx.bumps.vault_authority,
+                    authority_seed: GACHA_VAULT_AUTHORITY_SEED,
+                    system_program: &ctx.accounts.system_program.to_account_info(),
+                    mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+                },
+            )?;
+            write_account(&session, session_info)?;
+            ctx.accounts.vault_state.settle_session(&session.currency);
+            rest = &rest[group_len..];
+            claimed += 1;
+        }
+        require!(claimed > 0, MochiError::InvalidCardCount);
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// Tx2 Flip path – lists the session's rares on the marketplace straight from gacha custody
+    /// instead of sending them to the wallet; `prices[i] == 0` delivers rare i to the user as a
+    /// normal claim. Each listed card gets a `CardProvenance` record naming the pack session.
+    /// Meant to follow `open_pack` in the same transaction.
+    /// remaining_accounts: [rare_card_records...][core_assets...]
+    ///   then per listed rare, in order: [market_card_record, listing, provenance]
+    /// Compute: every listed rare costs a Core transfer and a freeze CPI plus up to three
+    /// account creations on top of a plain claim; alongside `open_pack` expect to raise the
+    /// compute budget (1.4M CU max) and keep flips to a few rares per transaction.
+    pub fn flip_pack_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlipPackV2<'info>>,
+        prices: Vec<u64>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        flip_pack_v2_inner(ctx, prices, expected_nonce)
+    }
+
+    /// Lists rare `index` of the session straight from gacha custody at `price_lamports`,
+    /// skipping the wallet round-trip. The session resolves as a whole, so the other rares
+    /// are delivered to the user as in a claim (this is `flip_pack_v2` with one price set).
+    /// Accounts as for `flip_pack_v2`, with a single [market_card_record, listing, provenance].
+    pub fn list_from_pack<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlipPackV2<'info>>,
+        index: u8,
+        price_lamports: u64,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        let rare_count = ctx.accounts.pack_session.rare_card_keys.len();
+        require!((index as usize) < rare_count, MochiError::InvalidCardCount);
+        require!(
+            price_lamports > 0
+                && ctx
+                    .accounts
+                    .market_vault_state
+                    .meets_listing_floor(price_lamports, &None),
+            MochiError::InvalidPrice
+        );
+        let mut prices = vec![0u64; rare_count];
+        prices[index as usize] = price_lamports;
+        flip_pack_v2_inner(ctx, prices, expected_nonce)
+    }
+
+    /// Tx2 Sellback path – frees Rare+ reservations and pays the refund.
+    /// remaining_accounts: [rare_card_records...][core_assets...][optional token accounts]
+    pub fn sellback_pack_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        sellback_v2_inner(ctx, false, expected_nonce)
+    }
+
+    /// Deflationary sellback: same buyback as `sellback_pack_v2`, but the reserved rares are
+    /// burned and their records marked Burned. The rares' Core assets must follow the records.
+    pub fn sellback_burn_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        sellback_v2_inner(ctx, true, expected_nonce)
+    }
+
+    /// Post-window cleanup – frees Rare+ reservations without payout.
+    pub fn expire_session_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.pack_session;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        require!(now > session.expires_at, MochiError::SessionNotExpired);
+        // A lapsed layaway gets its installments back; paid-up sessions forfeit the payment.
+        if !session.is_paid() {
+            let vault_key = ctx.accounts.vault_state.key();
+            let seeds = &[
+                GACHA_VAULT_AUTHORITY_SEED,
+                vault_key.as_ref(),
+                &[ctx.bumps.vault_authority],
+            ];
+            refund_layaway(
+                &mut ctx.accounts.vault_state,
+                session,
+                &ctx.accounts.vault_authority.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                &[&seeds[..]],
+            )?;
+        }
+        free_reserved_rares(
+            session,
+            &ctx.accounts.vault_authority.key(),
+            tracked_inventory(
+                &ctx.accounts.vault_state,
+                &mut ctx.accounts.rarity_inventory,
+            )?,
+            ctx.remaining_accounts,
+        )?;
+
+        ctx.accounts.vault_state.settle_session(&session.currency);
+        session.state = PackState::Expired;
+        Ok(())
+    }
+
+    /// Admin-only hard reset for V2 sessions; frees any passed Rare+ CardRecords.
+    /// With `force_close_delay_seconds` set, this only runs once a matching
+    /// `admin_request_force_close` has aged past the delay (unless `emergency_mode`), giving the
+    /// user a window to resolve the session first. The request is closed to the admin.
+    pub fn admin_force_close_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminForceCloseV2<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let delay = ctx.accounts.vault_state.force_close_delay_seconds;
+        if delay > 0 && !ctx.accounts.vault_state.emergency_mode {
+            let request = ctx
+                .accounts
+                .force_close_request
+                .as_ref()
+                .ok_or(MochiError::ForceCloseNotRequested)?;
+            // A request left over from an earlier session at this PDA does not count.
+            require!(
+                request.session_created_at == ctx.accounts.pack_session.created_at,
+                MochiError::StaleForceCloseRequest
+            );
+            let now = Clock::get()?.unix_timestamp;
+            require!(
+                now >= request.requested_at.saturating_add(delay),
+                MochiError::ForceCloseLocked
+            );
+        }
         let session = &mut ctx.accounts.pack_session;
+        // An open layaway's installments sit in escrow; hand them back before wiping the session.
+        if session.state == PackState::PendingDecision && !session.is_paid() {
+            let vault_key = ctx.accounts.vault_state.key();
+            let seeds = &[
+                GACHA_VAULT_AUTHORITY_SEED,
+                vault_key.as_ref(),
+                &[ctx.bumps.vault_authority],
+            ];
+            refund_layaway(
+                &mut ctx.accounts.vault_state,
+                session,
+                &ctx.accounts.vault_authority.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                &[&seeds[..]],
+            )?;
+        }
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        let rare_count = session.rare_card_keys.len();
+        let (card_accounts, _, _) = split_rare_accounts(&ctx.remaining_accounts, rare_count)?;
+        // Only this session's rares that are still reserved to its user go back to stock.
+        let vault_key = ctx.accounts.vault_state.key();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let mut freed_cards: u8 = 0;
+        for (idx, acc_info) in card_accounts.iter().enumerate() {
+            require_keys_eq!(
+                acc_info.key(),
+                session.rare_card_keys[idx],
+                MochiError::CardKeyMismatch
+            );
+            if release_session_record(
+                acc_info,
+                &vault_key,
+                &session.user,
+                &vault_authority,
+                inventory.as_deref_mut(),
+            )? {
+                freed_cards = freed_cards.saturating_add(1);
+            }
+        }
+
+        // Zero session but keep account alive for the user; they can reuse it on next open.
+        if session.state == PackState::PendingDecision {
+            ctx.accounts.vault_state.settle_session(&session.currency);
+        }
         session.state = PackState::Uninitialized;
         session.paid_amount = 0;
+        session.paid_sol_amount = 0;
         session.created_at = 0;
         session.expires_at = 0;
         session.currency = Currency::Sol;
-        session.card_record_keys = [Pubkey::default(); PACK_CARD_COUNT];
-        session.client_seed_hash = [0u8; 32];
-        session.rarity_prices = Vec::new();
+        session.rare_card_keys.clear();
+        session.rare_templates.clear();
+        session.total_slots = PACK_CARD_COUNT as u8;
+        emit!(AdminSessionAction {
+            admin: ctx.accounts.admin.key(),
+            user: ctx.accounts.user.key(),
+            action: ADMIN_ACTION_FORCE_CLOSE,
+            freed_cards,
+        });
         Ok(())
     }
 
-    pub fn admin_reset_cards<'info>(
-        ctx: Context<'_, '_, 'info, 'info, AdminResetCards<'info>>,
+    /// First phase of a time-locked force close: records when the admin asked, so the user can
+    /// see it coming and resolve the session within `force_close_delay_seconds`.
+    pub fn admin_request_force_close(ctx: Context<RequestForceClose>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let request = &mut ctx.accounts.force_close_request;
+        request.vault_state = ctx.accounts.vault_state.key();
+        request.user = ctx.accounts.user.key();
+        request.requested_at = now;
+        request.bump = ctx.bumps.force_close_request;
+        request.session_created_at = ctx.accounts.pack_session.created_at;
+        emit!(ForceCloseRequested {
+            admin: ctx.accounts.admin.key(),
+            user: ctx.accounts.user.key(),
+            executable_at: now.saturating_add(ctx.accounts.vault_state.force_close_delay_seconds),
+        });
+        Ok(())
+    }
+
+    /// Primary admin only: delay between `admin_request_force_close` and
+    /// `admin_force_close_v2` (0 = immediate), and the emergency switch that skips it.
+    pub fn set_force_close_delay(
+        ctx: Context<SetVaultConfig>,
+        force_close_delay_seconds: i64,
+        emergency_mode: bool,
     ) -> Result<()> {
-        // Admin loop to set any provided CardRecords back to Available/ vault authority owner.
         require_keys_eq!(
             ctx.accounts.admin.key(),
             ctx.accounts.vault_state.admin,
             MochiError::Unauthorized
         );
-        msg!("admin_reset_cards rem len {}", ctx.remaining_accounts.len());
-        for acc_info in ctx.remaining_accounts.iter() {
-            if let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) {
-                if card_record.vault_state == ctx.accounts.vault_state.key() {
-                    card_record.status = CardStatus::Available;
-                    card_record.owner = ctx.accounts.vault_authority.key();
-                    let mut data = acc_info.try_borrow_mut_data()?;
-                    let mut cursor = std::io::Cursor::new(&mut data[..]);
-                    card_record.try_serialize(&mut cursor)?;
-                }
-            }
-        }
+        require!(force_close_delay_seconds >= 0, MochiError::InvalidWindow);
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.force_close_delay_seconds = force_close_delay_seconds;
+        vault_state.emergency_mode = emergency_mode;
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct OpenPackV2<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        init_if_needed,
-        payer = user,
-        seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()],
-        bump,
-        space = 8 + PackSessionV2::SIZE,
-    )]
-    pub pack_session: Account<'info, PackSessionV2>,
-    /// CHECK: Vault authority PDA (validated by seeds)
+    /// Support tool: gives a stuck user more time on a PendingDecision V2 session. The new
+    /// deadline must be later than the current one, so this can never cut a window short.
+    pub fn admin_extend_session(
+        ctx: Context<AdminExtendSession>,
+        new_expires_at: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        let previous_expires_at = session.expires_at;
+        require!(
+            new_expires_at > previous_expires_at,
+            MochiError::InvalidWindow
+        );
+        session.expires_at = new_expires_at;
+        emit!(AdminSessionExtended {
+            admin: ctx.accounts.admin.key(),
+            user: ctx.accounts.user.key(),
+            previous_expires_at,
+            new_expires_at,
+        });
+        Ok(())
+    }
+
+    /// Moves a pending V1 session onto the V2 rails so it can be resolved via claim/sellback_pack_v2.
+    /// Rare+ reservations carry over; commons are released. The V1 account is closed to the user.
+    /// remaining_accounts: the V1 session's card_records, in reservation order.
+    pub fn migrate_session_to_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateSessionToV2<'info>>,
+    ) -> Result<()> {
+        let legacy = &ctx.accounts.legacy_session;
+        require!(
+            legacy.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        require!(
+            ctx.accounts.pack_session.state != PackState::PendingDecision,
+            MochiError::SessionExists
+        );
+        require!(
+            ctx.remaining_accounts.len() == PACK_CARD_COUNT,
+            MochiError::InvalidCardCount
+        );
+
+        let mut rare_keys: Vec<Pubkey> = Vec::with_capacity(MAX_RARE_CARDS);
+        let mut rare_templates: Vec<u32> = Vec::with_capacity(MAX_RARE_CARDS);
+        for (idx, acc_info) in ctx.remaining_accounts.iter().enumerate() {
+            require_keys_eq!(
+                acc_info.key(),
+                legacy.card_record_keys[idx],
+                MochiError::CardKeyMismatch
+            );
+            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            require!(
+                card_record.status == CardStatus::Reserved,
+                MochiError::CardNotReserved
+            );
+            require_keys_eq!(
+                card_record.owner,
+                ctx.accounts.user.key(),
+                MochiError::Unauthorized
+            );
+            if is_rare_or_above(&card_record.rarity) {
+                require!(
+                    rare_keys.len() < MAX_RARE_CARDS,
+                    MochiError::TooManyRareCards
+                );
+                rare_keys.push(acc_info.key());
+                rare_templates.push(card_record.template_id);
+            } else {
+                card_record.status = CardStatus::Available;
+                card_record.owner = ctx.accounts.vault_authority.key();
+                persist_card_record(&card_record, acc_info)?;
+            }
+        }
+
+        // The legacy session leaves PendingDecision as it closes; the V2 one is counted and
+        // numbered exactly like a fresh open_pack_v2.
+        let currency = legacy.currency.clone();
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.settle_session(&currency);
+        vault_state.open_session(None, &currency)?;
+        let nonce = vault_state.next_session_nonce()?;
+
+        let session = &mut ctx.accounts.pack_session;
+        // A reused V2 PDA must not carry a previous session's recipient, layaway price or flags.
+        session.reset_for_open();
+        session.user = ctx.accounts.user.key();
+        session.currency = currency;
+        session.paid_amount = legacy.paid_amount;
+        session.created_at = legacy.created_at;
+        session.expires_at = legacy.expires_at;
+        session.state = PackState::PendingDecision;
+        session.client_seed_hash = legacy.client_seed_hash;
+        session.manifest_hash = legacy.manifest_hash;
+        session.nonce = nonce;
+        session.rare_card_keys = rare_keys;
+        session.rare_templates = rare_templates;
+        session.total_slots = PACK_CARD_COUNT as u8;
+        session.bump = ctx.bumps.pack_session;
+        Ok(())
+    }
+
+    pub fn open_pack_start<'info>(
+        ctx: Context<'_, '_, 'info, 'info, OpenPackStart<'info>>,
+        currency: Currency,
+        client_seed_hash: [u8; 32],
+        rarity_prices: Vec<u64>,
+        manifest_hash: [u8; 32],
+    ) -> Result<()> {
+        // V1 sessions carry no instructions sysvar, so gated vaults only open through V2.
+        require!(
+            ctx.accounts.vault_state.open_gate_authority.is_none(),
+            MochiError::OpenGateRequired
+        );
+        // Counted up front; any failure below reverts it with the rest of the open.
+        ctx.accounts.vault_state.open_session(None, &currency)?;
+        let vault_state = &ctx.accounts.vault_state;
+        let now = Clock::get()?.unix_timestamp;
+
+        require_keys_eq!(
+            ctx.accounts.vault_treasury.key(),
+            vault_state.treasury,
+            MochiError::VaultMismatch
+        );
+        require!(
+            vault_state.accepts_currency(&currency)
+                && currency != Currency::Both
+                && currency != Currency::Mochi,
+            MochiError::CurrencyNotAccepted
+        );
+        // sellback_pack sums every entry, so an over-long vec would inflate the payout.
+        require!(
+            rarity_prices.len() == PACK_CARD_COUNT,
+            MochiError::InvalidCardCount
+        );
+
+        let (card_accounts, _asset_accounts, extra_accounts) =
+            partition_pack_accounts(&ctx.remaining_accounts)?;
+        msg!("open_pack_start rem len {}", ctx.remaining_accounts.len());
+        for (i, ai) in ctx.remaining_accounts.iter().enumerate() {
+            msg!("  rem[{}] = {}", i, ai.key);
+        }
+        let mut user_token: Option<Account<'info, TokenAccount>> = None;
+        let mut vault_token: Option<Account<'info, TokenAccount>> = None;
+        if currency == Currency::Token {
+            require!(extra_accounts.len() >= 2, MochiError::MissingTokenAccount);
+            user_token = Some(Account::try_from(&extra_accounts[0])?);
+            vault_token = Some(Account::try_from(&extra_accounts[1])?);
+        }
+
+        // Payment handling (simplified). For SOL we move lamports; for tokens we debit from user token account.
+        match currency {
+            Currency::Sol => {
+                let price = vault_state.pack_price_sol;
+                require!(price > 0, MochiError::InvalidPrice);
+                require!(
+                    ctx.accounts.user.lamports() >= price,
+                    MochiError::InsufficientFunds
+                );
+                invoke(
+                    &system_instruction::transfer(
+                        &ctx.accounts.user.key(),
+                        &ctx.accounts.vault_treasury.key(),
+                        price,
+                    ),
+                    &[
+                        ctx.accounts.user.to_account_info(),
+                        ctx.accounts.vault_treasury.to_account_info(),
+                        ctx.accounts.system_program.to_account_info(),
+                    ],
+                )?;
+            }
+            Currency::Token => {
+                let price = vault_state.pack_price_usdc;
+                require!(price > 0, MochiError::InvalidPrice);
+                let user_token = user_token.as_ref().ok_or(MochiError::MissingTokenAccount)?;
+                let vault_token = vault_token
+                    .as_ref()
+                    .ok_or(MochiError::MissingTokenAccount)?;
+                if let Some(mint) = vault_state.usdc_mint {
+                    require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
+                    require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
+                }
+                let price = vault_state.pack_price_usdc;
+                let cpi_accounts = Transfer {
+                    from: user_token.to_account_info(),
+                    to: vault_token.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                };
+                let cpi_ctx =
+                    CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+                token::transfer(cpi_ctx, price)?;
+            }
+            Currency::Both | Currency::Mochi => return err!(MochiError::CurrencyNotAccepted),
+        }
+
+        let mut card_record_keys: [Pubkey; PACK_CARD_COUNT] = [Pubkey::default(); PACK_CARD_COUNT];
+        let mut rarity_counts = [0u8; RARITY_COUNT];
+
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            matches!(
+                session.state,
+                PackState::Uninitialized
+                    | PackState::Accepted
+                    | PackState::Rejected
+                    | PackState::Expired
+            ),
+            MochiError::SessionExists
+        );
+        session.user = ctx.accounts.user.key();
+        session.currency = currency.clone();
+        session.paid_amount = match currency {
+            Currency::Sol => vault_state.pack_price_sol,
+            Currency::Token => vault_state.pack_price_usdc,
+            Currency::Both | Currency::Mochi => return err!(MochiError::CurrencyNotAccepted),
+        };
+        charge_spend_limit(
+            &ctx.accounts.user_stats,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            vault_state.spend_lamports(&currency, session.paid_amount, 0),
+            now,
+        )?;
+        session.created_at = now;
+        session.expires_at = now
+            .checked_add(vault_state.capped_window(vault_state.claim_window_seconds))
+            .ok_or(MochiError::MathOverflow)?;
+        session.state = PackState::PendingDecision;
+        session.client_seed_hash = client_seed_hash;
+        session.manifest_hash = manifest_hash;
+        session.rarity_prices = rarity_prices;
+
+        // Validate + Reserve CardRecords in one pass
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        for (idx, acc_info) in card_accounts.iter().enumerate() {
+            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            require_keys_eq!(
+                card_record.vault_state,
+                ctx.accounts.vault_state.key(),
+                MochiError::VaultMismatch
+            );
+            require!(
+                card_record.status == CardStatus::Available,
+                MochiError::CardNotAvailable
+            );
+            card_record_keys[idx] = acc_info.key();
+            rarity_counts[card_record.rarity.clone() as usize] += 1;
+            if let Some(inventory) = inventory.as_deref_mut() {
+                inventory.remove(&card_record.rarity);
+            }
+            card_record.status = CardStatus::Reserved;
+            card_record.owner = ctx.accounts.user.key();
+            // Manually serialize because we constructed Account<T> from raw AccountInfo
+            let mut data = acc_info.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(&mut data[..]);
+            card_record.try_serialize(&mut cursor)?;
+        }
+        session.card_record_keys = card_record_keys;
+
+        let nonce = ctx
+            .accounts
+            .vault_state
+            .session_nonce
+            .checked_add(1)
+            .ok_or(MochiError::MathOverflow)?;
+        ctx.accounts.vault_state.session_nonce = nonce;
+        ctx.accounts.pack_session.nonce = nonce;
+        emit!(PackOpened {
+            vault_state: ctx.accounts.vault_state.key(),
+            user: ctx.accounts.user.key(),
+            pack_session: ctx.accounts.pack_session.key(),
+            paid_amount: ctx.accounts.pack_session.paid_amount,
+            rarity_counts,
+        });
+        Ok(())
+    }
+
+    pub fn claim_pack<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        charge_claim_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.vault_treasury,
+            &ctx.accounts.system_program,
+        )?;
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        require!(now <= session.expires_at, MochiError::SessionExpired);
+
+        let (card_accounts, asset_accounts, _extras) =
+            partition_pack_accounts(&ctx.remaining_accounts)?;
+        msg!(
+            "claim_pack: cards {} assets {} rarity_prices_len {} state {:?}",
+            card_accounts.len(),
+            asset_accounts.len(),
+            session.rarity_prices.len(),
+            session.state
+        );
+        require!(
+            asset_accounts.len() == PACK_CARD_COUNT,
+            MochiError::InvalidCardCount
+        );
+        // Defensive: ensure rarity_prices never allocates huge vec on deserialize
+        if session.rarity_prices.len() > PACK_CARD_COUNT {
+            session.rarity_prices.truncate(PACK_CARD_COUNT);
+        }
+        for i in 0..PACK_CARD_COUNT {
+            let acc_info: &AccountInfo<'info> = &card_accounts[i];
+            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            require!(
+                card_record.status == CardStatus::Reserved,
+                MochiError::CardNotReserved
+            );
+            require_keys_eq!(
+                card_record.owner,
+                ctx.accounts.user.key(),
+                MochiError::Unauthorized
+            );
+            msg!("claim idx {} card {}", i, acc_info.key());
+            card_record.status = CardStatus::UserOwned;
+            card_record.owner = ctx.accounts.user.key();
+            // Transfer Core asset to user
+            let asset_info: &AccountInfo<'info> = &asset_accounts[i];
+            msg!("claim transfer asset {}", asset_info.key());
+            transfer_core_asset(
+                &asset_info,
+                &ctx.accounts.vault_authority,
+                &ctx.accounts.user.to_account_info(), // claimer pays any rent
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.vault_state.key(),
+                ctx.bumps.vault_authority,
+                GACHA_VAULT_AUTHORITY_SEED,
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.mpl_core_program.to_account_info(),
+            )?;
+            msg!("claim transfer done {}", asset_info.key());
+            // Persist card_record changes
+            let mut data = acc_info.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(&mut data[..]);
+            card_record.try_serialize(&mut cursor)?;
+        }
+
+        ctx.accounts.vault_state.settle_session(&session.currency);
+        session.state = PackState::Accepted;
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// New: claim selected cards in smaller batches to reduce heap/CU pressure.
+    /// remaining_accounts = [card_records..., core_assets...] with equal lengths >0.
+    pub fn claim_pack_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        require!(now <= session.expires_at, MochiError::SessionExpired);
+
+        let (card_accounts, asset_accounts, _extras) =
+            partition_half_accounts(&ctx.remaining_accounts)?;
+        // Restrict batch size to 1 or 2 to avoid heap blowups.
+        require!(
+            card_accounts.len() > 0 && card_accounts.len() <= 2,
+            MochiError::InvalidCardCount
+        );
+        for i in 0..card_accounts.len() {
+            let acc_info: &AccountInfo<'info> = &card_accounts[i];
+            // Only cards reserved by this session may be claimed in a batch.
+            require!(
+                session.card_record_keys.contains(acc_info.key),
+                MochiError::CardKeyMismatch
+            );
+            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            require!(
+                card_record.status == CardStatus::Reserved,
+                MochiError::CardNotReserved
+            );
+            require_keys_eq!(
+                card_record.owner,
+                ctx.accounts.user.key(),
+                MochiError::Unauthorized
+            );
+            card_record.status = CardStatus::UserOwned;
+            card_record.owner = ctx.accounts.user.key();
+            let asset_info: &AccountInfo<'info> = &asset_accounts[i];
+            require_keys_eq!(
+                asset_info.key(),
+                card_record.core_asset,
+                MochiError::AssetMismatch
+            );
+            transfer_core_asset(
+                &asset_info,
+                &ctx.accounts.vault_authority,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.vault_state.key(),
+                ctx.bumps.vault_authority,
+                GACHA_VAULT_AUTHORITY_SEED,
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.mpl_core_program.to_account_info(),
+            )?;
+            let mut data = acc_info.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(&mut data[..]);
+            card_record.try_serialize(&mut cursor)?;
+        }
+        // Keep session pending; frontend/backend should call finalize_claim when all cards processed.
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// New: finalize after all cards are user-owned; sets state = Accepted.
+    /// remaining_accounts must be the session's card_record PDAs, in reservation order.
+    pub fn finalize_claim<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        require!(now <= session.expires_at, MochiError::SessionExpired);
+        require!(
+            ctx.remaining_accounts.len() == PACK_CARD_COUNT,
+            MochiError::InvalidCardCount
+        );
+        for (idx, acc_info) in ctx.remaining_accounts.iter().enumerate() {
+            require_keys_eq!(
+                acc_info.key(),
+                session.card_record_keys[idx],
+                MochiError::CardKeyMismatch
+            );
+            let card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            require!(
+                card_record.status == CardStatus::UserOwned,
+                MochiError::CardNotReserved
+            );
+            require_keys_eq!(
+                card_record.owner,
+                ctx.accounts.user.key(),
+                MochiError::Unauthorized
+            );
+        }
+        ctx.accounts.vault_state.settle_session(&session.currency);
+        session.state = PackState::Accepted;
+        Ok(())
+    }
+
+    pub fn sellback_pack<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
+        expected_nonce: u64,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.pack_session;
+        require!(session.nonce == expected_nonce, MochiError::StaleSession);
+        let vault_state = &ctx.accounts.vault_state;
+        require!(!vault_state.sellback_paused, MochiError::SellbackPaused);
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        require!(now <= session.expires_at, MochiError::SessionExpired);
+        require_keys_eq!(
+            ctx.accounts.vault_treasury.key(),
+            vault_state.treasury,
+            MochiError::VaultMismatch
+        );
+
+        let total_value: u64 = session.rarity_prices.iter().copied().sum();
+        let payout = total_value
+            .checked_mul(vault_state.buyback_bps as u64)
+            .and_then(|x| x.checked_div(10_000))
+            .ok_or(MochiError::MathOverflow)?;
+        // Refunds come out of the treasury, so the fee simply stays there.
+        let payout = payout - sellback_fee(vault_state, payout)?;
+
+        let (card_accounts, asset_accounts, extra_accounts) =
+            partition_pack_accounts(&ctx.remaining_accounts)?;
+        require!(
+            asset_accounts.len() == PACK_CARD_COUNT,
+            MochiError::InvalidCardCount
+        );
+
+        match session.currency {
+            // open_pack_start never creates combined or MOCHI sessions.
+            Currency::Both | Currency::Mochi => return err!(MochiError::CurrencyNotAccepted),
+            // A zero buyback still frees the cards, just without a transfer CPI.
+            _ if payout == 0 => {}
+            Currency::Sol => {
+                invoke(
+                    &system_instruction::transfer(
+                        &ctx.accounts.vault_treasury.key(),
+                        &ctx.accounts.user.key(),
+                        payout,
+                    ),
+                    &[
+                        ctx.accounts.vault_treasury.to_account_info(),
+                        ctx.accounts.user.to_account_info(),
+                        ctx.accounts.system_program.to_account_info(),
+                    ],
+                )?;
+            }
+            Currency::Token => {
+                require!(extra_accounts.len() >= 2, MochiError::MissingTokenAccount);
+                let user_token: Account<TokenAccount> = Account::try_from(&extra_accounts[0])?;
+                let vault_token: Account<TokenAccount> = Account::try_from(&extra_accounts[1])?;
+                if let Some(mint) = vault_state.usdc_mint {
+                    require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
+                    require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
+                }
+                let cpi_accounts = Transfer {
+                    from: vault_token.to_account_info(),
+                    to: user_token.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                };
+                let vault_key = vault_state.key();
+                let seeds = &[
+                    GACHA_VAULT_AUTHORITY_SEED,
+                    vault_key.as_ref(),
+                    &[ctx.bumps.vault_authority],
+                ];
+                let signer = &[&seeds[..]];
+                let cpi_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    cpi_accounts,
+                    signer,
+                );
+                token::transfer(cpi_ctx, payout)?;
+            }
+        }
+
+        // Assets remain in vault authority escrow; only the records go back to stock.
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        for (idx, acc_info) in card_accounts.iter().enumerate() {
+            require_keys_eq!(
+                acc_info.key(),
+                session.card_record_keys[idx],
+                MochiError::CardKeyMismatch
+            );
+            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            require!(
+                card_record.status == CardStatus::Reserved,
+                MochiError::CardNotReserved
+            );
+            require_keys_eq!(card_record.owner, session.user, MochiError::Unauthorized);
+            card_record.status = CardStatus::Available;
+            card_record.owner = ctx.accounts.vault_authority.key();
+            if let Some(inventory) = inventory.as_deref_mut() {
+                inventory.add(&card_record.rarity)?;
+            }
+            persist_card_record(&card_record, acc_info)?;
+        }
+
+        ctx.accounts.vault_state.settle_session(&session.currency);
+        session.state = PackState::Rejected;
+        Ok(())
+    }
+
+    pub fn expire_session<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolvePack<'info>>,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.pack_session;
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        require!(now > session.expires_at, MochiError::SessionNotExpired);
+
+        // Cards already moved by claim_pack_batch stay with the user; cards still reserved
+        // to the user are freed. Anything else has left the session and is left alone.
+        let (card_accounts, _asset_accounts, extras) =
+            partition_pack_accounts(&ctx.remaining_accounts)?;
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        let (mut claimed_value, mut unclaimed_value) = (0u128, 0u128);
+        let mut claimed = 0u8;
+        for (idx, acc_info) in card_accounts.iter().enumerate() {
+            require_keys_eq!(
+                acc_info.key(),
+                session.card_record_keys[idx],
+                MochiError::CardKeyMismatch
+            );
+            let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            if card_record.owner != session.user {
+                continue;
+            }
+            let value = session.rarity_prices.get(idx).copied().unwrap_or(0) as u128;
+            if card_record.status == CardStatus::UserOwned {
+                claimed_value += value;
+                claimed += 1;
+                continue;
+            }
+            if card_record.status != CardStatus::Reserved {
+                continue;
+            }
+            unclaimed_value += value;
+            card_record.status = CardStatus::Available;
+            card_record.owner = ctx.accounts.vault_authority.key();
+            if let Some(inventory) = inventory.as_deref_mut() {
+                inventory.add(&card_record.rarity)?;
+            }
+            persist_card_record(&card_record, acc_info)?;
+        }
+
+        let refund = partial_claim_refund(
+            session.paid_amount,
+            claimed,
+            claimed_value,
+            unclaimed_value,
+            ctx.accounts.vault_state.partial_claim_refund_bps,
+        )?;
+        if refund > 0 {
+            let vault_key = ctx.accounts.vault_state.key();
+            let seeds = &[
+                GACHA_VAULT_AUTHORITY_SEED,
+                vault_key.as_ref(),
+                &[ctx.bumps.vault_authority],
+            ];
+            let signer = &[&seeds[..]];
+            match session.currency {
+                Currency::Sol => refund_pack_sol(
+                    &ctx.accounts.vault_authority.to_account_info(),
+                    &ctx.accounts.user.to_account_info(),
+                    &ctx.accounts.system_program.to_account_info(),
+                    signer,
+                    refund,
+                )?,
+                Currency::Token => refund_pack_tokens(
+                    ctx.accounts.vault_state.usdc_mint,
+                    extras,
+                    &ctx.accounts.vault_authority.to_account_info(),
+                    &ctx.accounts.token_program.to_account_info(),
+                    signer,
+                    refund,
+                )?,
+                // open_pack_start never creates combined or MOCHI sessions.
+                Currency::Both | Currency::Mochi => return err!(MochiError::CurrencyNotAccepted),
+            }
+            emit!(PartialClaimRefunded {
+                user: session.user,
+                claimed_cards: claimed,
+                amount: refund,
+            });
+        }
+
+        ctx.accounts.vault_state.settle_session(&session.currency);
+        session.state = PackState::Expired;
+        Ok(())
+    }
+
+    pub fn admin_force_expire<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminForceExpire<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            session.state == PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+
+        let (card_accounts, _asset_accounts, _extras) =
+            partition_pack_accounts(&ctx.remaining_accounts)?;
+        let vault_key = ctx.accounts.vault_state.key();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        let mut freed_cards: u8 = 0;
+        for acc_info in card_accounts.iter() {
+            if release_session_record(
+                acc_info,
+                &vault_key,
+                &session.user,
+                &vault_authority,
+                inventory.as_deref_mut(),
+            )? {
+                freed_cards = freed_cards.saturating_add(1);
+            }
+        }
+
+        ctx.accounts.vault_state.settle_session(&session.currency);
+        session.state = PackState::Expired;
+        emit!(AdminSessionAction {
+            admin: ctx.accounts.admin.key(),
+            user: ctx.accounts.user.key(),
+            action: ADMIN_ACTION_EXPIRE,
+            freed_cards,
+        });
+        Ok(())
+    }
+
+    pub fn admin_reset_session<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminResetSession<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+
+        // Optionally free card records passed in remaining accounts that are still reserved to
+        // this session's user.
+        let vault_key = ctx.accounts.vault_state.key();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let session_user = ctx.accounts.pack_session.user;
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        let mut freed_cards: u8 = 0;
+        for acc_info in ctx.remaining_accounts.iter() {
+            if release_session_record(
+                acc_info,
+                &vault_key,
+                &session_user,
+                &vault_authority,
+                inventory.as_deref_mut(),
+            )? {
+                freed_cards = freed_cards.saturating_add(1);
+            }
+        }
+
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            session.state != PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        session.state = PackState::Uninitialized;
+        session.paid_amount = 0;
+        session.created_at = 0;
+        session.expires_at = 0;
+        session.currency = Currency::Sol;
+        session.card_record_keys = [Pubkey::default(); PACK_CARD_COUNT];
+        session.client_seed_hash = [0u8; 32];
+        session.manifest_hash = [0u8; 32];
+        session.rarity_prices = Vec::new();
+        emit!(AdminSessionAction {
+            admin: ctx.accounts.admin.key(),
+            user: ctx.accounts.user.key(),
+            action: ADMIN_ACTION_RESET,
+            freed_cards,
+        });
+        Ok(())
+    }
+
+    pub fn user_reset_session<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UserResetSession<'info>>,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.pack_session;
+        require!(
+            session.state != PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+
+        for acc_info in ctx.remaining_accounts.iter() {
+            if let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) {
+                if card_record.vault_state == ctx.accounts.vault_state.key() {
+                    card_record.status = CardStatus::Available;
+                    card_record.owner = ctx.accounts.vault_authority.key();
+                }
+            }
+        }
+        // Account will be closed to user via `close = user` attribute.
+        Ok(())
+    }
+
+    /// V2 counterpart of `user_reset_session`: returns a finished session's rent to the user.
+    /// Rares are already settled in every terminal state, so no CardRecords are touched.
+    pub fn close_session_v2(ctx: Context<CloseSessionV2>) -> Result<()> {
+        require!(
+            ctx.accounts.pack_session.state != PackState::PendingDecision,
+            MochiError::InvalidSessionState
+        );
+        // Account will be closed to user via `close = user` attribute.
+        Ok(())
+    }
+
+    /// Grow a V1 session opened before session nonces existed; the nonce is zero-filled.
+    pub fn migrate_pack_session(ctx: Context<MigratePackSession>) -> Result<()> {
+        let target_len: usize = 8 + PackSession::SIZE;
+        let session_info = ctx.accounts.pack_session.to_account_info();
+        require!(
+            session_info.owner == ctx.program_id,
+            MochiError::Unauthorized
+        );
+        if session_info.data_len() >= target_len {
+            return Ok(());
+        }
+
+        let required_lamports = Rent::get()?.minimum_balance(target_len);
+        if session_info.lamports() < required_lamports {
+            let diff = required_lamports
+                .checked_sub(session_info.lamports())
+                .ok_or(MochiError::MathOverflow)?;
+            invoke(
+                &system_instruction::transfer(&ctx.accounts.payer.key(), session_info.key, diff),
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    session_info.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+        session_info.realloc(target_len, true)?;
+        Ok(())
+    }
+
+    /// Resize a V2 session PDA created before reward_minted existed so open_pack can reuse it.
+    pub fn migrate_pack_session_v2(ctx: Context<MigratePackSessionV2>) -> Result<()> {
+        let target_len: usize = 8 + PackSessionV2::SIZE;
+        let session_info = ctx.accounts.pack_session.to_account_info();
+        require!(
+            session_info.owner == ctx.program_id,
+            MochiError::Unauthorized
+        );
+        if session_info.data_len() == target_len {
+            return Ok(());
+        }
+
+        let required_lamports = Rent::get()?.minimum_balance(target_len);
+        if session_info.lamports() < required_lamports {
+            let diff = required_lamports
+                .checked_sub(session_info.lamports())
+                .ok_or(MochiError::MathOverflow)?;
+            invoke(
+                &system_instruction::transfer(&ctx.accounts.payer.key(), session_info.key, diff),
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    session_info.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+        session_info.realloc(target_len, true)?;
+        Ok(())
+    }
+
+    pub fn list_card(
+        ctx: Context<ListCard>,
+        price_lamports: u64,
+        currency_mint: Option<Pubkey>,
+        template_id: u32,
+        rarity: Rarity,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts
+                .vault_state
+                .accepts_listing_currency(&currency_mint),
+            MochiError::CurrencyNotAccepted
+        );
+        require!(
+            ctx.accounts
+                .vault_state
+                .meets_listing_floor(price_lamports, &currency_mint),
+            MochiError::InvalidPrice
+        );
+        // Enforce canonical marketplace vault PDA so listings cannot target a bogus vault.
+        let (expected_vault, _) =
+            Pubkey::find_program_address(&[MARKETPLACE_VAULT_SEED], ctx.program_id);
+        require_keys_eq!(
+            ctx.accounts.vault_state.key(),
+            expected_vault,
+            MochiError::VaultMismatch
+        );
+
+        // init_if_needed would otherwise clobber a live listing; a fresh account is all zeroes
+        // (which decodes as Active), so only an existing one is checked.
+        let existing = &ctx.accounts.listing;
+        require!(
+            existing.vault_state == Pubkey::default() || existing.status != ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+
+        let vault_key = ctx.accounts.vault_state.key();
+        let core_key = ctx.accounts.core_asset.key();
+        let seller_key = ctx.accounts.seller.key();
+
+        // Load or initialize the CardRecord with the canonical marketplace seeds.
+        let record = &mut ctx.accounts.card_record;
+        let is_uninitialized = record.vault_state == Pubkey::default();
+        if is_uninitialized {
+            record.vault_state = vault_key;
+            record.core_asset = core_key;
+            record.template_id = template_id;
+            record.rarity = rarity.clone();
+            record.status = CardStatus::UserOwned;
+            record.owner = seller_key;
+        } else {
+            require_keys_eq!(record.vault_state, vault_key, MochiError::VaultMismatch);
+            require_keys_eq!(record.core_asset, core_key, MochiError::AssetMismatch);
+            require!(
+                record.template_id == template_id,
+                MochiError::TemplateMismatch
+            );
+            require!(record.rarity == rarity, MochiError::RarityMismatch);
+        }
+
+        require!(
+            record.owner == seller_key || record.owner == ctx.accounts.vault_authority.key(),
+            MochiError::Unauthorized
+        );
+        require!(
+            record.status == CardStatus::UserOwned || record.status == CardStatus::Available,
+            MochiError::CardNotAvailable
+        );
+
+        // Move custody into the marketplace vault if the seller still holds the asset.
+        if record.owner != ctx.accounts.vault_authority.key() {
+            transfer_core_asset_user(
+                &ctx.accounts.core_asset,
+                &ctx.accounts.seller.to_account_info(),
+                &ctx.accounts.seller.to_account_info(),
+                &ctx.accounts.vault_authority.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.mpl_core_program.to_account_info(),
+            )?;
+        }
+
+        // Freeze while listed so the escrowed asset can only leave via cancel/fill.
+        freeze_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.seller.to_account_info(),
+                vault_state: &vault_key,
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+
+        record.status = CardStatus::Reserved;
+        record.owner = ctx.accounts.vault_authority.key();
+
+        // Write the Listing account directly; anchor will serialize on exit.
+        let listing = &mut ctx.accounts.listing;
+        listing.vault_state = vault_key;
+        listing.seller = seller_key;
+        listing.core_asset = record.core_asset;
+        listing.price_lamports = price_lamports;
+        listing.currency_mint = currency_mint;
+        listing.status = ListingStatus::Active;
+        listing.fee_bps_override = None;
+
+        let listing_key = listing.key();
+        let index = &mut ctx.accounts.seller_index;
+        index.vault_state = vault_key;
+        index.seller = seller_key;
+        index.bump = ctx.bumps.seller_index;
+        index.insert(listing_key)?;
+        Ok(())
+    }
+
+    /// Admin stocks the marketplace with treasury inventory: for assets the marketplace vault
+    /// authority already holds, creates (or reuses an Available) CardRecord, marks it Reserved
+    /// and opens a buy-it-now SOL Listing with the vault authority as seller, freezing the asset
+    /// as list_card does. Treasury listings have no seller index; fills omit it.
+    /// remaining_accounts: [card_record, core_asset, listing] per entry, in argument order.
+    pub fn treasury_list_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TreasuryListBatch<'info>>,
+        templates: Vec<u32>,
+        rarities: Vec<Rarity>,
+        prices: Vec<u64>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let count = templates.len();
+        require!(
+            count > 0
+                && count <= MAX_TREASURY_LIST_BATCH
+                && rarities.len() == count
+                && prices.len() == count
+                && ctx.remaining_accounts.len() == count * 3,
+            MochiError::InvalidCardCount
+        );
+        for &price in &prices {
+            require!(
+                price > 0 && ctx.accounts.vault_state.meets_listing_floor(price, &None),
+                MochiError::InvalidPrice
+            );
+        }
+
+        let vault_key = ctx.accounts.vault_state.key();
+        let authority_key = ctx.accounts.vault_authority.key();
+        let admin = ctx.accounts.admin.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let mpl_core_program = ctx.accounts.mpl_core_program.to_account_info();
+        for (idx, entry) in ctx.remaining_accounts.chunks_exact(3).enumerate() {
+            let (record_info, asset_info, listing_info) = (&entry[0], &entry[1], &entry[2]);
+            require!(
+                listing_is_backed(asset_info, &authority_key),
+                MochiError::AssetMismatch
+            );
+
+            let created = init_pda(
+                &admin,
+                record_info,
+                8 + CardRecord::SIZE,
+                &[
+                    CARD_RECORD_SEED,
+                    vault_key.as_ref(),
+                    asset_info.key.as_ref(),
+                ],
+                &system_program,
+                ctx.program_id,
+            )?;
+            if !created {
+                let existing = CardRecord::try_deserialize(&mut &record_info.data.borrow()[..])?;
+                require!(
+                    existing.status == CardStatus::Available && existing.owner == authority_key,
+                    MochiError::CardNotAvailable
+                );
+                require!(
+                    existing.template_id == templates[idx],
+                    MochiError::TemplateMismatch
+                );
+                require!(existing.rarity == rarities[idx], MochiError::RarityMismatch);
+            }
+            let record = CardRecord {
+                vault_state: vault_key,
+                core_asset: asset_info.key(),
+                template_id: templates[idx],
+                rarity: rarities[idx].clone(),
+                status: CardStatus::Reserved,
+                owner: authority_key,
+            };
+            persist_card_record(&record, record_info)?;
+
+            // Same guard as list_card: never clobber a live listing.
+            if !init_pda(
+                &admin,
+                listing_info,
+                8 + Listing::SIZE,
+                &[LISTING_SEED, vault_key.as_ref(), asset_info.key.as_ref()],
+                &system_program,
+                ctx.program_id,
+            )? {
+                let existing = Listing::try_deserialize(&mut &listing_info.data.borrow()[..])?;
+                require!(
+                    existing.status != ListingStatus::Active,
+                    MochiError::InvalidListingState
+                );
+            }
+            let listing = Listing {
+                vault_state: vault_key,
+                seller: authority_key,
+                core_asset: asset_info.key(),
+                price_lamports: prices[idx],
+                currency_mint: None,
+                status: ListingStatus::Active,
+                fee_bps_override: None,
+            };
+            write_account(&listing, listing_info)?;
+
+            freeze_core_asset(
+                asset_info,
+                &CoreCpi {
+                    authority: &ctx.accounts.vault_authority,
+                    payer: &admin,
+                    vault_state: &vault_key,
+                    vault_bump: ctx.bumps.vault_authority,
+                    authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                    system_program: &system_program,
+                    mpl_core_program: &mpl_core_program,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Seller reprices an Active listing in place; the asset stays frozen in escrow, so no
+    /// Core round-trip or rent is paid again.
+    pub fn relist(
+        ctx: Context<Relist>,
+        new_price_lamports: u64,
+        new_currency_mint: Option<Pubkey>,
+    ) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        require_keys_eq!(
+            listing.seller,
+            ctx.accounts.seller.key(),
+            MochiError::Unauthorized
+        );
+        require!(new_price_lamports > 0, MochiError::InvalidPrice);
+        require!(
+            ctx.accounts
+                .vault_state
+                .accepts_listing_currency(&new_currency_mint),
+            MochiError::CurrencyNotAccepted
+        );
+        require!(
+            ctx.accounts
+                .vault_state
+                .meets_listing_floor(new_price_lamports, &new_currency_mint),
+            MochiError::InvalidPrice
+        );
+        let record = &ctx.accounts.card_record;
+        require!(
+            record.status == CardStatus::Reserved
+                && record.owner == ctx.accounts.vault_authority.key(),
+            MochiError::CardNotReserved
+        );
+        listing.price_lamports = new_price_lamports;
+        listing.currency_mint = new_currency_mint;
+        Ok(())
+    }
+
+    pub fn cancel_listing(ctx: Context<CancelListing>) -> Result<()> {
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        require_keys_eq!(
+            listing.seller,
+            ctx.accounts.seller.key(),
+            MochiError::Unauthorized
+        );
+
+        // Defensive: recover or rebuild card_record even if prior data drifted.
+        let mut record =
+            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
+                .or_else(|_| {
+                    CardRecord::try_deserialize_unchecked(
+                        &mut &ctx.accounts.card_record.data.borrow()[..],
+                    )
+                })
+                .unwrap_or(CardRecord {
+                    vault_state: ctx.accounts.vault_state.key(),
+                    core_asset: listing.core_asset,
+                    template_id: 0,
+                    rarity: Rarity::Common,
+                    status: CardStatus::Reserved,
+                    owner: ctx.accounts.vault_authority.key(),
+                });
+        record.vault_state = ctx.accounts.vault_state.key();
+        record.core_asset = listing.core_asset;
+        record.status = CardStatus::UserOwned;
+        record.owner = ctx.accounts.seller.key();
+
+        thaw_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.vault_authority,
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+
+        // Persist repaired record.
+        {
+            let mut data = ctx.accounts.card_record.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(data.as_mut());
+            record.try_serialize(&mut cursor)?;
+        }
+
+        listing.status = ListingStatus::Cancelled;
+        update_seller_index(&ctx.accounts.seller_index, |index| {
+            index.remove(&listing.key());
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    pub fn fill_listing<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FillListing<'info>>,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        require!(
+            ctx.accounts.listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        // No self-fills: they would only wash volume and skew price history.
+        require_keys_neq!(
+            ctx.accounts.buyer.key(),
+            ctx.accounts.seller.key(),
+            MochiError::SelfTrade
+        );
+        require_keys_eq!(
+            ctx.accounts.vault_treasury.key(),
+            ctx.accounts.vault_state.treasury,
+            MochiError::VaultMismatch
+        );
+        check_fill_cosigner(
+            &ctx.accounts.vault_state,
+            ctx.accounts.listing.price_lamports,
+            ctx.accounts.cosigner.as_ref(),
+        )?;
+        // Deliver before paying: the asset moves first so payment only happens once the
+        // buyer holds the card.
+        let record = &mut ctx.accounts.card_record;
+        record.status = CardStatus::UserOwned;
+        record.owner = ctx.accounts.buyer.key();
+        thaw_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.vault_authority,
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.buyer.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+
+        let quote = fill_quote(
+            &ctx.accounts.listing,
+            &ctx.accounts.vault_state,
+            &ctx.accounts.core_asset,
+        )?;
+        let fee = quote.fee;
+        let seller_amount = quote.seller_receives;
+        // Direct pay: buyer -> treasury/fee split (fee) and buyer -> seller (net). No escrow on listing PDA.
+        // remaining_accounts: [fee_split, recipients...] when the vault has a fee split.
+        pay_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.buyer.to_account_info(),
+            &ctx.accounts.vault_treasury.to_account_info(),
+            ctx.remaining_accounts,
+            fee,
+            &ctx.accounts.system_program.to_account_info(),
+            &[],
+        )?;
+        pay_royalty(
+            &quote,
+            &ctx.accounts.buyer.to_account_info(),
+            ctx.accounts.royalty_recipient.as_ref(),
+            &ctx.accounts.system_program.to_account_info(),
+            &[],
+        )?;
+        invoke(
+            &system_instruction::transfer(
+                &ctx.accounts.buyer.key(),
+                &ctx.accounts.seller.key(),
+                seller_amount,
+            ),
+            &[
+                ctx.accounts.buyer.to_account_info(),
+                ctx.accounts.seller.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+
+        let listing = &mut ctx.accounts.listing;
+        listing.status = ListingStatus::Filled;
+        update_seller_index(&ctx.accounts.seller_index, |index| {
+            index.remove(&listing.key());
+            Ok(())
+        })?;
+        ctx.accounts.vault_state.record_sale(&quote)?;
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// Escrowed buy, step 1: the buyer pre-commits the full fill amount into the marketplace
+    /// vault authority so a seller or relayer can settle later without the buyer signing.
+    pub fn commit_fill(ctx: Context<CommitFill>) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+        require!(
+            listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        require_keys_neq!(
+            ctx.accounts.buyer.key(),
+            listing.seller,
+            MochiError::SelfTrade
+        );
+        let amount =
+            fill_quote(listing, &ctx.accounts.vault_state, &ctx.accounts.core_asset)?.buyer_pays;
+        invoke(
+            &system_instruction::transfer(
+                &ctx.accounts.buyer.key(),
+                &ctx.accounts.vault_authority.key(),
+                amount,
+            ),
+            &[
+                ctx.accounts.buyer.to_account_info(),
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+        )?;
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.escrowed_lamports = vault_state
+            .escrowed_lamports
+            .checked_add(amount)
+            .ok_or(MochiError::MathOverflow)?;
+        let commit = &mut ctx.accounts.fill_commit;
+        commit.listing = listing.key();
+        commit.buyer = ctx.accounts.buyer.key();
+        commit.amount = amount;
+        commit.created_at = Clock::get()?.unix_timestamp;
+        commit.bump = ctx.bumps.fill_commit;
+        Ok(())
+    }
+
+    /// Escrowed buy, step 2: any signer (seller or relayer) settles a committed fill. Fails if
+    /// the listing was repriced since the commit; the buyer can then cancel and recommit.
+    /// remaining_accounts: [fee_split, recipients...] when the vault has a fee split.
+    pub fn execute_fill<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteFill<'info>>,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        require!(
+            ctx.accounts.listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        require_keys_eq!(
+            ctx.accounts.vault_treasury.key(),
+            ctx.accounts.vault_state.treasury,
+            MochiError::VaultMismatch
+        );
+        check_fill_cosigner(
+            &ctx.accounts.vault_state,
+            ctx.accounts.listing.price_lamports,
+            ctx.accounts.cosigner.as_ref(),
+        )?;
+        let quote = fill_quote(
+            &ctx.accounts.listing,
+            &ctx.accounts.vault_state,
+            &ctx.accounts.core_asset,
+        )?;
+        require!(
+            quote.buyer_pays == ctx.accounts.fill_commit.amount,
+            MochiError::InvalidPrice
+        );
+        let vault_key = ctx.accounts.vault_state.key();
+        let seeds = &[
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer = &[&seeds[..]];
+        pay_fee(
+            &ctx.accounts.vault_state,
+            &ctx.accounts.vault_authority.to_account_info(),
+            &ctx.accounts.vault_treasury.to_account_info(),
+            ctx.remaining_accounts,
+            quote.fee,
+            &ctx.accounts.system_program.to_account_info(),
+            signer,
+        )?;
+        pay_royalty(
+            &quote,
+            &ctx.accounts.vault_authority.to_account_info(),
+            ctx.accounts.royalty_recipient.as_ref(),
+            &ctx.accounts.system_program.to_account_info(),
+            signer,
+        )?;
+        invoke_signed(
+            &system_instruction::transfer(
+                &ctx.accounts.vault_authority.key(),
+                &ctx.accounts.seller.key(),
+                quote.seller_receives,
+            ),
+            &[
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.accounts.seller.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer,
+        )?;
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.escrowed_lamports = vault_state
+            .escrowed_lamports
+            .checked_sub(quote.buyer_pays)
+            .ok_or(MochiError::MathOverflow)?;
+
+        let record = &mut ctx.accounts.card_record;
+        record.status = CardStatus::UserOwned;
+        record.owner = ctx.accounts.buyer.key();
+        thaw_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.vault_authority,
+                vault_state: &vault_key,
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.buyer.to_account_info(),
+            &vault_key,
+            ctx.bumps.vault_authority,
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+
+        ctx.accounts.listing.status = ListingStatus::Filled;
+        let listing_key = ctx.accounts.listing.key();
+        update_seller_index(&ctx.accounts.seller_index, |index| {
+            index.remove(&listing_key);
+            Ok(())
+        })?;
+        ctx.accounts.vault_state.record_sale(&quote)?;
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    /// Buyer pulls a committed fill back out of escrow (e.g. the seller never executed).
+    pub fn cancel_fill_commit(ctx: Context<CancelFillCommit>) -> Result<()> {
+        let amount = ctx.accounts.fill_commit.amount;
+        let vault_key = ctx.accounts.vault_state.key();
+        let seeds = &[
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer = &[&seeds[..]];
+        invoke_signed(
+            &system_instruction::transfer(
+                &ctx.accounts.vault_authority.key(),
+                &ctx.accounts.buyer.key(),
+                amount,
+            ),
+            &[
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.accounts.buyer.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer,
+        )?;
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.escrowed_lamports = vault_state
+            .escrowed_lamports
+            .checked_sub(amount)
+            .ok_or(MochiError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Read-only: fails with ListingUnbacked unless the Active listing's asset is still held
+    /// by the marketplace vault authority, so a fill would not die in the Core transfer.
+    pub fn verify_listing_backing(ctx: Context<ListingBacking>) -> Result<()> {
+        require!(
+            ctx.accounts.listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        require!(
+            listing_is_backed(
+                &ctx.accounts.core_asset,
+                &ctx.accounts.vault_authority.key()
+            ),
+            MochiError::ListingUnbacked
+        );
+        Ok(())
+    }
+
+    /// Admin cleanup for an Active listing whose asset has left marketplace custody (moved or
+    /// burned outside the program): marks it Cancelled without touching the asset. Refused
+    /// while the asset is still backed; use `admin_force_cancel_listing` for those.
+    pub fn invalidate_unbacked_listing(ctx: Context<InvalidateUnbackedListing>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        require!(
+            !listing_is_backed(
+                &ctx.accounts.core_asset,
+                &ctx.accounts.vault_authority.key()
+            ),
+            MochiError::ListingStillBacked
+        );
+        listing.status = ListingStatus::Cancelled;
+        update_seller_index(&ctx.accounts.seller_index, |index| {
+            index.remove(&listing.key());
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Read-only cost breakdown for filling a listing, returned via return data.
+    pub fn quote_fill(ctx: Context<QuoteFill>) -> Result<FillQuote> {
+        require!(
+            ctx.accounts.listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        fill_quote(
+            &ctx.accounts.listing,
+            &ctx.accounts.vault_state,
+            &ctx.accounts.core_asset,
+        )
+    }
+
+    /// Read-only view of a PendingDecision V2 session's reserved pull, returned via return
+    /// data so the wallet can render it before keep/sellback. Only the rares live on-chain;
+    /// the rest of the lineup is covered by `manifest_hash` (check with `verify_manifest`).
+    /// remaining_accounts: the session's rare_card_records, in order.
+    pub fn peek_pack<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PeekPack<'info>>,
+    ) -> Result<PackPeek> {
+        pack_peek(&ctx.accounts.pack_session, ctx.remaining_accounts)
+    }
+
+    /// User-signed peek_pack: returns the same view and, when the vault is commit-to-keep,
+    /// locks the session into keep-only so a later sellback fails with SellbackLocked.
+    /// remaining_accounts: the session's rare_card_records, in order.
+    pub fn reveal_pack_v2<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RevealPackV2<'info>>,
+    ) -> Result<PackPeek> {
+        let peek = pack_peek(&ctx.accounts.pack_session, ctx.remaining_accounts)?;
+        if ctx.accounts.vault_state.sellback_locked_after_reveal {
+            ctx.accounts.pack_session.sellback_locked = true;
+        }
+        Ok(peek)
+    }
+
+    /// Read-only "what can I claim" summary of the user's V2 session for this vault.
+    pub fn user_pending_summary<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PeekPack<'info>>,
+    ) -> Result<PendingSummary> {
+        let session = &ctx.accounts.pack_session;
+        let now = Clock::get()?.unix_timestamp;
+        Ok(PendingSummary {
+            rare_count: session.rare_card_keys.len() as u8,
+            expires_at: session.expires_at,
+            state: session.state.clone(),
+            claimable: session.state == PackState::PendingDecision
+                && session.is_paid()
+                && now <= session.expires_at,
+        })
+    }
+
+    /// Read-only integrity check for a V2 session; call before claiming to surface corruption early.
+    pub fn assert_session_valid<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PeekPack<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.pack_session.validate()
+    }
+
+    /// Read-only pity state for `user`, returned via return data: their run of opens without
+    /// a Rare+, the vault threshold, and whether the next open is guaranteed one. Users
+    /// without a UserStats account read as a zero counter.
+    pub fn pity_status(ctx: Context<PityStatus>) -> Result<PityStatusInfo> {
+        let threshold = ctx.accounts.vault_state.pity_threshold;
+        let info = ctx.accounts.user_stats.to_account_info();
+        let current_counter = if *info.owner == crate::ID && !info.data_is_empty() {
+            load_user_stats(&info)?.packs_since_rare
+        } else {
+            0
+        };
+        Ok(PityStatusInfo {
+            current_counter,
+            threshold,
+            guaranteed_next: pity_due(current_counter, threshold),
+        })
+    }
+
+    /// Read-only status of a gacha CardRecord, returned via return data. When the card is
+    /// held by the owner's pending V2 pack session (pass it as `pack_session`), the
+    /// session's `expires_at` is included; otherwise `expires_at` is 0.
+    pub fn card_availability(ctx: Context<CardAvailability>) -> Result<CardAvailabilityInfo> {
+        let record = &ctx.accounts.card_record;
+        let mut expires_at = 0;
+        if record.status == CardStatus::Reserved {
+            if let Some(session) = &ctx.accounts.pack_session {
+                if session.state == PackState::PendingDecision
+                    && session.rare_card_keys.contains(&record.key())
+                {
+                    expires_at = session.expires_at;
+                }
+            }
+        }
+        Ok(CardAvailabilityInfo {
+            status: record.status.clone(),
+            expires_at,
+        })
+    }
+
+    /// Read-only Available card counts indexed by `Rarity`, returned via return data.
+    pub fn inventory_counts(ctx: Context<InventoryCounts>) -> Result<[u32; RARITY_COUNT]> {
+        Ok(ctx.accounts.rarity_inventory.available_count)
+    }
+
+    /// Direct card-for-card trade between two signing wallets, optionally with a SOL
+    /// sweetener paid by `user_a` (or by `user_b` when `sweetener_from_b`). Both Core
+    /// transfers and the payment land in one transaction or not at all.
+    pub fn swap_cards(
+        ctx: Context<SwapCards>,
+        sweetener_lamports: u64,
+        sweetener_from_b: bool,
+    ) -> Result<()> {
+        let user_a = ctx.accounts.user_a.key();
+        let user_b = ctx.accounts.user_b.key();
+        require_keys_neq!(user_a, user_b, MochiError::SelfTrade);
+        for (record, asset, owner) in [
+            (
+                &ctx.accounts.card_record_a,
+                &ctx.accounts.core_asset_a,
+                user_a,
+            ),
+            (
+                &ctx.accounts.card_record_b,
+                &ctx.accounts.core_asset_b,
+                user_b,
+            ),
+        ] {
+            require_keys_eq!(record.core_asset, asset.key(), MochiError::AssetMismatch);
+            require!(
+                record.status == CardStatus::UserOwned && record.owner == owner,
+                MochiError::Unauthorized
+            );
+            require_keys_eq!(*asset.owner, mpl_core::ID, MochiError::AssetMismatch);
+            let base = mpl_core::accounts::BaseAssetV1::from_bytes(&asset.data.borrow())
+                .map_err(|_| error!(MochiError::AssetMismatch))?;
+            require_keys_eq!(base.owner, owner, MochiError::Unauthorized);
+        }
+
+        let a_info = ctx.accounts.user_a.to_account_info();
+        let b_info = ctx.accounts.user_b.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let mpl_core_program = ctx.accounts.mpl_core_program.to_account_info();
+        transfer_core_asset_user(
+            &ctx.accounts.core_asset_a,
+            &a_info,
+            &a_info,
+            &b_info,
+            &system_program,
+            &mpl_core_program,
+        )?;
+        transfer_core_asset_user(
+            &ctx.accounts.core_asset_b,
+            &b_info,
+            &b_info,
+            &a_info,
+            &system_program,
+            &mpl_core_program,
+        )?;
+        if sweetener_lamports > 0 {
+            let (from, to) = if sweetener_from_b {
+                (&b_info, &a_info)
+            } else {
+                (&a_info, &b_info)
+            };
+            invoke(
+                &system_instruction::transfer(from.key, to.key, sweetener_lamports),
+                &[from.clone(), to.clone(), system_program.clone()],
+            )?;
+        }
+
+        ctx.accounts.card_record_a.owner = user_b;
+        ctx.accounts.card_record_b.owner = user_a;
+        emit!(CardsSwapped {
+            user_a,
+            user_b,
+            core_asset_a: ctx.accounts.core_asset_a.key(),
+            core_asset_b: ctx.accounts.core_asset_b.key(),
+            sweetener_lamports,
+            sweetener_from_b,
+        });
+        Ok(())
+    }
+
+    pub fn redeem_burn(ctx: Context<RedeemBurn>) -> Result<()> {
+        let record = &mut ctx.accounts.card_record;
+        require_keys_eq!(
+            record.owner,
+            ctx.accounts.user.key(),
+            MochiError::Unauthorized
+        );
+        require_keys_eq!(
+            record.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        require_keys_eq!(
+            record.core_asset,
+            ctx.accounts.core_asset.key(),
+            MochiError::CardKeyMismatch
+        );
+        // Only a card the user holds outright may be burned, so reserved or already-burned
+        // records can never pay out.
+        require!(
+            record.status == CardStatus::UserOwned,
+            MochiError::CardNotAvailable
+        );
+        // The burning user funds the CPI, so an empty vault authority can't make it fail.
+        burn_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            GACHA_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+        record.status = CardStatus::Burned;
+        ctx.accounts.vault_state.retire_cards(1);
+
+        let reward_amount = ctx
+            .accounts
+            .vault_state
+            .capped_reward(ctx.accounts.vault_state.burn_reward_per_card);
+        if reward_amount > 0 {
+            let mochi_mint = ctx
+                .accounts
+                .vault_state
+                .mochi_mint
+                .ok_or(MochiError::MintMismatch)?;
+            require_keys_eq!(
+                ctx.accounts.reward_mint.key(),
+                mochi_mint,
+                MochiError::MintMismatch
+            );
+            require_keys_eq!(
+                ctx.accounts.user_token_account.mint,
+                mochi_mint,
+                MochiError::MintMismatch
+            );
+            require_keys_eq!(
+                ctx.accounts.user_token_account.owner,
+                ctx.accounts.user.key(),
+                MochiError::Unauthorized
+            );
+            require!(
+                ctx.accounts.reward_mint.mint_authority
+                    == COption::Some(ctx.accounts.vault_authority.key()),
+                MochiError::Unauthorized
+            );
+            let vault_key = ctx.accounts.vault_state.key();
+            let seeds = &[
+                GACHA_VAULT_AUTHORITY_SEED,
+                vault_key.as_ref(),
+                &[ctx.bumps.vault_authority],
+            ];
+            let signer = &[&seeds[..]];
+            let cpi_accounts = MintTo {
+                mint: ctx.accounts.reward_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                signer,
+            );
+            token::mint_to(cpi_ctx, reward_amount)?;
+            emit!(BurnRewarded {
+                user: ctx.accounts.user.key(),
+                core_asset: ctx.accounts.core_asset.key(),
+                mint: mochi_mint,
+                amount: reward_amount,
+            });
+        }
+        Ok(())
+    }
+
+    /// Defines collection set `set_id`: the distinct templates a user must hold one of each
+    /// to claim the set bonus.
+    pub fn create_template_set(
+        ctx: Context<CreateTemplateSet>,
+        set_id: u32,
+        template_ids: Vec<u32>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            !template_ids.is_empty() && template_ids.len() <= MAX_SET_TEMPLATES,
+            MochiError::InvalidCardCount
+        );
+        require!(
+            template_ids
+                .iter()
+                .enumerate()
+                .all(|(i, t)| !template_ids[..i].contains(t)),
+            MochiError::TemplateMismatch
+        );
+        let set = &mut ctx.accounts.template_set;
+        set.vault_state = ctx.accounts.vault_state.key();
+        set.set_id = set_id;
+        set.template_ids = template_ids;
+        set.bump = ctx.bumps.template_set;
+        Ok(())
+    }
+
+    /// Mints `set_completion_reward` MOCHI to a user holding one card of every template in the
+    /// set, once per set: the SetBonusClaim PDA created here refuses a second claim. Ownership
+    /// is read from the CardRecords, so it reflects the last owner the program saw.
+    /// remaining_accounts: one UserOwned card_record per set template, in set order.
+    pub fn claim_set_bonus<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimSetBonus<'info>>,
+    ) -> Result<()> {
+        let reward_amount = ctx.accounts.vault_state.set_completion_reward;
+        require!(reward_amount > 0, MochiError::RewardDisabled);
+        let set = &ctx.accounts.template_set;
+        require!(
+            ctx.remaining_accounts.len() == set.template_ids.len(),
+            MochiError::InvalidCardCount
+        );
+        let user_key = ctx.accounts.user.key();
+        for (acc_info, template_id) in ctx.remaining_accounts.iter().zip(&set.template_ids) {
+            let record: Account<CardRecord> = Account::try_from(acc_info)?;
+            require_keys_eq!(
+                record.vault_state,
+                ctx.accounts.vault_state.key(),
+                MochiError::VaultMismatch
+            );
+            require!(
+                record.status == CardStatus::UserOwned && record.owner == user_key,
+                MochiError::Unauthorized
+            );
+            require!(
+                record.template_id == *template_id,
+                MochiError::TemplateMismatch
+            );
+        }
+
+        let mochi_mint = ctx
+            .accounts
+            .vault_state
+            .mochi_mint
+            .ok_or(MochiError::MintMismatch)?;
+        require_keys_eq!(
+            ctx.accounts.reward_mint.key(),
+            mochi_mint,
+            MochiError::MintMismatch
+        );
+        require_keys_eq!(
+            ctx.accounts.user_token_account.mint,
+            mochi_mint,
+            MochiError::MintMismatch
+        );
+        require_keys_eq!(
+            ctx.accounts.user_token_account.owner,
+            user_key,
+            MochiError::Unauthorized
+        );
+        require!(
+            ctx.accounts.reward_mint.mint_authority
+                == COption::Some(ctx.accounts.vault_authority.key()),
+            MochiError::Unauthorized
+        );
+        let vault_key = ctx.accounts.vault_state.key();
+        let seeds = &[
+            GACHA_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer = &[&seeds[..]];
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.reward_mint.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        token::mint_to(cpi_ctx, reward_amount)?;
+
+        let claim = &mut ctx.accounts.set_bonus_claim;
+        claim.template_set = ctx.accounts.template_set.key();
+        claim.user = user_key;
+        claim.claimed_at = Clock::get()?.unix_timestamp;
+        claim.bump = ctx.bumps.set_bonus_claim;
+        emit!(SetBonusClaimed {
+            user: user_key,
+            set_id: ctx.accounts.template_set.set_id,
+            amount: reward_amount,
+        });
+        Ok(())
+    }
+
+    /// Defines fusion recipe `recipe_id`: `input_count` cards of `input_rarity` (all of one
+    /// template when `same_template`) fuse into one vault card of the higher `output_rarity`.
+    pub fn create_fusion_recipe(
+        ctx: Context<CreateFusionRecipe>,
+        recipe_id: u32,
+        input_rarity: Rarity,
+        input_count: u8,
+        same_template: bool,
+        output_rarity: Rarity,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            (2..=MAX_FUSION_INPUTS).contains(&(input_count as usize)),
+            MochiError::InvalidCardCount
+        );
+        require!(
+            input_rarity != Rarity::Energy
+                && output_rarity != Rarity::Energy
+                && output_rarity.clone() as u8 > input_rarity.clone() as u8,
+            MochiError::InvalidFusionRecipe
+        );
+        let recipe = &mut ctx.accounts.fusion_recipe;
+        recipe.vault_state = ctx.accounts.vault_state.key();
+        recipe.recipe_id = recipe_id;
+        recipe.input_rarity = input_rarity;
+        recipe.input_count = input_count;
+        recipe.same_template = same_template;
+        recipe.output_rarity = output_rarity;
+        recipe.bump = ctx.bumps.fusion_recipe;
+        Ok(())
+    }
+
+    /// Burns the user's recipe inputs and hands them an Available vault card of the recipe's
+    /// output rarity, all or nothing. The output comes from deposited stock; nothing is minted.
+    /// remaining_accounts: [input_card_records (input_count)...][input_assets...]
+    ///   [output_card_record, output_asset]
+    pub fn fuse_cards<'info>(ctx: Context<'_, '_, 'info, 'info, FuseCards<'info>>) -> Result<()> {
+        let recipe = &ctx.accounts.fusion_recipe;
+        let input_count = recipe.input_count as usize;
+        require!(
+            ctx.remaining_accounts.len() == input_count * 2 + 2,
+            MochiError::InvalidCardCount
+        );
+        let (input_records, rest) = ctx.remaining_accounts.split_at(input_count);
+        let (input_assets, output) = rest.split_at(input_count);
+        let user_info = ctx.accounts.user.to_account_info();
+        let user_key = user_info.key();
+        let vault_key = ctx.accounts.vault_state.key();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let mpl_core_program = ctx.accounts.mpl_core_program.to_account_info();
+
+        // Validate every input before burning any.
+        let mut inputs: Vec<Account<CardRecord>> = Vec::with_capacity(input_count);
+        for (idx, (record_info, asset_info)) in input_records.iter().zip(input_assets).enumerate() {
+            require!(
+                !input_records[..idx]
+                    .iter()
+                    .any(|r| r.key == record_info.key),
+                MochiError::DuplicateCard
+            );
+            let record: Account<CardRecord> = Account::try_from(record_info)?;
+            require_keys_eq!(record.vault_state, vault_key, MochiError::VaultMismatch);
+            require!(
+                record.status == CardStatus::UserOwned && record.owner == user_key,
+                MochiError::Unauthorized
+            );
+            require_keys_eq!(
+                record.core_asset,
+                asset_info.key(),
+                MochiError::AssetMismatch
+            );
+            require!(
+                record.rarity == recipe.input_rarity,
+                MochiError::RarityMismatch
+            );
+            if let Some(first) = inputs.first().filter(|_| recipe.same_template) {
+                require!(
+                    record.template_id == first.template_id,
+                    MochiError::TemplateMismatch
+                );
+            }
+            inputs.push(record);
+        }
+
+        let (output_record_info, output_asset) = (&output[0], &output[1]);
+        let mut output_record: Account<CardRecord> = Account::try_from(output_record_info)?;
+        require_keys_eq!(
+            output_record.vault_state,
+            vault_key,
+            MochiError::VaultMismatch
+        );
+        require!(
+            output_record.status == CardStatus::Available,
+            MochiError::CardNotAvailable
+        );
+        require!(
+            output_record.rarity == recipe.output_rarity,
+            MochiError::RarityMismatch
+        );
+        require_keys_eq!(
+            output_record.core_asset,
+            output_asset.key(),
+            MochiError::AssetMismatch
+        );
+
+        for ((record_info, asset_info), mut record) in
+            input_records.iter().zip(input_assets).zip(inputs)
+        {
+            burn_core_asset(
+                asset_info,
+                &ctx.accounts.vault_authority,
+                &user_info,
+                &vault_key,
+                ctx.bumps.vault_authority,
+                GACHA_VAULT_AUTHORITY_SEED,
+                &system_program,
+                &mpl_core_program,
+            )?;
+            record.status = CardStatus::Burned;
+            persist_card_record(&record, record_info)?;
+        }
+        transfer_core_asset(
+            output_asset,
+            &ctx.accounts.vault_authority,
+            &user_info,
+            &user_info,
+            &vault_key,
+            ctx.bumps.vault_authority,
+            GACHA_VAULT_AUTHORITY_SEED,
+            &system_program,
+            &mpl_core_program,
+        )?;
+        output_record.status = CardStatus::UserOwned;
+        output_record.owner = user_key;
+        persist_card_record(&output_record, output_record_info)?;
+        if let Some(inventory) = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )? {
+            inventory.remove(&output_record.rarity);
+        }
+        ctx.accounts.vault_state.retire_cards(input_count as u64);
+        emit!(CardsFused {
+            user: user_key,
+            recipe_id: ctx.accounts.fusion_recipe.recipe_id,
+            output_asset: output_asset.key(),
+        });
+        Ok(())
+    }
+
+    /// Self-imposed cap on pack spend, in lamports, per `period_seconds` window (0 lifts it).
+    /// Token and MOCHI opens count at the vault's pack price ratio (see `spend_lamports`).
+    /// The window is fixed, not rolling: it opens at the first counted spend after the last
+    /// one lapsed and resets wholesale `period_seconds` later. A tighter limit applies at
+    /// once; a looser one (higher cap, shorter window or removal) is staged and only applies
+    /// SPEND_LIMIT_RAISE_COOLDOWN later. Only the user can set it.
+    pub fn set_spend_limit(
+        ctx: Context<SetSpendLimit>,
+        limit_lamports: u64,
+        period_seconds: i64,
+    ) -> Result<()> {
+        require!(
+            limit_lamports == 0 || period_seconds > 0,
+            MochiError::InvalidWindow
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let user = ctx.accounts.user.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let mut stats = open_user_stats(
+            &ctx.accounts.user_stats,
+            &user,
+            &system_program,
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.user_stats,
+        )?;
+        stats.apply_pending(now);
+        let tighter = limit_lamports > 0
+            && (stats.spend_limit_lamports == 0
+                || (limit_lamports <= stats.spend_limit_lamports
+                    && period_seconds >= stats.period_seconds));
+        if tighter {
+            if stats.spend_limit_lamports == 0 {
+                stats.period_start = now;
+                stats.period_spent = 0;
+            }
+            stats.spend_limit_lamports = limit_lamports;
+            stats.period_seconds = period_seconds;
+            stats.pending_effective_at = 0;
+        } else {
+            stats.pending_limit_lamports = limit_lamports;
+            stats.pending_period_seconds = period_seconds;
+            stats.pending_effective_at = now
+                .checked_add(SPEND_LIMIT_RAISE_COOLDOWN)
+                .ok_or(MochiError::MathOverflow)?;
+        }
+        store_user_stats(&stats, &ctx.accounts.user_stats, &user, &system_program)
+    }
+
+    /// Admin giveaway: hand an Available vault card straight to a user outside the pack flow.
+    pub fn admin_airdrop_card(ctx: Context<AdminAirdropCard>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let record = &mut ctx.accounts.card_record;
+        require_keys_eq!(
+            record.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        require!(
+            record.status == CardStatus::Available,
+            MochiError::CardNotAvailable
+        );
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.recipient.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            GACHA_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+        record.status = CardStatus::UserOwned;
+        record.owner = ctx.accounts.recipient.key();
+        if let Some(inventory) = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )? {
+            inventory.remove(&record.rarity);
+        }
+
+        emit!(AirdropCard {
+            admin: ctx.accounts.admin.key(),
+            recipient: record.owner,
+            core_asset: record.core_asset,
+            template_id: record.template_id,
+        });
+        Ok(())
+    }
+
+    /// Publishes a Merkle root of `(wallet, asset, template_id, leaf_index)` allocations and
+    /// creates its claimed-leaf bitmap. Each root gets its own bitmap; a zero root ends the drop.
+    pub fn set_airdrop_root(ctx: Context<SetAirdropRoot>, airdrop_root: [u8; 32]) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let bitmap = &mut ctx.accounts.airdrop_bitmap;
+        if bitmap.vault_state == Pubkey::default() {
+            bitmap.vault_state = ctx.accounts.vault_state.key();
+            bitmap.root = airdrop_root;
+            bitmap.claimed = vec![0u8; MAX_AIRDROP_LEAVES / 8];
+            bitmap.bump = ctx.bumps.airdrop_bitmap;
+        }
+        ctx.accounts.vault_state.airdrop_root = airdrop_root;
+        Ok(())
+    }
+
+    /// Wallet-initiated airdrop: proves its allocation against `airdrop_root` and takes the card.
+    pub fn claim_airdrop(
+        ctx: Context<ClaimAirdrop>,
+        proof: Vec<[u8; 32]>,
+        leaf_index: u32,
+        template_id: u32,
+        asset: Pubkey,
+    ) -> Result<()> {
+        lock_vault(&mut ctx.accounts.vault_state)?;
+        let root = ctx.accounts.vault_state.airdrop_root;
+        require!(root != [0u8; 32], MochiError::AirdropInactive);
+        require_keys_eq!(
+            ctx.accounts.core_asset.key(),
+            asset,
+            MochiError::AssetMismatch
+        );
+        let claimer_key = ctx.accounts.claimer.key();
+        let leaf = airdrop_leaf(&claimer_key, &asset, template_id, leaf_index);
+        require!(
+            verify_merkle_proof(&proof, root, leaf),
+            MochiError::InvalidProof
+        );
+        require!(
+            (leaf_index as usize) < MAX_AIRDROP_LEAVES,
+            MochiError::InvalidProof
+        );
+        let bitmap = &mut ctx.accounts.airdrop_bitmap;
+        let (byte, bit) = ((leaf_index / 8) as usize, 1u8 << (leaf_index % 8));
+        require!(bitmap.claimed[byte] & bit == 0, MochiError::AirdropClaimed);
+
+        let record = &mut ctx.accounts.card_record;
+        require!(
+            record.template_id == template_id,
+            MochiError::TemplateMismatch
+        );
+        require!(
+            record.status == CardStatus::Available,
+            MochiError::CardNotAvailable
+        );
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.claimer.to_account_info(),
+            &ctx.accounts.claimer.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            GACHA_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+        record.status = CardStatus::UserOwned;
+        record.owner = claimer_key;
+        bitmap.claimed[byte] |= bit;
+        if let Some(inventory) = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )? {
+            inventory.remove(&record.rarity);
+        }
+
+        emit!(AirdropClaimed {
+            claimer: claimer_key,
+            core_asset: asset,
+            template_id,
+            leaf_index,
+        });
+        unlock_vault(&mut ctx.accounts.vault_state);
+        Ok(())
+    }
+
+    pub fn admin_migrate_asset(ctx: Context<AdminMigrateAsset>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let record = &mut ctx.accounts.card_record;
+        require_keys_eq!(
+            record.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.destination.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            GACHA_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+        if record.status == CardStatus::Available {
+            if let Some(inventory) = tracked_inventory(
+                &ctx.accounts.vault_state,
+                &mut ctx.accounts.rarity_inventory,
+            )? {
+                inventory.remove(&record.rarity);
+            }
+        }
+        record.owner = ctx.accounts.destination.key();
+        record.status = CardStatus::Deprecated;
+        Ok(())
+    }
+
+    /// Batched `admin_migrate_asset` for collection moves: sends up to MAX_MIGRATE_BATCH vault
+    /// cards to `destination` and deprecates their records.
+    /// remaining_accounts: [card_records...][core_assets...]
+    pub fn admin_migrate_assets_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminMigrateAssetsBatch<'info>>,
+        destination: Pubkey,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require_keys_eq!(
+            ctx.accounts.destination.key(),
+            destination,
+            MochiError::Unauthorized
+        );
+        let (card_accounts, asset_accounts, _) = partition_half_accounts(ctx.remaining_accounts)?;
+        require!(
+            card_accounts.len() <= MAX_MIGRATE_BATCH,
+            MochiError::InvalidCardCount
+        );
+
+        let vault_key = ctx.accounts.vault_state.key();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        for (record_info, asset_info) in card_accounts.iter().zip(asset_accounts.iter()) {
+            let (expected, _) = Pubkey::find_program_address(
+                &[
+                    CARD_RECORD_SEED,
+                    vault_key.as_ref(),
+                    asset_info.key.as_ref(),
+                ],
+                ctx.program_id,
+            );
+            require_keys_eq!(record_info.key(), expected, MochiError::CardKeyMismatch);
+            let mut record: Account<CardRecord> = Account::try_from(record_info)?;
+            require_keys_eq!(record.vault_state, vault_key, MochiError::VaultMismatch);
+            require!(
+                record.status == CardStatus::Available && record.owner == vault_authority,
+                MochiError::CardNotAvailable
+            );
+            transfer_core_asset(
+                asset_info,
+                &ctx.accounts.vault_authority,
+                &ctx.accounts.admin.to_account_info(),
+                &ctx.accounts.destination.to_account_info(),
+                &vault_key,
+                ctx.bumps.vault_authority,
+                GACHA_VAULT_AUTHORITY_SEED,
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.mpl_core_program.to_account_info(),
+            )?;
+            if let Some(inventory) = inventory.as_deref_mut() {
+                inventory.remove(&record.rarity);
+            }
+            record.owner = destination;
+            record.status = CardStatus::Deprecated;
+            persist_card_record(&record, record_info)?;
+        }
+        Ok(())
+    }
+
+    /// Retires unsold inventory: burns up to MAX_MIGRATE_BATCH Available vault cards and marks
+    /// their records Burned. Companion to the single-card redeem burn.
+    /// remaining_accounts: [card_records...][core_assets...]
+    pub fn admin_burn_cards_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminBurnCardsBatch<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let (card_accounts, asset_accounts, _) = partition_half_accounts(ctx.remaining_accounts)?;
+        require!(
+            card_accounts.len() <= MAX_MIGRATE_BATCH,
+            MochiError::InvalidCardCount
+        );
+
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        let vault_key = ctx.accounts.vault_state.key();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        for (record_info, asset_info) in card_accounts.iter().zip(asset_accounts.iter()) {
+            let (expected, _) = Pubkey::find_program_address(
+                &[
+                    CARD_RECORD_SEED,
+                    vault_key.as_ref(),
+                    asset_info.key.as_ref(),
+                ],
+                ctx.program_id,
+            );
+            require_keys_eq!(record_info.key(), expected, MochiError::CardKeyMismatch);
+            let mut record: Account<CardRecord> = Account::try_from(record_info)?;
+            require_keys_eq!(record.vault_state, vault_key, MochiError::VaultMismatch);
+            require!(
+                record.status == CardStatus::Available && record.owner == vault_authority,
+                MochiError::CardNotAvailable
+            );
+            burn_core_asset(
+                asset_info,
+                &ctx.accounts.vault_authority,
+                &ctx.accounts.admin.to_account_info(),
+                &vault_key,
+                ctx.bumps.vault_authority,
+                GACHA_VAULT_AUTHORITY_SEED,
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.mpl_core_program.to_account_info(),
+            )?;
+            if let Some(inventory) = inventory.as_deref_mut() {
+                inventory.remove(&record.rarity);
+            }
+            record.status = CardStatus::Burned;
+            persist_card_record(&record, record_info)?;
+        }
+        ctx.accounts
+            .vault_state
+            .retire_cards(card_accounts.len() as u64);
+        Ok(())
+    }
+
+    /// Grow a listing created before fee overrides existed; the new field is zero-filled, so
+    /// migrated listings pay the vault-wide fee.
+    pub fn migrate_listing(ctx: Context<MigrateListing>) -> Result<()> {
+        let target_len: usize = 8 + Listing::SIZE;
+        let listing_info = ctx.accounts.listing.to_account_info();
+        require!(
+            listing_info.owner == ctx.program_id,
+            MochiError::Unauthorized
+        );
+        if listing_info.data_len() >= target_len {
+            return Ok(());
+        }
+
+        let required_lamports = Rent::get()?.minimum_balance(target_len);
+        if listing_info.lamports() < required_lamports {
+            let diff = required_lamports
+                .checked_sub(listing_info.lamports())
+                .ok_or(MochiError::MathOverflow)?;
+            invoke(
+                &system_instruction::transfer(&ctx.accounts.payer.key(), listing_info.key, diff),
+                &[
+                    ctx.accounts.payer.to_account_info(),
+                    listing_info.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+        listing_info.realloc(target_len, true)?;
+        Ok(())
+    }
+
+    /// Admin-only prune for malformed listings that point to a wrong/nonexistent vault_state.
+    /// This does NOT move any assets; it simply marks the listing as Cancelled to hide it.
+    pub fn admin_prune_listing(ctx: Context<AdminPruneListing>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        // Overwrite the listing account regardless of prior contents to mark it Cancelled.
+        let listing = Listing {
+            vault_state: ctx.accounts.vault_state.key(),
+            seller: Pubkey::default(),
+            core_asset: Pubkey::default(),
+            price_lamports: 0,
+            currency_mint: None,
+            status: ListingStatus::Cancelled,
+            fee_bps_override: None,
+        };
+        let mut data = ctx.accounts.listing.try_borrow_mut_data()?;
+        let mut cursor = std::io::Cursor::new(&mut data[..]);
+        // AccountSerialize already writes the discriminator; avoid writing it twice.
+        listing.try_serialize(&mut cursor)?;
+        Ok(())
+    }
+
+    /// Admin-only escape hatch to repair/cancel corrupted listings.
+    /// Returns NFT to seller and marks listing + card_record accordingly.
+    pub fn admin_force_cancel_listing(ctx: Context<AdminForceCancel>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let listing = &mut ctx.accounts.listing;
+        require_keys_eq!(
+            listing.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        require_keys_eq!(
+            listing.seller,
+            ctx.accounts.seller.key(),
+            MochiError::Unauthorized
+        );
+
+        // Defensive: recover card_record even if drifted.
+        let mut record =
+            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
+                .or_else(|_| {
+                    CardRecord::try_deserialize_unchecked(
+                        &mut &ctx.accounts.card_record.data.borrow()[..],
+                    )
+                })
+                .unwrap_or(CardRecord {
+                    vault_state: ctx.accounts.vault_state.key(),
+                    core_asset: listing.core_asset,
+                    template_id: 0,
+                    rarity: Rarity::Common,
+                    status: CardStatus::Reserved,
+                    owner: ctx.accounts.vault_authority.key(),
+                });
+        record.vault_state = ctx.accounts.vault_state.key();
+        record.core_asset = listing.core_asset;
+        record.status = CardStatus::UserOwned;
+        record.owner = listing.seller;
+
+        // Return NFT to seller.
+        thaw_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.vault_authority,
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+
+        // Persist repaired card_record
+        {
+            let mut data = ctx.accounts.card_record.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(&mut data[..]);
+            cursor.write_all(&CardRecord::discriminator())?;
+            record.try_serialize(&mut cursor)?;
+        }
+
+        listing.status = ListingStatus::Cancelled;
+        update_seller_index(&ctx.accounts.seller_index, |index| {
+            index.remove(&listing.key());
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Promotional pricing for one Active listing: `Some(bps)` charges that fee on fill
+    /// instead of the vault-wide `marketplace_fee_bps` (0 for zero-fee events); `None`
+    /// restores the global rate. Cleared whenever the card is relisted.
+    pub fn set_listing_fee_override(
+        ctx: Context<SetListingFeeOverride>,
+        fee_bps_override: Option<u16>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        if let Some(bps) = fee_bps_override {
+            require!(bps <= 10_000, MochiError::InvalidPrice);
+        }
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        listing.fee_bps_override = fee_bps_override;
+        Ok(())
+    }
+
+    /// Recovery for a compromised seller key: points an Active listing (asset still frozen in
+    /// escrow) at a wallet the real owner controls, after off-chain verification. Fill proceeds
+    /// and a later cancel then go to `new_seller`.
+    pub fn reassign_listing_seller(
+        ctx: Context<ReassignListingSeller>,
+        new_seller: Pubkey,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let listing = &mut ctx.accounts.listing;
+        require!(
+            listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        let old_seller = listing.seller;
+        require!(
+            new_seller != Pubkey::default() && new_seller != old_seller,
+            MochiError::Unauthorized
+        );
+        let listing_key = listing.key();
+        update_seller_index(&ctx.accounts.old_seller_index, |index| {
+            index.remove(&listing_key);
+            Ok(())
+        })?;
+        update_seller_index(&ctx.accounts.new_seller_index, |index| {
+            index.insert(listing_key)
+        })?;
+        listing.seller = new_seller;
+        emit!(ListingReassigned {
+            admin: ctx.accounts.admin.key(),
+            listing: listing_key,
+            core_asset: listing.core_asset,
+            old_seller,
+            new_seller,
+        });
+        Ok(())
+    }
+
+    /// Seller drops index entries for listings that are no longer Active (e.g. closed by an
+    /// admin path that doesn't touch the index), freeing slots for new listings.
+    /// remaining_accounts: [listings...]
+    pub fn prune_seller_index<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PruneSellerIndex<'info>>,
+    ) -> Result<()> {
+        let index = &mut ctx.accounts.seller_index;
+        for acc_info in ctx.remaining_accounts.iter() {
+            let listing: Account<Listing> = Account::try_from(acc_info)?;
+            if listing.status != ListingStatus::Active || listing.seller != index.seller {
+                index.remove(&acc_info.key());
+            }
+        }
+        Ok(())
+    }
+
+    /// Admin-only guardrail to return a stuck listing's asset to its original seller.
+    /// Destination is fixed to listing.seller; admin cannot redirect funds.
+    pub fn emergency_return_asset(ctx: Context<EmergencyReturnAsset>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let listing = &mut ctx.accounts.listing;
+        require_keys_eq!(
+            listing.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        require_keys_eq!(
+            listing.seller,
+            ctx.accounts.seller.key(),
+            MochiError::Unauthorized
+        );
+
+        let mut record =
+            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
+                .or_else(|_| {
+                    CardRecord::try_deserialize_unchecked(
+                        &mut &ctx.accounts.card_record.data.borrow()[..],
+                    )
+                })
+                .unwrap_or(CardRecord {
+                    vault_state: ctx.accounts.vault_state.key(),
+                    core_asset: listing.core_asset,
+                    template_id: 0,
+                    rarity: Rarity::Common,
+                    status: CardStatus::Reserved,
+                    owner: ctx.accounts.vault_authority.key(),
+                });
+        record.vault_state = ctx.accounts.vault_state.key();
+        record.core_asset = listing.core_asset;
+        record.status = CardStatus::UserOwned;
+        record.owner = listing.seller;
+
+        thaw_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.vault_authority,
+                payer: &ctx.accounts.vault_authority,
+                vault_state: &ctx.accounts.vault_state.key(),
+                vault_bump: ctx.bumps.vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                mpl_core_program: &ctx.accounts.mpl_core_program.to_account_info(),
+            },
+        )?;
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_authority,
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.mpl_core_program.to_account_info(),
+        )?;
+
+        {
+            let mut data = ctx.accounts.card_record.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(&mut data[..]);
+            cursor.write_all(&CardRecord::discriminator())?;
+            record.try_serialize(&mut cursor)?;
+        }
+
+        listing.status = ListingStatus::Cancelled;
+        Ok(())
+    }
+
+    /// Admin-only rescue for legacy listings anchored to an old/non-canonical vault_state PDA.
+    /// Returns the asset to the original seller and marks the listing cancelled.
+    pub fn admin_rescue_legacy_listing(ctx: Context<AdminRescueLegacyListing>) -> Result<()> {
+        require!(
+            ctx.accounts
+                .marketplace_vault_state
+                .is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let listing = &mut ctx.accounts.listing;
+        require_keys_eq!(
+            listing.vault_state,
+            ctx.accounts.legacy_vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        require_keys_eq!(
+            listing.seller,
+            ctx.accounts.seller.key(),
+            MochiError::Unauthorized
+        );
+
+        let (authority_seed, authority_bump) = legacy_authority_seed(
+            &ctx.accounts.legacy_vault_state.key(),
+            &ctx.accounts.legacy_vault_authority.key(),
+        )?;
+
+        let mut record =
+            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
+                .or_else(|_| {
+                    CardRecord::try_deserialize_unchecked(
+                        &mut &ctx.accounts.card_record.data.borrow()[..],
+                    )
+                })
+                .unwrap_or(CardRecord {
+                    vault_state: listing.vault_state,
+                    core_asset: listing.core_asset,
+                    template_id: 0,
+                    rarity: Rarity::Common,
+                    status: CardStatus::Reserved,
+                    owner: ctx.accounts.legacy_vault_authority.key(),
+                });
+        record.vault_state = listing.vault_state;
+        record.core_asset = listing.core_asset;
+        record.status = CardStatus::UserOwned;
+        record.owner = listing.seller;
+
+        let should_transfer = record.owner == ctx.accounts.legacy_vault_authority.key();
+        if should_transfer {
+            transfer_core_asset(
+                &ctx.accounts.core_asset,
+                &ctx.accounts.legacy_vault_authority,
+                &ctx.accounts.legacy_vault_authority,
+                &ctx.accounts.seller.to_account_info(),
+                &ctx.accounts.legacy_vault_state.key(),
+                authority_bump,
+                authority_seed,
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.mpl_core_program.to_account_info(),
+            )?;
+        } else if record.owner != listing.seller {
+            // If the asset is already with the seller, no transfer is needed; otherwise fail.
+            return err!(MochiError::Unauthorized);
+        }
+
+        // Best-effort persist; if the legacy card_record is missing or too small, skip persistence.
+        if let Ok(mut data) = ctx.accounts.card_record.try_borrow_mut_data() {
+            if data.len() >= 8 + CardRecord::SIZE {
+                let mut cursor = std::io::Cursor::new(&mut data[..]);
+                let _ = cursor.write_all(&CardRecord::discriminator());
+                let _ = record.try_serialize(&mut cursor);
+            }
+        }
+
+        listing.status = ListingStatus::Cancelled;
+        Ok(())
+    }
+
+    /// Admin-only alternative to `admin_rescue_legacy_listing` that keeps the listing for sale:
+    /// moves the escrowed asset from the legacy vault authority to the canonical marketplace
+    /// vault authority, recreates the CardRecord and Listing under canonical seeds with the
+    /// same seller and terms, and cancels the legacy listing (its record is deprecated).
+    pub fn migrate_listing_to_canonical(ctx: Context<MigrateListingToCanonical>) -> Result<()> {
+        require!(
+            ctx.accounts
+                .marketplace_vault_state
+                .is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let market_key = ctx.accounts.marketplace_vault_state.key();
+        let legacy_key = ctx.accounts.legacy_vault_state.key();
+        let core_key = ctx.accounts.core_asset.key();
+        require_keys_neq!(legacy_key, market_key, MochiError::VaultMismatch);
+        let legacy_listing = &mut ctx.accounts.legacy_listing;
+        require_keys_eq!(
+            legacy_listing.vault_state,
+            legacy_key,
+            MochiError::VaultMismatch
+        );
+        require!(
+            legacy_listing.status == ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+        require_keys_eq!(
+            legacy_listing.seller,
+            ctx.accounts.seller.key(),
+            MochiError::Unauthorized
+        );
+        let (authority_seed, authority_bump) =
+            legacy_authority_seed(&legacy_key, &ctx.accounts.legacy_vault_authority.key())?;
+        require!(
+            listing_is_backed(
+                &ctx.accounts.core_asset,
+                &ctx.accounts.legacy_vault_authority.key()
+            ),
+            MochiError::ListingUnbacked
+        );
+        // Same guard as list_card: never clobber a live canonical listing.
+        let listing = &ctx.accounts.listing;
+        require!(
+            listing.vault_state == Pubkey::default() || listing.status != ListingStatus::Active,
+            MochiError::InvalidListingState
+        );
+
+        let mut legacy_record =
+            CardRecord::try_deserialize(&mut &ctx.accounts.legacy_card_record.data.borrow()[..])
+                .or_else(|_| {
+                    CardRecord::try_deserialize_unchecked(
+                        &mut &ctx.accounts.legacy_card_record.data.borrow()[..],
+                    )
+                })?;
+        require_keys_eq!(
+            legacy_record.core_asset,
+            core_key,
+            MochiError::AssetMismatch
+        );
+
+        let admin = ctx.accounts.admin.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let mpl_core_program = ctx.accounts.mpl_core_program.to_account_info();
+        thaw_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.legacy_vault_authority,
+                payer: &admin,
+                vault_state: &legacy_key,
+                vault_bump: authority_bump,
+                authority_seed,
+                system_program: &system_program,
+                mpl_core_program: &mpl_core_program,
+            },
+        )?;
+        transfer_core_asset(
+            &ctx.accounts.core_asset,
+            &ctx.accounts.legacy_vault_authority,
+            &admin,
+            &ctx.accounts.marketplace_vault_authority.to_account_info(),
+            &legacy_key,
+            authority_bump,
+            authority_seed,
+            &system_program,
+            &mpl_core_program,
+        )?;
+        freeze_core_asset(
+            &ctx.accounts.core_asset,
+            &CoreCpi {
+                authority: &ctx.accounts.marketplace_vault_authority,
+                payer: &admin,
+                vault_state: &market_key,
+                vault_bump: ctx.bumps.marketplace_vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &system_program,
+                mpl_core_program: &mpl_core_program,
+            },
+        )?;
+
+        let market_authority = ctx.accounts.marketplace_vault_authority.key();
+        let record = &mut ctx.accounts.card_record;
+        record.vault_state = market_key;
+        record.core_asset = core_key;
+        record.template_id = legacy_record.template_id;
+        record.rarity = legacy_record.rarity.clone();
+        record.status = CardStatus::Reserved;
+        record.owner = market_authority;
+
+        let listing = &mut ctx.accounts.listing;
+        listing.vault_state = market_key;
+        listing.seller = legacy_listing.seller;
+        listing.core_asset = core_key;
+        listing.price_lamports = legacy_listing.price_lamports;
+        listing.currency_mint = legacy_listing.currency_mint;
+        listing.status = ListingStatus::Active;
+        listing.fee_bps_override = legacy_listing.fee_bps_override;
+
+        let listing_key = listing.key();
+        let index = &mut ctx.accounts.seller_index;
+        index.vault_state = market_key;
+        index.seller = legacy_listing.seller;
+        index.bump = ctx.bumps.seller_index;
+        index.insert(listing_key)?;
+
+        legacy_listing.status = ListingStatus::Cancelled;
+        legacy_record.status = CardStatus::Deprecated;
+        legacy_record.owner = market_authority;
+        persist_card_record(&legacy_record, &ctx.accounts.legacy_card_record)?;
+        Ok(())
+    }
+
+    /// Admin-only consistency check between a listing and its card_record.
+    /// Active listings force the record to Reserved/vault-owned; terminal listings
+    /// take the owner from the Core asset itself.
+    pub fn reconcile_card_listing(ctx: Context<ReconcileCardListing>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let listing = &ctx.accounts.listing;
+        require_keys_eq!(
+            listing.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        let vault_authority = ctx.accounts.vault_authority.key();
+
+        let mut record =
+            CardRecord::try_deserialize(&mut &ctx.accounts.card_record.data.borrow()[..])
+                .or_else(|_| {
+                    CardRecord::try_deserialize_unchecked(
+                        &mut &ctx.accounts.card_record.data.borrow()[..],
+                    )
+                })
+                .unwrap_or(CardRecord {
+                    vault_state: ctx.accounts.vault_state.key(),
+                    core_asset: listing.core_asset,
+                    template_id: 0,
+                    rarity: Rarity::Common,
+                    status: CardStatus::Available,
+                    owner: Pubkey::default(),
+                });
+        let previous_status = record.status.clone();
+        let previous_owner = record.owner;
+
+        (record.status, record.owner) =
+            listing_record_state(listing, &ctx.accounts.core_asset, &vault_authority)?;
+        record.vault_state = ctx.accounts.vault_state.key();
+        record.core_asset = listing.core_asset;
+
+        if record.status == previous_status && record.owner == previous_owner {
+            return Ok(());
+        }
+
+        {
+            let mut data = ctx.accounts.card_record.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(data.as_mut());
+            record.try_serialize(&mut cursor)?;
+        }
+
+        emit!(RecordReconciled {
+            listing: listing.key(),
+            card_record: ctx.accounts.card_record.key(),
+            core_asset: listing.core_asset,
+            listing_status: listing.status.clone(),
+            previous_status,
+            previous_owner,
+            status: record.status,
+            owner: record.owner,
+        });
+        Ok(())
+    }
+
+    /// Create the CardRecord for a listing whose record account never got initialized, so
+    /// cancel/repair can run. Fails if the record already exists.
+    pub fn admin_init_missing_record(ctx: Context<AdminInitMissingRecord>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let listing = &ctx.accounts.listing;
+        require_keys_eq!(
+            listing.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        let (status, owner) = listing_record_state(
+            listing,
+            &ctx.accounts.core_asset,
+            &ctx.accounts.vault_authority.key(),
+        )?;
+        let record = &mut ctx.accounts.card_record;
+        record.vault_state = ctx.accounts.vault_state.key();
+        record.core_asset = listing.core_asset;
+        record.template_id = 0;
+        record.rarity = Rarity::Common;
+        record.status = status;
+        record.owner = owner;
+        Ok(())
+    }
+
+    pub fn deprecate_card(ctx: Context<DeprecateCard>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let record = &mut ctx.accounts.card_record;
+        require_keys_eq!(
+            record.vault_state,
+            ctx.accounts.vault_state.key(),
+            MochiError::VaultMismatch
+        );
+        if record.status == CardStatus::Available {
+            if let Some(inventory) = tracked_inventory(
+                &ctx.accounts.vault_state,
+                &mut ctx.accounts.rarity_inventory,
+            )? {
+                inventory.remove(&record.rarity);
+            }
+        }
+        record.status = CardStatus::Deprecated;
+        Ok(())
+    }
+
+    /// Reclaim the rent of a Burned or Deprecated CardRecord; live records are refused.
+    pub fn close_card_record(ctx: Context<CloseCardRecord>) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            matches!(
+                ctx.accounts.card_record.status,
+                CardStatus::Burned | CardStatus::Deprecated
+            ),
+            MochiError::CardNotTerminal
+        );
+        // Account will be closed to admin via `close = admin` attribute.
+        Ok(())
+    }
+
+    /// Re-mint recovery: moves a vault card's template/rarity onto a replacement Core asset.
+    /// CardRecords are keyed by asset, so a new record is created for `new_core_asset` and the
+    /// old one is deprecated. The old asset must be gone (closed or burned) and the new one held
+    /// by the vault authority.
+    pub fn admin_rebind_asset(
+        ctx: Context<AdminRebindAsset>,
+        new_core_asset: Pubkey,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require_keys_eq!(
+            ctx.accounts.new_core_asset.key(),
+            new_core_asset,
+            MochiError::AssetMismatch
+        );
+        let old_record = &mut ctx.accounts.old_card_record;
+        require!(
+            old_record.status == CardStatus::Available,
+            MochiError::CardNotAvailable
+        );
+
+        let old_asset = &ctx.accounts.old_core_asset;
+        let old_asset_live = !old_asset.data_is_empty()
+            && *old_asset.owner == mpl_core::ID
+            && mpl_core::accounts::BaseAssetV1::from_bytes(&old_asset.data.borrow()).is_ok();
+        require!(!old_asset_live, MochiError::AssetMismatch);
+
+        let new_asset = &ctx.accounts.new_core_asset;
+        require_keys_eq!(*new_asset.owner, mpl_core::ID, MochiError::AssetMismatch);
+        let asset = mpl_core::accounts::BaseAssetV1::from_bytes(&new_asset.data.borrow())
+            .map_err(|_| error!(MochiError::AssetMismatch))?;
+        require_keys_eq!(
+            asset.owner,
+            ctx.accounts.vault_authority.key(),
+            MochiError::AssetMismatch
+        );
+
+        let new_record = &mut ctx.accounts.new_card_record;
+        new_record.vault_state = old_record.vault_state;
+        new_record.core_asset = new_core_asset;
+        new_record.template_id = old_record.template_id;
+        new_record.rarity = old_record.rarity.clone();
+        new_record.status = CardStatus::Available;
+        new_record.owner = ctx.accounts.vault_authority.key();
+        old_record.status = CardStatus::Deprecated;
+
+        emit!(AssetRebound {
+            vault_state: ctx.accounts.vault_state.key(),
+            old_core_asset: old_asset.key(),
+            new_core_asset,
+            template_id: new_record.template_id,
+        });
+        Ok(())
+    }
+
+    pub fn admin_force_close_session<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminForceClose<'info>>,
+    ) -> Result<()> {
+        // Admin-only override: closes pack_session regardless of state and frees card records.
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+
+        // Free card records passed in remaining accounts that are still reserved to this
+        // session's user (best-effort; anything else is skipped).
+        let vault_key = ctx.accounts.vault_state.key();
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let session_user = ctx.accounts.pack_session.user;
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        let mut freed_cards: u8 = 0;
+        for acc_info in ctx.remaining_accounts.iter() {
+            if release_session_record(
+                acc_info,
+                &vault_key,
+                &session_user,
+                &vault_authority,
+                inventory.as_deref_mut(),
+            )? {
+                freed_cards = freed_cards.saturating_add(1);
+            }
+        }
+
+        // Zero out the pack_session; account will be closed to admin via the context.
+        let session = &mut ctx.accounts.pack_session;
+        if session.state == PackState::PendingDecision {
+            ctx.accounts.vault_state.settle_session(&session.currency);
+        }
+        session.state = PackState::Uninitialized;
+        session.paid_amount = 0;
+        session.created_at = 0;
+        session.expires_at = 0;
+        session.currency = Currency::Sol;
+        session.card_record_keys = [Pubkey::default(); PACK_CARD_COUNT];
+        session.client_seed_hash = [0u8; 32];
+        session.manifest_hash = [0u8; 32];
+        session.rarity_prices = Vec::new();
+        emit!(AdminSessionAction {
+            admin: ctx.accounts.admin.key(),
+            user: ctx.accounts.user.key(),
+            action: ADMIN_ACTION_FORCE_CLOSE,
+            freed_cards,
+        });
+        Ok(())
+    }
+
+    /// Admin-only recovery of SOL sent straight to the vault authority PDA.
+    /// Leaves the PDA rent-exempt so the runtime accepts the debit.
+    pub fn admin_withdraw_authority_lamports(
+        ctx: Context<AdminWithdrawAuthorityLamports>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        let floor = Rent::get()?.minimum_balance(0);
+        let remaining = ctx
+            .accounts
+            .vault_authority
+            .lamports()
+            .checked_sub(amount)
+            .ok_or(MochiError::InsufficientFunds)?;
+        // Layaway deposits escrowed by reserve_pack belong to users, not the vault.
+        let floor = floor
+            .checked_add(ctx.accounts.vault_state.escrowed_lamports)
+            .ok_or(MochiError::MathOverflow)?;
+        require!(remaining >= floor, MochiError::InsufficientFunds);
+
+        let vault_key = ctx.accounts.vault_state.key();
+        let seeds = &[
+            GACHA_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer = &[&seeds[..]];
+        invoke_signed(
+            &system_instruction::transfer(
+                &ctx.accounts.vault_authority.key(),
+                &ctx.accounts.destination.key(),
+                amount,
+            ),
+            &[
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.accounts.destination.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer,
+        )?;
+        emit!(AuthorityLamportsRecovered {
+            admin: ctx.accounts.admin.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Move marketplace fee proceeds into the gacha treasury to fund buybacks.
+    /// The marketplace treasury must be its vault authority PDA so the program can sign.
+    pub fn sweep_marketplace_fees(ctx: Context<SweepMarketplaceFees>, amount: u64) -> Result<()> {
+        require!(
+            ctx.accounts
+                .market_vault_state
+                .is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(
+            ctx.accounts
+                .gacha_vault_state
+                .is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require_keys_eq!(
+            ctx.accounts.market_vault_state.treasury,
+            ctx.accounts.market_vault_authority.key(),
+            MochiError::VaultMismatch
+        );
+        require_keys_eq!(
+            ctx.accounts.gacha_treasury.key(),
+            ctx.accounts.gacha_vault_state.treasury,
+            MochiError::VaultMismatch
+        );
+        let floor = Rent::get()?.minimum_balance(0);
+        let remaining = ctx
+            .accounts
+            .market_vault_authority
+            .lamports()
+            .checked_sub(amount)
+            .ok_or(MochiError::InsufficientFunds)?;
+        // Buyer funds escrowed by commit_fill sit in the same PDA and are not fees.
+        let floor = floor
+            .checked_add(ctx.accounts.market_vault_state.escrowed_lamports)
+            .ok_or(MochiError::MathOverflow)?;
+        require!(remaining >= floor, MochiError::InsufficientFunds);
+
+        let vault_key = ctx.accounts.market_vault_state.key();
+        let seeds = &[
+            MARKETPLACE_VAULT_AUTHORITY_SEED,
+            vault_key.as_ref(),
+            &[ctx.bumps.market_vault_authority],
+        ];
+        let signer = &[&seeds[..]];
+        invoke_signed(
+            &system_instruction::transfer(
+                &ctx.accounts.market_vault_authority.key(),
+                &ctx.accounts.gacha_treasury.key(),
+                amount,
+            ),
+            &[
+                ctx.accounts.market_vault_authority.to_account_info(),
+                ctx.accounts.gacha_treasury.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+            ],
+            signer,
+        )?;
+        Ok(())
+    }
+
+    pub fn admin_reset_cards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AdminResetCards<'info>>,
+        only_rarity: Option<Rarity>,
+    ) -> Result<()> {
+        // Admin loop to set any provided CardRecords back to Available/ vault authority owner.
+        // With `only_rarity`, records of any other rarity are left untouched.
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        msg!("admin_reset_cards rem len {}", ctx.remaining_accounts.len());
+        let mut inventory = tracked_inventory(
+            &ctx.accounts.vault_state,
+            &mut ctx.accounts.rarity_inventory,
+        )?;
+        for acc_info in ctx.remaining_accounts.iter() {
+            if let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) {
+                // Spelled out: `Option::is_none_or` postdates the SBF toolchain's rustc.
+                let rarity_matches = match only_rarity.as_ref() {
+                    Some(rarity) => card_record.rarity == *rarity,
+                    None => true,
+                };
+                if card_record.vault_state == ctx.accounts.vault_state.key() && rarity_matches {
+                    if card_record.status != CardStatus::Available {
+                        if let Some(inventory) = inventory.as_deref_mut() {
+                            inventory.add(&card_record.rarity)?;
+                        }
+                    }
+                    card_record.status = CardStatus::Available;
+                    card_record.owner = ctx.accounts.vault_authority.key();
+                    let mut data = acc_info.try_borrow_mut_data()?;
+                    let mut cursor = std::io::Cursor::new(&mut data[..]);
+                    card_record.try_serialize(&mut cursor)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Admin repricing of treasury-held listings by a signed basis-point delta. Cuts of 100%
+    /// or more are rejected; smaller cuts stop at the listing floor for the listing's
+    /// currency, and never below 1.
+    /// remaining_accounts: [listings...]; listings not sold by the vault authority are skipped.
+    pub fn reprice_listings<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RepriceListings<'info>>,
+        delta_bps: i32,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.is_admin(&ctx.accounts.admin.key()),
+            MochiError::Unauthorized
+        );
+        require!(delta_bps > -10_000, MochiError::InvalidPrice);
+        for acc_info in ctx.remaining_accounts.iter() {
+            let mut listing: Account<Listing> = Account::try_from(acc_info)?;
+            require_keys_eq!(
+                listing.vault_state,
+                ctx.accounts.vault_state.key(),
+                MochiError::VaultMismatch
+            );
+            if listing.seller != ctx.accounts.vault_authority.key()
+                || listing.status != ListingStatus::Active
+            {
+                continue;
+            }
+            let repriced = (listing.price_lamports as i128)
+                .checked_mul(10_000i128 + delta_bps as i128)
+                .and_then(|v| v.checked_div(10_000))
+                .ok_or(MochiError::MathOverflow)?;
+            let repriced = u64::try_from(repriced).map_err(|_| MochiError::MathOverflow)?;
+            let floor = ctx
+                .accounts
+                .vault_state
+                .listing_floor(&listing.currency_mint);
+            listing.price_lamports = repriced.max(floor).max(1);
+            let mut data = acc_info.try_borrow_mut_data()?;
+            let mut cursor = std::io::Cursor::new(&mut data[..]);
+            listing.try_serialize(&mut cursor)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct OpenPackV2<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + PackSessionV2::SIZE,
+    )]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Treasury to receive SOL fees (typically same as vault_authority PDA)
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    #[account(mut)]
+    pub reward_mint: Account<'info, Mint>,
+    #[account(mut, constraint = reward_vault.owner == vault_authority.key(), constraint = reward_vault.mint == reward_mint.key())]
+    pub reward_vault: Account<'info, TokenAccount>,
+    /// Reward ATA of the purchaser, or of the recipient for gift packs (checked in handler)
+    #[account(mut, constraint = user_token_account.mint == reward_mint.key())]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+    /// CHECK: purchaser's membership Core asset for the boosted reward (checked in handler)
+    pub membership_asset: Option<UncheckedAccount<'info>>,
+    /// CHECK: instructions sysvar; required while `open_gate_authority` is set
+    #[account(address = ix_sysvar::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// CHECK: purchaser's UserStats PDA (seeds checked); enforces their spend limit if created
+    #[account(mut, seeds = [USER_STATS_SEED, vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub user_stats: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReservePack<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + PackSessionV2::SIZE,
+    )]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds); holds layaway escrow
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+    /// CHECK: instructions sysvar; required while `open_gate_authority` is set
+    #[account(address = ix_sysvar::ID)]
+    pub instructions_sysvar: Option<UncheckedAccount<'info>>,
+    /// CHECK: purchaser's UserStats PDA (seeds checked); enforces their spend limit if created
+    #[account(mut, seeds = [USER_STATS_SEED, vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub user_stats: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddPayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds); holds layaway escrow
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelLayaway<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds); holds layaway escrow
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireLayaway<'info> {
+    pub caller: Signer<'info>,
+    /// CHECK: layaway owner (for PDA derivation); receives the refund
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds); holds layaway escrow
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct ResolvePackV2<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut, address = vault_state.treasury)]
+    pub vault_treasury: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct RelayerClaimBatch<'info> {
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimGiftV2<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    /// CHECK: purchaser wallet (for PDA derivation)
+    pub user: UncheckedAccount<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FlipPackV2<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub market_vault_state: Account<'info, VaultState>,
+    /// CHECK: Marketplace vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, market_vault_state.key().as_ref()], bump)]
+    pub market_vault_authority: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + SellerListingIndex::SIZE,
+        seeds = [SELLER_INDEX_SEED, market_vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Account<'info, SellerListingIndex>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminForceCloseV2<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: target user wallet (for PDA derivation); receives any layaway refund
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+    /// Pending request from `admin_request_force_close`; required while a delay is set.
+    #[account(
+        mut,
+        seeds = [FORCE_CLOSE_REQUEST_SEED, vault_state.key().as_ref(), user.key().as_ref()],
+        bump = force_close_request.bump,
+        close = admin
+    )]
+    pub force_close_request: Option<Account<'info, ForceCloseRequest>>,
+}
+
+#[derive(Accounts)]
+pub struct RequestForceClose<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: target user wallet (for PDA derivation)
+    pub user: UncheckedAccount<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// Re-requesting overwrites a stale request and restarts the delay.
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + ForceCloseRequest::SIZE,
+        seeds = [FORCE_CLOSE_REQUEST_SEED, vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub force_close_request: Account<'info, ForceCloseRequest>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminExtendSession<'info> {
+    pub admin: Signer<'info>,
+    /// CHECK: target user wallet (for PDA derivation)
+    pub user: UncheckedAccount<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+}
+
+#[derive(Accounts)]
+#[instruction(args: InitializeVaultArgs)]
+pub struct InitializeVault<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [GACHA_VAULT_SEED, &gacha_vault_seed(args.vault_id)],
+        bump,
+        space = 8 + VaultState::SIZE,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: PDA that holds custody/treasury authority (validated by seeds)
+    #[account(
+        seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()],
+        bump,
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// CHECK: when passed, must be the Core collection named by `core_collection`
+    pub core_collection_account: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeMarketplaceVault<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [MARKETPLACE_VAULT_SEED],
+        bump,
+        space = 8 + VaultState::SIZE,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: marketplace escrow/vault authority PDA
+    #[account(
+        seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()],
+        bump,
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(template_id: u32)]
+pub struct DepositCard<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset account (Metaplex Core asset), validated off-chain
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()],
+        bump,
+        space = 8 + CardRecord::SIZE,
+    )]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [TEMPLATE_STATS_SEED, vault_state.key().as_ref(), &template_id.to_le_bytes()],
+        bump,
+        space = 8 + TemplateStats::SIZE,
+    )]
+    pub template_stats: Account<'info, TemplateStats>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct ReserveFor<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the card record is keyed by
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + CardReservation::SIZE,
+        seeds = [CARD_RESERVATION_SEED, card_record.key().as_ref()],
+        bump
+    )]
+    pub reservation: Account<'info, CardReservation>,
+    pub system_program: Program<'info, System>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReserved<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset (transferred out of the vault)
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    #[account(
+        mut,
+        seeds = [CARD_RESERVATION_SEED, card_record.key().as_ref()],
+        bump = reservation.bump,
+        has_one = buyer,
+        close = admin
+    )]
+    pub reservation: Account<'info, CardReservation>,
+    /// CHECK: vault admin, refunded the reservation rent
+    #[account(mut, address = vault_state.admin)]
+    pub admin: UncheckedAccount<'info>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Unreserve<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the card record is keyed by
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    #[account(
+        mut,
+        seeds = [CARD_RESERVATION_SEED, card_record.key().as_ref()],
+        bump = reservation.bump,
+        close = admin
+    )]
+    pub reservation: Account<'info, CardReservation>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseOrphanedCard<'info> {
+    pub caller: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, has_one = vault_state)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: V2 session of the card's owner; may not exist (checked in handler)
+    #[account(seeds = [b"pack_session_v2", vault_state.key().as_ref(), card_record.owner.as_ref()], bump)]
+    pub pack_session: UncheckedAccount<'info>,
+    /// CHECK: V1 session of the card's owner; may not exist (checked in handler)
+    #[account(seeds = [b"pack_session", vault_state.key().as_ref(), card_record.owner.as_ref()], bump)]
+    pub legacy_session: UncheckedAccount<'info>,
+    /// CHECK: `reserve_for` hold on the card; must not exist (checked in handler)
+    #[account(seeds = [CARD_RESERVATION_SEED, card_record.key().as_ref()], bump)]
+    pub reservation: UncheckedAccount<'info>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterExistingCards<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateSessionToV2<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub legacy_session: Account<'info, PackSession>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + PackSessionV2::SIZE,
+    )]
+    pub pack_session: Account<'info, PackSessionV2>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenPackStart<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + PackSession::SIZE,
+    )]
+    pub pack_session: Account<'info, PackSession>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Treasury to receive SOL fees
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// CHECK: mpl-core program id (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// CHECK: purchaser's UserStats PDA (seeds checked); enforces their spend limit if created
+    #[account(mut, seeds = [USER_STATS_SEED, vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub user_stats: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct ResolvePack<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSession>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminForceExpire<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: user wallet (used for PDA derivation only)
+    pub user: UncheckedAccount<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSession>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminResetSession<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: user wallet (used for PDA derivation only)
+    pub user: UncheckedAccount<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub pack_session: Account<'info, PackSession>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminForceClose<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: user wallet (used for PDA derivation only)
+    pub user: UncheckedAccount<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub pack_session: Account<'info, PackSession>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminResetCards<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminRebindAsset<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: the lost/burned asset; only inspected to confirm it is gone
+    pub old_core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), old_core_asset.key().as_ref()], bump)]
+    pub old_card_record: Account<'info, CardRecord>,
+    /// CHECK: replacement Core asset, deserialized in the handler
+    pub new_core_asset: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + CardRecord::SIZE,
+        seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), new_core_asset.key().as_ref()],
+        bump
+    )]
+    pub new_card_record: Account<'info, CardRecord>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminWithdrawAuthorityLamports<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Recipient of the recovered lamports (chosen by admin)
+    #[account(mut)]
+    pub destination: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePackSession<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: session owner (used for PDA derivation only)
+    pub user: UncheckedAccount<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: legacy pack session PDA (may be undersized); seeds enforced.
+    #[account(mut, seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePackSessionV2<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: session owner (used for PDA derivation only)
+    pub user: UncheckedAccount<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: legacy V2 session PDA (may be undersized); seeds enforced.
+    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepMarketplaceFees<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub market_vault_state: Account<'info, VaultState>,
+    /// CHECK: Marketplace vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, market_vault_state.key().as_ref()], bump)]
+    pub market_vault_authority: UncheckedAccount<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &gacha_vault_state.vault_seed()], bump)]
+    pub gacha_vault_state: Account<'info, VaultState>,
+    /// Gacha treasury (must match gacha_vault_state.treasury)
+    #[account(mut)]
+    pub gacha_treasury: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UserResetSession<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub pack_session: Account<'info, PackSession>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSessionV2<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub pack_session: Account<'info, PackSessionV2>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeClaim<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSession>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ListCard<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + CardRecord::SIZE,
+        seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()],
+        bump
+    )]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Core asset account (Metaplex Core), validated off-chain
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Listing::SIZE,
+        seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + SellerListingIndex::SIZE,
+        seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), seller.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Account<'info, SellerListingIndex>,
+}
+
+#[derive(Accounts)]
+pub struct PruneSellerIndex<'info> {
+    pub seller: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), seller.key().as_ref()], bump = seller_index.bump)]
+    pub seller_index: Account<'info, SellerListingIndex>,
+}
+
+#[derive(Accounts)]
+pub struct TreasuryListBatch<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds); holds the treasury inventory
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Relist<'info> {
+    pub seller: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Core asset, only used for PDA derivation
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelListing<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    /// CHECK: We will deserialize or rebuild defensively.
+    pub card_record: UncheckedAccount<'info>,
+    /// CHECK: Core asset (Metaplex Core), validated off-chain
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// CHECK: seller's listing index; absent for sellers who listed before the index existed
+    /// (checked in `update_seller_index`)
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), seller.key().as_ref()], bump)]
+    pub seller_index: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FillListing<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(mut, address = listing.seller)]
+    pub seller: SystemAccount<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Core asset account (Metaplex Core), validated off-chain
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    /// CHECK: System program
+    pub system_program: UncheckedAccount<'info>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// CHECK: seller's listing index; absent for sellers who listed before the index existed
+    /// (checked in `update_seller_index`)
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), listing.seller.as_ref()], bump)]
+    pub seller_index: UncheckedAccount<'info>,
+    /// Required signer when the listing price reaches `cosign_threshold_lamports`.
+    pub cosigner: Option<Signer<'info>>,
+    /// CHECK: payee of a verified collection's royalty; required and matched against
+    /// VaultState in the handler when the fill enforces one.
+    #[account(mut)]
+    pub royalty_recipient: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CommitFill<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the listing is keyed by
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + FillCommit::SIZE,
+        seeds = [FILL_COMMIT_SEED, listing.key().as_ref()],
+        bump
+    )]
+    pub fill_commit: Account<'info, FillCommit>,
+    /// CHECK: Vault authority PDA (validated by seeds); holds the escrowed lamports
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteFill<'info> {
+    pub executor: Signer<'info>,
+    #[account(mut, address = fill_commit.buyer)]
+    pub buyer: SystemAccount<'info>,
+    #[account(mut, address = listing.seller)]
+    pub seller: SystemAccount<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Core asset account (Metaplex Core)
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        mut,
+        close = buyer,
+        seeds = [FILL_COMMIT_SEED, listing.key().as_ref()],
+        bump = fill_commit.bump
+    )]
+    pub fill_commit: Account<'info, FillCommit>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub vault_treasury: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// CHECK: seller's listing index; absent for sellers who listed before the index existed
+    /// (checked in `update_seller_index`)
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), listing.seller.as_ref()], bump)]
+    pub seller_index: UncheckedAccount<'info>,
+    /// Required signer when the listing price reaches `cosign_threshold_lamports`.
+    pub cosigner: Option<Signer<'info>>,
+    /// CHECK: payee of a verified collection's royalty; required and matched against
+    /// VaultState in the handler when the fill enforces one.
+    #[account(mut)]
+    pub royalty_recipient: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelFillCommit<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        close = buyer,
+        has_one = buyer,
+        seeds = [FILL_COMMIT_SEED, fill_commit.listing.as_ref()],
+        bump = fill_commit.bump
+    )]
+    pub fill_commit: Account<'info, FillCommit>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteFill<'info> {
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the listing is keyed by
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+}
+
+#[derive(Accounts)]
+pub struct ListingBacking<'info> {
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the listing is keyed by; may no longer be a live asset
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InvalidateUnbackedListing<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the listing is keyed by; may no longer be a live asset
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// CHECK: seller's listing index; absent for sellers who listed before the index existed
+    /// (checked in `update_seller_index`)
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), listing.seller.as_ref()], bump)]
+    pub seller_index: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PeekPack<'info> {
+    /// CHECK: session owner (PDA derivation only)
+    pub user: UncheckedAccount<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub pack_session: Account<'info, PackSessionV2>,
+}
+
+#[derive(Accounts)]
+pub struct RevealPackV2<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub pack_session: Account<'info, PackSessionV2>,
+}
+
+#[derive(Accounts)]
+pub struct CardAvailability<'info> {
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(has_one = vault_state)]
+    pub card_record: Account<'info, CardRecord>,
+    /// Pending session of the card's current owner; omit when the card is not reserved.
+    #[account(
+        seeds = [b"pack_session_v2", vault_state.key().as_ref(), card_record.owner.as_ref()],
+        bump
+    )]
+    pub pack_session: Option<Account<'info, PackSessionV2>>,
+}
+
+#[derive(Accounts)]
+pub struct SwapCards<'info> {
+    #[account(mut)]
+    pub user_a: Signer<'info>,
+    #[account(mut)]
+    pub user_b: Signer<'info>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, card_record_a.vault_state.as_ref(), core_asset_a.key().as_ref()], bump)]
+    pub card_record_a: Account<'info, CardRecord>,
+    /// CHECK: user_a's Core asset (owner checked in handler)
+    #[account(mut)]
+    pub core_asset_a: UncheckedAccount<'info>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, card_record_b.vault_state.as_ref(), core_asset_b.key().as_ref()], bump)]
+    pub card_record_b: Account<'info, CardRecord>,
+    /// CHECK: user_b's Core asset (owner checked in handler)
+    #[account(mut)]
+    pub core_asset_b: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemBurn<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Core asset account (Metaplex Core), validated off-chain
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// MOCHI mint for the burn reward (checked against vault_state.mochi_mint when paid)
+    #[account(mut)]
+    pub reward_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(set_id: u32)]
+pub struct CreateTemplateSet<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + TemplateSet::SIZE,
+        seeds = [TEMPLATE_SET_SEED, vault_state.key().as_ref(), &set_id.to_le_bytes()],
+        bump
+    )]
+    pub template_set: Account<'info, TemplateSet>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimSetBonus<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(has_one = vault_state)]
+    pub template_set: Account<'info, TemplateSet>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + SetBonusClaim::SIZE,
+        seeds = [SET_BONUS_CLAIM_SEED, template_set.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub set_bonus_claim: Account<'info, SetBonusClaim>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// MOCHI mint (checked against vault_state.mochi_mint)
+    #[account(mut)]
+    pub reward_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(recipe_id: u32)]
+pub struct CreateFusionRecipe<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + FusionRecipe::SIZE,
+        seeds = [FUSION_RECIPE_SEED, vault_state.key().as_ref(), &recipe_id.to_le_bytes()],
+        bump
+    )]
+    pub fusion_recipe: Account<'info, FusionRecipe>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FuseCards<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(has_one = vault_state)]
+    pub fusion_recipe: Account<'info, FusionRecipe>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct SetSpendLimit<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: user's UserStats PDA (seeds checked); created or grown in the handler
+    #[account(mut, seeds = [USER_STATS_SEED, vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub user_stats: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PityStatus<'info> {
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: only used to derive the UserStats address
+    pub user: UncheckedAccount<'info>,
+    /// CHECK: user's UserStats PDA (seeds checked); may not exist yet
+    #[account(seeds = [USER_STATS_SEED, vault_state.key().as_ref(), user.key().as_ref()], bump)]
+    pub user_stats: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAirdropCard<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Core asset account (Metaplex Core)
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Airdrop recipient
+    pub recipient: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+#[instruction(airdrop_root: [u8; 32])]
+pub struct SetAirdropRoot<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + AirdropBitmap::SIZE,
+        seeds = [AIRDROP_BITMAP_SEED, vault_state.key().as_ref(), airdrop_root.as_ref()],
+        bump
+    )]
+    pub airdrop_bitmap: Account<'info, AirdropBitmap>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(code_hash: [u8; 32])]
+pub struct CreateDiscountCode<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + DiscountCode::SIZE,
+        seeds = [DISCOUNT_CODE_SEED, vault_state.key().as_ref(), code_hash.as_ref()],
+        bump
+    )]
+    pub discount_code: Account<'info, DiscountCode>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAirdrop<'info> {
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [AIRDROP_BITMAP_SEED, vault_state.key().as_ref(), vault_state.airdrop_root.as_ref()],
+        bump = airdrop_bitmap.bump
+    )]
+    pub airdrop_bitmap: Account<'info, AirdropBitmap>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: Core asset (transferred out of the vault)
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminMigrateAsset<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: emergency destination (validated off-chain by admin authority)
+    pub destination: UncheckedAccount<'info>,
+    /// CHECK: Core asset account (Metaplex Core)
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminMigrateAssetsBatch<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: migration destination; must match the `destination` argument
+    pub destination: UncheckedAccount<'info>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminBurnCardsBatch<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program (CPI target)
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct AdminForceCancel<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    /// CHECK: we will deserialize or rebuild
+    pub card_record: UncheckedAccount<'info>,
+    /// CHECK: core asset
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    /// CHECK: vault authority
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Seller (funds will be returned)
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+    /// CHECK: system program
+    pub system_program: UncheckedAccount<'info>,
+    /// CHECK: mpl-core
+    pub mpl_core_program: UncheckedAccount<'info>,
+    /// CHECK: seller's listing index; absent for sellers who listed before the index existed
+    /// (checked in `update_seller_index`)
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), listing.seller.as_ref()], bump)]
+    pub seller_index: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetListingFeeOverride<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the listing is keyed by
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_seller: Pubkey)]
+pub struct ReassignListingSeller<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the listing is keyed by
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: current seller's listing index, if it exists (checked in `update_seller_index`)
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), listing.seller.as_ref()], bump)]
+    pub old_seller_index: UncheckedAccount<'info>,
+    /// CHECK: new seller's listing index, if it exists (checked in `update_seller_index`)
+    #[account(mut, seeds = [SELLER_INDEX_SEED, vault_state.key().as_ref(), new_seller.as_ref()], bump)]
+    pub new_seller_index: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyReturnAsset<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    /// CHECK: we will deserialize or rebuild
+    pub card_record: UncheckedAccount<'info>,
+    /// CHECK: core asset
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    /// CHECK: vault authority
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Seller destination (must match listing.seller)
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminRescueLegacyListing<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub marketplace_vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub legacy_vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, legacy_vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    /// CHECK: legacy card record PDA
+    pub card_record: UncheckedAccount<'info>,
+    /// CHECK: core asset tied to listing
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, legacy_vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: legacy vault authority PDA (seed prefix verified in handler)
+    #[account(mut)]
+    pub legacy_vault_authority: UncheckedAccount<'info>,
+    /// Seller destination (must match listing.seller)
+    #[account(mut)]
+    pub seller: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateListingToCanonical<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub marketplace_vault_state: Account<'info, VaultState>,
+    /// CHECK: canonical marketplace vault authority PDA (validated by seeds)
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, marketplace_vault_state.key().as_ref()], bump)]
+    pub marketplace_vault_authority: UncheckedAccount<'info>,
+    pub legacy_vault_state: Account<'info, VaultState>,
+    /// CHECK: legacy vault authority PDA (seed prefix verified in handler)
+    #[account(mut)]
+    pub legacy_vault_authority: UncheckedAccount<'info>,
+    /// CHECK: legacy card record PDA; deserialized in handler
+    #[account(mut, seeds = [CARD_RECORD_SEED, legacy_vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub legacy_card_record: UncheckedAccount<'info>,
+    #[account(mut, seeds = [LISTING_SEED, legacy_vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub legacy_listing: Account<'info, Listing>,
+    /// CHECK: core asset tied to the listing
+    #[account(mut)]
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + CardRecord::SIZE,
+        seeds = [CARD_RECORD_SEED, marketplace_vault_state.key().as_ref(), core_asset.key().as_ref()],
+        bump
+    )]
+    pub card_record: Account<'info, CardRecord>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + Listing::SIZE,
+        seeds = [LISTING_SEED, marketplace_vault_state.key().as_ref(), core_asset.key().as_ref()],
+        bump
+    )]
+    pub listing: Account<'info, Listing>,
+    /// CHECK: listing seller (must match legacy_listing.seller); keys the seller index
+    pub seller: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + SellerListingIndex::SIZE,
+        seeds = [SELLER_INDEX_SEED, marketplace_vault_state.key().as_ref(), seller.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Account<'info, SellerListingIndex>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: mpl-core program
+    pub mpl_core_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcileCardListing<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    /// CHECK: we will deserialize or rebuild
+    pub card_record: UncheckedAccount<'info>,
+    /// CHECK: core asset; owner program checked in handler before reading
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    /// CHECK: vault authority
+    pub vault_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminInitMissingRecord<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + CardRecord::SIZE,
+        seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()],
+        bump
+    )]
+    pub card_record: Account<'info, CardRecord>,
+    /// CHECK: core asset; owner program checked before reading
+    pub core_asset: UncheckedAccount<'info>,
+    #[account(seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: Account<'info, Listing>,
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    /// CHECK: vault authority
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DeprecateCard<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut)]
+    pub card_record: Account<'info, CardRecord>,
+    /// Per-rarity stock counter; required once the vault tracks inventory.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct BurnVaultMochi<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub mochi_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub vault_mochi: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RotatePaymentMint<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// Vault token account of the outgoing mint; pass with `sweep_destination` to sweep it.
+    #[account(mut)]
+    pub old_vault_token: Option<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub sweep_destination: Option<Account<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCardRecord<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(mut, has_one = vault_state, close = admin)]
+    pub card_record: Account<'info, CardRecord>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateListing<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Core asset the listing is keyed by
+    pub core_asset: UncheckedAccount<'info>,
+    /// CHECK: legacy listing PDA (may be undersized); seeds enforced.
+    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
+    pub listing: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RepriceListings<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: Vault authority PDA (validated by seeds)
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminPruneListing<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: listing PDA may have been created with wrong seeds; we only mark Cancelled.
+    #[account(mut)]
+    pub listing: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRewardConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: vault authority PDA (seed checked in handler)
     #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
     pub vault_authority: UncheckedAccount<'info>,
-    /// Treasury to receive SOL fees (typically same as vault_authority PDA)
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetVaultConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMarketplaceConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+#[derive(Accounts)]
+pub struct ManageAdmins<'info> {
+    pub admin: Signer<'info>,
     #[account(mut)]
-    pub vault_treasury: SystemAccount<'info>,
+    pub vault_state: Account<'info, VaultState>,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    pub admin: Signer<'info>,
     #[account(mut)]
-    pub reward_mint: Account<'info, Mint>,
-    #[account(mut, constraint = reward_vault.owner == vault_authority.key(), constraint = reward_vault.mint == reward_mint.key())]
-    pub reward_vault: Account<'info, TokenAccount>,
-    #[account(mut, constraint = user_token_account.owner == user.key(), constraint = user_token_account.mint == reward_mint.key())]
-    pub user_token_account: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
+    pub vault_state: Account<'info, VaultState>,
+    /// CHECK: read-only balance check against the treasury being replaced
+    #[account(address = vault_state.treasury)]
+    pub current_treasury: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeSplit<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + FeeSplit::SIZE,
+        seeds = [FEE_SPLIT_SEED, vault_state.key().as_ref()],
+        bump
+    )]
+    pub fee_split: Account<'info, FeeSplit>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetRarityInventory<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + RarityInventory::SIZE,
+        seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()],
+        bump
+    )]
+    pub rarity_inventory: Account<'info, RarityInventory>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecomputeVaultCounters<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    /// Rebuilt alongside the counters when passed.
+    #[account(mut, seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Option<Account<'info, RarityInventory>>,
+}
+
+#[derive(Accounts)]
+pub struct InventoryCounts<'info> {
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(seeds = [RARITY_INVENTORY_SEED, vault_state.key().as_ref()], bump = rarity_inventory.bump)]
+    pub rarity_inventory: Account<'info, RarityInventory>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyVaultAuthorityBump<'info> {
+    pub vault_state: Account<'info, VaultState>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyOdds<'info> {
+    #[account(seeds = [GACHA_VAULT_SEED, &vault_state.vault_seed()], bump)]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyManifest<'info> {
+    /// CHECK: PackSession or PackSessionV2; owner and discriminator checked in handler.
+    pub pack_session: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVaultState<'info> {
+    /// Must match the admin already stored in the legacy vault.
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
+    /// CHECK: migrating legacy account; seeds enforced above.
+    pub vault_state: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateMarketplaceVault<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: legacy marketplace vault PDA (may be undersized); seeds enforced.
+    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
+    pub vault_state: UncheckedAccount<'info>,
+    /// CHECK: marketplace vault authority PDA
+    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct VaultState {
+    pub admin: Pubkey,
+    pub vault_authority: Pubkey,
+    pub pack_price_sol: u64,
+    pub pack_price_usdc: u64,
+    pub buyback_bps: u16,
+    pub claim_window_seconds: i64,
+    pub marketplace_fee_bps: u16,
+    pub core_collection: Option<Pubkey>,
+    pub usdc_mint: Option<Pubkey>,
+    pub mochi_mint: Option<Pubkey>,
+    pub reward_per_pack: u64,
+    pub vault_authority_bump: u8,
+    pub padding: [u8; 7],
+    pub max_window_override: i64,
+    pub treasury: Pubkey,
+    pub no_buyback_rarities: u16,
+    pub odds_commitment: [u8; 32],
+    pub accepted_currencies: u8,
+    pub sellback_cooldown_seconds: i64,
+    /// Last nonce handed to a pack session; bumped on every V1 and V2 open.
+    pub session_nonce: u64,
+    /// When set, fees are routed through this vault's FeeSplit PDA instead of the treasury.
+    pub fee_split_enabled: bool,
+    /// Upper bounds on pack prices; 0 means unbounded.
+    pub max_pack_price_sol: u64,
+    pub max_pack_price_usdc: u64,
+    /// Rare+ ceiling per open; 0 falls back to MAX_RARE_CARDS.
+    pub max_rares_per_pack: u8,
+    /// Which gacha vault this is; 0 is the original singleton.
+    pub vault_id: u64,
+    /// MOCHI paid per card burned in `redeem_burn`; 0 disables.
+    pub burn_reward_per_card: u64,
+    /// Marketplace only: buyer lamports held in the vault authority for pending fill commits.
+    pub escrowed_lamports: u64,
+    /// Set while an asset-moving instruction runs; see `lock_vault`.
+    pub locked: bool,
+    /// Charged to the claimer per Keep claim to fund vault operations; 0 disables.
+    pub claim_fee_lamports: u64,
+    /// Marketplace only: lifetime fill count, buyer-paid volume and marketplace fees.
+    pub total_sales: u64,
+    pub total_volume_lamports: u64,
+    pub total_fees_collected: u64,
+    /// Marketplace only: ceil the fill fee instead of truncating it.
+    pub round_fees_up: bool,
+    /// Marketplace only: SPL mints listings may be priced in; default (zero) slots are empty.
+    pub allowed_currency_mints: [Pubkey; MAX_CURRENCY_MINTS],
+    /// USDC leg of a `Currency::Both` pack; zero leaves the combined SKU unpriced.
+    pub combo_price_usdc: u64,
+    /// `set_odds_commitment` is rejected until this timestamp (0 = unlocked).
+    pub odds_locked_until: i64,
+    /// Hard ceiling on any session's claim window, overrides included (0 = uncapped).
+    pub max_session_seconds: i64,
+    /// Most cards `deposit_card` will accept per template (0 = unlimited).
+    pub max_per_template: u32,
+    /// Merkle root for `claim_airdrop`; zero means no airdrop is live.
+    pub airdrop_root: [u8; 32],
+    /// Secondary admins (zero slots are empty); only `admin` may change this set.
+    pub admins: [Pubkey; MAX_ADMINS],
+    /// Decaying emissions: when non-zero, replaces `reward_per_pack` (see `effective_reward`).
+    pub reward_start: u64,
+    pub reward_decay_bps_per_epoch: u16,
+    pub epoch_seconds: i64,
+    pub reward_schedule_start: i64,
+    /// Marketplace: extra signer required on fills at or above `cosign_threshold_lamports`.
+    pub cosigner: Option<Pubkey>,
+    pub cosign_threshold_lamports: u64,
+    /// Layaway window for `reserve_pack` (0 disables layaway) and its minimum deposit.
+    pub layaway_window_seconds: i64,
+    pub layaway_min_deposit_bps: u16,
+    /// Price of a `Currency::Mochi` pack in raw MOCHI units (0 = not sold for MOCHI).
+    pub pack_price_mochi: u64,
+    /// Set by `set_rarity_inventory`; tracked paths then require the RarityInventory account.
+    pub rarity_inventory_enabled: bool,
+    /// Marketplace: minimum listing price for SOL and for mint-denominated listings (0 = none).
+    pub min_listing_price_lamports: u64,
+    pub min_listing_price_token: u64,
+    /// Core collection whose holders get `membership_reward_multiplier_bps` on open rewards.
+    pub membership_collection: Option<Pubkey>,
+    pub membership_reward_multiplier_bps: u16,
+    /// Dynamic buyback (V2 sellback): stock level per rarity at which `buyback_bps` applies
+    /// unchanged (0 = off), the bps shift per card of deviation, and the clamp range.
+    pub buyback_target_count: u32,
+    pub buyback_step_bps: u16,
+    pub min_buyback_bps: u16,
+    pub max_buyback_bps: u16,
+    /// Processing fee taken from the discounted sellback payout, in bps (0 = none).
+    pub sellback_fee_bps: u16,
+    /// PendingDecision sessions paid in `usdc_mint` (Token or Both); `rotate_payment_mint`
+    /// waits for zero. Sessions opened before this counter existed are not included.
+    pub pending_token_sessions: u32,
+    /// Vault authority lamports below which LowTreasuryWarning is emitted (0 = off).
+    pub min_authority_balance: u64,
+    /// Cap on PendingDecision sessions (0 = none) and the live count, kept alongside
+    /// `pending_token_sessions` with the same caveat for pre-counter sessions.
+    pub max_active_sessions: u32,
+    pub active_session_count: u32,
+    /// Time-lock on `admin_force_close_v2` (0 = immediate); `emergency_mode` bypasses it.
+    pub force_close_delay_seconds: i64,
+    pub emergency_mode: bool,
+    /// Vault-wide cap on live cards across all templates (0 = none) and the live count:
+    /// deposits and registrations add, burns subtract.
+    pub max_total_cards: u64,
+    pub total_cards_deposited: u64,
+    /// Commit-to-keep: `reveal_pack_v2` locks the session it reveals against sellback.
+    /// False (legacy) allows sellback after reveal.
+    pub sellback_locked_after_reveal: bool,
+    /// Temporary halt on buybacks to protect treasury liquidity; opens and claims continue.
+    pub sellback_paused: bool,
+    /// MOCHI minted once per user per completed `TemplateSet` (raw units; 0 disables).
+    pub set_completion_reward: u64,
+    /// Backend signer gating opens: each open must be preceded by an ed25519 verify of
+    /// `open_gate_message`. None (legacy) leaves opens ungated.
+    pub open_gate_authority: Option<Pubkey>,
+    /// Refund share (bps) of the unclaimed part of a partially batch-claimed V1 session at
+    /// expiry. Zero (legacy) frees the unclaimed cards without a refund.
+    pub partial_claim_refund_bps: u16,
+    /// Marketplace maker-taker split: the seller pays `maker_fee_bps` out of the price and the
+    /// buyer pays `taker_fee_bps` on top. Both zero (legacy) uses `marketplace_fee_bps`.
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+    /// Most templates a pack manifest may cover; 0 (legacy) means `MAX_MANIFEST_LEN`.
+    pub max_manifest_len: u16,
+    /// Marketplace: verified Core collections whose royalties fills enforce, with the royalty
+    /// bps and payee per slot. Default (zero) slots are empty.
+    pub royalty_collections: [Pubkey; MAX_ROYALTY_COLLECTIONS],
+    pub royalty_bps: [u16; MAX_ROYALTY_COLLECTIONS],
+    pub royalty_recipients: [Pubkey; MAX_ROYALTY_COLLECTIONS],
+    /// Open reward sizing (see `set_reward_mode`); Flat (legacy) uses `effective_reward`.
+    pub reward_mode: RewardMode,
+    pub reward_rate_bps: u16,
+    pub reward_mochi_per_sol: u64,
+    pub reward_mochi_per_usdc: u64,
+    /// Cap on any single open reward, membership boost included (0 = uncapped).
+    pub max_reward_per_pack: u64,
+    /// Rare-less V2 opens after which the next must reserve a Rare+ (0 = no pity).
+    pub pity_threshold: u16,
+}
+impl VaultState {
+    pub const SIZE: usize = 32 // admin
+        + 32 // vault_authority
+        + 8 // pack_price_sol
+        + 8 // pack_price_usdc
+        + 2 // buyback_bps
+        + 8 // claim_window_seconds
+        + 2 // marketplace_fee_bps
+        + 1 + 32 // core_collection Option
+        + 1 + 32 // usdc_mint Option
+        + 1 + 32 // mochi_mint Option
+        + 8 // reward_per_pack
+        + 1 // vault_authority_bump
+        + 7 // padding
+        + 8 // max_window_override
+        + 32 // treasury
+        + 2 // no_buyback_rarities
+        + 32 // odds_commitment
+        + 1 // accepted_currencies
+        + 8 // sellback_cooldown_seconds
+        + 8 // session_nonce
+        + 1 // fee_split_enabled
+        + 8 // max_pack_price_sol
+        + 8 // max_pack_price_usdc
+        + 1 // max_rares_per_pack
+        + 8 // vault_id
+        + 8 // burn_reward_per_card
+        + 8 // escrowed_lamports
+        + 1 // locked
+        + 8 // claim_fee_lamports
+        + 8 // total_sales
+        + 8 // total_volume_lamports
+        + 8 // total_fees_collected
+        + 1 // round_fees_up
+        + 32 * MAX_CURRENCY_MINTS // allowed_currency_mints
+        + 8 // combo_price_usdc
+        + 8 // odds_locked_until
+        + 8 // max_session_seconds
+        + 4 // max_per_template
+        + 32 // airdrop_root
+        + 32 * MAX_ADMINS // admins
+        + 8 // reward_start
+        + 2 // reward_decay_bps_per_epoch
+        + 8 // epoch_seconds
+        + 8 // reward_schedule_start
+        + 1 + 32 // cosigner Option
+        + 8 // cosign_threshold_lamports
+        + 8 // layaway_window_seconds
+        + 2 // layaway_min_deposit_bps
+        + 8 // pack_price_mochi
+        + 1 // rarity_inventory_enabled
+        + 8 // min_listing_price_lamports
+        + 8 // min_listing_price_token
+        + 1 + 32 // membership_collection Option
+        + 2 // membership_reward_multiplier_bps
+        + 4 // buyback_target_count
+        + 2 // buyback_step_bps
+        + 2 // min_buyback_bps
+        + 2 // max_buyback_bps
+        + 2 // sellback_fee_bps
+        + 4 // pending_token_sessions
+        + 8 // min_authority_balance
+        + 4 // max_active_sessions
+        + 4 // active_session_count
+        + 8 // force_close_delay_seconds
+        + 1 // emergency_mode
+        + 8 // max_total_cards
+        + 8 // total_cards_deposited
+        + 1 // sellback_locked_after_reveal
+        + 1 // sellback_paused
+        + 8 // set_completion_reward
+        + 1 + 32 // open_gate_authority
+        + 2 // partial_claim_refund_bps
+        + 2 // maker_fee_bps
+        + 2 // taker_fee_bps
+        + 2 // max_manifest_len
+        + 32 * MAX_ROYALTY_COLLECTIONS // royalty_collections
+        + 2 * MAX_ROYALTY_COLLECTIONS // royalty_bps
+        + 32 * MAX_ROYALTY_COLLECTIONS // royalty_recipients
+        + 1 // reward_mode enum
+        + 2 // reward_rate_bps
+        + 8 // reward_mochi_per_sol
+        + 8 // reward_mochi_per_usdc
+        + 8 // max_reward_per_pack
+        + 2; // pity_threshold
+
+    pub fn vault_seed(&self) -> Vec<u8> {
+        gacha_vault_seed(self.vault_id)
+    }
+
+    pub fn record_sale(&mut self, quote: &FillQuote) -> Result<()> {
+        self.total_sales = self
+            .total_sales
+            .checked_add(1)
+            .ok_or(MochiError::MathOverflow)?;
+        self.total_volume_lamports = self
+            .total_volume_lamports
+            .checked_add(quote.buyer_pays)
+            .ok_or(MochiError::MathOverflow)?;
+        self.total_fees_collected = self
+            .total_fees_collected
+            .checked_add(quote.fee)
+            .ok_or(MochiError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Per-pack MOCHI reward at `now`: `reward_start * ((10000 - decay) / 10000)^epochs` since
+    /// the schedule was set, or the flat `reward_per_pack` when no schedule is configured.
+    pub fn effective_reward(&self, now: i64) -> Result<u64> {
+        if self.reward_start == 0 || self.epoch_seconds <= 0 {
+            return Ok(self.reward_per_pack);
+        }
+        const ONE: u128 = 1_000_000_000_000;
+        let mut epochs =
+            (now.saturating_sub(self.reward_schedule_start).max(0) / self.epoch_seconds) as u64;
+        let mut base =
+            (10_000u128 - self.reward_decay_bps_per_epoch.min(10_000) as u128) * (ONE / 10_000);
+        let mut factor = ONE;
+        // Square-and-multiply keeps this O(log epochs) with every product below ONE^2.
+        while epochs > 0 && factor > 0 {
+            if epochs & 1 == 1 {
+                factor = factor * base / ONE;
+            }
+            base = base * base / ONE;
+            epochs >>= 1;
+        }
+        let reward = (self.reward_start as u128)
+            .checked_mul(factor)
+            .ok_or(MochiError::MathOverflow)?
+            / ONE;
+        Ok(reward as u64)
+    }
+
+    /// `RewardMode::Proportional` reward for a pack paid `paid_amount` (and `paid_sol_amount`
+    /// for the SOL leg of `Currency::Both`).
+    pub fn proportional_reward(
+        &self,
+        currency: &Currency,
+        paid_amount: u64,
+        paid_sol_amount: u64,
+    ) -> Result<u64> {
+        let in_mochi = |amount: u64, rate: u64, unit: u128| {
+            (amount as u128)
+                .checked_mul(rate as u128)
+                .map(|v| v / unit)
+                .ok_or(MochiError::MathOverflow)
+        };
+        let value = match currency {
+            Currency::Sol => in_mochi(paid_amount, self.reward_mochi_per_sol, SOL_UNIT)?,
+            Currency::Token => in_mochi(paid_amount, self.reward_mochi_per_usdc, USDC_UNIT)?,
+            Currency::Both => in_mochi(paid_sol_amount, self.reward_mochi_per_sol, SOL_UNIT)?
+                .checked_add(in_mochi(
+                    paid_amount,
+                    self.reward_mochi_per_usdc,
+                    USDC_UNIT,
+                )?)
+                .ok_or(MochiError::MathOverflow)?,
+            Currency::Mochi => paid_amount as u128,
+        };
+        let reward = value
+            .checked_mul(self.reward_rate_bps as u128)
+            .ok_or(MochiError::MathOverflow)?
+            / 10_000;
+        u64::try_from(reward).map_err(|_| error!(MochiError::MathOverflow))
+    }
+
+    pub fn capped_reward(&self, reward: u64) -> u64 {
+        match self.max_reward_per_pack {
+            0 => reward,
+            max => reward.min(max),
+        }
+    }
+
+    /// Counts one new card against `max_total_cards`.
+    pub fn count_deposit(&mut self) -> Result<()> {
+        require!(
+            self.max_total_cards == 0 || self.total_cards_deposited < self.max_total_cards,
+            MochiError::GlobalCapReached
+        );
+        self.total_cards_deposited = self
+            .total_cards_deposited
+            .checked_add(1)
+            .ok_or(MochiError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Frees cap room for burned cards; saturates for cards deposited before the counter.
+    pub fn retire_cards(&mut self, count: u64) {
+        self.total_cards_deposited = self.total_cards_deposited.saturating_sub(count);
+    }
+
+    /// Hands out the next pack session nonce.
+    pub fn next_session_nonce(&mut self) -> Result<u64> {
+        self.session_nonce = self
+            .session_nonce
+            .checked_add(1)
+            .ok_or(MochiError::MathOverflow)?;
+        Ok(self.session_nonce)
+    }
+
+    /// Counts a session entering PendingDecision, enforcing `max_active_sessions`.
+    /// `replaced` is the currency of an expired PendingDecision session being overwritten.
+    pub fn open_session(&mut self, replaced: Option<&Currency>, currency: &Currency) -> Result<()> {
+        if let Some(previous) = replaced {
+            self.settle_session(previous);
+        }
+        require!(
+            self.max_active_sessions == 0 || self.active_session_count < self.max_active_sessions,
+            MochiError::TooManySessions
+        );
+        self.active_session_count = self.active_session_count.saturating_add(1);
+        if matches!(currency, Currency::Token | Currency::Both) {
+            self.pending_token_sessions = self.pending_token_sessions.saturating_add(1);
+        }
+        Ok(())
+    }
+
+    /// Uncounts a session leaving PendingDecision; saturates for pre-counter sessions.
+    pub fn settle_session(&mut self, currency: &Currency) {
+        self.active_session_count = self.active_session_count.saturating_sub(1);
+        if matches!(currency, Currency::Token | Currency::Both) {
+            self.pending_token_sessions = self.pending_token_sessions.saturating_sub(1);
+        }
+    }
+
+    /// The primary `admin` or any non-empty `admins` slot.
+    pub fn is_admin(&self, key: &Pubkey) -> bool {
+        *key == self.admin || (*key != Pubkey::default() && self.admins.contains(key))
+    }
+
+    pub fn capped_window(&self, window_seconds: i64) -> i64 {
+        match self.max_session_seconds {
+            0 => window_seconds,
+            max => window_seconds.min(max),
+        }
+    }
+
+    /// Royalty bps and payee when `collection` is in the verified set.
+    pub fn royalty_terms(&self, collection: Option<Pubkey>) -> Option<(u16, Pubkey)> {
+        let collection = collection.filter(|key| *key != Pubkey::default())?;
+        self.royalty_collections
+            .iter()
+            .position(|key| *key == collection)
+            .map(|i| (self.royalty_bps[i], self.royalty_recipients[i]))
+    }
+
+    pub fn manifest_ceiling(&self) -> usize {
+        match self.max_manifest_len {
+            0 => MAX_MANIFEST_LEN,
+            n => (n as usize).min(MAX_MANIFEST_LEN),
+        }
+    }
+
+    pub fn rare_ceiling(&self) -> usize {
+        match self.max_rares_per_pack {
+            0 => MAX_RARE_CARDS,
+            n => (n as usize).min(MAX_RARE_CARDS),
+        }
+    }
+
+    pub fn check_pack_prices(&self) -> Result<()> {
+        require!(
+            self.max_pack_price_sol == 0 || self.pack_price_sol <= self.max_pack_price_sol,
+            MochiError::InvalidPrice
+        );
+        require!(
+            self.max_pack_price_usdc == 0 || self.pack_price_usdc <= self.max_pack_price_usdc,
+            MochiError::InvalidPrice
+        );
+        Ok(())
+    }
+
+    /// Listings may be priced in SOL (`None`) or an allowlisted mint; an empty list means SOL only.
+    pub fn accepts_listing_currency(&self, currency_mint: &Option<Pubkey>) -> bool {
+        match currency_mint {
+            None => true,
+            Some(mint) => *mint != Pubkey::default() && self.allowed_currency_mints.contains(mint),
+        }
+    }
+
+    /// Minimum listing price for a currency (SOL when `None`); zero means no floor.
+    pub fn listing_floor(&self, currency_mint: &Option<Pubkey>) -> u64 {
+        match currency_mint {
+            None => self.min_listing_price_lamports,
+            Some(_) => self.min_listing_price_token,
+        }
+    }
+
+    /// `price` clears the floor for its currency; a zero floor accepts any price.
+    pub fn meets_listing_floor(&self, price: u64, currency_mint: &Option<Pubkey>) -> bool {
+        price >= self.listing_floor(currency_mint)
+    }
+
+    /// Zero (the migrated default) keeps the legacy accept-everything behaviour.
+    pub fn accepts_currency(&self, currency: &Currency) -> bool {
+        self.accepted_currencies == 0
+            || self.accepted_currencies & (1u8 << (currency.clone() as u8)) != 0
+    }
+
+    /// What an open cost in lamports, for spend limits. Token legs are valued at the vault's
+    /// pack price ratio (`pack_price_sol` per `pack_price_usdc` or `pack_price_mochi`). A
+    /// leg the vault can't value that way saturates, so a limited user can't open with it.
+    pub fn spend_lamports(
+        &self,
+        currency: &Currency,
+        paid_amount: u64,
+        paid_sol_amount: u64,
+    ) -> u64 {
+        let to_lamports = |amount: u64, token_price: u64| -> u64 {
+            if amount == 0 {
+                return 0;
+            }
+            if self.pack_price_sol == 0 || token_price == 0 {
+                return u64::MAX;
+            }
+            let lamports = amount as u128 * self.pack_price_sol as u128 / token_price as u128;
+            u64::try_from(lamports).unwrap_or(u64::MAX)
+        };
+        match currency {
+            Currency::Sol => paid_amount,
+            Currency::Token => to_lamports(paid_amount, self.pack_price_usdc),
+            Currency::Both => {
+                paid_sol_amount.saturating_add(to_lamports(paid_amount, self.pack_price_usdc))
+            }
+            Currency::Mochi => to_lamports(paid_amount, self.pack_price_mochi),
+        }
+    }
+}
+
+/// Audit trail for admin interventions on a user's pack session.
+/// `action`: 0 = force close, 1 = reset, 2 = force expire.
+#[event]
+pub struct AdminSessionAction {
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub action: u8,
+    pub freed_cards: u8,
+}
+
+#[event]
+pub struct CardsFused {
+    pub user: Pubkey,
+    pub recipe_id: u32,
+    pub output_asset: Pubkey,
+}
+
+#[event]
+pub struct SetBonusClaimed {
+    pub user: Pubkey,
+    pub set_id: u32,
+    pub amount: u64,
+}
+
+/// `rarity_counts` tallies the cards the open reserved on chain, indexed by `Rarity`. V2 and
+/// layaway sessions only hold their rares, so their commons are not counted.
+#[event]
+pub struct PackOpened {
+    pub vault_state: Pubkey,
+    pub user: Pubkey,
+    pub pack_session: Pubkey,
+    pub paid_amount: u64,
+    pub rarity_counts: [u8; RARITY_COUNT],
+}
+
+#[event]
+pub struct PartialClaimRefunded {
+    pub user: Pubkey,
+    pub claimed_cards: u8,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CountersRecomputed {
+    pub vault_state: Pubkey,
+    pub records: u32,
+    pub sessions: u32,
+    pub active_session_count: u32,
+    pub pending_token_sessions: u32,
+    pub total_cards_deposited: u64,
+}
+
+#[event]
+pub struct ForceCloseRequested {
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub executable_at: i64,
+}
+
+#[event]
+pub struct MochiBurned {
+    pub admin: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct LowTreasuryWarning {
+    pub balance: u64,
+    pub threshold: u64,
+}
+
+#[event]
+pub struct PaymentMintRotated {
+    pub admin: Pubkey,
+    pub old_mint: Option<Pubkey>,
+    pub new_mint: Pubkey,
+    pub swept: u64,
+}
+
+#[event]
+pub struct CardsSwapped {
+    pub user_a: Pubkey,
+    pub user_b: Pubkey,
+    pub core_asset_a: Pubkey,
+    pub core_asset_b: Pubkey,
+    pub sweetener_lamports: u64,
+    pub sweetener_from_b: bool,
+}
+
+#[event]
+pub struct ListingReassigned {
+    pub admin: Pubkey,
+    pub listing: Pubkey,
+    pub core_asset: Pubkey,
+    pub old_seller: Pubkey,
+    pub new_seller: Pubkey,
+}
+
+#[event]
+pub struct AdminSessionExtended {
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub previous_expires_at: i64,
+    pub new_expires_at: i64,
+}
+
+#[event]
+pub struct AssetRebound {
+    pub vault_state: Pubkey,
+    pub old_core_asset: Pubkey,
+    pub new_core_asset: Pubkey,
+    pub template_id: u32,
+}
+
+#[event]
+pub struct BurnRewarded {
+    pub user: Pubkey,
+    pub core_asset: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct RewardMinted {
+    pub user: Pubkey,
+    pub ata: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AuthorityLamportsRecovered {
+    pub admin: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AirdropClaimed {
+    pub claimer: Pubkey,
+    pub core_asset: Pubkey,
+    pub template_id: u32,
+    pub leaf_index: u32,
+}
+
+#[event]
+pub struct AirdropCard {
+    pub admin: Pubkey,
+    pub recipient: Pubkey,
+    pub core_asset: Pubkey,
+    pub template_id: u32,
+}
+
+#[event]
+pub struct RecordReconciled {
+    pub listing: Pubkey,
+    pub card_record: Pubkey,
+    pub core_asset: Pubkey,
+    pub listing_status: ListingStatus,
+    pub previous_status: CardStatus,
+    pub previous_owner: Pubkey,
+    pub status: CardStatus,
+    pub owner: Pubkey,
+}
+
+#[event]
+pub struct TreasuryChanged {
+    pub vault_state: Pubkey,
+    pub old_treasury: Pubkey,
+    pub new_treasury: Pubkey,
+}
+
+/// Active listings of one seller, so storefronts can load a single account instead of
+/// scanning every listing. Capped at MAX_SELLER_LISTINGS; list_card rejects beyond that.
+#[account]
+pub struct SellerListingIndex {
+    pub vault_state: Pubkey,
+    pub seller: Pubkey,
+    pub listings: Vec<Pubkey>,
+    pub bump: u8,
+}
+impl SellerListingIndex {
+    pub const SIZE: usize = 32 + 32 + 4 + 32 * MAX_SELLER_LISTINGS + 1;
+
+    pub fn insert(&mut self, listing: Pubkey) -> Result<()> {
+        if self.listings.contains(&listing) {
+            return Ok(());
+        }
+        require!(
+            self.listings.len() < MAX_SELLER_LISTINGS,
+            MochiError::SellerIndexFull
+        );
+        self.listings.push(listing);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, listing: &Pubkey) {
+        self.listings.retain(|key| key != listing);
+    }
+}
+
+/// Buyer funds pre-committed to one listing; the lamports themselves sit in the
+/// marketplace vault authority and are counted in `VaultState.escrowed_lamports`.
+#[account]
+pub struct FillCommit {
+    pub listing: Pubkey,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+impl FillCommit {
+    pub const SIZE: usize = 32 + 32 + 8 + 8 + 1;
+}
+
+/// Where a marketplace card came from, written by `flip_pack_v2` so buyers can see it was
+/// listed straight out of a pack. Keyed by (market_vault_state, core_asset).
+#[account]
+pub struct CardProvenance {
+    pub core_asset: Pubkey,
+    pub source_vault: Pubkey,
+    pub pack_session: Pubkey,
+    pub opened_by: Pubkey,
+    pub template_id: u32,
+    pub rarity: Rarity,
+    pub recorded_at: i64,
+}
+impl CardProvenance {
+    pub const SIZE: usize = 32 + 32 + 32 + 32 + 4 + 1 + 8;
+}
+
+/// Claimed-leaf bitmap for one airdrop root (bit `i` set = leaf `i` claimed).
+#[account]
+pub struct AirdropBitmap {
+    pub vault_state: Pubkey,
+    pub root: [u8; 32],
+    pub claimed: Vec<u8>,
+    pub bump: u8,
+}
+impl AirdropBitmap {
+    pub const SIZE: usize = 32 + 32 + 4 + MAX_AIRDROP_LEAVES / 8 + 1;
+}
+
+/// Bounded-use pack discount for one wallet, keyed by the sha256 of its code and redeemer.
+#[account]
+pub struct DiscountCode {
+    pub vault_state: Pubkey,
+    pub code_hash: [u8; 32],
+    pub discount_bps: u16,
+    pub max_uses: u32,
+    pub uses: u32,
+    pub bump: u8,
+}
+impl DiscountCode {
+    pub const SIZE: usize = 32 + 32 + 2 + 4 + 4 + 1;
+}
+
+/// Per-template mint counter backing `max_per_template`.
+#[account]
+pub struct TemplateStats {
+    pub vault_state: Pubkey,
+    pub template_id: u32,
+    pub deposited_count: u32,
+    pub bump: u8,
+}
+impl TemplateStats {
+    pub const SIZE: usize = 32 + 4 + 4 + 1;
+}
+
+/// Marker for a card held by `reserve_for`; closed on claim or unreserve.
+#[account]
+pub struct CardReservation {
+    pub card_record: Pubkey,
+    pub buyer: Pubkey,
+    pub created_at: i64,
+    pub bump: u8,
+}
+impl CardReservation {
+    pub const SIZE: usize = 32 + 32 + 8 + 1;
+}
+
+/// Pending two-phase force close of one user's V2 session.
+#[account]
+pub struct ForceCloseRequest {
+    pub vault_state: Pubkey,
+    pub user: Pubkey,
+    pub requested_at: i64,
+    pub bump: u8,
+    /// `created_at` of the session the request was made against.
+    pub session_created_at: i64,
+}
+impl ForceCloseRequest {
+    pub const SIZE: usize = 32 + 32 + 8 + 1 + 8;
+}
+
+/// Collection set: holding one card of each template earns `set_completion_reward`.
+#[account]
+pub struct TemplateSet {
+    pub vault_state: Pubkey,
+    pub set_id: u32,
+    pub template_ids: Vec<u32>,
+    pub bump: u8,
+}
+impl TemplateSet {
+    pub const SIZE: usize = 32 + 4 + 4 + (4 * MAX_SET_TEMPLATES) + 1;
+}
+
+/// Per-user responsible-gaming state: a self-imposed cap on SOL spent on packs.
+#[account]
+pub struct UserStats {
+    pub vault_state: Pubkey,
+    pub user: Pubkey,
+    /// Lamports the user may spend on packs per window (token opens converted, see
+    /// `VaultState::spend_lamports`); 0 means no limit.
+    pub spend_limit_lamports: u64,
+    pub period_seconds: i64,
+    /// Start of the current window and the lamports spent in it.
+    pub period_start: i64,
+    pub period_spent: u64,
+    /// Looser limit staged by `set_spend_limit`; applies once `pending_effective_at` passes
+    /// (0 = nothing staged).
+    pub pending_limit_lamports: u64,
+    pub pending_period_seconds: i64,
+    pub pending_effective_at: i64,
+    pub bump: u8,
+    /// V2 opens in a row without a Rare+, for the vault's pity guarantee.
+    pub packs_since_rare: u32,
+}
+impl UserStats {
+    pub const SIZE: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 4;
+
+    pub fn apply_pending(&mut self, now: i64) {
+        if self.pending_effective_at != 0 && now >= self.pending_effective_at {
+            self.spend_limit_lamports = self.pending_limit_lamports;
+            self.period_seconds = self.pending_period_seconds;
+            self.pending_effective_at = 0;
+        }
+    }
+
+    /// Adds `lamports` to the current fixed window. Once `period_seconds` have passed since
+    /// `period_start`, the window restarts at `now` with nothing spent.
+    pub fn record_spend(&mut self, lamports: u64, now: i64) -> Result<()> {
+        self.apply_pending(now);
+        if self.spend_limit_lamports == 0 {
+            return Ok(());
+        }
+        if now >= self.period_start.saturating_add(self.period_seconds) {
+            self.period_start = now;
+            self.period_spent = 0;
+        }
+        let spent = self
+            .period_spent
+            .checked_add(lamports)
+            .ok_or(MochiError::MathOverflow)?;
+        require!(
+            spent <= self.spend_limit_lamports,
+            MochiError::SpendLimitExceeded
+        );
+        self.period_spent = spent;
+        Ok(())
+    }
+}
+
+/// Fusion rule: `input_count` cards of `input_rarity` become one `output_rarity` card.
+#[account]
+pub struct FusionRecipe {
+    pub vault_state: Pubkey,
+    pub recipe_id: u32,
+    pub input_rarity: Rarity,
+    pub input_count: u8,
+    /// Inputs must all share one template, not just the rarity.
+    pub same_template: bool,
+    pub output_rarity: Rarity,
+    pub bump: u8,
+}
+impl FusionRecipe {
+    pub const SIZE: usize = 32 + 4 + 1 + 1 + 1 + 1 + 1;
+}
+
+/// Marks a user's set bonus as paid; its existence blocks repeat claims.
+#[account]
+pub struct SetBonusClaim {
+    pub template_set: Pubkey,
+    pub user: Pubkey,
+    pub claimed_at: i64,
+    pub bump: u8,
+}
+impl SetBonusClaim {
+    pub const SIZE: usize = 32 + 32 + 8 + 1;
+}
+
+/// Available cards per `Rarity` (indexed by discriminant), so storefronts can read stock
+/// levels without scanning every CardRecord.
+#[account]
+pub struct RarityInventory {
+    pub vault_state: Pubkey,
+    pub available_count: [u32; RARITY_COUNT],
+    pub bump: u8,
+}
+impl RarityInventory {
+    pub const SIZE: usize = 32 + 4 * RARITY_COUNT + 1;
+
+    pub fn add(&mut self, rarity: &Rarity) -> Result<()> {
+        let slot = &mut self.available_count[rarity.clone() as usize];
+        *slot = slot.checked_add(1).ok_or(MochiError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Saturates so a stale seed count never blocks an open.
+    pub fn remove(&mut self, rarity: &Rarity) {
+        let slot = &mut self.available_count[rarity.clone() as usize];
+        *slot = slot.saturating_sub(1);
+    }
+
+    /// Available Rare+ cards (`Rarity::Rare` through `Rarity::MegaHyperRare`).
+    pub fn rare_stock(&self) -> u64 {
+        self.available_count[Rarity::Rare as usize..=Rarity::MegaHyperRare as usize]
+            .iter()
+            .map(|&count| count as u64)
+            .sum()
+    }
+}
+
+#[account]
+pub struct FeeSplit {
+    pub vault_state: Pubkey,
+    pub recipients: Vec<FeeRecipient>,
+    pub bump: u8,
+}
+impl FeeSplit {
+    pub const SIZE: usize = 32 + 4 + (32 + 2) * MAX_FEE_RECIPIENTS + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct FeeRecipient {
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+#[account]
+pub struct CardRecord {
+    pub vault_state: Pubkey,
+    pub core_asset: Pubkey,
+    pub template_id: u32,
+    pub rarity: Rarity,
+    pub status: CardStatus,
+    pub owner: Pubkey,
+}
+impl CardRecord {
+    pub const SIZE: usize = 32 + 32 + 4 + 1 + 1 + 32;
+}
+
+#[account]
+pub struct PackSessionV2 {
+    pub user: Pubkey,
+    pub currency: Currency,
+    pub paid_amount: u64,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub rare_card_keys: Vec<Pubkey>,
+    pub rare_templates: Vec<u32>,
+    pub state: PackState,
+    pub client_seed_hash: [u8; 32],
+    pub total_slots: u8,
+    pub bump: u8,
+    /// Set once this session's open reward has been paid out.
+    pub reward_minted: bool,
+    /// Commitment to the full pack contents (see `manifest_hash`).
+    pub manifest_hash: [u8; 32],
+    /// Gift recipient of the cards and open reward; default means the purchaser (`user`).
+    /// Sellback refunds always go to `user`.
+    pub recipient: Pubkey,
+    /// SOL leg of a `Currency::Both` session (`paid_amount` holds the USDC leg); zero otherwise.
+    pub paid_sol_amount: u64,
+    /// Layaway target: rares stay locked until `paid_amount` reaches it. Zero for paid-up opens.
+    pub full_price: u64,
+    /// Entropy behind an `open_pack_random` draw and a commitment to the candidate pool it
+    /// drew from (see `verify_pack_fairness`); both zero when the backend chose the rares.
+    pub entropy: [u8; 32],
+    pub candidate_pool_hash: [u8; 32],
+    /// Drawn from VaultState.session_nonce at open; claims, flips and sellbacks must echo it
+    /// back. Zero for sessions grown by `migrate_pack_session_v2`.
+    pub nonce: u64,
+    /// Commit-to-keep: set by `reveal_pack_v2` while the vault refuses sellback after reveal.
+    pub sellback_locked: bool,
+}
+impl PackSessionV2 {
+    pub const SIZE: usize = 32 // user
+        + 1 // currency enum
+        + 8 // paid_amount
+        + 8 // created_at
+        + 8 // expires_at
+        + 4 + (32 * MAX_RARE_CARDS) // rare_card_keys vec
+        + 4 + (4 * MAX_RARE_CARDS) // rare_templates vec
+        + 1 // state enum
+        + 32 // client_seed_hash
+        + 1 // total_slots
+        + 1 // bump
+        + 1 // reward_minted
+        + 32 // manifest_hash
+        + 32 // recipient
+        + 8 // paid_sol_amount
+        + 8 // full_price
+        + 32 // entropy
+        + 32 // candidate_pool_hash
+        + 8 // nonce
+        + 1; // sellback_locked
+
+    pub fn is_paid(&self) -> bool {
+        self.paid_amount >= self.full_price
+    }
+
+    /// Clears a reused PDA (after sellback, claim or expiry) before a new open writes it, so
+    /// nothing from the previous pack can survive a shorter or differently shaped reopen.
+    pub fn reset_for_open(&mut self) {
+        self.rare_card_keys.clear();
+        self.rare_templates.clear();
+        self.reward_minted = false;
+        self.paid_amount = 0;
+        self.paid_sol_amount = 0;
+        self.full_price = 0;
+        self.recipient = Pubkey::default();
+        self.entropy = [0u8; 32];
+        self.candidate_pool_hash = [0u8; 32];
+        self.sellback_locked = false;
+    }
+
+    pub fn beneficiary(&self) -> Pubkey {
+        if self.recipient == Pubkey::default() {
+            self.user
+        } else {
+            self.recipient
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.rare_card_keys.len() == self.rare_templates.len(),
+            MochiError::SessionTemplateMismatch
+        );
+        require!(
+            self.rare_card_keys.len() <= MAX_RARE_CARDS,
+            MochiError::InvalidCardCount
+        );
+        require!(
+            self.rare_card_keys.iter().all(|k| *k != Pubkey::default()),
+            MochiError::SessionDefaultKey
+        );
+        require!(
+            self.rare_card_keys
+                .iter()
+                .enumerate()
+                .all(|(i, k)| !self.rare_card_keys[..i].contains(k)),
+            MochiError::DuplicateCard
+        );
+        require!(
+            self.total_slots as usize == PACK_CARD_COUNT,
+            MochiError::SessionSlotMismatch
+        );
+        require!(
+            self.expires_at > self.created_at,
+            MochiError::SessionWindowInvalid
+        );
+        match self.state {
+            PackState::Uninitialized => require!(
+                self.rare_card_keys.is_empty(),
+                MochiError::InvalidSessionState
+            ),
+            _ => require!(self.created_at > 0, MochiError::InvalidSessionState),
+        }
+        Ok(())
+    }
 }
 
-#[derive(Accounts)]
-pub struct ResolvePackV2<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
-    pub pack_session: Account<'info, PackSessionV2>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    #[account(mut)]
-    pub vault_treasury: SystemAccount<'info>,
-    pub token_program: Program<'info, Token>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-    /// CHECK: mpl-core program
-    pub mpl_core_program: UncheckedAccount<'info>,
+#[account]
+pub struct PackSession {
+    pub user: Pubkey,
+    pub currency: Currency,
+    pub paid_amount: u64,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub card_record_keys: [Pubkey; PACK_CARD_COUNT],
+    pub state: PackState,
+    pub client_seed_hash: [u8; 32],
+    pub rarity_prices: Vec<u64>,
+    /// Copied from VaultState.session_nonce at open; claims must echo it back.
+    pub nonce: u64,
+    /// Commitment to the full pack contents (see `manifest_hash`).
+    pub manifest_hash: [u8; 32],
+}
+impl PackSession {
+    pub const SIZE: usize =
+        32 + 1 + 8 + 8 + 8 + (32 * PACK_CARD_COUNT) + 1 + 32 + 4 + 8 * PACK_CARD_COUNT + 8 + 32;
 }
 
-#[derive(Accounts)]
-pub struct AdminForceCloseV2<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    /// CHECK: target user wallet (for PDA derivation)
-    pub user: UncheckedAccount<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [b"pack_session_v2", vault_state.key().as_ref(), user.key().as_ref()], bump)]
-    pub pack_session: Account<'info, PackSessionV2>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+#[account]
+pub struct Listing {
+    pub vault_state: Pubkey,
+    pub seller: Pubkey,
+    pub core_asset: Pubkey,
+    pub price_lamports: u64,
+    pub currency_mint: Option<Pubkey>,
+    pub status: ListingStatus,
+    /// Admin-set promotional fee for this listing, used instead of `marketplace_fee_bps`.
+    pub fee_bps_override: Option<u16>,
+}
+impl Listing {
+    pub const SIZE: usize = 32 + 32 + 32 + 8 + 1 + 32 + 1 // currency_mint option + status
+        + 1 + 2; // fee_bps_override option
+}
+
+/// What fill_listing will move, in lamports; buyer_pays = fee + royalty + seller_receives.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct FillQuote {
+    pub buyer_pays: u64,
+    pub fee: u64,
+    pub royalty: u64,
+    pub seller_receives: u64,
+    /// True when the asset's collection is verified, so `royalty` goes to `royalty_recipient`
+    /// out of the seller's proceeds.
+    pub royalty_enforced: bool,
+    pub royalty_recipient: Pubkey,
+}
+
+/// `initialize_vault` settings, serialized field by field in this order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct InitializeVaultArgs {
+    pub pack_price_sol: u64,
+    pub pack_price_usdc: u64,
+    pub buyback_bps: u16,
+    pub claim_window_seconds: i64,
+    pub marketplace_fee_bps: u16,
+    pub core_collection: Option<Pubkey>,
+    pub usdc_mint: Option<Pubkey>,
+    pub mochi_mint: Option<Pubkey>,
+    pub reward_per_pack: u64,
+    pub treasury: Pubkey,
+    pub vault_id: u64,
+}
+
+/// `migrate_vault_state` settings written into the grown legacy vault.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MigrateVaultStateArgs {
+    pub pack_price_sol: u64,
+    pub pack_price_usdc: u64,
+    pub buyback_bps: u16,
+    pub claim_window_seconds: i64,
+    pub marketplace_fee_bps: u16,
+    pub usdc_mint: Option<Pubkey>,
+    pub mochi_mint: Option<Pubkey>,
+    pub reward_per_pack: u64,
+    pub treasury: Pubkey,
+}
+
+/// One `set_royalty_collections` entry.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct VerifiedCollection {
+    pub collection: Pubkey,
+    pub royalty_bps: u16,
+    pub recipient: Pubkey,
+}
+
+/// Returned by `peek_pack`: the reserved rares in session order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PackPeek {
+    pub rare_templates: Vec<u32>,
+    pub rare_rarities: Vec<Rarity>,
+    pub manifest_hash: [u8; 32],
+    pub expires_at: i64,
+}
+
+/// Returned by `user_pending_summary`; `claimable` is false once the window has passed.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PendingSummary {
+    pub rare_count: u8,
+    pub expires_at: i64,
+    pub state: PackState,
+    pub claimable: bool,
+}
+
+/// Returned by `card_availability`; `expires_at` is 0 unless a pending session holds the card.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CardAvailabilityInfo {
+    pub status: CardStatus,
+    pub expires_at: i64,
+}
+
+/// Returned by `pity_status`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PityStatusInfo {
+    pub current_counter: u32,
+    pub threshold: u16,
+    pub guaranteed_next: bool,
+}
+
+/// Explicit card/asset pairing for the `*_tagged` claim instructions.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CardAssetPair {
+    pub card_record: Pubkey,
+    pub core_asset: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    DoubleRare,
+    UltraRare,
+    IllustrationRare,
+    SpecialIllustrationRare,
+    MegaHyperRare,
+    Energy,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum CardStatus {
+    Available,
+    Reserved,
+    UserOwned,
+    RedeemPending,
+    Burned,
+    Deprecated,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum Currency {
+    Sol,
+    Token,
+    /// `pack_price_sol` in SOL plus `combo_price_usdc` in USDC (open_pack only).
+    Both,
+    /// `pack_price_mochi` in MOCHI (open_pack only).
+    Mochi,
+}
+
+/// How `open_pack` sizes the MOCHI open reward; see `set_reward_mode`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub enum RewardMode {
+    Flat,
+    Proportional,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub enum PackState {
+    Uninitialized,
+    PendingDecision,
+    Accepted,
+    Rejected,
+    Expired,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum ListingStatus {
+    Active,
+    Filled,
+    Cancelled,
+    Burned,
+    Deprecated,
+}
+
+#[error_code]
+pub enum MochiError {
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Invalid price")]
+    InvalidPrice,
+    #[msg("Insufficient funds")]
+    InsufficientFunds,
+    #[msg("Invalid card count")]
+    InvalidCardCount,
+    #[msg("Card not available")]
+    CardNotAvailable,
+    #[msg("Vault mismatch")]
+    VaultMismatch,
+    #[msg("Listing invalid state")]
+    InvalidListingState,
+    #[msg("Session already exists")]
+    SessionExists,
+    #[msg("Invalid session state")]
+    InvalidSessionState,
+    #[msg("Session expired")]
+    SessionExpired,
+    #[msg("Session not expired")]
+    SessionNotExpired,
+    #[msg("Card not reserved")]
+    CardNotReserved,
+    #[msg("Reward disabled")]
+    RewardDisabled,
+    #[msg("Asset mismatch")]
+    AssetMismatch,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Missing token account")]
+    MissingTokenAccount,
+    #[msg("Mint mismatch")]
+    MintMismatch,
+    #[msg("Core CPI error")]
+    CoreCpiError,
+    #[msg("Too many Rare+ cards provided")]
+    TooManyRareCards,
+    #[msg("Card rarity must be Rare or above")]
+    CardTooCommon,
+    #[msg("Card template mismatch")]
+    TemplateMismatch,
+    #[msg("Card key mismatch")]
+    CardKeyMismatch,
+    #[msg("Rarity mismatch")]
+    RarityMismatch,
+    #[msg("Invalid claim window")]
+    InvalidWindow,
+    #[msg("Card rarity is not eligible for buyback")]
+    NotBuybackEligible,
+    #[msg("Odds table does not match commitment")]
+    OddsMismatch,
+    #[msg("Currency not accepted by this vault")]
+    CurrencyNotAccepted,
+    #[msg("Sellback cooldown has not elapsed")]
+    SellbackCooldown,
+    #[msg("Session nonce does not match; transaction targets a previous session")]
+    StaleSession,
+    #[msg("Invalid fee split")]
+    InvalidFeeSplit,
+    #[msg("Pack price bounds already set")]
+    PriceBoundsLocked,
+    #[msg("Treasury still holds program-controlled funds")]
+    TreasuryNotEmpty,
+    #[msg("Pack manifest does not match the session commitment")]
+    ManifestMismatch,
+    #[msg("Seller already has the maximum number of active listings")]
+    SellerIndexFull,
+    #[msg("Vault is locked by an instruction in progress")]
+    VaultLocked,
+    #[msg("Invalid mpl-core program")]
+    InvalidCoreProgram,
+    #[msg("Buyer cannot fill their own listing")]
+    SelfTrade,
+    #[msg("Account does not match its expected PDA")]
+    InvalidPda,
+    #[msg("Odds are locked for the current event")]
+    OddsLocked,
+    #[msg("Deposit cap reached for this template")]
+    TemplateCapReached,
+    #[msg("No airdrop is active")]
+    AirdropInactive,
+    #[msg("Invalid Merkle proof")]
+    InvalidProof,
+    #[msg("Airdrop allocation already claimed")]
+    AirdropClaimed,
+    #[msg("Admin set is full")]
+    AdminSetFull,
+    #[msg("Admin not found")]
+    AdminNotFound,
+    #[msg("Invalid discount code")]
+    InvalidDiscount,
+    #[msg("Discount code has no uses left")]
+    DiscountExhausted,
+    #[msg("Fill requires the marketplace cosigner")]
+    CosignerRequired,
+    #[msg("Layaway is not enabled")]
+    LayawayDisabled,
+    #[msg("Layaway session is not fully paid")]
+    LayawayUnpaid,
+    #[msg("Rarity inventory account required")]
+    MissingRarityInventory,
+    #[msg("Card is still held by a live session or reservation")]
+    CardStillHeld,
+    #[msg("Session rare keys and templates differ in length")]
+    SessionTemplateMismatch,
+    #[msg("Session holds a default rare card key")]
+    SessionDefaultKey,
+    #[msg("Session slot count does not match pack size")]
+    SessionSlotMismatch,
+    #[msg("Session expiry is not after creation")]
+    SessionWindowInvalid,
+    #[msg("Account is not the configured Core collection")]
+    InvalidCollection,
+    #[msg("Card record is not burned or deprecated")]
+    CardNotTerminal,
+    #[msg("Token-paid sessions are still pending")]
+    TokenSessionsPending,
+    #[msg("Vault has reached its active session cap")]
+    TooManySessions,
+    #[msg("Force close has not been requested")]
+    ForceCloseNotRequested,
+    #[msg("Force close delay has not elapsed")]
+    ForceCloseLocked,
+    #[msg("Vault-wide card cap reached")]
+    GlobalCapReached,
+    #[msg("Session rares were not drawn on-chain")]
+    NotRandomDraw,
+    #[msg("Pack outcome does not match its committed draw")]
+    FairnessMismatch,
+    #[msg("Sellback is locked once a pack is revealed")]
+    SellbackLocked,
+    #[msg("Card passed more than once")]
+    DuplicateCard,
+    #[msg("Sellbacks are paused")]
+    SellbackPaused,
+    #[msg("Listed asset is no longer in marketplace custody")]
+    ListingUnbacked,
+    #[msg("Listed asset is still in marketplace custody")]
+    ListingStillBacked,
+    #[msg("Fusion recipe must upgrade to a higher rarity")]
+    InvalidFusionRecipe,
+    #[msg("Open requires a valid gate signature")]
+    OpenGateRequired,
+    #[msg("Open gate signature has expired")]
+    OpenGateExpired,
+    #[msg("Pack purchase would exceed the spending limit")]
+    SpendLimitExceeded,
+    #[msg("Pack manifest exceeds the maximum length")]
+    ManifestTooLong,
+    #[msg("Invalid verified royalty collection entry")]
+    InvalidRoyaltyConfig,
+    #[msg("Royalty recipient missing or does not match the verified collection")]
+    RoyaltyRecipientMismatch,
+    #[msg("Proportional rewards need a reward rate between 1 and 10000 bps")]
+    InvalidRewardConfig,
+    #[msg("Pity guarantee is due: this open must include a Rare+ card")]
+    PityRareRequired,
+    #[msg("Force close request was made for an earlier session")]
+    StaleForceCloseRequest,
+    #[msg("Candidate pool must be every Available Rare+ card in ascending key order")]
+    IncompleteCandidatePool,
 }
 
-#[derive(Accounts)]
-pub struct InitializeVault<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(
-        init,
-        payer = admin,
-        seeds = [GACHA_VAULT_SEED],
-        bump,
-        space = 8 + VaultState::SIZE,
-    )]
-    pub vault_state: Account<'info, VaultState>,
-    /// CHECK: PDA that holds custody/treasury authority (validated by seeds)
-    #[account(
-        seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()],
-        bump,
-    )]
-    pub vault_authority: UncheckedAccount<'info>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-}
+/// Shared body of flip_pack_v2 and list_from_pack; `prices[i] == 0` claims rare i instead.
+fn flip_pack_v2_inner<'info>(
+    ctx: Context<'_, '_, 'info, 'info, FlipPackV2<'info>>,
+    prices: Vec<u64>,
+    expected_nonce: u64,
+) -> Result<()> {
+    lock_vault(&mut ctx.accounts.vault_state)?;
+    let user_info = ctx.accounts.user.to_account_info();
+    charge_claim_fee(
+        &ctx.accounts.vault_state,
+        &user_info,
+        &ctx.accounts.vault_treasury,
+        &ctx.accounts.system_program,
+    )?;
+    let now = Clock::get()?.unix_timestamp;
+    let session = &mut ctx.accounts.pack_session;
+    require!(session.nonce == expected_nonce, MochiError::StaleSession);
+    require!(
+        session.state == PackState::PendingDecision,
+        MochiError::InvalidSessionState
+    );
+    require!(now <= session.expires_at, MochiError::SessionExpired);
+    require!(session.is_paid(), MochiError::LayawayUnpaid);
+    // Gift packs belong to their recipient; the purchaser can't sell them.
+    require_keys_eq!(
+        session.recipient,
+        Pubkey::default(),
+        MochiError::Unauthorized
+    );
+
+    let rare_count = session.rare_card_keys.len();
+    require!(prices.len() == rare_count, MochiError::InvalidCardCount);
+    let (card_accounts, asset_accounts, listing_accounts) =
+        split_rare_accounts(ctx.remaining_accounts, rare_count)?;
+    let listed = prices.iter().filter(|price| **price > 0).count();
+    require!(
+        asset_accounts.len() == rare_count && listing_accounts.len() == listed * 3,
+        MochiError::InvalidCardCount
+    );
+    // Flip listings are SOL-priced and held to the same floor as list_card.
+    for &price in prices.iter().filter(|price| **price > 0) {
+        require!(
+            ctx.accounts
+                .market_vault_state
+                .meets_listing_floor(price, &None),
+            MochiError::InvalidPrice
+        );
+    }
+
+    let user_key = ctx.accounts.user.key();
+    let gacha_key = ctx.accounts.vault_state.key();
+    let market_key = ctx.accounts.market_vault_state.key();
+    let market_authority = ctx.accounts.market_vault_authority.to_account_info();
+    let system_program = ctx.accounts.system_program.to_account_info();
+    let mpl_core_program = ctx.accounts.mpl_core_program.to_account_info();
+    let mut listing_slots = listing_accounts.chunks(3);
+    for i in 0..rare_count {
+        let card_info: &AccountInfo<'info> = &card_accounts[i];
+        require_keys_eq!(
+            card_info.key(),
+            session.rare_card_keys[i],
+            MochiError::CardKeyMismatch
+        );
+        let mut record: Account<CardRecord> = Account::try_from(card_info)?;
+        require!(
+            record.status == CardStatus::Reserved,
+            MochiError::CardNotReserved
+        );
+        require_keys_eq!(record.owner, session.user, MochiError::Unauthorized);
+        let asset_info: &AccountInfo<'info> = &asset_accounts[i];
+        let core_key = asset_info.key();
+        require_keys_eq!(core_key, record.core_asset, MochiError::AssetMismatch);
+        record.status = CardStatus::UserOwned;
+        record.owner = user_key;
+
+        if prices[i] == 0 {
+            transfer_core_asset(
+                asset_info,
+                &ctx.accounts.vault_authority,
+                &user_info,
+                &user_info,
+                &gacha_key,
+                ctx.bumps.vault_authority,
+                GACHA_VAULT_AUTHORITY_SEED,
+                &system_program,
+                &mpl_core_program,
+            )?;
+            persist_card_record(&record, card_info)?;
+            continue;
+        }
+
+        let slot = listing_slots.next().ok_or(MochiError::InvalidCardCount)?;
+        let (market_record_info, listing_info, provenance_info) = (&slot[0], &slot[1], &slot[2]);
+        transfer_core_asset(
+            asset_info,
+            &ctx.accounts.vault_authority,
+            &user_info,
+            &market_authority,
+            &gacha_key,
+            ctx.bumps.vault_authority,
+            GACHA_VAULT_AUTHORITY_SEED,
+            &system_program,
+            &mpl_core_program,
+        )?;
+        freeze_core_asset(
+            asset_info,
+            &CoreCpi {
+                authority: &market_authority,
+                payer: &user_info,
+                vault_state: &market_key,
+                vault_bump: ctx.bumps.market_vault_authority,
+                authority_seed: MARKETPLACE_VAULT_AUTHORITY_SEED,
+                system_program: &system_program,
+                mpl_core_program: &mpl_core_program,
+            },
+        )?;
+
+        init_pda(
+            &user_info,
+            market_record_info,
+            8 + CardRecord::SIZE,
+            &[CARD_RECORD_SEED, market_key.as_ref(), core_key.as_ref()],
+            &system_program,
+            ctx.program_id,
+        )?;
+        let market_record = CardRecord {
+            vault_state: market_key,
+            core_asset: core_key,
+            template_id: record.template_id,
+            rarity: record.rarity.clone(),
+            status: CardStatus::Reserved,
+            owner: market_authority.key(),
+        };
+        persist_card_record(&market_record, market_record_info)?;
+
+        let listing_existed = !init_pda(
+            &user_info,
+            listing_info,
+            8 + Listing::SIZE,
+            &[LISTING_SEED, market_key.as_ref(), core_key.as_ref()],
+            &system_program,
+            ctx.program_id,
+        )?;
+        if listing_existed {
+            let previous = Listing::try_deserialize(&mut &listing_info.data.borrow()[..])?;
+            require!(
+                previous.status != ListingStatus::Active,
+                MochiError::InvalidListingState
+            );
+        }
+        let listing = Listing {
+            vault_state: market_key,
+            seller: user_key,
+            core_asset: core_key,
+            price_lamports: prices[i],
+            currency_mint: None,
+            status: ListingStatus::Active,
+            fee_bps_override: None,
+        };
+        write_account(&listing, listing_info)?;
+
+        init_pda(
+            &user_info,
+            provenance_info,
+            8 + CardProvenance::SIZE,
+            &[PROVENANCE_SEED, market_key.as_ref(), core_key.as_ref()],
+            &system_program,
+            ctx.program_id,
+        )?;
+        let provenance = CardProvenance {
+            core_asset: core_key,
+            source_vault: gacha_key,
+            pack_session: session.key(),
+            opened_by: user_key,
+            template_id: record.template_id,
+            rarity: record.rarity.clone(),
+            recorded_at: now,
+        };
+        write_account(&provenance, provenance_info)?;
+
+        ctx.accounts.seller_index.insert(listing_info.key())?;
+        persist_card_record(&record, card_info)?;
+    }
 
-#[derive(Accounts)]
-pub struct InitializeMarketplaceVault<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(
-        init,
-        payer = admin,
-        seeds = [MARKETPLACE_VAULT_SEED],
-        bump,
-        space = 8 + VaultState::SIZE,
-    )]
-    pub vault_state: Account<'info, VaultState>,
-    /// CHECK: marketplace escrow/vault authority PDA
-    #[account(
-        seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()],
-        bump,
-    )]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
+    let index = &mut ctx.accounts.seller_index;
+    index.vault_state = market_key;
+    index.seller = user_key;
+    index.bump = ctx.bumps.seller_index;
+    ctx.accounts.vault_state.settle_session(&session.currency);
+    session.state = PackState::Accepted;
+    unlock_vault(&mut ctx.accounts.vault_state);
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct DepositCard<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    /// CHECK: Core asset account (Metaplex Core asset), validated off-chain
-    pub core_asset: UncheckedAccount<'info>,
-    #[account(
-        init,
-        payer = admin,
-        seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()],
-        bump,
-        space = 8 + CardRecord::SIZE,
-    )]
-    pub card_record: Account<'info, CardRecord>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-}
+/// Shared body of sellback_pack_v2 and sellback_burn_v2: refunds the buyback, then either
+/// returns the reserved rares to inventory or (`burn`) burns them out of circulation.
+/// remaining_accounts: [rare_card_records...][core_assets... (required when burning)][token accounts...]
+fn sellback_v2_inner<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ResolvePackV2<'info>>,
+    burn: bool,
+    expected_nonce: u64,
+) -> Result<()> {
+    let session = &mut ctx.accounts.pack_session;
+    require!(session.nonce == expected_nonce, MochiError::StaleSession);
+    let vault_state = &ctx.accounts.vault_state;
+    require!(!vault_state.sellback_paused, MochiError::SellbackPaused);
+    require!(!session.sellback_locked, MochiError::SellbackLocked);
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        session.state == PackState::PendingDecision,
+        MochiError::InvalidSessionState
+    );
+    require!(session.is_paid(), MochiError::LayawayUnpaid);
+    require!(now <= session.expires_at, MochiError::SessionExpired);
+    require!(
+        now.saturating_sub(session.created_at) >= vault_state.sellback_cooldown_seconds,
+        MochiError::SellbackCooldown
+    );
 
-#[derive(Accounts)]
-pub struct OpenPackStart<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        init,
-        payer = user,
-        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
-        bump,
-        space = 8 + PackSession::SIZE,
-    )]
-    pub pack_session: Account<'info, PackSession>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    /// Treasury to receive SOL fees
-    #[account(mut)]
-    pub vault_treasury: SystemAccount<'info>,
-    pub token_program: Program<'info, Token>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-    /// CHECK: mpl-core program id (CPI target)
-    pub mpl_core_program: UncheckedAccount<'info>,
-}
+    let mut inventory = tracked_inventory(vault_state, &mut ctx.accounts.rarity_inventory)?;
+    let rare_count = session.rare_card_keys.len();
+    let (card_accounts, asset_accounts, extras) =
+        split_rare_accounts(ctx.remaining_accounts, rare_count)?;
+    if burn {
+        require!(
+            asset_accounts.len() == rare_count,
+            MochiError::InvalidCardCount
+        );
+    }
 
-#[derive(Accounts)]
-pub struct ResolvePack<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()], bump)]
-    pub pack_session: Account<'info, PackSession>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    #[account(mut)]
-    pub vault_treasury: SystemAccount<'info>,
-    pub token_program: Program<'info, Token>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-    /// CHECK: mpl-core program
-    pub mpl_core_program: UncheckedAccount<'info>,
-}
+    let mut rarities = Vec::new();
+    if vault_state.buyback_target_count > 0 && inventory.is_some() {
+        for (acc_info, key) in card_accounts.iter().zip(&session.rare_card_keys) {
+            require_keys_eq!(acc_info.key(), *key, MochiError::CardKeyMismatch);
+            let card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+            rarities.push(card_record.rarity.clone());
+        }
+    }
+    let buyback_bps = dynamic_buyback_bps(vault_state, inventory.as_deref(), &rarities);
+
+    // Both legs of a combined session are refunded at the same buyback rate.
+    let payout = session
+        .paid_amount
+        .checked_mul(buyback_bps as u64)
+        .and_then(|v| v.checked_div(10_000))
+        .ok_or(MochiError::MathOverflow)?;
+    let sol_payout = session
+        .paid_sol_amount
+        .checked_mul(buyback_bps as u64)
+        .and_then(|v| v.checked_div(10_000))
+        .ok_or(MochiError::MathOverflow)?;
+    // Token fees stay in the vault's token account; the SOL fee is paid out to the treasury.
+    let fee = sellback_fee(vault_state, payout)?;
+    let sol_fee = sellback_fee(vault_state, sol_payout)?;
+
+    // Pay refund
+    let vault_key = vault_state.key();
+    let seeds = &[
+        GACHA_VAULT_AUTHORITY_SEED,
+        vault_key.as_ref(),
+        &[ctx.bumps.vault_authority],
+    ];
+    let signer = &[&seeds[..]];
+    let vault_authority = ctx.accounts.vault_authority.to_account_info();
+    let user = ctx.accounts.user.to_account_info();
+    let system_program = ctx.accounts.system_program.to_account_info();
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let vault_treasury = ctx.accounts.vault_treasury.to_account_info();
+    match session.currency {
+        Currency::Sol => {
+            // extras: [fee_split, recipients...] when configured.
+            pay_fee(
+                vault_state,
+                &vault_authority,
+                &vault_treasury,
+                extras,
+                fee,
+                &system_program,
+                signer,
+            )?;
+            refund_pack_sol(
+                &vault_authority,
+                &user,
+                &system_program,
+                signer,
+                payout - fee,
+            )?;
+        }
+        Currency::Token => {
+            refund_pack_tokens(
+                vault_state.usdc_mint,
+                extras,
+                &vault_authority,
+                &token_program,
+                signer,
+                payout - fee,
+            )?;
+        }
+        Currency::Mochi => {
+            // MOCHI was collected into the vault's MOCHI account, so the buyback comes from there.
+            refund_pack_tokens(
+                vault_state.mochi_mint,
+                extras,
+                &vault_authority,
+                &token_program,
+                signer,
+                payout - fee,
+            )?;
+        }
+        Currency::Both => {
+            refund_pack_tokens(
+                vault_state.usdc_mint,
+                extras,
+                &vault_authority,
+                &token_program,
+                signer,
+                payout - fee,
+            )?;
+            // extras: [user_usdc, vault_usdc, fee_split, recipients...].
+            pay_fee(
+                vault_state,
+                &vault_authority,
+                &vault_treasury,
+                extras.get(2..).unwrap_or(&[]),
+                sol_fee,
+                &system_program,
+                signer,
+            )?;
+            refund_pack_sol(
+                &vault_authority,
+                &user,
+                &system_program,
+                signer,
+                sol_payout - sol_fee,
+            )?;
+        }
+    }
 
-#[derive(Accounts)]
-pub struct AdminForceExpire<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    /// CHECK: user wallet (used for PDA derivation only)
-    pub user: UncheckedAccount<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()], bump)]
-    pub pack_session: Account<'info, PackSession>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    #[account(mut)]
-    pub vault_treasury: SystemAccount<'info>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-}
+    let mut burned = 0u64;
+    for (idx, acc_info) in card_accounts.iter().enumerate() {
+        require_keys_eq!(
+            acc_info.key(),
+            session.rare_card_keys[idx],
+            MochiError::CardKeyMismatch
+        );
+        // Sessions written before open rejected duplicate keys may list a rare twice.
+        if session.rare_card_keys[..idx].contains(acc_info.key) {
+            continue;
+        }
+        let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+        require!(
+            card_record.status == CardStatus::Reserved,
+            MochiError::CardNotReserved
+        );
+        require_keys_eq!(
+            card_record.owner,
+            ctx.accounts.user.key(),
+            MochiError::Unauthorized
+        );
+        require!(
+            vault_state.no_buyback_rarities & rarity_bit(&card_record.rarity) == 0,
+            MochiError::NotBuybackEligible
+        );
+        if burn {
+            let asset_info = &asset_accounts[idx];
+            require_keys_eq!(
+                card_record.core_asset,
+                asset_info.key(),
+                MochiError::CardKeyMismatch
+            );
+            burn_core_asset(
+                asset_info,
+                &vault_authority,
+                &user,
+                &vault_key,
+                ctx.bumps.vault_authority,
+                GACHA_VAULT_AUTHORITY_SEED,
+                &system_program,
+                &ctx.accounts.mpl_core_program.to_account_info(),
+            )?;
+            card_record.status = CardStatus::Burned;
+            burned += 1;
+        } else {
+            card_record.status = CardStatus::Available;
+            card_record.owner = ctx.accounts.vault_authority.key();
+            if let Some(inventory) = inventory.as_deref_mut() {
+                inventory.add(&card_record.rarity)?;
+            }
+        }
+        persist_card_record(&card_record, acc_info)?;
+    }
 
-#[derive(Accounts)]
-pub struct AdminResetSession<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    /// CHECK: user wallet (used for PDA derivation only)
-    pub user: UncheckedAccount<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        mut,
-        close = user,
-        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
-        bump
-    )]
-    pub pack_session: Account<'info, PackSession>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+    ctx.accounts.vault_state.retire_cards(burned);
+    ctx.accounts.vault_state.settle_session(&session.currency);
+    session.state = PackState::Rejected;
+    warn_low_authority_balance(&ctx.accounts.vault_state, &ctx.accounts.vault_authority);
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct AdminForceClose<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    /// CHECK: user wallet (used for PDA derivation only)
-    pub user: UncheckedAccount<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        mut,
-        close = admin,
-        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
-        bump
-    )]
-    pub pack_session: Account<'info, PackSession>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+/// Per-rarity tally of the first `count` CardRecords in `accounts`, for PackOpened.
+fn rarity_counts(accounts: &[AccountInfo], count: usize) -> Result<[u8; RARITY_COUNT]> {
+    let mut counts = [0u8; RARITY_COUNT];
+    for info in accounts.iter().take(count) {
+        let record = CardRecord::try_deserialize(&mut &info.data.borrow()[..])?;
+        counts[record.rarity as usize] += 1;
+    }
+    Ok(counts)
 }
 
-#[derive(Accounts)]
-pub struct AdminResetCards<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+/// Charges SOL pack spend against the purchaser's UserStats; a no-op until they create one.
+fn charge_spend_limit<'info>(
+    user_stats: &AccountInfo<'info>,
+    user: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    lamports: u64,
+    now: i64,
+) -> Result<()> {
+    if *user_stats.owner != crate::ID || user_stats.data_is_empty() || lamports == 0 {
+        return Ok(());
+    }
+    let mut stats = load_user_stats(user_stats)?;
+    stats.record_spend(lamports, now)?;
+    store_user_stats(&stats, user_stats, user, system_program)
 }
 
-#[derive(Accounts)]
-pub struct UserResetSession<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        mut,
-        close = user,
-        seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()],
-        bump
-    )]
-    pub pack_session: Account<'info, PackSession>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+/// Applies the vault's pity rule to an open: once the purchaser's rare-less run reaches
+/// `pity_threshold` the open must reserve a Rare+, which resets the run. Creates their
+/// UserStats on first use; a no-op while pity is off.
+fn track_pity<'info>(
+    user_stats: &AccountInfo<'info>,
+    user: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    vault_state: &Account<'info, VaultState>,
+    user_stats_bump: u8,
+    has_rare: bool,
+) -> Result<()> {
+    let threshold = vault_state.pity_threshold;
+    if threshold == 0 {
+        return Ok(());
+    }
+    let mut stats = open_user_stats(
+        user_stats,
+        user,
+        system_program,
+        &vault_state.key(),
+        user_stats_bump,
+    )?;
+    if pity_due(stats.packs_since_rare, threshold) {
+        require!(has_rare, MochiError::PityRareRequired);
+    }
+    stats.packs_since_rare = if has_rare {
+        0
+    } else {
+        stats.packs_since_rare.saturating_add(1)
+    };
+    store_user_stats(&stats, user_stats, user, system_program)
 }
 
-#[derive(Accounts)]
-pub struct FinalizeClaim<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [b"pack_session", vault_state.key().as_ref(), user.key().as_ref()], bump)]
-    pub pack_session: Account<'info, PackSession>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+fn pity_due(packs_since_rare: u32, threshold: u16) -> bool {
+    threshold > 0 && packs_since_rare >= threshold as u32
 }
 
-#[derive(Accounts)]
-pub struct ListCard<'info> {
-    #[account(mut)]
-    pub seller: Signer<'info>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        init_if_needed,
-        payer = seller,
-        space = 8 + CardRecord::SIZE,
-        seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()],
-        bump
-    )]
-    pub card_record: Account<'info, CardRecord>,
-    /// CHECK: Core asset account (Metaplex Core), validated off-chain
-    pub core_asset: UncheckedAccount<'info>,
-    #[account(
-        init_if_needed,
-        payer = seller,
-        space = 8 + Listing::SIZE,
-        seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()],
-        bump
-    )]
-    pub listing: Account<'info, Listing>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
-    /// CHECK: mpl-core program (CPI target)
-    pub mpl_core_program: UncheckedAccount<'info>,
+/// Loads `user`'s UserStats, creating it (paid by `user`) when it does not exist yet.
+fn open_user_stats<'info>(
+    user_stats: &AccountInfo<'info>,
+    user: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    vault_state: &Pubkey,
+    bump: u8,
+) -> Result<UserStats> {
+    if user_stats.data_is_empty() {
+        init_pda(
+            user,
+            user_stats,
+            8 + UserStats::SIZE,
+            &[USER_STATS_SEED, vault_state.as_ref(), user.key.as_ref()],
+            system_program,
+            &crate::ID,
+        )?;
+        return Ok(UserStats {
+            vault_state: *vault_state,
+            user: user.key(),
+            spend_limit_lamports: 0,
+            period_seconds: 0,
+            period_start: 0,
+            period_spent: 0,
+            pending_limit_lamports: 0,
+            pending_period_seconds: 0,
+            pending_effective_at: 0,
+            bump,
+            packs_since_rare: 0,
+        });
+    }
+    require_keys_eq!(*user_stats.owner, crate::ID, MochiError::InvalidPda);
+    load_user_stats(user_stats)
 }
 
-#[derive(Accounts)]
-pub struct CancelListing<'info> {
-    #[account(mut)]
-    pub seller: Signer<'info>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    /// CHECK: We will deserialize or rebuild defensively.
-    pub card_record: UncheckedAccount<'info>,
-    /// CHECK: Core asset (Metaplex Core), validated off-chain
-    #[account(mut)]
-    pub core_asset: UncheckedAccount<'info>,
-    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    pub listing: Account<'info, Listing>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-    /// CHECK: mpl-core program (CPI target)
-    pub mpl_core_program: UncheckedAccount<'info>,
+/// Reads a UserStats, zero-filling fields appended after the account was created.
+fn load_user_stats(user_stats: &AccountInfo) -> Result<UserStats> {
+    let mut data = user_stats.try_borrow_data()?.to_vec();
+    if data.len() < 8 + UserStats::SIZE {
+        data.resize(8 + UserStats::SIZE, 0);
+    }
+    UserStats::try_deserialize(&mut &data[..])
 }
 
-#[derive(Accounts)]
-pub struct FillListing<'info> {
-    #[account(mut)]
-    pub buyer: Signer<'info>,
-    #[account(mut)]
-    pub seller: SystemAccount<'info>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut)]
-    pub card_record: Account<'info, CardRecord>,
-    /// CHECK: Core asset account (Metaplex Core), validated off-chain
-    #[account(mut)]
-    pub core_asset: UncheckedAccount<'info>,
-    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    pub listing: Account<'info, Listing>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    #[account(mut)]
-    pub vault_treasury: SystemAccount<'info>,
-    /// CHECK: System program
-    pub system_program: UncheckedAccount<'info>,
-    /// CHECK: mpl-core program (CPI target)
-    pub mpl_core_program: UncheckedAccount<'info>,
+/// Writes `stats` back, first growing an account created before its latest fields (the
+/// rent top-up is paid by `payer`).
+fn store_user_stats<'info>(
+    stats: &UserStats,
+    user_stats: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let target_len = 8 + UserStats::SIZE;
+    if user_stats.data_len() < target_len {
+        let required = Rent::get()?.minimum_balance(target_len);
+        if user_stats.lamports() < required {
+            invoke(
+                &system_instruction::transfer(
+                    payer.key,
+                    user_stats.key,
+                    required - user_stats.lamports(),
+                ),
+                &[payer.clone(), user_stats.clone(), system_program.clone()],
+            )?;
+        }
+        user_stats.realloc(target_len, false)?;
+    }
+    write_account(stats, user_stats)
 }
 
-#[derive(Accounts)]
-pub struct RedeemBurn<'info> {
-    pub user: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut)]
-    pub card_record: Account<'info, CardRecord>,
-    /// CHECK: Core asset account (Metaplex Core), validated off-chain
-    #[account(mut)]
-    pub core_asset: UncheckedAccount<'info>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
-    /// CHECK: mpl-core program (CPI target)
-    pub mpl_core_program: UncheckedAccount<'info>,
+/// Message the open gate authority signs: user || vault_state || nonce || expires_at (LE).
+/// The nonce is the session's `created_at` before this open, so a signature is single-use.
+pub fn open_gate_message(
+    user: &Pubkey,
+    vault_state: &Pubkey,
+    nonce: i64,
+    expires_at: i64,
+) -> [u8; 80] {
+    let mut message = [0u8; 80];
+    message[..32].copy_from_slice(user.as_ref());
+    message[32..64].copy_from_slice(vault_state.as_ref());
+    message[64..72].copy_from_slice(&nonce.to_le_bytes());
+    message[72..].copy_from_slice(&expires_at.to_le_bytes());
+    message
 }
 
-#[derive(Accounts)]
-pub struct AdminMigrateAsset<'info> {
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut)]
-    pub card_record: Account<'info, CardRecord>,
-    /// CHECK: emergency destination (validated off-chain by admin authority)
-    pub destination: UncheckedAccount<'info>,
-    /// CHECK: Core asset account (Metaplex Core)
-    #[account(mut)]
-    pub core_asset: UncheckedAccount<'info>,
-    /// CHECK: Vault authority PDA (validated by seeds)
-    #[account(seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
-    /// CHECK: mpl-core program (CPI target)
-    pub mpl_core_program: UncheckedAccount<'info>,
-}
+/// Requires the instruction just before this one to be an ed25519 verify, by the vault's open
+/// gate authority, of `open_gate_message` for this user and session. No-op while ungated.
+fn verify_open_gate(
+    vault_state: &Account<VaultState>,
+    instructions_sysvar: Option<&UncheckedAccount>,
+    user: &Pubkey,
+    nonce: i64,
+) -> Result<()> {
+    let Some(gate_authority) = vault_state.open_gate_authority else {
+        return Ok(());
+    };
+    let sysvar = instructions_sysvar.ok_or(MochiError::OpenGateRequired)?;
+    let current = ix_sysvar::load_current_index_checked(&sysvar.to_account_info())?;
+    require!(current > 0, MochiError::OpenGateRequired);
+    let verify_ix =
+        ix_sysvar::load_instruction_at_checked(current as usize - 1, &sysvar.to_account_info())?;
+    require_keys_eq!(
+        verify_ix.program_id,
+        ed25519_program::ID,
+        MochiError::OpenGateRequired
+    );
 
-#[derive(Accounts)]
-pub struct AdminForceCancel<'info> {
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    /// CHECK: we will deserialize or rebuild
-    pub card_record: UncheckedAccount<'info>,
-    /// CHECK: core asset
-    #[account(mut)]
-    pub core_asset: UncheckedAccount<'info>,
-    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    pub listing: Account<'info, Listing>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    /// CHECK: vault authority
-    pub vault_authority: UncheckedAccount<'info>,
-    /// Seller (funds will be returned)
-    #[account(mut)]
-    pub seller: SystemAccount<'info>,
-    /// CHECK: system program
-    pub system_program: UncheckedAccount<'info>,
-    /// CHECK: mpl-core
-    pub mpl_core_program: UncheckedAccount<'info>,
+    // Ed25519SignatureOffsets follows the 2-byte header; every offset must point into this
+    // instruction's own data (index u16::MAX).
+    let data = &verify_ix.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        MochiError::OpenGateRequired
+    );
+    let field = |i: usize| u16::from_le_bytes([data[2 + i * 2], data[3 + i * 2]]) as usize;
+    let (sig_ix, pubkey_offset, pubkey_ix) = (field(1), field(2), field(3));
+    let (message_offset, message_size, message_ix) = (field(4), field(5), field(6));
+    let own = u16::MAX as usize;
+    require!(
+        sig_ix == own && pubkey_ix == own && message_ix == own && message_size == 80,
+        MochiError::OpenGateRequired
+    );
+    let pubkey = data
+        .get(pubkey_offset..pubkey_offset + 32)
+        .ok_or(MochiError::OpenGateRequired)?;
+    let message = data
+        .get(message_offset..message_offset + 80)
+        .ok_or(MochiError::OpenGateRequired)?;
+    require!(
+        pubkey == gate_authority.as_ref(),
+        MochiError::OpenGateRequired
+    );
+
+    let mut expires_at = [0u8; 8];
+    expires_at.copy_from_slice(&message[72..]);
+    let expires_at = i64::from_le_bytes(expires_at);
+    let expected = open_gate_message(user, &vault_state.key(), nonce, expires_at);
+    require!(message == expected, MochiError::OpenGateRequired);
+    require!(
+        Clock::get()?.unix_timestamp <= expires_at,
+        MochiError::OpenGateExpired
+    );
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct EmergencyReturnAsset<'info> {
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [CARD_RECORD_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    /// CHECK: we will deserialize or rebuild
-    pub card_record: UncheckedAccount<'info>,
-    /// CHECK: core asset
-    #[account(mut)]
-    pub core_asset: UncheckedAccount<'info>,
-    #[account(mut, seeds = [LISTING_SEED, vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    pub listing: Account<'info, Listing>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    /// CHECK: vault authority
-    pub vault_authority: UncheckedAccount<'info>,
-    /// Seller destination (must match listing.seller)
-    #[account(mut)]
-    pub seller: SystemAccount<'info>,
-    pub system_program: Program<'info, System>,
-    /// CHECK: mpl-core program
-    pub mpl_core_program: UncheckedAccount<'info>,
+/// What a V2 open instruction asks `open_pack_v2_inner` for. `discount_bps` comes from a
+/// redeemed DiscountCode (0 otherwise); `random_draw` carries the entropy and candidate pool
+/// hash of an `open_pack_random` draw.
+struct PackOpen {
+    currency: Currency,
+    client_seed_hash: [u8; 32],
+    rare_templates: Vec<u32>,
+    claim_window_seconds: i64,
+    manifest_hash: [u8; 32],
+    recipient: Option<Pubkey>,
+    discount_bps: u16,
+    random_draw: Option<([u8; 32], [u8; 32])>,
 }
 
-#[derive(Accounts)]
-pub struct AdminRescueLegacyListing<'info> {
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub marketplace_vault_state: Account<'info, VaultState>,
-    #[account(mut)]
-    pub legacy_vault_state: Account<'info, VaultState>,
-    #[account(mut, seeds = [CARD_RECORD_SEED, legacy_vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    /// CHECK: legacy card record PDA
-    pub card_record: UncheckedAccount<'info>,
-    /// CHECK: core asset tied to listing
-    #[account(mut)]
-    pub core_asset: UncheckedAccount<'info>,
-    #[account(mut, seeds = [LISTING_SEED, legacy_vault_state.key().as_ref(), core_asset.key().as_ref()], bump)]
-    pub listing: Account<'info, Listing>,
-    /// CHECK: legacy vault authority PDA (seed prefix verified in handler)
-    #[account(mut)]
-    pub legacy_vault_authority: UncheckedAccount<'info>,
-    /// Seller destination (must match listing.seller)
-    #[account(mut)]
-    pub seller: SystemAccount<'info>,
-    pub system_program: Program<'info, System>,
-    /// CHECK: mpl-core program
-    pub mpl_core_program: UncheckedAccount<'info>,
+/// Shared body of the V2 open instructions; `claim_window_seconds` is the already-resolved window
+/// and `remaining_accounts` starts at the rare card records.
+fn open_pack_v2_inner<'info>(
+    ctx: Context<'_, '_, 'info, 'info, OpenPackV2<'info>>,
+    remaining_accounts: &[AccountInfo<'info>],
+    open: PackOpen,
+) -> Result<()> {
+    let PackOpen {
+        currency,
+        client_seed_hash,
+        rare_templates,
+        claim_window_seconds,
+        manifest_hash,
+        recipient,
+        discount_bps,
+        random_draw,
+    } = open;
+    verify_open_gate(
+        &ctx.accounts.vault_state,
+        ctx.accounts.instructions_sysvar.as_ref(),
+        &ctx.accounts.user.key(),
+        ctx.accounts.pack_session.created_at,
+    )?;
+    // Counted up front; any failure below reverts it with the rest of the open. An expired
+    // session this open may overwrite is settled first.
+    let previous = &ctx.accounts.pack_session;
+    let replaced = (previous.state == PackState::PendingDecision).then_some(&previous.currency);
+    ctx.accounts.vault_state.open_session(replaced, &currency)?;
+    let nonce = ctx.accounts.vault_state.next_session_nonce()?;
+    let vault_state = &ctx.accounts.vault_state;
+    let now = Clock::get()?.unix_timestamp;
+
+    let rare_count = rare_templates.len();
+    require!(
+        rare_count <= vault_state.rare_ceiling(),
+        MochiError::TooManyRareCards
+    );
+    require!(
+        remaining_accounts.len() >= rare_count,
+        MochiError::InvalidCardCount
+    );
+    msg!(
+        "reward cfg amount {} mint {:?}",
+        vault_state.reward_per_pack,
+        vault_state.mochi_mint
+    );
+
+    require_keys_eq!(
+        ctx.accounts.vault_treasury.key(),
+        vault_state.treasury,
+        MochiError::VaultMismatch
+    );
+    require!(
+        vault_state.accepts_currency(&currency),
+        MochiError::CurrencyNotAccepted
+    );
+
+    // Fail fast if an active session already exists.
+    let session = &mut ctx.accounts.pack_session;
+    // An unpaid layaway still holds escrow, so it blocks reuse until cancelled.
+    if session.state == PackState::PendingDecision
+        && (now <= session.expires_at || !session.is_paid())
+    {
+        return err!(MochiError::SessionExists);
+    }
+    session.reset_for_open();
+
+    // Reserve Rare+ CardRecords before any funds move, so bad inventory fails fast.
+    let rare_keys = reserve_rare_records(
+        &vault_state.key(),
+        &ctx.accounts.user.key(),
+        remaining_accounts,
+        &rare_templates,
+        tracked_inventory(vault_state, &mut ctx.accounts.rarity_inventory)?,
+    )?;
+
+    // Take payment last.
+    let (paid_amount, paid_sol_amount) = match currency {
+        Currency::Sol => {
+            let price = apply_discount(vault_state.pack_price_sol, discount_bps)?;
+            require!(price > 0, MochiError::InvalidPrice);
+            // remaining_accounts after the rares: [fee_split, recipients...] when configured.
+            pay_fee(
+                vault_state,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.vault_treasury.to_account_info(),
+                &remaining_accounts[rare_count..],
+                price,
+                &ctx.accounts.system_program.to_account_info(),
+                &[],
+            )?;
+            (price, 0)
+        }
+        Currency::Token => {
+            let price = apply_discount(vault_state.pack_price_usdc, discount_bps)?;
+            require!(price > 0, MochiError::InvalidPrice);
+            collect_pack_tokens(
+                vault_state.usdc_mint,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.vault_authority.key(),
+                &remaining_accounts[rare_count..],
+                price,
+                &ctx.accounts.token_program.to_account_info(),
+            )?;
+            (price, 0)
+        }
+        Currency::Both => {
+            let sol_price = apply_discount(vault_state.pack_price_sol, discount_bps)?;
+            let usdc_price = apply_discount(vault_state.combo_price_usdc, discount_bps)?;
+            require!(sol_price > 0 && usdc_price > 0, MochiError::InvalidPrice);
+            // remaining_accounts after the rares: [user_usdc, vault_usdc, fee_split, recipients...].
+            let extras = &remaining_accounts[rare_count..];
+            collect_pack_tokens(
+                vault_state.usdc_mint,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.vault_authority.key(),
+                extras,
+                usdc_price,
+                &ctx.accounts.token_program.to_account_info(),
+            )?;
+            pay_fee(
+                vault_state,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.vault_treasury.to_account_info(),
+                &extras[2..],
+                sol_price,
+                &ctx.accounts.system_program.to_account_info(),
+                &[],
+            )?;
+            (usdc_price, sol_price)
+        }
+        Currency::Mochi => {
+            // Collected (not burned) into the vault's MOCHI account, where it funds open
+            // rewards and sellback refunds: a closed loop rather than a deflationary sink.
+            // remaining_accounts after the rares: [user_mochi, vault_mochi].
+            let price = apply_discount(vault_state.pack_price_mochi, discount_bps)?;
+            require!(price > 0, MochiError::InvalidPrice);
+            collect_pack_tokens(
+                vault_state.mochi_mint,
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.vault_authority.key(),
+                &remaining_accounts[rare_count..],
+                price,
+                &ctx.accounts.token_program.to_account_info(),
+            )?;
+            (price, 0)
+        }
+    };
+    warn_low_authority_balance(vault_state, &ctx.accounts.vault_authority);
+    charge_spend_limit(
+        &ctx.accounts.user_stats,
+        &ctx.accounts.user.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        vault_state.spend_lamports(&currency, paid_amount, paid_sol_amount),
+        now,
+    )?;
+    track_pity(
+        &ctx.accounts.user_stats,
+        &ctx.accounts.user.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        vault_state,
+        ctx.bumps.user_stats,
+        rare_count > 0,
+    )?;
+
+    // Write session state
+    session.user = ctx.accounts.user.key();
+    session.currency = currency;
+    session.paid_amount = paid_amount;
+    session.paid_sol_amount = paid_sol_amount;
+    session.created_at = now;
+    session.expires_at = now
+        .checked_add(vault_state.capped_window(claim_window_seconds))
+        .ok_or(MochiError::MathOverflow)?;
+    session.state = PackState::PendingDecision;
+    session.client_seed_hash = client_seed_hash;
+    session.manifest_hash = manifest_hash;
+    session.rare_card_keys = rare_keys;
+    session.rare_templates = rare_templates;
+    require!(
+        PACK_CARD_COUNT <= vault_state.manifest_ceiling(),
+        MochiError::ManifestTooLong
+    );
+    session.total_slots = PACK_CARD_COUNT as u8;
+    session.bump = ctx.bumps.pack_session;
+    session.recipient = recipient.unwrap_or_default();
+    session.full_price = 0;
+    (session.entropy, session.candidate_pool_hash) = random_draw.unwrap_or_default();
+    session.nonce = nonce;
+    // A fresh session starts unrewarded; the flag flips only once the payout below lands,
+    // so the reward is tied to this PendingDecision session rather than to the call.
+    session.reward_minted = false;
+    emit!(PackOpened {
+        vault_state: vault_state.key(),
+        user: session.user,
+        pack_session: session.key(),
+        paid_amount,
+        rarity_counts: rarity_counts(remaining_accounts, rare_count)?,
+    });
+
+    // Atomic MOCHI reward: transfer from PDA-owned vault, or mint if PDA holds mint authority.
+    let reward_amount = match vault_state.reward_mode {
+        RewardMode::Flat => vault_state.effective_reward(now)?,
+        RewardMode::Proportional => vault_state.proportional_reward(
+            &ctx.accounts.pack_session.currency,
+            paid_amount,
+            paid_sol_amount,
+        )?,
+    };
+    if reward_amount == 0 && vault_state.reward_mode == RewardMode::Proportional {
+        // A pack too cheap to earn a whole MOCHI unit still opens.
+        return Ok(());
+    }
+    if reward_amount == 0 && vault_state.reward_start > 0 {
+        // A fully decayed schedule ends emissions without blocking opens.
+        return Ok(());
+    }
+    require!(reward_amount > 0, MochiError::RewardDisabled);
+    let reward_amount = match ctx.accounts.membership_asset.as_ref() {
+        Some(asset) => membership_reward(
+            vault_state,
+            reward_amount,
+            &asset.to_account_info(),
+            &ctx.accounts.user.key(),
+        )?,
+        None => reward_amount,
+    };
+    let reward_amount = vault_state.capped_reward(reward_amount);
+    let mochi_mint = vault_state.mochi_mint.ok_or(MochiError::MintMismatch)?;
+    require_keys_eq!(
+        ctx.accounts.reward_mint.key(),
+        mochi_mint,
+        MochiError::MintMismatch
+    );
+    require_keys_eq!(
+        ctx.accounts.user_token_account.mint,
+        mochi_mint,
+        MochiError::MintMismatch
+    );
+    require_keys_eq!(
+        ctx.accounts.reward_vault.mint,
+        mochi_mint,
+        MochiError::MintMismatch
+    );
+    // Gift packs pay the open reward to the recipient, not the purchaser.
+    let beneficiary = ctx.accounts.pack_session.beneficiary();
+    require_keys_eq!(
+        ctx.accounts.user_token_account.owner,
+        beneficiary,
+        MochiError::Unauthorized
+    );
+    let vault_key = vault_state.key();
+    let seeds = &[
+        GACHA_VAULT_AUTHORITY_SEED,
+        vault_key.as_ref(),
+        &[ctx.bumps.vault_authority],
+    ];
+    let signer = &[&seeds[..]];
+    let mut rewarded = false;
+    if ctx.accounts.reward_vault.owner == ctx.accounts.vault_authority.key() {
+        require!(
+            ctx.accounts.reward_vault.amount >= reward_amount,
+            MochiError::InsufficientFunds
+        );
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        token::transfer(cpi_ctx, reward_amount)?;
+        rewarded = true;
+    }
+    if !rewarded
+        && ctx.accounts.reward_mint.mint_authority
+            == COption::Some(ctx.accounts.vault_authority.key())
+    {
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.reward_mint.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        token::mint_to(cpi_ctx, reward_amount)?;
+        rewarded = true;
+    }
+    require!(rewarded, MochiError::Unauthorized);
+    ctx.accounts.pack_session.reward_minted = true;
+    emit!(RewardMinted {
+        user: ctx.accounts.user.key(),
+        ata: ctx.accounts.user_token_account.key(),
+        mint: mochi_mint,
+        amount: reward_amount,
+    });
+    msg!("reward delivered");
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct DeprecateCard<'info> {
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(mut)]
-    pub card_record: Account<'info, CardRecord>,
+/// Checks `collection_info` is the Metaplex Core collection `expected` points at.
+fn verify_core_collection(collection_info: &AccountInfo, expected: Option<Pubkey>) -> Result<()> {
+    let expected = expected.ok_or(MochiError::InvalidCollection)?;
+    require_keys_eq!(
+        collection_info.key(),
+        expected,
+        MochiError::InvalidCollection
+    );
+    require_keys_eq!(
+        *collection_info.owner,
+        mpl_core::ID,
+        MochiError::InvalidCollection
+    );
+    let collection =
+        mpl_core::accounts::BaseCollectionV1::from_bytes(&collection_info.data.borrow())
+            .map_err(|_| error!(MochiError::InvalidCollection))?;
+    require!(
+        collection.key == mpl_core::types::Key::CollectionV1,
+        MochiError::InvalidCollection
+    );
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct AdminPruneListing<'info> {
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    /// CHECK: listing PDA may have been created with wrong seeds; we only mark Cancelled.
-    #[account(mut)]
-    pub listing: UncheckedAccount<'info>,
+/// `reward` boosted by the membership multiplier, after checking `asset` is a Core asset of
+/// the membership collection owned by `holder`. Unchanged while no membership is configured.
+fn membership_reward(
+    vault_state: &VaultState,
+    reward: u64,
+    asset: &AccountInfo,
+    holder: &Pubkey,
+) -> Result<u64> {
+    let collection = match vault_state.membership_collection {
+        Some(collection) if vault_state.membership_reward_multiplier_bps > 0 => collection,
+        _ => return Ok(reward),
+    };
+    require_keys_eq!(*asset.owner, mpl_core::ID, MochiError::AssetMismatch);
+    let base = mpl_core::accounts::BaseAssetV1::from_bytes(&asset.data.borrow())
+        .map_err(|_| error!(MochiError::AssetMismatch))?;
+    require_keys_eq!(base.owner, *holder, MochiError::Unauthorized);
+    require!(
+        matches!(base.update_authority, UpdateAuthority::Collection(key) if key == collection),
+        MochiError::AssetMismatch
+    );
+    let boosted = (reward as u128)
+        .checked_mul(vault_state.membership_reward_multiplier_bps as u128)
+        .and_then(|v| v.checked_div(10_000))
+        .ok_or(MochiError::MathOverflow)?;
+    u64::try_from(boosted).map_err(|_| error!(MochiError::MathOverflow))
 }
 
-#[derive(Accounts)]
-pub struct SetRewardConfig<'info> {
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    pub vault_state: Account<'info, VaultState>,
-    /// CHECK: vault authority PDA (seed checked in handler)
-    #[account(mut, seeds = [GACHA_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub token_program: Program<'info, Token>,
+/// Seed prefix and bump of a legacy vault's authority, which may be its marketplace or its
+/// gacha authority PDA.
+fn legacy_authority_seed(legacy_vault: &Pubkey, authority: &Pubkey) -> Result<(&'static [u8], u8)> {
+    let (market_auth, market_bump) = Pubkey::find_program_address(
+        &[MARKETPLACE_VAULT_AUTHORITY_SEED, legacy_vault.as_ref()],
+        &crate::ID,
+    );
+    if market_auth == *authority {
+        return Ok((MARKETPLACE_VAULT_AUTHORITY_SEED, market_bump));
+    }
+    let (gacha_auth, gacha_bump) = Pubkey::find_program_address(
+        &[GACHA_VAULT_AUTHORITY_SEED, legacy_vault.as_ref()],
+        &crate::ID,
+    );
+    require_keys_eq!(gacha_auth, *authority, MochiError::VaultMismatch);
+    Ok((GACHA_VAULT_AUTHORITY_SEED, gacha_bump))
 }
 
-#[derive(Accounts)]
-pub struct MigrateVaultState<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut, seeds = [GACHA_VAULT_SEED], bump)]
-    /// CHECK: migrating legacy account; seeds enforced above.
-    pub vault_state: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
+/// Collection of a Core asset, or None when it has none or is not a live Core asset.
+fn asset_collection(asset: &AccountInfo) -> Option<Pubkey> {
+    if *asset.owner != mpl_core::ID {
+        return None;
+    }
+    match mpl_core::accounts::BaseAssetV1::from_bytes(&asset.data.borrow())
+        .ok()?
+        .update_authority
+    {
+        UpdateAuthority::Collection(key) => Some(key),
+        _ => None,
+    }
 }
 
-#[derive(Accounts)]
-pub struct MigrateMarketplaceVault<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    /// CHECK: legacy marketplace vault PDA (may be undersized); seeds enforced.
-    #[account(mut, seeds = [MARKETPLACE_VAULT_SEED], bump)]
-    pub vault_state: UncheckedAccount<'info>,
-    /// CHECK: marketplace vault authority PDA
-    #[account(seeds = [MARKETPLACE_VAULT_AUTHORITY_SEED, vault_state.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub system_program: Program<'info, System>,
+/// Pays an enforced fill royalty from `payer` to the verified collection's recipient, which
+/// must be passed whenever the quote carries a royalty.
+fn pay_royalty<'info>(
+    quote: &FillQuote,
+    payer: &AccountInfo<'info>,
+    recipient: Option<&UncheckedAccount<'info>>,
+    system_program: &AccountInfo<'info>,
+    payer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if quote.royalty == 0 {
+        return Ok(());
+    }
+    let recipient = recipient.ok_or(MochiError::RoyaltyRecipientMismatch)?;
+    require_keys_eq!(
+        recipient.key(),
+        quote.royalty_recipient,
+        MochiError::RoyaltyRecipientMismatch
+    );
+    invoke_signed(
+        &system_instruction::transfer(payer.key, recipient.key, quote.royalty),
+        &[
+            payer.clone(),
+            recipient.to_account_info(),
+            system_program.clone(),
+        ],
+        payer_seeds,
+    )?;
+    Ok(())
 }
 
-#[account]
-pub struct VaultState {
-    pub admin: Pubkey,
-    pub vault_authority: Pubkey,
-    pub pack_price_sol: u64,
-    pub pack_price_usdc: u64,
-    pub buyback_bps: u16,
-    pub claim_window_seconds: i64,
-    pub marketplace_fee_bps: u16,
-    pub core_collection: Option<Pubkey>,
-    pub usdc_mint: Option<Pubkey>,
-    pub mochi_mint: Option<Pubkey>,
-    pub reward_per_pack: u64,
-    pub vault_authority_bump: u8,
-    pub padding: [u8; 7],
-}
-impl VaultState {
-    pub const SIZE: usize = 32 // admin
-        + 32 // vault_authority
-        + 8 // pack_price_sol
-        + 8 // pack_price_usdc
-        + 2 // buyback_bps
-        + 8 // claim_window_seconds
-        + 2 // marketplace_fee_bps
-        + 1 + 32 // core_collection Option
-        + 1 + 32 // usdc_mint Option
-        + 1 + 32 // mochi_mint Option
-        + 8 // reward_per_pack
-        + 1 // vault_authority_bump
-        + 7; // padding
+/// True while `asset` is a live Core asset owned by the marketplace `vault_authority`.
+fn listing_is_backed(asset: &AccountInfo, vault_authority: &Pubkey) -> bool {
+    if *asset.owner != mpl_core::ID {
+        return false;
+    }
+    mpl_core::accounts::BaseAssetV1::from_bytes(&asset.data.borrow())
+        .map(|base| base.owner == *vault_authority)
+        .unwrap_or(false)
 }
 
-#[event]
-pub struct RewardMinted {
-    pub user: Pubkey,
-    pub ata: Pubkey,
-    pub mint: Pubkey,
-    pub amount: u64,
-}
+/// Pay `amount` lamports from `payer` to the treasury, or across the vault's FeeSplit when
+/// one is enabled. The last recipient takes the rounding remainder. `payer_seeds` is empty
+/// for wallet payers and the authority seeds when paying out of a vault PDA.
+fn pay_fee<'info>(
+    vault_state: &Account<VaultState>,
+    payer: &AccountInfo<'info>,
+    treasury: &AccountInfo<'info>,
+    split_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    system_program: &AccountInfo<'info>,
+    payer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    if !vault_state.fee_split_enabled {
+        invoke_signed(
+            &system_instruction::transfer(payer.key, treasury.key, amount),
+            &[payer.clone(), treasury.clone(), system_program.clone()],
+            payer_seeds,
+        )?;
+        return Ok(());
+    }
 
-#[account]
-pub struct CardRecord {
-    pub vault_state: Pubkey,
-    pub core_asset: Pubkey,
-    pub template_id: u32,
-    pub rarity: Rarity,
-    pub status: CardStatus,
-    pub owner: Pubkey,
-}
-impl CardRecord {
-    pub const SIZE: usize = 32 + 32 + 4 + 1 + 1 + 32;
-}
+    let split_info = split_accounts.first().ok_or(MochiError::InvalidFeeSplit)?;
+    require_keys_eq!(*split_info.owner, crate::ID, MochiError::InvalidFeeSplit);
+    let fee_split = FeeSplit::try_deserialize(&mut &split_info.data.borrow()[..])?;
+    // The split must be the paying vault's own, not one configured for another vault.
+    require_keys_eq!(
+        fee_split.vault_state,
+        vault_state.key(),
+        MochiError::InvalidFeeSplit
+    );
+    let (expected, _) =
+        Pubkey::find_program_address(&[FEE_SPLIT_SEED, vault_state.key().as_ref()], &crate::ID);
+    require_keys_eq!(split_info.key(), expected, MochiError::InvalidFeeSplit);
+    require!(
+        !fee_split.recipients.is_empty(),
+        MochiError::InvalidFeeSplit
+    );
+    let recipient_infos = &split_accounts[1..];
+    require!(
+        recipient_infos.len() >= fee_split.recipients.len(),
+        MochiError::InvalidFeeSplit
+    );
 
-#[account]
-pub struct PackSessionV2 {
-    pub user: Pubkey,
-    pub currency: Currency,
-    pub paid_amount: u64,
-    pub created_at: i64,
-    pub expires_at: i64,
-    pub rare_card_keys: Vec<Pubkey>,
-    pub rare_templates: Vec<u32>,
-    pub state: PackState,
-    pub client_seed_hash: [u8; 32],
-    pub total_slots: u8,
-    pub bump: u8,
-}
-impl PackSessionV2 {
-    pub const SIZE: usize = 32 // user
-        + 1 // currency enum
-        + 8 // paid_amount
-        + 8 // created_at
-        + 8 // expires_at
-        + 4 + (32 * MAX_RARE_CARDS) // rare_card_keys vec
-        + 4 + (4 * MAX_RARE_CARDS) // rare_templates vec
-        + 1 // state enum
-        + 32 // client_seed_hash
-        + 1 // total_slots
-        + 1; // bump
+    let mut distributed: u64 = 0;
+    let last = fee_split.recipients.len().saturating_sub(1);
+    for (idx, recipient) in fee_split.recipients.iter().enumerate() {
+        let recipient_info = &recipient_infos[idx];
+        require_keys_eq!(
+            recipient_info.key(),
+            recipient.recipient,
+            MochiError::InvalidFeeSplit
+        );
+        let share = if idx == last {
+            amount
+                .checked_sub(distributed)
+                .ok_or(MochiError::MathOverflow)?
+        } else {
+            amount
+                .checked_mul(recipient.bps as u64)
+                .and_then(|v| v.checked_div(10_000))
+                .ok_or(MochiError::MathOverflow)?
+        };
+        distributed = distributed
+            .checked_add(share)
+            .ok_or(MochiError::MathOverflow)?;
+        if share > 0 {
+            invoke_signed(
+                &system_instruction::transfer(payer.key, recipient_info.key, share),
+                &[
+                    payer.clone(),
+                    recipient_info.clone(),
+                    system_program.clone(),
+                ],
+                payer_seeds,
+            )?;
+        }
+    }
+    Ok(())
 }
 
-#[account]
-pub struct PackSession {
-    pub user: Pubkey,
-    pub currency: Currency,
-    pub paid_amount: u64,
-    pub created_at: i64,
-    pub expires_at: i64,
-    pub card_record_keys: [Pubkey; PACK_CARD_COUNT],
-    pub state: PackState,
-    pub client_seed_hash: [u8; 32],
-    pub rarity_prices: Vec<u64>,
+fn refund_pack_sol<'info>(
+    vault_authority: &AccountInfo<'info>,
+    user: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    invoke_signed(
+        &system_instruction::transfer(vault_authority.key, user.key, amount),
+        &[
+            vault_authority.clone(),
+            user.clone(),
+            system_program.clone(),
+        ],
+        signer,
+    )?;
+    Ok(())
 }
-impl PackSession {
-    pub const SIZE: usize =
-        32 + 1 + 8 + 8 + 8 + (32 * PACK_CARD_COUNT) + 1 + 32 + 4 + 8 * PACK_CARD_COUNT;
+
+/// Returns a layaway's installments from the vault authority to `user` and releases them from
+/// `escrowed_lamports`.
+fn refund_layaway<'info>(
+    vault_state: &mut VaultState,
+    session: &mut PackSessionV2,
+    vault_authority: &AccountInfo<'info>,
+    user: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+) -> Result<()> {
+    let refund = session.paid_amount;
+    refund_pack_sol(vault_authority, user, system_program, signer, refund)?;
+    session.paid_amount = 0;
+    vault_state.escrowed_lamports = vault_state
+        .escrowed_lamports
+        .checked_sub(refund)
+        .ok_or(MochiError::MathOverflow)?;
+    Ok(())
 }
 
-#[account]
-pub struct Listing {
-    pub vault_state: Pubkey,
-    pub seller: Pubkey,
-    pub core_asset: Pubkey,
-    pub price_lamports: u64,
-    pub currency_mint: Option<Pubkey>,
-    pub status: ListingStatus,
+/// Emits LowTreasuryWarning when the vault authority, which funds claim rent and SOL
+/// sellbacks, holds less than `min_authority_balance`. A zero threshold never warns.
+fn warn_low_authority_balance(vault_state: &VaultState, vault_authority: &AccountInfo) {
+    let balance = vault_authority.lamports();
+    if balance < vault_state.min_authority_balance {
+        emit!(LowTreasuryWarning {
+            balance,
+            threshold: vault_state.min_authority_balance,
+        });
+    }
 }
-impl Listing {
-    pub const SIZE: usize = 32 + 32 + 32 + 8 + 1 + 32 + 1; // currency_mint option + status
+
+/// The `sellback_fee_bps` share of a sellback payout; never more than the payout itself.
+fn sellback_fee(vault_state: &VaultState, payout: u64) -> Result<u64> {
+    let fee = (payout as u128 * vault_state.sellback_fee_bps.min(10_000) as u128) / 10_000;
+    u64::try_from(fee).map_err(|_| error!(MochiError::MathOverflow))
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum Rarity {
-    Common,
-    Uncommon,
-    Rare,
-    DoubleRare,
-    UltraRare,
-    IllustrationRare,
-    SpecialIllustrationRare,
-    MegaHyperRare,
-    Energy,
+/// Returns `amount` of `mint` from `token_accounts[1]` (the vault's) to `token_accounts[0]`
+/// (the user's). A zero refund is a no-op.
+fn refund_pack_tokens<'info>(
+    mint: Option<Pubkey>,
+    token_accounts: &'info [AccountInfo<'info>],
+    vault_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    require!(token_accounts.len() >= 2, MochiError::MissingTokenAccount);
+    let user_token: Account<TokenAccount> = Account::try_from(&token_accounts[0])?;
+    let vault_token: Account<TokenAccount> = Account::try_from(&token_accounts[1])?;
+    if let Some(mint) = mint {
+        require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
+        require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
+    }
+    let cpi_accounts = Transfer {
+        from: vault_token.to_account_info(),
+        to: user_token.to_account_info(),
+        authority: vault_authority.clone(),
+    };
+    token::transfer(
+        CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer),
+        amount,
+    )
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum CardStatus {
-    Available,
-    Reserved,
-    UserOwned,
-    RedeemPending,
-    Burned,
-    Deprecated,
+/// Debits `amount` of `mint` from `token_accounts[0]` (the user's) into `token_accounts[1]`,
+/// which must be a `mint` account held by `vault_authority`.
+fn collect_pack_tokens<'info>(
+    mint: Option<Pubkey>,
+    user: &AccountInfo<'info>,
+    vault_authority: &Pubkey,
+    token_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    token_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let mint = mint.ok_or(MochiError::MintMismatch)?;
+    require!(token_accounts.len() >= 2, MochiError::MissingTokenAccount);
+    let load = |info: &AccountInfo| -> Result<TokenAccount> {
+        require_keys_eq!(*info.owner, token::ID, MochiError::MissingTokenAccount);
+        TokenAccount::try_deserialize(&mut &info.try_borrow_data()?[..])
+    };
+    let user_token = load(&token_accounts[0])?;
+    let vault_token = load(&token_accounts[1])?;
+    require_keys_eq!(user_token.mint, mint, MochiError::MintMismatch);
+    require_keys_eq!(vault_token.mint, mint, MochiError::MintMismatch);
+    require_keys_eq!(
+        vault_token.owner,
+        *vault_authority,
+        MochiError::VaultMismatch
+    );
+    let cpi_accounts = Transfer {
+        from: token_accounts[0].clone(),
+        to: token_accounts[1].clone(),
+        authority: user.clone(),
+    };
+    token::transfer(CpiContext::new(token_program.clone(), cpi_accounts), amount)
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum Currency {
-    Sol,
-    Token,
+/// Single source of truth for fill pricing, shared by fill_listing and quote_fill.
+fn fill_quote(
+    listing: &Listing,
+    vault_state: &VaultState,
+    core_asset: &AccountInfo,
+) -> Result<FillQuote> {
+    let price = listing.price_lamports;
+    let rounding = if vault_state.round_fees_up { 9_999 } else { 0 };
+    let bps_of_price = |bps: u16| {
+        price
+            .checked_mul(bps as u64)
+            .and_then(|v| v.checked_add(rounding))
+            .and_then(|v| v.checked_div(10_000))
+            .ok_or(MochiError::MathOverflow)
+    };
+    // A listing override is a single seller-side fee, as is the legacy vault-wide fee.
+    let maker_taker = vault_state.maker_fee_bps > 0 || vault_state.taker_fee_bps > 0;
+    let (maker_fee, taker_fee) = match listing.fee_bps_override {
+        Some(bps) => (bps_of_price(bps)?, 0),
+        None if maker_taker => (
+            bps_of_price(vault_state.maker_fee_bps)?,
+            bps_of_price(vault_state.taker_fee_bps)?,
+        ),
+        None => (bps_of_price(vault_state.marketplace_fee_bps)?, 0),
+    };
+    let fee = maker_fee
+        .checked_add(taker_fee)
+        .ok_or(MochiError::MathOverflow)?;
+    // Royalties are only collected for assets of a verified collection.
+    let terms = vault_state.royalty_terms(asset_collection(core_asset));
+    let (royalty, royalty_recipient) = match terms {
+        Some((bps, recipient)) => (bps_of_price(bps)?, recipient),
+        None => (0, Pubkey::default()),
+    };
+    let seller_receives = price
+        .checked_sub(maker_fee)
+        .and_then(|v| v.checked_sub(royalty))
+        .ok_or(MochiError::MathOverflow)?;
+    let buyer_pays = price
+        .checked_add(taker_fee)
+        .ok_or(MochiError::MathOverflow)?;
+    Ok(FillQuote {
+        buyer_pays,
+        fee,
+        royalty,
+        seller_receives,
+        royalty_enforced: terms.is_some(),
+        royalty_recipient,
+    })
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
-pub enum PackState {
-    Uninitialized,
-    PendingDecision,
-    Accepted,
-    Rejected,
-    Expired,
+/// Marks the leading `rare_templates.len()` records of `accounts` Reserved for `user`,
+/// checking each is an Available Rare+ card of this vault with the expected template.
+/// Every record is checked, and the keys are checked to be distinct, before any is written.
+fn reserve_rare_records(
+    vault_state: &Pubkey,
+    user: &Pubkey,
+    accounts: &[AccountInfo],
+    rare_templates: &[u32],
+    mut inventory: Option<&mut RarityInventory>,
+) -> Result<Vec<Pubkey>> {
+    require!(
+        accounts.len() >= rare_templates.len(),
+        MochiError::InvalidCardCount
+    );
+    let mut rare_keys: Vec<Pubkey> = Vec::with_capacity(rare_templates.len());
+    let mut records = Vec::with_capacity(rare_templates.len());
+    for (acc_info, template_id) in accounts.iter().zip(rare_templates) {
+        require!(!rare_keys.contains(acc_info.key), MochiError::DuplicateCard);
+        let card_record = load_card_record(acc_info)?;
+        require_keys_eq!(
+            card_record.vault_state,
+            *vault_state,
+            MochiError::VaultMismatch
+        );
+        require!(
+            card_record.status == CardStatus::Available,
+            MochiError::CardNotAvailable
+        );
+        require!(
+            is_rare_or_above(&card_record.rarity),
+            MochiError::CardTooCommon
+        );
+        require!(
+            card_record.template_id == *template_id,
+            MochiError::TemplateMismatch
+        );
+        rare_keys.push(acc_info.key());
+        records.push(card_record);
+    }
+    for (acc_info, mut card_record) in accounts.iter().zip(records) {
+        card_record.status = CardStatus::Reserved;
+        card_record.owner = *user;
+        if let Some(inventory) = inventory.as_deref_mut() {
+            inventory.remove(&card_record.rarity);
+        }
+        persist_card_record(&card_record, acc_info)?;
+    }
+    Ok(rare_keys)
+}
+
+/// `count` distinct indices below `pool_len` (partial Fisher-Yates keyed by `entropy`).
+fn pick_random_indices(entropy: [u8; 32], pool_len: usize, count: usize) -> Vec<usize> {
+    let mut pool: Vec<usize> = (0..pool_len).collect();
+    let mut state = entropy;
+    for i in 0..count {
+        state = hashv(&[&state, &(i as u32).to_le_bytes()]).to_bytes();
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&state[..8]);
+        let r = u64::from_le_bytes(word);
+        let j = i + (r % (pool_len - i) as u64) as usize;
+        pool.swap(i, j);
+    }
+    pool.truncate(count);
+    pool
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum ListingStatus {
-    Active,
-    Filled,
-    Cancelled,
-    Burned,
-    Deprecated,
+/// `price` less `discount_bps` basis points, rounded in the buyer's favour.
+fn apply_discount(price: u64, discount_bps: u16) -> Result<u64> {
+    let off = (price as u128)
+        .checked_mul(discount_bps as u128)
+        .ok_or(MochiError::MathOverflow)?
+        .div_ceil(10_000);
+    Ok(price.saturating_sub(off as u64))
 }
 
-#[error_code]
-pub enum MochiError {
-    #[msg("Unauthorized")]
-    Unauthorized,
-    #[msg("Invalid price")]
-    InvalidPrice,
-    #[msg("Insufficient funds")]
-    InsufficientFunds,
-    #[msg("Invalid card count")]
-    InvalidCardCount,
-    #[msg("Card not available")]
-    CardNotAvailable,
-    #[msg("Vault mismatch")]
-    VaultMismatch,
-    #[msg("Listing invalid state")]
-    InvalidListingState,
-    #[msg("Session already exists")]
-    SessionExists,
-    #[msg("Invalid session state")]
-    InvalidSessionState,
-    #[msg("Session expired")]
-    SessionExpired,
-    #[msg("Session not expired")]
-    SessionNotExpired,
-    #[msg("Card not reserved")]
-    CardNotReserved,
-    #[msg("Reward disabled")]
-    RewardDisabled,
-    #[msg("Asset mismatch")]
-    AssetMismatch,
-    #[msg("Math overflow")]
-    MathOverflow,
-    #[msg("Missing token account")]
-    MissingTokenAccount,
-    #[msg("Mint mismatch")]
-    MintMismatch,
-    #[msg("Core CPI error")]
-    CoreCpiError,
-    #[msg("Too many Rare+ cards provided")]
-    TooManyRareCards,
-    #[msg("Card rarity must be Rare or above")]
-    CardTooCommon,
-    #[msg("Card template mismatch")]
-    TemplateMismatch,
-    #[msg("Card key mismatch")]
-    CardKeyMismatch,
-    #[msg("Rarity mismatch")]
-    RarityMismatch,
+/// Applies `update` to the seller listing index PDA at `index_info` when it exists. The PDA
+/// is always passed, so a caller can't leave an existing index stale; sellers who listed
+/// before the index existed have none, and that is skipped.
+fn update_seller_index(
+    index_info: &AccountInfo,
+    update: impl FnOnce(&mut SellerListingIndex) -> Result<()>,
+) -> Result<()> {
+    if index_info.data_is_empty() {
+        return Ok(());
+    }
+    require_keys_eq!(*index_info.owner, crate::ID, MochiError::InvalidPda);
+    let mut index = SellerListingIndex::try_deserialize(&mut &index_info.data.borrow()[..])?;
+    update(&mut index)?;
+    write_account(&index, index_info)
+}
+
+/// Reads a program-owned CardRecord without tying it to the `'info` lifetime, so records
+/// can come from a rebuilt account slice.
+fn load_card_record(acc_info: &AccountInfo) -> Result<CardRecord> {
+    require_keys_eq!(*acc_info.owner, crate::ID, MochiError::InvalidPda);
+    CardRecord::try_deserialize(&mut &acc_info.try_borrow_data()?[..])
 }
 
 fn persist_card_record(card_record: &CardRecord, acc_info: &AccountInfo) -> Result<()> {
+    write_account(card_record, acc_info)
+}
+
+fn write_account<T: AccountSerialize>(value: &T, acc_info: &AccountInfo) -> Result<()> {
     let mut data = acc_info.try_borrow_mut_data()?;
     let mut cursor = std::io::Cursor::new(&mut data[..]);
-    card_record.try_serialize(&mut cursor)?;
+    value.try_serialize(&mut cursor)?;
     Ok(())
 }
 
+/// Create the program PDA at `seeds` (bump found here) paid by `payer`, or accept it if it
+/// already exists with room for `space`. Returns whether it was created.
+fn init_pda<'info>(
+    payer: &AccountInfo<'info>,
+    target: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<bool> {
+    let (expected, bump) = Pubkey::find_program_address(seeds, program_id);
+    require_keys_eq!(target.key(), expected, MochiError::InvalidPda);
+    if !target.data_is_empty() {
+        require_keys_eq!(*target.owner, *program_id, MochiError::InvalidPda);
+        require!(target.data_len() >= space, MochiError::InvalidPda);
+        return Ok(false);
+    }
+    let bump_arr = [bump];
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(&bump_arr);
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            target.key,
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            program_id,
+        ),
+        &[payer.clone(), target.clone(), system_program.clone()],
+        &[&signer_seeds],
+    )?;
+    Ok(true)
+}
+
+/// `deposit_card`'s per-template cap for accounts passed outside the context: creates the
+/// TemplateStats PDA on first use, then counts one deposit against `max_per_template`.
+fn count_template_deposit<'info>(
+    stats_info: &AccountInfo<'info>,
+    vault_key: &Pubkey,
+    template_id: u32,
+    max_per_template: u32,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let template_bytes = template_id.to_le_bytes();
+    let seeds: [&[u8]; 3] = [TEMPLATE_STATS_SEED, vault_key.as_ref(), &template_bytes];
+    let created = init_pda(
+        payer,
+        stats_info,
+        8 + TemplateStats::SIZE,
+        &seeds,
+        system_program,
+        program_id,
+    )?;
+    let mut stats = if created {
+        TemplateStats {
+            vault_state: *vault_key,
+            template_id,
+            deposited_count: 0,
+            bump: Pubkey::find_program_address(&seeds, program_id).1,
+        }
+    } else {
+        TemplateStats::try_deserialize(&mut &stats_info.data.borrow()[..])?
+    };
+    require!(
+        max_per_template == 0 || stats.deposited_count < max_per_template,
+        MochiError::TemplateCapReached
+    );
+    stats.deposited_count = stats
+        .deposited_count
+        .checked_add(1)
+        .ok_or(MochiError::MathOverflow)?;
+    write_account(&stats, stats_info)
+}
+
 fn is_rare_or_above(rarity: &Rarity) -> bool {
     matches!(
         rarity,
@@ -2549,6 +10902,273 @@ fn is_rare_or_above(rarity: &Rarity) -> bool {
     )
 }
 
+/// sha256 over the little-endian u16 entries of an odds table.
+fn odds_table_hash(odds_table: &[u16]) -> [u8; 32] {
+    let bytes: Vec<u8> = odds_table.iter().flat_map(|v| v.to_le_bytes()).collect();
+    hash(&bytes).to_bytes()
+}
+
+/// sha256 over the ordered candidate record keys an `open_pack_random` draw picked from.
+fn candidate_pool_hash(candidates: &[AccountInfo]) -> [u8; 32] {
+    let keys: Vec<&[u8]> = candidates.iter().map(|acc| acc.key.as_ref()).collect();
+    hashv(&keys).to_bytes()
+}
+
+/// What an expiring V1 session that was only partly batch-claimed refunds: `refund_bps` of
+/// `paid_amount`, pro-rated by the slot prices of the unclaimed cards (by card count when no
+/// prices were recorded). Untouched and fully claimed sessions refund nothing.
+fn partial_claim_refund(
+    paid_amount: u64,
+    claimed: u8,
+    claimed_value: u128,
+    unclaimed_value: u128,
+    refund_bps: u16,
+) -> Result<u64> {
+    if claimed == 0 || claimed as usize >= PACK_CARD_COUNT {
+        return Ok(0);
+    }
+    let paid = paid_amount as u128;
+    let unclaimed_share = (paid * unclaimed_value)
+        .checked_div(claimed_value + unclaimed_value)
+        .unwrap_or(paid * (PACK_CARD_COUNT - claimed as usize) as u128 / PACK_CARD_COUNT as u128);
+    u64::try_from(unclaimed_share * refund_bps as u128 / 10_000)
+        .map_err(|_| error!(MochiError::MathOverflow))
+}
+
+/// sha256 over the little-endian u32 template ids of a pack, in slot order.
+fn manifest_hash(manifest: &[u32]) -> [u8; 32] {
+    let bytes: Vec<u8> = manifest.iter().flat_map(|v| v.to_le_bytes()).collect();
+    hash(&bytes).to_bytes()
+}
+
+/// Airdrop leaf: sha256(0x00 || wallet || asset || template_id LE || leaf_index LE).
+fn airdrop_leaf(wallet: &Pubkey, asset: &Pubkey, template_id: u32, leaf_index: u32) -> [u8; 32] {
+    hashv(&[
+        &[0u8],
+        wallet.as_ref(),
+        asset.as_ref(),
+        &template_id.to_le_bytes(),
+        &leaf_index.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Sorted-pair Merkle proof check; interior nodes are sha256(0x01 || min || max).
+fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        let (a, b) = if node <= *sibling {
+            (node, *sibling)
+        } else {
+            (*sibling, node)
+        };
+        hashv(&[&[1u8], &a, &b]).to_bytes()
+    });
+    computed == root
+}
+
+/// Bit for a rarity in rarity bitmasks (bit index = enum rank).
+fn rarity_bit(rarity: &Rarity) -> u16 {
+    1u16 << (rarity.clone() as u16)
+}
+
+/// Sellback rate for a session holding `rarities`. With dynamic buyback on, each rare shifts
+/// `buyback_bps` by `buyback_step_bps` per card its rarity's stock sits below (up) or above
+/// (down) `buyback_target_count`; the mean shift is applied and the result clamped to
+/// [`min_buyback_bps`, `max_buyback_bps`]. Otherwise, and without stock counts, `buyback_bps`.
+fn dynamic_buyback_bps(
+    vault_state: &VaultState,
+    inventory: Option<&RarityInventory>,
+    rarities: &[Rarity],
+) -> u16 {
+    let inventory = match inventory {
+        Some(inventory) if vault_state.buyback_target_count > 0 && !rarities.is_empty() => {
+            inventory
+        }
+        _ => return vault_state.buyback_bps,
+    };
+    let target = vault_state.buyback_target_count as i128;
+    let step = vault_state.buyback_step_bps as i128;
+    let total_shift: i128 = rarities
+        .iter()
+        .map(|rarity| (target - inventory.available_count[rarity.clone() as usize] as i128) * step)
+        .sum();
+    let shift = total_shift / rarities.len() as i128;
+    (vault_state.buyback_bps as i128 + shift).clamp(
+        vault_state.min_buyback_bps as i128,
+        vault_state.max_buyback_bps as i128,
+    ) as u16
+}
+
+/// The vault's RarityInventory when passed; required once `set_rarity_inventory` has run.
+fn tracked_inventory<'a>(
+    vault_state: &VaultState,
+    inventory: &'a mut Option<Account<'_, RarityInventory>>,
+) -> Result<Option<&'a mut RarityInventory>> {
+    require!(
+        !vault_state.rarity_inventory_enabled || inventory.is_some(),
+        MochiError::MissingRarityInventory
+    );
+    Ok(inventory.as_deref_mut())
+}
+
+/// Keep path shared by claim_pack_v2 and claim_gift_v2: moves the session's reserved rares
+/// (reserved to the purchaser, `session.user`) to `new_owner` and accepts the session.
+/// `payer` is the signing claimer and funds any rent the Core transfer needs.
+fn claim_session_rares<'info>(
+    session: &mut PackSessionV2,
+    card_accounts: &[AccountInfo<'info>],
+    asset_accounts: &[AccountInfo<'info>],
+    new_owner: &AccountInfo<'info>,
+    cpi: &CoreCpi<'_, 'info>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        session.state == PackState::PendingDecision,
+        MochiError::InvalidSessionState
+    );
+    require!(now <= session.expires_at, MochiError::SessionExpired);
+    require!(session.is_paid(), MochiError::LayawayUnpaid);
+
+    let rare_count = session.rare_card_keys.len();
+    require!(
+        card_accounts.len() == rare_count && asset_accounts.len() == rare_count,
+        MochiError::InvalidCardCount
+    );
+
+    for i in 0..rare_count {
+        let acc_info = &card_accounts[i];
+        require_keys_eq!(
+            acc_info.key(),
+            session.rare_card_keys[i],
+            MochiError::CardKeyMismatch
+        );
+        // Sessions written before open rejected duplicate keys may list a rare twice.
+        if session.rare_card_keys[..i].contains(acc_info.key) {
+            continue;
+        }
+        require_keys_eq!(*acc_info.owner, crate::ID, MochiError::CardKeyMismatch);
+        let mut card_record = CardRecord::try_deserialize(&mut &acc_info.data.borrow()[..])?;
+        require!(
+            card_record.status == CardStatus::Reserved,
+            MochiError::CardNotReserved
+        );
+        require_keys_eq!(card_record.owner, session.user, MochiError::Unauthorized);
+        let asset_info = &asset_accounts[i];
+        require_keys_eq!(
+            asset_info.key(),
+            card_record.core_asset,
+            MochiError::AssetMismatch
+        );
+        transfer_core_asset(
+            asset_info,
+            cpi.authority,
+            cpi.payer,
+            new_owner,
+            cpi.vault_state,
+            cpi.vault_bump,
+            cpi.authority_seed,
+            cpi.system_program,
+            cpi.mpl_core_program,
+        )?;
+        card_record.status = CardStatus::UserOwned;
+        card_record.owner = new_owner.key();
+        persist_card_record(&card_record, acc_info)?;
+    }
+
+    session.state = PackState::Accepted;
+    Ok(())
+}
+
+/// Order `accounts` to match `expected_cards` using explicit (card_record, core_asset) pairs
+/// instead of position. Each record's `core_asset` is still checked against its asset by the caller.
+fn resolve_card_pairs<'info>(
+    accounts: &[AccountInfo<'info>],
+    expected_cards: &[Pubkey],
+    pairs: &[CardAssetPair],
+) -> Result<(Vec<AccountInfo<'info>>, Vec<AccountInfo<'info>>)> {
+    require!(
+        pairs.len() == expected_cards.len(),
+        MochiError::InvalidCardCount
+    );
+    let mut cards = Vec::with_capacity(pairs.len());
+    let mut assets = Vec::with_capacity(pairs.len());
+    for card_key in expected_cards {
+        let pair = pairs
+            .iter()
+            .find(|p| p.card_record == *card_key)
+            .ok_or(MochiError::CardKeyMismatch)?;
+        let card = find_account(accounts, &pair.card_record).ok_or(MochiError::CardKeyMismatch)?;
+        let asset = find_account(accounts, &pair.core_asset).ok_or(MochiError::AssetMismatch)?;
+        cards.push(card.clone());
+        assets.push(asset.clone());
+    }
+    Ok((cards, assets))
+}
+
+fn find_account<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    key: &Pubkey,
+) -> Option<&'a AccountInfo<'info>> {
+    accounts.iter().find(|acc| acc.key == key)
+}
+
+/// Frees `acc_info` if it is a CardRecord of `vault_key` still reserved to `user`; anything
+/// else (listed, claimed, burned, or held by another session) is left untouched.
+fn release_session_record<'info>(
+    acc_info: &'info AccountInfo<'info>,
+    vault_key: &Pubkey,
+    user: &Pubkey,
+    vault_authority: &Pubkey,
+    inventory: Option<&mut RarityInventory>,
+) -> Result<bool> {
+    let Ok(mut card_record) = Account::<CardRecord>::try_from(acc_info) else {
+        return Ok(false);
+    };
+    if card_record.vault_state != *vault_key
+        || card_record.status != CardStatus::Reserved
+        || card_record.owner != *user
+    {
+        return Ok(false);
+    }
+    card_record.status = CardStatus::Available;
+    card_record.owner = *vault_authority;
+    if let Some(inventory) = inventory {
+        inventory.add(&card_record.rarity)?;
+    }
+    persist_card_record(&card_record, acc_info)?;
+    Ok(true)
+}
+
+/// Returns a session's reserved rares to stock. `accounts` starts with the session's rare
+/// CardRecords, in `rare_card_keys` order.
+fn free_reserved_rares<'info>(
+    session: &PackSessionV2,
+    vault_authority: &Pubkey,
+    mut inventory: Option<&mut RarityInventory>,
+    accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let (card_accounts, _, _) = split_rare_accounts(accounts, session.rare_card_keys.len())?;
+    for (idx, acc_info) in card_accounts.iter().enumerate() {
+        require_keys_eq!(
+            acc_info.key(),
+            session.rare_card_keys[idx],
+            MochiError::CardKeyMismatch
+        );
+        let mut card_record: Account<CardRecord> = Account::try_from(acc_info)?;
+        require!(
+            card_record.status == CardStatus::Reserved,
+            MochiError::CardNotReserved
+        );
+        card_record.status = CardStatus::Available;
+        card_record.owner = *vault_authority;
+        if let Some(inventory) = inventory.as_deref_mut() {
+            inventory.add(&card_record.rarity)?;
+        }
+        persist_card_record(&card_record, acc_info)?;
+    }
+    Ok(())
+}
+
 fn split_rare_accounts<'info>(
     accounts: &'info [AccountInfo<'info>],
     rare_count: usize,
@@ -2606,6 +11226,18 @@ fn partition_half_accounts<'info>(
     Ok((cards, assets, extras))
 }
 
+/// Seed suffix for gacha vault `vault_id`. Vault 0 uses an empty suffix, which derives the
+/// same address as the original `[GACHA_VAULT_SEED]` singleton, so the existing vault (and
+/// its authority, card records and sessions) keep their addresses; `migrate_vault_state`
+/// zero-fills `vault_id` on it.
+fn gacha_vault_seed(vault_id: u64) -> Vec<u8> {
+    if vault_id == 0 {
+        Vec::new()
+    } else {
+        vault_id.to_le_bytes().to_vec()
+    }
+}
+
 fn transfer_core_asset<'info>(
     asset: &AccountInfo<'info>,
     authority: &AccountInfo<'info>,
@@ -2620,6 +11252,7 @@ fn transfer_core_asset<'info>(
     let bump_arr = [vault_bump];
     let seeds: [&[u8]; 3] = [authority_seed, vault_state.as_ref(), &bump_arr];
     let signer: &[&[&[u8]]] = &[&seeds];
+    check_core_program(mpl_core_program)?;
     let mut builder = TransferV1CpiBuilder::new(mpl_core_program);
     builder
         .asset(asset)
@@ -2629,7 +11262,7 @@ fn transfer_core_asset<'info>(
         .system_program(Some(system_program));
     builder
         .invoke_signed(signer)
-        .map_err(|_| MochiError::CoreCpiError.into())
+        .map_err(|err| core_cpi_error("transfer", asset, err))
 }
 
 fn burn_core_asset<'info>(
@@ -2644,6 +11277,7 @@ fn burn_core_asset<'info>(
 ) -> Result<()> {
     let seeds = &[authority_seed, vault_state.as_ref(), &[vault_bump]];
     let signer = &[&seeds[..]];
+    check_core_program(mpl_core_program)?;
     let mut builder = BurnV1CpiBuilder::new(mpl_core_program);
     builder
         .asset(asset)
@@ -2652,8 +11286,76 @@ fn burn_core_asset<'info>(
         .system_program(Some(system_program));
     builder
         .invoke_signed(signer)
-        .map_err(|_| MochiError::CoreCpiError.into())
+        .map_err(|err| core_cpi_error("burn", asset, err))
+}
+
+/// Signer and program accounts for a Core CPI signed by a vault authority PDA
+/// (`authority_seed` + `vault_state` + `vault_bump`), rent paid by `payer`.
+struct CoreCpi<'a, 'info> {
+    authority: &'a AccountInfo<'info>,
+    payer: &'a AccountInfo<'info>,
+    vault_state: &'a Pubkey,
+    vault_bump: u8,
+    authority_seed: &'a [u8],
+    system_program: &'a AccountInfo<'info>,
+    mpl_core_program: &'a AccountInfo<'info>,
+}
+
+/// Freeze an escrowed asset so nothing but the vault authority can move it while listed.
+fn freeze_core_asset<'info>(asset: &AccountInfo<'info>, cpi: &CoreCpi<'_, 'info>) -> Result<()> {
+    let bump_arr = [cpi.vault_bump];
+    let seeds: [&[u8]; 3] = [cpi.authority_seed, cpi.vault_state.as_ref(), &bump_arr];
+    let signer: &[&[&[u8]]] = &[&seeds];
+    check_core_program(cpi.mpl_core_program)?;
+    let mut builder = AddPluginV1CpiBuilder::new(cpi.mpl_core_program);
+    builder
+        .asset(asset)
+        .payer(cpi.payer)
+        .authority(Some(cpi.authority))
+        .system_program(cpi.system_program)
+        .plugin(Plugin::FreezeDelegate(FreezeDelegate { frozen: true }));
+    builder
+        .invoke_signed(signer)
+        .map_err(|err| core_cpi_error("freeze", asset, err))
+}
+
+/// Thaw and drop the freeze plugin before the asset leaves escrow.
+/// Listings created before freezing was introduced carry no plugin; those are a no-op.
+fn thaw_core_asset<'info>(asset: &AccountInfo<'info>, cpi: &CoreCpi<'_, 'info>) -> Result<()> {
+    if mpl_core::fetch_plugin::<mpl_core::accounts::BaseAssetV1, FreezeDelegate>(
+        asset,
+        PluginType::FreezeDelegate,
+    )
+    .is_err()
+    {
+        return Ok(());
+    }
+    check_core_program(cpi.mpl_core_program)?;
+    let bump_arr = [cpi.vault_bump];
+    let seeds: [&[u8]; 3] = [cpi.authority_seed, cpi.vault_state.as_ref(), &bump_arr];
+    let signer: &[&[&[u8]]] = &[&seeds];
+    let mut update = UpdatePluginV1CpiBuilder::new(cpi.mpl_core_program);
+    update
+        .asset(asset)
+        .payer(cpi.payer)
+        .authority(Some(cpi.authority))
+        .system_program(cpi.system_program)
+        .plugin(Plugin::FreezeDelegate(FreezeDelegate { frozen: false }));
+    update
+        .invoke_signed(signer)
+        .map_err(|err| core_cpi_error("thaw", asset, err))?;
+    let mut remove = RemovePluginV1CpiBuilder::new(cpi.mpl_core_program);
+    remove
+        .asset(asset)
+        .payer(cpi.payer)
+        .authority(Some(cpi.authority))
+        .system_program(cpi.system_program)
+        .plugin_type(PluginType::FreezeDelegate);
+    remove
+        .invoke_signed(signer)
+        .map_err(|err| core_cpi_error("remove freeze", asset, err))
 }
+
 fn transfer_core_asset_user<'info>(
     asset: &AccountInfo<'info>,
     authority: &AccountInfo<'info>,
@@ -2662,6 +11364,7 @@ fn transfer_core_asset_user<'info>(
     system_program: &AccountInfo<'info>,
     mpl_core_program: &AccountInfo<'info>,
 ) -> Result<()> {
+    check_core_program(mpl_core_program)?;
     let mut builder = TransferV1CpiBuilder::new(mpl_core_program);
     builder
         .asset(asset)
@@ -2671,5 +11374,370 @@ fn transfer_core_asset_user<'info>(
         .system_program(Some(system_program));
     builder
         .invoke()
-        .map_err(|_| MochiError::CoreCpiError.into())
+        .map_err(|err| core_cpi_error("user transfer", asset, err))
+}
+
+/// Record status/owner implied by a listing: Active listings hold the card in escrow;
+/// closed ones defer to whoever owns the Core asset now.
+fn listing_record_state(
+    listing: &Listing,
+    core_asset: &AccountInfo,
+    vault_authority: &Pubkey,
+) -> Result<(CardStatus, Pubkey)> {
+    match listing.status {
+        ListingStatus::Active => Ok((CardStatus::Reserved, *vault_authority)),
+        ListingStatus::Cancelled | ListingStatus::Filled => {
+            require_keys_eq!(*core_asset.owner, mpl_core::ID, MochiError::AssetMismatch);
+            let asset = mpl_core::accounts::BaseAssetV1::from_bytes(&core_asset.data.borrow())
+                .map_err(|_| error!(MochiError::AssetMismatch))?;
+            let status = if asset.owner == *vault_authority {
+                CardStatus::Reserved
+            } else {
+                CardStatus::UserOwned
+            };
+            Ok((status, asset.owner))
+        }
+        _ => err!(MochiError::InvalidListingState),
+    }
+}
+
+/// Transfer the vault's claim fee from the claimer to the treasury; a zero fee is a no-op.
+fn charge_claim_fee<'info>(
+    vault_state: &VaultState,
+    claimer: &AccountInfo<'info>,
+    treasury: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    if vault_state.claim_fee_lamports == 0 {
+        return Ok(());
+    }
+    require_keys_eq!(
+        treasury.key(),
+        vault_state.treasury,
+        MochiError::VaultMismatch
+    );
+    invoke(
+        &system_instruction::transfer(claimer.key, treasury.key, vault_state.claim_fee_lamports),
+        &[claimer.clone(), treasury.clone(), system_program.clone()],
+    )?;
+    Ok(())
+}
+
+/// `mpl_core_program` is an unchecked account everywhere; refuse to CPI into anything else.
+fn check_core_program(mpl_core_program: &AccountInfo) -> Result<()> {
+    require_keys_eq!(
+        mpl_core_program.key(),
+        mpl_core::ID,
+        MochiError::InvalidCoreProgram
+    );
+    Ok(())
+}
+
+/// Enforce the marketplace cosigner gate for a fill at `price_lamports`.
+fn check_fill_cosigner(
+    vault_state: &VaultState,
+    price_lamports: u64,
+    cosigner: Option<&Signer>,
+) -> Result<()> {
+    let required = match vault_state.cosigner {
+        Some(key) if price_lamports >= vault_state.cosign_threshold_lamports => key,
+        _ => return Ok(()),
+    };
+    let signer = cosigner.ok_or(MochiError::CosignerRequired)?;
+    require_keys_eq!(signer.key(), required, MochiError::CosignerRequired);
+    Ok(())
+}
+
+/// Take the vault lock for an asset-moving instruction. The flag is written back before any
+/// CPI runs, so a call that re-enters through a CPI target sees it and fails.
+fn lock_vault(vault_state: &mut Account<VaultState>) -> Result<()> {
+    require!(!vault_state.locked, MochiError::VaultLocked);
+    vault_state.locked = true;
+    vault_state.exit(&crate::ID)
+}
+
+/// Release the lock; Anchor persists the cleared flag when the instruction exits.
+fn unlock_vault(vault_state: &mut Account<VaultState>) {
+    vault_state.locked = false;
+}
+
+/// Log the underlying mpl-core failure before collapsing it into `CoreCpiError`,
+/// so failed transactions show the real cause.
+fn core_cpi_error(op: &str, asset: &AccountInfo, err: ProgramError) -> anchor_lang::error::Error {
+    msg!("mpl-core {} failed for asset {}: {:?}", op, asset.key, err);
+    MochiError::CoreCpiError.into()
+}
+
+/// Shared body of peek_pack and reveal_pack_v2.
+fn pack_peek<'info>(
+    session: &PackSessionV2,
+    accounts: &'info [AccountInfo<'info>],
+) -> Result<PackPeek> {
+    require!(
+        session.state == PackState::PendingDecision,
+        MochiError::InvalidSessionState
+    );
+    let rare_count = session.rare_card_keys.len();
+    require!(accounts.len() == rare_count, MochiError::InvalidCardCount);
+    let mut rarities = Vec::with_capacity(rare_count);
+    for (acc_info, key) in accounts.iter().zip(&session.rare_card_keys) {
+        require_keys_eq!(acc_info.key(), *key, MochiError::CardKeyMismatch);
+        let record: Account<CardRecord> = Account::try_from(acc_info)?;
+        rarities.push(record.rarity.clone());
+    }
+    Ok(PackPeek {
+        rare_templates: session.rare_templates.clone(),
+        rare_rarities: rarities,
+        manifest_hash: session.manifest_hash,
+        expires_at: session.expires_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An all-zero account body: every number 0, every option None, every enum its first variant.
+    fn zeroed<T: AnchorDeserialize>(size: usize) -> T {
+        T::deserialize(&mut &vec![0u8; size][..]).unwrap()
+    }
+
+    fn listing(price_lamports: u64) -> Listing {
+        let mut listing: Listing = zeroed(Listing::SIZE);
+        listing.price_lamports = price_lamports;
+        listing
+    }
+
+    /// Quotes a fill against an asset outside any verified collection (no royalty).
+    fn quote(listing: &Listing, vault_state: &VaultState) -> FillQuote {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::default();
+        let mut lamports = 0;
+        let mut data = [];
+        let asset = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        fill_quote(listing, vault_state, &asset).unwrap()
+    }
+
+    #[test]
+    fn maker_taker_fees_split_between_parties() {
+        let mut vault_state: VaultState = zeroed(VaultState::SIZE);
+        vault_state.marketplace_fee_bps = 1_000;
+        vault_state.maker_fee_bps = 100;
+        vault_state.taker_fee_bps = 50;
+        let quote = quote(&listing(1_000_000), &vault_state);
+        assert_eq!(quote.seller_receives, 990_000);
+        assert_eq!(quote.buyer_pays, 1_005_000);
+        assert_eq!(quote.fee, 15_000);
+        assert_eq!(quote.buyer_pays, quote.fee + quote.seller_receives);
+    }
+
+    #[test]
+    fn single_fee_is_seller_side() {
+        let mut vault_state: VaultState = zeroed(VaultState::SIZE);
+        vault_state.marketplace_fee_bps = 250;
+        let quote = quote(&listing(1_000_000), &vault_state);
+        assert_eq!(quote.buyer_pays, 1_000_000);
+        assert_eq!(quote.fee, 25_000);
+        assert_eq!(quote.seller_receives, 975_000);
+    }
+
+    #[test]
+    fn fee_rounding_follows_vault_policy() {
+        let mut vault_state: VaultState = zeroed(VaultState::SIZE);
+        vault_state.marketplace_fee_bps = 250;
+        // 999 * 2.5% = 24.975 lamports.
+        let truncated = quote(&listing(999), &vault_state);
+        assert_eq!(truncated.fee, 24);
+        assert_eq!(truncated.seller_receives, 975);
+        vault_state.round_fees_up = true;
+        let ceiled = quote(&listing(999), &vault_state);
+        assert_eq!(ceiled.fee, 25);
+        assert_eq!(ceiled.seller_receives, 974);
+        // Exact fees are never bumped.
+        assert_eq!(quote(&listing(1_000), &vault_state).fee, 25);
+    }
+
+    #[test]
+    fn fee_override_replaces_vault_fees() {
+        let mut vault_state: VaultState = zeroed(VaultState::SIZE);
+        vault_state.marketplace_fee_bps = 500;
+        vault_state.maker_fee_bps = 100;
+        vault_state.taker_fee_bps = 100;
+        let mut promo = listing(1_000_000);
+        promo.fee_bps_override = Some(0);
+        let free = quote(&promo, &vault_state);
+        assert_eq!(free.fee, 0);
+        assert_eq!(free.buyer_pays, 1_000_000);
+        assert_eq!(free.seller_receives, 1_000_000);
+        promo.fee_bps_override = Some(30);
+        let reduced = quote(&promo, &vault_state);
+        assert_eq!(reduced.fee, 3_000);
+        assert_eq!(reduced.buyer_pays, 1_000_000);
+        assert_eq!(reduced.seller_receives, 997_000);
+    }
+
+    #[test]
+    fn cosigner_required_only_at_threshold() {
+        let cosigner_key = Pubkey::new_unique();
+        let owner = Pubkey::default();
+        let mut lamports = 0;
+        let mut data = [];
+        let info = AccountInfo::new(
+            &cosigner_key,
+            true,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        let cosigner = Signer::try_from(&info).unwrap();
+
+        let mut vault_state: VaultState = zeroed(VaultState::SIZE);
+        vault_state.cosign_threshold_lamports = 1_000;
+        // No cosigner configured: nothing is required at any price.
+        assert!(check_fill_cosigner(&vault_state, 5_000, None).is_ok());
+
+        vault_state.cosigner = Some(cosigner_key);
+        assert!(check_fill_cosigner(&vault_state, 999, None).is_ok());
+        assert!(check_fill_cosigner(&vault_state, 1_000, None).is_err());
+        assert!(check_fill_cosigner(&vault_state, 1_000, Some(&cosigner)).is_ok());
+
+        vault_state.cosigner = Some(Pubkey::new_unique());
+        assert!(check_fill_cosigner(&vault_state, 1_000, Some(&cosigner)).is_err());
+    }
+
+    #[test]
+    fn listing_floor_is_per_currency_and_inclusive() {
+        let mut vault_state: VaultState = zeroed(VaultState::SIZE);
+        let mint = Some(Pubkey::new_unique());
+        // Zero floors accept anything.
+        assert!(vault_state.meets_listing_floor(0, &None));
+        assert!(vault_state.meets_listing_floor(0, &mint));
+
+        vault_state.min_listing_price_lamports = 10_000;
+        vault_state.min_listing_price_token = 50;
+        assert!(!vault_state.meets_listing_floor(9_999, &None));
+        assert!(vault_state.meets_listing_floor(10_000, &None));
+        assert!(!vault_state.meets_listing_floor(49, &mint));
+        assert!(vault_state.meets_listing_floor(50, &mint));
+    }
+
+    fn reward_schedule(reward_start: u64, decay_bps: u16) -> VaultState {
+        let mut vault_state: VaultState = zeroed(VaultState::SIZE);
+        vault_state.reward_per_pack = 7;
+        vault_state.reward_start = reward_start;
+        vault_state.reward_decay_bps_per_epoch = decay_bps;
+        vault_state.epoch_seconds = 100;
+        vault_state.reward_schedule_start = 1_000;
+        vault_state
+    }
+
+    #[test]
+    fn effective_reward_decays_per_whole_epoch() {
+        let vault_state = reward_schedule(1_000, 1_000);
+        assert_eq!(vault_state.effective_reward(500).unwrap(), 1_000);
+        assert_eq!(vault_state.effective_reward(1_000).unwrap(), 1_000);
+        assert_eq!(vault_state.effective_reward(1_099).unwrap(), 1_000);
+        assert_eq!(vault_state.effective_reward(1_100).unwrap(), 900);
+        assert_eq!(vault_state.effective_reward(1_250).unwrap(), 810);
+        assert_eq!(vault_state.effective_reward(1_300).unwrap(), 729);
+    }
+
+    #[test]
+    fn effective_reward_is_monotonic_and_reaches_zero() {
+        let vault_state = reward_schedule(u64::MAX, 250);
+        let mut previous = u64::MAX;
+        for epoch in 0..200 {
+            let reward = vault_state.effective_reward(1_000 + epoch * 100).unwrap();
+            assert!(reward <= previous);
+            previous = reward;
+        }
+        assert_eq!(vault_state.effective_reward(i64::MAX).unwrap(), 0);
+        // A 100% decay ends emissions after the first epoch.
+        assert_eq!(
+            reward_schedule(1_000, 10_000)
+                .effective_reward(1_100)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn effective_reward_falls_back_to_flat_reward() {
+        assert_eq!(
+            reward_schedule(0, 1_000).effective_reward(5_000).unwrap(),
+            7
+        );
+        let mut vault_state = reward_schedule(1_000, 1_000);
+        vault_state.epoch_seconds = 0;
+        assert_eq!(vault_state.effective_reward(5_000).unwrap(), 7);
+    }
+
+    #[test]
+    fn manifest_hash_covers_le_template_ids_in_order() {
+        let manifest = [1u32, 2, 0x0102_0304];
+        let mut bytes = Vec::new();
+        for template_id in manifest {
+            bytes.extend_from_slice(&template_id.to_le_bytes());
+        }
+        assert_eq!(manifest_hash(&manifest), hash(&bytes).to_bytes());
+        assert_ne!(
+            manifest_hash(&manifest),
+            manifest_hash(&[2, 1, 0x0102_0304])
+        );
+        assert_ne!(manifest_hash(&manifest), manifest_hash(&manifest[..2]));
+        assert_eq!(manifest_hash(&[]), hash(&[]).to_bytes());
+    }
+
+    #[test]
+    fn partial_claim_refund_covers_only_unclaimed_value() {
+        // Two of eleven cards claimed, worth 300 of the pack's 1_000 recorded value.
+        assert_eq!(
+            partial_claim_refund(1_100, 2, 300, 700, 10_000).unwrap(),
+            770
+        );
+        assert_eq!(
+            partial_claim_refund(1_100, 2, 300, 700, 5_000).unwrap(),
+            385
+        );
+        // Without recorded prices the refund follows the unclaimed card count.
+        assert_eq!(partial_claim_refund(1_100, 2, 0, 0, 10_000).unwrap(), 900);
+    }
+
+    #[test]
+    fn partial_claim_refund_skips_untouched_and_complete_sessions() {
+        assert_eq!(partial_claim_refund(1_100, 0, 0, 1_000, 10_000).unwrap(), 0);
+        assert_eq!(
+            partial_claim_refund(1_100, PACK_CARD_COUNT as u8, 1_000, 0, 10_000).unwrap(),
+            0
+        );
+        assert_eq!(partial_claim_refund(1_100, 2, 300, 700, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn rare_stock_skips_common_uncommon_and_energy() {
+        let mut inventory = RarityInventory {
+            vault_state: Pubkey::default(),
+            available_count: [0; RARITY_COUNT],
+            bump: 0,
+        };
+        inventory.available_count[Rarity::Common as usize] = 50;
+        inventory.available_count[Rarity::Uncommon as usize] = 20;
+        inventory.available_count[Rarity::Energy as usize] = 9;
+        assert_eq!(inventory.rare_stock(), 0);
+        inventory.available_count[Rarity::Rare as usize] = 4;
+        inventory.available_count[Rarity::MegaHyperRare as usize] = 1;
+        assert_eq!(inventory.rare_stock(), 5);
+    }
 }
@@ -48,6 +48,7 @@ from tx_builder import (
     build_fill_listing_ix,
     build_list_card_ix,
     build_cancel_listing_ix,
+    build_relist_ix,
     build_open_pack_ix,
     build_open_pack_v2_ix,
     build_sellback_pack_ix,
@@ -58,6 +59,7 @@ from tx_builder import (
     card_record_pda,
     instruction_to_dict,
     listing_pda,
+    seller_listing_index_pda,
     message_from_instructions,
     market_vault_authority_pda,
     market_vault_state_pda,
@@ -73,6 +75,7 @@ from tx_builder import (
     to_pubkey,
     vault_authority_pda,
     vault_state_pda,
+    manifest_hash,
     versioned_tx_b64,
     build_system_transfer_ix,
 )
@@ -2300,6 +2303,8 @@ def parse_pack_session_account(data: bytes) -> Optional[dict]:
             break
         rarity_prices.append(int.from_bytes(data[offset : offset + 8], "little"))
         offset += 8
+    # Sessions opened before nonces existed have no trailing nonce; treat as 0.
+    nonce = int.from_bytes(data[offset : offset + 8], "little") if len(data) >= offset + 8 else 0
     currency = "SOL" if currency_idx == 0 else "Token"
     state = PACK_STATE_LABELS[state_idx] if 0 <= state_idx < len(PACK_STATE_LABELS) else str(state_idx)
     return {
@@ -2312,6 +2317,7 @@ def parse_pack_session_account(data: bytes) -> Optional[dict]:
         "state": state,
         "client_seed_hash": client_seed_hash,
         "rarity_prices": rarity_prices,
+        "nonce": nonce,
     }
 
 
@@ -2361,7 +2367,11 @@ def parse_pack_session_v2_account(data: bytes) -> Optional[dict]:
     client_seed_hash = data[offset : offset + 32]
     offset += 32
     total_slots = data[offset] if offset < len(data) else 11
-    currency = "SOL" if currency_idx == 0 else "Token"
+    # total_slots, bump, reward_minted, manifest_hash, recipient, paid_sol_amount,
+    # full_price, entropy, candidate_pool_hash precede the nonce
+    nonce_offset = offset + 147
+    nonce = int.from_bytes(data[nonce_offset : nonce_offset + 8], "little") if len(data) >= nonce_offset + 8 else 0
+    currency = {0: "SOL", 2: "Both", 3: "Mochi"}.get(currency_idx, "Token")
     state = PACK_STATE_LABELS[state_idx] if 0 <= state_idx < len(PACK_STATE_LABELS) else str(state_idx)
     return {
         "user": user,
@@ -2374,6 +2384,7 @@ def parse_pack_session_v2_account(data: bytes) -> Optional[dict]:
         "state": state,
         "client_seed_hash": client_seed_hash,
         "total_slots": total_slots,
+        "nonce": nonce,
     }
 
 
@@ -2461,6 +2472,11 @@ def parse_listing_account(data: bytes) -> Optional[dict]:
         offset += 32
     status_idx = data[offset] if offset < len(data) else 0
     status = LISTING_STATUS_LABELS[status_idx] if 0 <= status_idx < len(LISTING_STATUS_LABELS) else str(status_idx)
+    offset += 1
+    # Listings created before fee overrides were added stop at status.
+    fee_bps_override = None
+    if len(data) >= offset + 3 and data[offset] == 1:
+        fee_bps_override = int.from_bytes(data[offset + 1 : offset + 3], "little")
     return {
         "vault_state": vault_state,
         "seller": seller,
@@ -2468,9 +2484,26 @@ def parse_listing_account(data: bytes) -> Optional[dict]:
         "price_lamports": price_lamports,
         "currency_mint": currency_mint,
         "status": status,
+        "fee_bps_override": fee_bps_override,
     }
 
 
+def parse_seller_listing_index(data: bytes) -> Optional[dict]:
+    offset = 8
+    if len(data) < offset + 32 + 32 + 4:
+        return None
+    vault_state = Pubkey.from_bytes(data[offset : offset + 32])
+    offset += 32
+    seller = Pubkey.from_bytes(data[offset : offset + 32])
+    offset += 32
+    count = int.from_bytes(data[offset : offset + 4], "little")
+    offset += 4
+    if len(data) < offset + 32 * count:
+        return None
+    listings = [Pubkey.from_bytes(data[offset + 32 * i : offset + 32 * (i + 1)]) for i in range(count)]
+    return {"vault_state": vault_state, "seller": seller, "listings": listings}
+
+
 def templates_to_csv(templates: List[Optional[int]]) -> str:
     return ",".join("" if t is None else str(t) for t in templates)
 
@@ -3056,6 +3089,7 @@ def build_pack_v2(req: PackBuildV2Request, db: Session = Depends(get_session)):
         rare_templates=rare_templates,
         user_currency_token=user_token_account,
         vault_currency_token=vault_token_account,
+        manifest=manifest_hash(template_ids),
     )
     expected_accounts: List[Tuple[str, Pubkey]] = [
         ("user", to_pubkey(req.wallet)),
@@ -3272,6 +3306,7 @@ def claim_pack_v2(req: SessionActionV2Request, db: Session = Depends(get_session
         vault_treasury=treasury,
         card_records=rare_cards,
         core_assets=core_assets,
+        nonce=session_info.get("nonce", 0),
     )
     compute_ix = set_compute_unit_limit(units=350_000)
     instructions = [compute_ix, ix]
@@ -3796,6 +3831,7 @@ def sellback_pack_v2(req: SessionActionV2Request, db: Session = Depends(get_sess
         vault_treasury=treasury,
         card_records=rare_cards,
         core_assets=core_assets,
+        nonce=session_info.get("nonce", 0),
         user_currency_token=to_pubkey(req.user_token_account) if req.user_token_account else None,
         vault_currency_token=to_pubkey(req.vault_token_account) if req.vault_token_account else None,
     )
@@ -4441,6 +4477,36 @@ def marketplace_listings(db: Session = Depends(get_session)):
     return results
 
 
+@app.get("/marketplace/seller/{wallet}/listings")
+def marketplace_seller_listings(wallet: str):
+    """Active listings of one seller, read from their on-chain SellerListingIndex."""
+    vault_state = market_vault_state_pda()
+    resp = sol_client.get_account_info(seller_listing_index_pda(vault_state, to_pubkey(wallet)))
+    if resp.value is None:
+        return {"seller": wallet, "listings": []}
+    index = parse_seller_listing_index(bytes(resp.value.data))
+    if not index:
+        raise HTTPException(status_code=500, detail="Unable to parse seller listing index")
+    listings = []
+    for listing_pk in index["listings"]:
+        info = sol_client.get_account_info(listing_pk)
+        if info.value is None:
+            continue
+        parsed = parse_listing_account(bytes(info.value.data))
+        if not parsed:
+            continue
+        listings.append(
+            {
+                "listing": str(listing_pk),
+                "core_asset": str(parsed["core_asset"]),
+                "price_lamports": parsed["price_lamports"],
+                "currency_mint": str(parsed["currency_mint"]) if parsed["currency_mint"] else None,
+                "status": parsed["status"],
+            }
+        )
+    return {"seller": wallet, "listings": listings}
+
+
 @app.post("/marketplace/list/build", response_model=TxResponse)
 def marketplace_list(req: ListRequest, db: Session = Depends(get_session)):
     vault_state = market_vault_state_pda()
@@ -4596,6 +4662,30 @@ def marketplace_fill(req: MarketplaceActionRequest, db: Session = Depends(get_se
     )
 
 
+@app.post("/marketplace/relist/build", response_model=TxResponse)
+def marketplace_relist(req: ListRequest):
+    if req.price_lamports <= 0:
+        raise HTTPException(status_code=400, detail="Price must be positive")
+    vault_state = market_vault_state_pda()
+    core_asset = to_pubkey(req.core_asset)
+    ix = build_relist_ix(
+        seller=to_pubkey(req.wallet),
+        vault_state=vault_state,
+        card_record=card_record_pda(vault_state, core_asset),
+        core_asset=core_asset,
+        listing=listing_pda(vault_state, core_asset),
+        vault_authority=market_vault_authority_pda(vault_state),
+        new_price_lamports=req.price_lamports,
+        new_currency_mint=req.currency_mint,
+    )
+    blockhash = get_latest_blockhash()
+    tx_b64 = message_from_instructions([ix], to_pubkey(req.wallet), blockhash)
+    instr = wrap_instruction_meta(instruction_to_dict(ix))
+
+    tx_v0_b64 = versioned_tx_b64(to_pubkey(req.wallet), blockhash, [ix])
+    return TxResponse(tx_b64=tx_b64, tx_v0_b64=tx_v0_b64, recent_blockhash=blockhash, instructions=[instr])
+
+
 @app.post("/marketplace/cancel/build", response_model=TxResponse)
 def marketplace_cancel(req: MarketplaceActionRequest, db: Session = Depends(get_session)):
     vault_state = market_vault_state_pda()
@@ -3,7 +3,7 @@ import hashlib
 import os
 from typing import List, Optional, Tuple
 
-from borsh_construct import CStruct, Enum, Option, U16, U32, U64, U8, Vec
+from borsh_construct import Bool, CStruct, Enum, Option, U16, U32, U64, U8, Vec
 from solders.instruction import AccountMeta, Instruction
 from solders.message import MessageV0
 from solders.hash import Hash
@@ -30,18 +30,24 @@ MARKETPLACE_VAULT_AUTHORITY_SEED = b"market_vault_authority"
 
 # Seed sale program (devnet mock)
 SEED_SALE_PROGRAM_ID = load_pubkey("SEED_SALE_PROGRAM_ID")
+SYSVAR_SLOT_HASHES_ID = Pubkey.from_string("SysvarS1otHashes111111111111111111111111111")
+SYSVAR_INSTRUCTIONS_ID = Pubkey.from_string("Sysvar1nstructions1111111111111111111111111")
+ED25519_PROGRAM_ID = Pubkey.from_string("Ed25519SigVerify111111111111111111111111111")
 
 
-CurrencyLayout = Enum("Sol" / CStruct(), "Token" / CStruct(), enum_name="Currency")
+CurrencyLayout = Enum("Sol" / CStruct(), "Token" / CStruct(), "Both" / CStruct(), "Mochi" / CStruct(), enum_name="Currency")
 OpenPackStartLayout = CStruct(
     "currency" / CurrencyLayout,
     "client_seed_hash" / U8[32],
     "rarity_prices" / Vec(U64),
+    "manifest_hash" / U8[32],
 )
 OpenPackV2Layout = CStruct(
     "currency" / CurrencyLayout,
     "client_seed_hash" / U8[32],
     "rare_templates" / Vec(U32),
+    "manifest_hash" / U8[32],
+    "recipient" / Option(U8[32]),
 )
 SeedInitLayout = CStruct(
     "start_ts" / U64,
@@ -49,8 +55,9 @@ SeedInitLayout = CStruct(
     "price_tokens_per_sol" / U64,
     "token_cap" / U64,
     "sol_cap_lamports" / U64,
+    "absorb_fee" / Bool,
 )
-SeedContributeLayout = CStruct("lamports" / U64)
+SeedContributeLayout = CStruct("lamports" / U64, "partial_fill" / Bool)
 ListCardLayout = CStruct(
     "price_lamports" / U64,
     "currency_mint" / Option(U8[32]),
@@ -58,6 +65,55 @@ ListCardLayout = CStruct(
     "rarity" / U8,
 )
 
+CardAssetPairLayout = CStruct(
+    "card_record" / U8[32],
+    "core_asset" / U8[32],
+)
+ClaimTaggedLayout = CStruct("pairs" / Vec(CardAssetPairLayout))
+VerifiedCollectionLayout = CStruct(
+    "collection" / U8[32],
+    "royalty_bps" / U16,
+    "recipient" / U8[32],
+)
+
+RelistLayout = CStruct(
+    "new_price_lamports" / U64,
+    "new_currency_mint" / Option(U8[32]),
+)
+
+ReservePackLayout = CStruct(
+    "client_seed_hash" / U8[32],
+    "rare_templates" / Vec(U32),
+    "manifest_hash" / U8[32],
+    "deposit" / U64,
+)
+OpenPackRandomLayout = CStruct(
+    "currency" / CurrencyLayout,
+    "client_seed_hash" / U8[32],
+    "num_candidates" / U8,
+    "num_rares" / U8,
+)
+OpenPackDiscountedLayout = CStruct(
+    "currency" / CurrencyLayout,
+    "client_seed_hash" / U8[32],
+    "rare_templates" / Vec(U32),
+    "manifest_hash" / U8[32],
+    "code" / Vec(U8),
+)
+OpenPackTargetedLayout = CStruct(
+    "currency" / CurrencyLayout,
+    "client_seed_hash" / U8[32],
+    "rare_templates" / Vec(U32),
+    "manifest_hash" / U8[32],
+    "guaranteed_rarity" / U8,
+)
+ClaimAirdropLayout = CStruct(
+    "proof" / Vec(U8[32]),
+    "leaf_index" / U32,
+    "template_id" / U32,
+    "asset" / U8[32],
+)
+
 RARITY_ORDER = [
     "Common",
     "Uncommon",
@@ -79,8 +135,10 @@ def to_pubkey(value: str) -> Pubkey:
     return Pubkey.from_string(value)
 
 
-def vault_state_pda() -> Pubkey:
-    return Pubkey.find_program_address([b"vault_state"], PROGRAM_ID)[0]
+def vault_state_pda(vault_id: int = 0) -> Pubkey:
+    # Vault 0 is the original singleton at [b"vault_state"]; others append the LE u64 id.
+    suffix = vault_id.to_bytes(8, "little") if vault_id else b""
+    return Pubkey.find_program_address([b"vault_state", suffix], PROGRAM_ID)[0]
 
 def market_vault_state_pda() -> Pubkey:
     return Pubkey.find_program_address([MARKETPLACE_VAULT_SEED], PROGRAM_ID)[0]
@@ -104,6 +162,27 @@ def pack_session_pda(vault_state: Pubkey, user: Pubkey) -> Pubkey:
         [b"pack_session", bytes(vault_state), bytes(user)], PROGRAM_ID
     )[0]
 
+def airdrop_bitmap_pda(vault_state: Pubkey, root: bytes) -> Pubkey:
+    return Pubkey.find_program_address([b"airdrop_bitmap", bytes(vault_state), root], PROGRAM_ID)[0]
+
+def airdrop_leaf(wallet: Pubkey, asset: Pubkey, template_id: int, leaf_index: int) -> bytes:
+    # Must match the program's airdrop_leaf / verify_merkle_proof (sorted pairs, 0x00/0x01 domains).
+    return hashlib.sha256(
+        b"\x00" + bytes(wallet) + bytes(asset) + template_id.to_bytes(4, "little") + leaf_index.to_bytes(4, "little")
+    ).digest()
+
+def airdrop_node(a: bytes, b: bytes) -> bytes:
+    lo, hi = (a, b) if a <= b else (b, a)
+    return hashlib.sha256(b"\x01" + lo + hi).digest()
+
+def discount_code_hash(code: bytes, redeemer: Pubkey) -> bytes:
+    # Codes are issued per wallet: the commitment covers the code and its redeemer.
+    return hashlib.sha256(code + bytes(redeemer)).digest()
+
+def discount_code_pda(vault_state: Pubkey, code: bytes, redeemer: Pubkey) -> Pubkey:
+    code_hash = discount_code_hash(code, redeemer)
+    return Pubkey.find_program_address([b"discount_code", bytes(vault_state), code_hash], PROGRAM_ID)[0]
+
 def pack_session_v2_pda(vault_state: Pubkey, user: Pubkey) -> Pubkey:
     return Pubkey.find_program_address(
         [b"pack_session_v2", bytes(vault_state), bytes(user)], PROGRAM_ID
@@ -115,6 +194,43 @@ def listing_pda(vault_state: Pubkey, core_asset: Pubkey) -> Pubkey:
         [b"listing", bytes(vault_state), bytes(core_asset)], PROGRAM_ID
     )[0]
 
+
+def seller_listing_index_pda(vault_state: Pubkey, seller: Pubkey) -> Pubkey:
+    return Pubkey.find_program_address(
+        [b"seller_index", bytes(vault_state), bytes(seller)], PROGRAM_ID
+    )[0]
+
+
+def template_set_pda(vault_state: Pubkey, set_id: int) -> Pubkey:
+    return Pubkey.find_program_address(
+        [b"template_set", bytes(vault_state), int(set_id).to_bytes(4, "little")], PROGRAM_ID
+    )[0]
+
+
+def fusion_recipe_pda(vault_state: Pubkey, recipe_id: int) -> Pubkey:
+    return Pubkey.find_program_address(
+        [b"fusion_recipe", bytes(vault_state), int(recipe_id).to_bytes(4, "little")], PROGRAM_ID
+    )[0]
+
+
+def set_bonus_claim_pda(template_set: Pubkey, user: Pubkey) -> Pubkey:
+    return Pubkey.find_program_address([b"set_bonus_claim", bytes(template_set), bytes(user)], PROGRAM_ID)[0]
+
+
+def user_stats_pda(vault_state: Pubkey, user: Pubkey) -> Pubkey:
+    return Pubkey.find_program_address([b"user_stats", bytes(vault_state), bytes(user)], PROGRAM_ID)[0]
+
+
+def force_close_request_pda(vault_state: Pubkey, user: Pubkey) -> Pubkey:
+    return Pubkey.find_program_address([b"force_close_request", bytes(vault_state), bytes(user)], PROGRAM_ID)[0]
+
+
+def fee_split_pda(vault_state: Pubkey) -> Pubkey:
+    return Pubkey.find_program_address([b"fee_split", bytes(vault_state)], PROGRAM_ID)[0]
+
+def rarity_inventory_pda(vault_state: Pubkey) -> Pubkey:
+    return Pubkey.find_program_address([b"rarity_inventory", bytes(vault_state)], PROGRAM_ID)[0]
+
 def seed_sale_pda(authority: Pubkey, mint: Pubkey) -> Pubkey:
     return Pubkey.find_program_address([b"seed_sale", bytes(authority), bytes(mint)], SEED_SALE_PROGRAM_ID)[0]
 
@@ -137,6 +253,10 @@ def vest_vault_token_pda(beneficiary: Pubkey) -> Pubkey:
 def encode_currency_tag(currency: str):
     if currency.lower() == "sol":
         return CurrencyLayout.enum.Sol()
+    if currency.lower() == "both":
+        return CurrencyLayout.enum.Both()
+    if currency.lower() == "mochi":
+        return CurrencyLayout.enum.Mochi()
     return CurrencyLayout.enum.Token()
 
 def encode_rarity_tag(rarity: str) -> int:
@@ -147,7 +267,15 @@ def encode_rarity_tag(rarity: str) -> int:
     raise ValueError(f"Unsupported rarity {rarity}")
 
 
-def encode_open_pack_start(currency: str, client_seed_hash: bytes, rarity_prices: List[int]) -> bytes:
+def manifest_hash(template_ids: List[Optional[int]]) -> bytes:
+    """sha256 over the LE u32 template ids of a pack, matching the program's manifest_hash."""
+    # Slots without a template hash as 0, so verify_manifest must be fed the same lineup.
+    return hashlib.sha256(b"".join(int(t or 0).to_bytes(4, "little") for t in template_ids)).digest()
+
+
+def encode_open_pack_start(
+    currency: str, client_seed_hash: bytes, rarity_prices: List[int], manifest: bytes = bytes(32)
+) -> bytes:
     if len(client_seed_hash) != 32:
         client_seed_hash = hashlib.sha256(client_seed_hash).digest()
     data = OpenPackStartLayout.build(
@@ -155,11 +283,18 @@ def encode_open_pack_start(currency: str, client_seed_hash: bytes, rarity_prices
             "currency": encode_currency_tag(currency),
             "client_seed_hash": list(client_seed_hash),
             "rarity_prices": rarity_prices,
+            "manifest_hash": list(manifest),
         }
     )
     return sighash("open_pack_start") + data
 
-def encode_open_pack_v2(currency: str, client_seed_hash: bytes, rare_templates: List[int]) -> bytes:
+def encode_open_pack_v2(
+    currency: str,
+    client_seed_hash: bytes,
+    rare_templates: List[int],
+    manifest: bytes = bytes(32),
+    recipient: Optional[Pubkey] = None,
+) -> bytes:
     if len(client_seed_hash) != 32:
         client_seed_hash = hashlib.sha256(client_seed_hash).digest()
     data = OpenPackV2Layout.build(
@@ -167,39 +302,121 @@ def encode_open_pack_v2(currency: str, client_seed_hash: bytes, rare_templates:
             "currency": encode_currency_tag(currency),
             "client_seed_hash": list(client_seed_hash),
             "rare_templates": rare_templates,
+            "manifest_hash": list(manifest),
+            "recipient": list(bytes(recipient)) if recipient else None,
         }
     )
     return sighash("open_pack") + data
 
+def encode_open_pack_discounted(
+    currency: str,
+    client_seed_hash: bytes,
+    rare_templates: List[int],
+    code: bytes,
+    manifest: bytes = bytes(32),
+) -> bytes:
+    if len(client_seed_hash) != 32:
+        client_seed_hash = hashlib.sha256(client_seed_hash).digest()
+    data = OpenPackDiscountedLayout.build(
+        {
+            "currency": encode_currency_tag(currency),
+            "client_seed_hash": list(client_seed_hash),
+            "rare_templates": rare_templates,
+            "manifest_hash": list(manifest),
+            "code": list(code),
+        }
+    )
+    return sighash("open_pack_discounted") + data
+
+def encode_open_pack_targeted(
+    currency: str,
+    client_seed_hash: bytes,
+    rare_templates: List[int],
+    guaranteed_rarity: str,
+    manifest: bytes = bytes(32),
+) -> bytes:
+    if len(client_seed_hash) != 32:
+        client_seed_hash = hashlib.sha256(client_seed_hash).digest()
+    data = OpenPackTargetedLayout.build(
+        {
+            "currency": encode_currency_tag(currency),
+            "client_seed_hash": list(client_seed_hash),
+            "rare_templates": rare_templates,
+            "manifest_hash": list(manifest),
+            "guaranteed_rarity": encode_rarity_tag(guaranteed_rarity),
+        }
+    )
+    return sighash("open_targeted_pack") + data
+
+def encode_open_pack_random(currency: str, client_seed_hash: bytes, num_candidates: int, num_rares: int) -> bytes:
+    if len(client_seed_hash) != 32:
+        client_seed_hash = hashlib.sha256(client_seed_hash).digest()
+    data = OpenPackRandomLayout.build(
+        {
+            "currency": encode_currency_tag(currency),
+            "client_seed_hash": list(client_seed_hash),
+            "num_candidates": num_candidates,
+            "num_rares": num_rares,
+        }
+    )
+    return sighash("open_pack_random") + data
+
+def encode_create_discount_code(code_hash: bytes, discount_bps: int, max_uses: int) -> bytes:
+    return (
+        sighash("create_discount_code")
+        + code_hash
+        + int(discount_bps).to_bytes(2, "little")
+        + int(max_uses).to_bytes(4, "little")
+    )
+
 
 def encode_set_reward_config(mochi_mint: Pubkey, reward_per_pack: int) -> bytes:
     return sighash("set_reward_config") + bytes(mochi_mint) + int(reward_per_pack).to_bytes(8, "little")
 
 
-def encode_claim_pack() -> bytes:
-    return sighash("claim_pack")
+def encode_set_mochi_price(pack_price_mochi: int) -> bytes:
+    return sighash("set_mochi_price") + int(pack_price_mochi).to_bytes(8, "little")
+
+
+def encode_claim_pack(nonce: int) -> bytes:
+    return sighash("claim_pack") + int(nonce).to_bytes(8, "little")
+
+def encode_claim_pack_v2(nonce: int) -> bytes:
+    return sighash("claim_pack_v2") + int(nonce).to_bytes(8, "little")
+
+
+def encode_claim_pack_v2_tagged(card_records: List[Pubkey], core_assets: List[Pubkey], nonce: int) -> bytes:
+    pairs = [
+        {"card_record": list(bytes(cr)), "core_asset": list(bytes(asset))}
+        for cr, asset in zip(card_records, core_assets)
+    ]
+    return (
+        sighash("claim_pack_v2_tagged")
+        + ClaimTaggedLayout.build({"pairs": pairs})
+        + int(nonce).to_bytes(8, "little")
+    )
 
-def encode_claim_pack_v2() -> bytes:
-    return sighash("claim_pack_v2")
 
+def encode_claim_pack_batch(nonce: int) -> bytes:
+    return sighash("claim_pack_batch") + int(nonce).to_bytes(8, "little")
 
-def encode_claim_pack_batch() -> bytes:
-    return sighash("claim_pack_batch")
 
+def encode_claim_all_rares_v2(nonce: int) -> bytes:
+    return sighash("claim_all_rares_v2") + int(nonce).to_bytes(8, "little")
 
-def encode_claim_pack_batch3() -> bytes:
-    return sighash("claim_pack_batch3")
 
+def encode_finalize_claim(nonce: int) -> bytes:
+    return sighash("finalize_claim") + int(nonce).to_bytes(8, "little")
 
-def encode_finalize_claim() -> bytes:
-    return sighash("finalize_claim")
 
+def encode_sellback_pack(nonce: int) -> bytes:
+    return sighash("sellback_pack") + int(nonce).to_bytes(8, "little")
 
-def encode_sellback_pack() -> bytes:
-    return sighash("sellback_pack")
+def encode_sellback_pack_v2(nonce: int) -> bytes:
+    return sighash("sellback_pack_v2") + int(nonce).to_bytes(8, "little")
 
-def encode_sellback_pack_v2() -> bytes:
-    return sighash("sellback_pack_v2")
+def encode_sellback_burn_v2(nonce: int) -> bytes:
+    return sighash("sellback_burn_v2") + int(nonce).to_bytes(8, "little")
 
 
 def encode_expire_session() -> bytes:
@@ -229,7 +446,14 @@ def encode_admin_force_cancel_listing() -> bytes:
 def encode_admin_prune_listing() -> bytes:
     return sighash("admin_prune_listing")
 
-def encode_seed_init(start_ts: int, end_ts: int, price_tokens_per_sol: int, token_cap: int, sol_cap_lamports: int) -> bytes:
+def encode_seed_init(
+    start_ts: int,
+    end_ts: int,
+    price_tokens_per_sol: int,
+    token_cap: int,
+    sol_cap_lamports: int,
+    absorb_fee: bool = False,
+) -> bytes:
     data = SeedInitLayout.build(
         {
             "start_ts": start_ts,
@@ -237,27 +461,40 @@ def encode_seed_init(start_ts: int, end_ts: int, price_tokens_per_sol: int, toke
             "price_tokens_per_sol": price_tokens_per_sol,
             "token_cap": token_cap,
             "sol_cap_lamports": sol_cap_lamports,
+            "absorb_fee": absorb_fee,
         }
     )
     return sighash("init_sale") + data
 
-def encode_seed_contribute(lamports: int) -> bytes:
-    data = SeedContributeLayout.build({"lamports": lamports})
+def encode_seed_contribute(lamports: int, partial_fill: bool = False) -> bytes:
+    data = SeedContributeLayout.build({"lamports": lamports, "partial_fill": partial_fill})
     return sighash("contribute") + data
 
 def encode_seed_claim() -> bytes:
     return sighash("claim")
 
+def encode_verify_contribution() -> bytes:
+    return sighash("verify_contribution")
+
 def encode_seed_cancel() -> bytes:
     return sighash("cancel_sale")
 
-def encode_init_vesting(start_ts: int, cliff_ts: int, end_ts: int, total_amount: int) -> bytes:
+def encode_seed_mark_funded() -> bytes:
+    return sighash("mark_funded")
+
+VESTING_CURVES = {"linear": 0, "front_loaded": 1, "back_loaded": 2}
+
+def encode_init_vesting(
+    start_ts: int, cliff_ts: int, end_ts: int, total_amount: int, curve: str = "linear", cliff_unlock_bps: int = 0
+) -> bytes:
     return (
         sighash("init_vesting")
         + start_ts.to_bytes(8, "little", signed=True)
         + cliff_ts.to_bytes(8, "little", signed=True)
         + end_ts.to_bytes(8, "little", signed=True)
         + total_amount.to_bytes(8, "little")
+        + bytes([VESTING_CURVES[curve]])
+        + cliff_unlock_bps.to_bytes(2, "little")
     )
 
 def encode_claim_vesting() -> bytes:
@@ -267,8 +504,20 @@ def encode_claim_vesting() -> bytes:
 def encode_user_reset_session() -> bytes:
     return sighash("user_reset_session")
 
+def encode_close_session_v2() -> bytes:
+    return sighash("close_session_v2")
+
 
-def encode_list_card(price_lamports: int, currency_mint: Optional[str], template_id: int, rarity_tag: int) -> bytes:
+def encode_card_availability() -> bytes:
+    return sighash("card_availability")
+
+
+def encode_list_card(
+    price_lamports: int,
+    currency_mint: Optional[str],
+    template_id: int,
+    rarity_tag: int,
+) -> bytes:
     currency_bytes = None if not currency_mint else list(Pubkey.from_string(currency_mint).to_bytes())
     data = ListCardLayout.build(
         {
@@ -298,10 +547,17 @@ def build_seed_init_ix(authority: Pubkey, mint: Pubkey, treasury: Pubkey, start_
     ]
     return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
 
-def build_seed_contribute_ix(buyer: Pubkey, authority: Pubkey, mint: Pubkey, treasury: Pubkey, lamports: int) -> Instruction:
+def build_seed_contribute_ix(
+    buyer: Pubkey,
+    authority: Pubkey,
+    mint: Pubkey,
+    treasury: Pubkey,
+    lamports: int,
+    partial_fill: bool = False,
+) -> Instruction:
     sale = seed_sale_pda(authority, mint)
     contrib = seed_contribution_pda(sale, buyer)
-    data = encode_seed_contribute(lamports)
+    data = encode_seed_contribute(lamports, partial_fill)
     accounts = [
         AccountMeta(buyer, True, True),
         AccountMeta(sale, False, True),
@@ -311,20 +567,53 @@ def build_seed_contribute_ix(buyer: Pubkey, authority: Pubkey, mint: Pubkey, tre
     ]
     return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
 
-def build_seed_claim_ix(buyer: Pubkey, authority: Pubkey, mint: Pubkey, user_ata: Pubkey) -> Instruction:
+def build_verify_contribution_ix(buyer: Pubkey, authority: Pubkey, mint: Pubkey) -> Instruction:
+    """Simulate to read (contributed_lamports, tokens_owed, claimed, expected_tokens) from return data."""
+    sale = seed_sale_pda(authority, mint)
+    contrib = seed_contribution_pda(sale, buyer)
+    data = encode_verify_contribution()
+    accounts = [
+        AccountMeta(buyer, False, False),
+        AccountMeta(sale, False, False),
+        AccountMeta(contrib, False, False),
+    ]
+    return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
+
+def build_migrate_contribution_ix(payer: Pubkey, authority: Pubkey, mint: Pubkey, buyer: Pubkey) -> Instruction:
+    sale = seed_sale_pda(authority, mint)
+    contrib = seed_contribution_pda(sale, buyer)
+    accounts = [
+        AccountMeta(payer, True, True),
+        AccountMeta(sale, False, False),
+        AccountMeta(buyer, False, False),
+        AccountMeta(contrib, False, True),
+        AccountMeta(SYS_PROGRAM_ID, False, False),
+    ]
+    return Instruction(SEED_SALE_PROGRAM_ID, sighash("migrate_contribution"), accounts)
+
+def build_seed_claim_ix(
+    buyer: Pubkey,
+    authority: Pubkey,
+    mint: Pubkey,
+    user_ata: Pubkey,
+    token_program: Pubkey = TOKEN_PROGRAM_ID,
+) -> Instruction:
     sale = seed_sale_pda(authority, mint)
     contrib = seed_contribution_pda(sale, buyer)
     vault_auth = seed_vault_authority_pda(sale)
     seed_vault = seed_vault_token_pda(sale)
     data = encode_seed_claim()
+    # The buyer is writable because a claim grows a legacy sale and pays the rent delta.
     accounts = [
-        AccountMeta(buyer, True, False),
+        AccountMeta(buyer, True, True),
         AccountMeta(sale, False, True),
         AccountMeta(contrib, False, True),
         AccountMeta(seed_vault, False, True),
         AccountMeta(vault_auth, False, False),
         AccountMeta(user_ata, False, True),
-        AccountMeta(TOKEN_PROGRAM_ID, False, False),
+        AccountMeta(token_program, False, False),
+        AccountMeta(mint, False, False),
+        AccountMeta(SYS_PROGRAM_ID, False, False),
     ]
     return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
 
@@ -337,11 +626,23 @@ def build_seed_cancel_ix(authority: Pubkey, mint: Pubkey) -> Instruction:
     ]
     return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
 
-def build_init_vesting_ix(authority: Pubkey, mint: Pubkey, beneficiary: Pubkey, start_ts: int, cliff_ts: int, end_ts: int, total_amount: int) -> Instruction:
+def build_seed_mark_funded_ix(authority: Pubkey, mint: Pubkey) -> Instruction:
+    sale = seed_sale_pda(authority, mint)
+    seed_vault = seed_vault_token_pda(sale)
+    data = encode_seed_mark_funded()
+    accounts = [
+        AccountMeta(authority, True, True),
+        AccountMeta(sale, False, True),
+        AccountMeta(seed_vault, False, False),
+        AccountMeta(SYS_PROGRAM_ID, False, False),
+    ]
+    return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
+
+def build_init_vesting_ix(authority: Pubkey, mint: Pubkey, beneficiary: Pubkey, start_ts: int, cliff_ts: int, end_ts: int, total_amount: int, curve: str = "linear", cliff_unlock_bps: int = 0) -> Instruction:
     vesting = vesting_pda(beneficiary)
     vest_vault = vest_vault_token_pda(beneficiary)
     vest_vault_authority = vesting  # same seeds
-    data = encode_init_vesting(start_ts, cliff_ts, end_ts, total_amount)
+    data = encode_init_vesting(start_ts, cliff_ts, end_ts, total_amount, curve, cliff_unlock_bps)
     accounts = [
         AccountMeta(authority, True, True),
         AccountMeta(mint, False, False),
@@ -355,21 +656,94 @@ def build_init_vesting_ix(authority: Pubkey, mint: Pubkey, beneficiary: Pubkey,
     ]
     return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
 
-def build_claim_vesting_ix(beneficiary: Pubkey, beneficiary_ata: Pubkey) -> Instruction:
+def build_fund_vesting_ix(authority: Pubkey, beneficiary: Pubkey, authority_ata: Pubkey, amount: int) -> Instruction:
+    accounts = [
+        AccountMeta(authority, True, False),
+        AccountMeta(vesting_pda(beneficiary), False, False),
+        AccountMeta(vest_vault_token_pda(beneficiary), False, True),
+        AccountMeta(authority_ata, False, True),
+        AccountMeta(TOKEN_PROGRAM_ID, False, False),
+    ]
+    return Instruction(SEED_SALE_PROGRAM_ID, sighash("fund_vesting") + int(amount).to_bytes(8, "little"), accounts)
+
+def build_split_vesting_ix(
+    authority: Pubkey, beneficiary: Pubkey, mint: Pubkey, splits: List[Tuple[Pubkey, int]]
+) -> Instruction:
+    """Carves `(new_beneficiary, amount)` grants out of `beneficiary`'s unvested remainder."""
+    vesting = vesting_pda(beneficiary)
+    accounts = [
+        AccountMeta(authority, True, True),
+        AccountMeta(beneficiary, False, False),
+        AccountMeta(vesting, False, True),
+        AccountMeta(vest_vault_token_pda(beneficiary), False, True),
+        AccountMeta(vesting, False, False),
+        AccountMeta(mint, False, False),
+        AccountMeta(TOKEN_PROGRAM_ID, False, False),
+        AccountMeta(SYS_PROGRAM_ID, False, False),
+    ]
+    data = sighash("split_vesting") + len(splits).to_bytes(4, "little")
+    for new_beneficiary, amount in splits:
+        data += bytes(new_beneficiary) + int(amount).to_bytes(8, "little")
+        accounts.extend(
+            [
+                AccountMeta(new_beneficiary, False, False),
+                AccountMeta(vesting_pda(new_beneficiary), False, True),
+                AccountMeta(vest_vault_token_pda(new_beneficiary), False, True),
+            ]
+        )
+    return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
+
+def build_claim_vesting_ix(beneficiary: Pubkey, beneficiary_ata: Pubkey, claim_all: bool = False) -> Instruction:
+    """`claim_all` sweeps the whole remainder once the schedule has ended (claim_all_vesting)."""
     vesting = vesting_pda(beneficiary)
     vest_vault = vest_vault_token_pda(beneficiary)
     vest_vault_authority = vesting
-    data = encode_claim_vesting()
+    data = sighash("claim_all_vesting") if claim_all else encode_claim_vesting()
     accounts = [
-        AccountMeta(beneficiary, True, False),
+        AccountMeta(beneficiary, True, True),
         AccountMeta(vesting, False, True),
         AccountMeta(vest_vault, False, True),
         AccountMeta(vest_vault_authority, False, False),
         AccountMeta(beneficiary_ata, False, True),
         AccountMeta(TOKEN_PROGRAM_ID, False, False),
+        AccountMeta(SYS_PROGRAM_ID, False, False),
     ]
     return Instruction(SEED_SALE_PROGRAM_ID, data, accounts)
 
+def build_vesting_details_ix(beneficiary: Pubkey) -> Instruction:
+    """Simulate to read (vested_now, claimable_now, total, next_unlock_ts, next_unlock_amount) from return data."""
+    accounts = [
+        AccountMeta(beneficiary, False, False),
+        AccountMeta(vesting_pda(beneficiary), False, False),
+    ]
+    return Instruction(SEED_SALE_PROGRAM_ID, sighash("vesting_details"), accounts)
+
+
+def encode_add_admin(new_admin: Pubkey) -> bytes:
+    return sighash("add_admin") + bytes(new_admin)
+
+def encode_remove_admin(old_admin: Pubkey) -> bytes:
+    return sighash("remove_admin") + bytes(old_admin)
+
+def encode_set_airdrop_root(root: bytes) -> bytes:
+    return sighash("set_airdrop_root") + root
+
+def encode_claim_airdrop(proof: List[bytes], leaf_index: int, template_id: int, asset: Pubkey) -> bytes:
+    data = ClaimAirdropLayout.build(
+        {
+            "proof": [list(p) for p in proof],
+            "leaf_index": leaf_index,
+            "template_id": template_id,
+            "asset": list(bytes(asset)),
+        }
+    )
+    return sighash("claim_airdrop") + data
+
+def encode_relist(new_price_lamports: int, new_currency_mint: Optional[str]) -> bytes:
+    currency_bytes = None if not new_currency_mint else list(Pubkey.from_string(new_currency_mint).to_bytes())
+    data = RelistLayout.build({"new_price_lamports": new_price_lamports, "new_currency_mint": currency_bytes})
+    return sighash("relist") + data
+
 
 def encode_cancel_listing() -> bytes:
     return sighash("cancel_listing")
@@ -391,6 +765,8 @@ def build_open_pack_ix(
     client_seed_hash: bytes,
     user_currency_token: Optional[Pubkey] = None,
     vault_currency_token: Optional[Pubkey] = None,
+    manifest: bytes = bytes(32),
+    track_inventory: bool = False,
 ) -> Instruction:
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=user, is_signer=True, is_writable=True),
@@ -404,6 +780,8 @@ def build_open_pack_ix(
             AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=user_stats_pda(vault_state, user), is_signer=False, is_writable=True),
+            _optional_rarity_inventory_meta(vault_state, track_inventory),
         ]
     )
     # For open_pack_start, only the 11 CardRecords are needed in remaining accounts.
@@ -413,7 +791,7 @@ def build_open_pack_ix(
             raise ValueError("Token currency requires token accounts")
         accounts.append(AccountMeta(pubkey=user_currency_token, is_signer=False, is_writable=True))
         accounts.append(AccountMeta(pubkey=vault_currency_token, is_signer=False, is_writable=True))
-    data = encode_open_pack_start(currency, client_seed_hash, rarity_prices)
+    data = encode_open_pack_start(currency, client_seed_hash, rarity_prices, manifest)
     return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
 
 def build_open_pack_v2_ix(
@@ -431,6 +809,13 @@ def build_open_pack_v2_ix(
     rare_templates: List[int],
     user_currency_token: Optional[Pubkey] = None,
     vault_currency_token: Optional[Pubkey] = None,
+    manifest: bytes = bytes(32),
+    recipient: Optional[Pubkey] = None,
+    discount_code: Optional[Pubkey] = None,
+    discount_preimage: Optional[bytes] = None,
+    track_inventory: bool = False,
+    membership_asset: Optional[Pubkey] = None,
+    gated: bool = False,
 ) -> Instruction:
     # Enforce on-chain account order from the deployed program; positional list only.
     named_accounts: List[Tuple[str, AccountMeta]] = [
@@ -443,14 +828,26 @@ def build_open_pack_v2_ix(
         ("user_token_account", AccountMeta(pubkey=user_token_account, is_signer=False, is_writable=True)),
         ("token_program", AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False)),
         ("reward_vault", AccountMeta(pubkey=reward_vault, is_signer=False, is_writable=True)),
+        ("rarity_inventory", _optional_rarity_inventory_meta(vault_state, track_inventory)),
+        (
+            "membership_asset",
+            AccountMeta(pubkey=membership_asset or PROGRAM_ID, is_signer=False, is_writable=False),
+        ),
+        ("instructions_sysvar", _optional_instructions_sysvar_meta(gated)),
+        ("user_stats", AccountMeta(pubkey=user_stats_pda(vault_state, user), is_signer=False, is_writable=True)),
     ]
+    if discount_code is not None:
+        # open_pack_discounted expects the DiscountCode ahead of the rares.
+        named_accounts.append(
+            ("discount_code", AccountMeta(pubkey=discount_code, is_signer=False, is_writable=True))
+        )
     named_accounts.extend(
         [
             (f"rare_card_record_{idx}", AccountMeta(pubkey=cr, is_signer=False, is_writable=True))
             for idx, cr in enumerate(rare_card_records)
         ]
     )
-    if currency.lower() == "usdc" or currency.lower() == "token":
+    if currency.lower() in ("usdc", "token", "both", "mochi"):
         if not user_currency_token or not vault_currency_token:
             raise ValueError("Token currency requires token accounts")
         named_accounts.append(
@@ -462,96 +859,280 @@ def build_open_pack_v2_ix(
     # System program comes last (after remaining accounts) to match the deployed binary.
     named_accounts.append(("system_program", AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False)))
     accounts: List[AccountMeta] = [meta for _, meta in named_accounts]
-    data = encode_open_pack_v2(currency, client_seed_hash, rare_templates)
+    if discount_code is not None:
+        if discount_preimage is None or recipient is not None:
+            raise ValueError("Discounted opens need the code preimage and cannot be gifted")
+        data = encode_open_pack_discounted(currency, client_seed_hash, rare_templates, discount_preimage, manifest)
+    else:
+        data = encode_open_pack_v2(currency, client_seed_hash, rare_templates, manifest, recipient)
     return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
 
 
-def build_claim_pack_ix(
+def build_open_targeted_pack_ix(
     user: Pubkey,
     vault_state: Pubkey,
     pack_session: Pubkey,
     vault_authority: Pubkey,
     vault_treasury: Pubkey,
-    card_records: List[Pubkey],
-    core_assets: List[Pubkey],
+    reward_mint: Pubkey,
+    reward_vault: Pubkey,
+    user_token_account: Pubkey,
+    rare_card_records: List[Pubkey],
+    currency: str,
+    client_seed_hash: bytes,
+    rare_templates: List[int],
+    guaranteed_rarity: str,
+    user_currency_token: Optional[Pubkey] = None,
+    vault_currency_token: Optional[Pubkey] = None,
+    manifest: bytes = bytes(32),
+    track_inventory: bool = False,
+    gated: bool = False,
 ) -> Instruction:
-    if len(card_records) != len(core_assets):
-        raise ValueError("card_records/core_assets length mismatch")
-    accounts: List[AccountMeta] = [
-        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
-        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
-    ]
-    accounts.extend(
-        [
-            AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
-            AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
-            AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
-        ]
+    """open_targeted_pack: one of `rare_card_records` must be exactly `guaranteed_rarity`."""
+    base = build_open_pack_v2_ix(
+        user,
+        vault_state,
+        pack_session,
+        vault_authority,
+        vault_treasury,
+        reward_mint,
+        reward_vault,
+        user_token_account,
+        rare_card_records,
+        currency,
+        client_seed_hash,
+        rare_templates,
+        user_currency_token,
+        vault_currency_token,
+        manifest,
+        track_inventory=track_inventory,
+        gated=gated,
     )
-    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
-    accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
-    return Instruction(program_id=PROGRAM_ID, data=encode_claim_pack(), accounts=accounts)
+    data = encode_open_pack_targeted(currency, client_seed_hash, rare_templates, guaranteed_rarity, manifest)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=list(base.accounts))
 
-def build_claim_pack_v2_ix(
+
+def build_open_pack_random_ix(
     user: Pubkey,
     vault_state: Pubkey,
     pack_session: Pubkey,
     vault_authority: Pubkey,
     vault_treasury: Pubkey,
-    card_records: List[Pubkey],
-    core_assets: List[Pubkey],
+    reward_mint: Pubkey,
+    reward_vault: Pubkey,
+    user_token_account: Pubkey,
+    candidate_records: List[Pubkey],
+    num_rares: int,
+    currency: str,
+    client_seed_hash: bytes,
+    user_currency_token: Optional[Pubkey] = None,
+    vault_currency_token: Optional[Pubkey] = None,
+    gated: bool = False,
 ) -> Instruction:
-    if len(card_records) != len(core_assets):
-        raise ValueError("card_records/core_assets length mismatch")
+    """open_pack_random: the program draws `num_rares` of `candidate_records` itself.
+
+    `candidate_records` must be every Available Rare+ record of the vault; they are sent in
+    ascending key order and checked against the RarityInventory.
+    """
+    candidate_records = sorted(candidate_records, key=bytes)
+    base = build_open_pack_v2_ix(
+        user,
+        vault_state,
+        pack_session,
+        vault_authority,
+        vault_treasury,
+        reward_mint,
+        reward_vault,
+        user_token_account,
+        candidate_records,
+        currency,
+        client_seed_hash,
+        [],
+        user_currency_token,
+        vault_currency_token,
+        track_inventory=True,
+        gated=gated,
+    )
+    accounts = list(base.accounts)
+    # SlotHashes leads the remaining accounts, ahead of the candidate pool.
+    first_candidate = len(accounts) - len(candidate_records) - 1
+    if currency.lower() in ("usdc", "token", "both", "mochi"):
+        first_candidate -= 2
+    accounts.insert(first_candidate, AccountMeta(pubkey=SYSVAR_SLOT_HASHES_ID, is_signer=False, is_writable=False))
+    data = encode_open_pack_random(currency, client_seed_hash, len(candidate_records), num_rares)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_reserve_pack_ix(
+    user: Pubkey,
+    vault_state: Pubkey,
+    rare_card_records: List[Pubkey],
+    rare_templates: List[int],
+    client_seed_hash: bytes,
+    deposit: int,
+    manifest: bytes = bytes(32),
+    track_inventory: bool = False,
+    gated: bool = False,
+) -> Instruction:
+    if len(client_seed_hash) != 32:
+        client_seed_hash = hashlib.sha256(client_seed_hash).digest()
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=user, is_signer=True, is_writable=True),
         AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
-        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+        _optional_instructions_sysvar_meta(gated),
+        AccountMeta(pubkey=user_stats_pda(vault_state, user), is_signer=False, is_writable=True),
     ]
-    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
-    accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
-    return Instruction(program_id=PROGRAM_ID, data=encode_claim_pack_v2(), accounts=accounts)
+    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in rare_card_records])
+    data = sighash("reserve_pack") + ReservePackLayout.build(
+        {
+            "client_seed_hash": list(client_seed_hash),
+            "rare_templates": rare_templates,
+            "manifest_hash": list(manifest),
+            "deposit": deposit,
+        }
+    )
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
 
 
-def build_claim_batch_ix(
+def build_add_payment_ix(
     user: Pubkey,
     vault_state: Pubkey,
-    pack_session: Pubkey,
-    vault_authority: Pubkey,
     vault_treasury: Pubkey,
-    card_records: List[Pubkey],
-    core_assets: List[Pubkey],
+    amount: int,
+    fee_recipients: Optional[List[Pubkey]] = None,
 ) -> Instruction:
-    if len(card_records) != len(core_assets):
-        raise ValueError("card_records/core_assets length mismatch")
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=user, is_signer=True, is_writable=True),
         AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
-        AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
         AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
     ]
-    accounts.extend(
-        [
-            AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
-            AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
-            AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
-        ]
+    # The completing payment settles through the fee split when one is configured.
+    if fee_recipients:
+        accounts.append(AccountMeta(pubkey=fee_split_pda(vault_state), is_signer=False, is_writable=False))
+        accounts.extend([AccountMeta(pubkey=r, is_signer=False, is_writable=True) for r in fee_recipients])
+    data = sighash("add_payment") + int(amount).to_bytes(8, "little")
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_cancel_layaway_ix(
+    user: Pubkey, vault_state: Pubkey, rare_card_records: List[Pubkey], track_inventory: bool = False
+) -> Instruction:
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+    ]
+    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in rare_card_records])
+    return Instruction(program_id=PROGRAM_ID, data=sighash("cancel_layaway"), accounts=accounts)
+
+
+def build_expire_layaway_ix(
+    caller: Pubkey,
+    user: Pubkey,
+    vault_state: Pubkey,
+    rare_card_records: List[Pubkey],
+    track_inventory: bool = False,
+) -> Instruction:
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=caller, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=user, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+    ]
+    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in rare_card_records])
+    return Instruction(program_id=PROGRAM_ID, data=sighash("expire_layaway"), accounts=accounts)
+
+
+def build_create_discount_code_ix(
+    admin: Pubkey, vault_state: Pubkey, code: bytes, redeemer: Pubkey, discount_bps: int, max_uses: int
+) -> Instruction:
+    """Issue `code` to `redeemer`; no other wallet can redeem it."""
+    code_hash = discount_code_hash(code, redeemer)
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=discount_code_pda(vault_state, code, redeemer), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    data = encode_create_discount_code(code_hash, discount_bps, max_uses)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_claim_pack_ix(
+    user: Pubkey,
+    vault_state: Pubkey,
+    pack_session: Pubkey,
+    vault_authority: Pubkey,
+    vault_treasury: Pubkey,
+    card_records: List[Pubkey],
+    core_assets: List[Pubkey],
+    nonce: int,
+) -> Instruction:
+    if len(card_records) != len(core_assets):
+        raise ValueError("card_records/core_assets length mismatch")
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
+    ]
+    accounts.extend(
+        [
+            AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+            # Claims never touch stock counts, so the inventory slot is always empty.
+            _optional_rarity_inventory_meta(vault_state, False),
+        ]
     )
     accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
     accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
-    return Instruction(program_id=PROGRAM_ID, data=encode_claim_pack_batch(), accounts=accounts)
+    return Instruction(program_id=PROGRAM_ID, data=encode_claim_pack(nonce), accounts=accounts)
+
+def build_claim_pack_v2_ix(
+    user: Pubkey,
+    vault_state: Pubkey,
+    pack_session: Pubkey,
+    vault_authority: Pubkey,
+    vault_treasury: Pubkey,
+    card_records: List[Pubkey],
+    core_assets: List[Pubkey],
+    nonce: int,
+) -> Instruction:
+    if len(card_records) != len(core_assets):
+        raise ValueError("card_records/core_assets length mismatch")
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        # Claims never touch stock counts, so the inventory slot is always empty.
+        _optional_rarity_inventory_meta(vault_state, False),
+    ]
+    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
+    accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
+    return Instruction(program_id=PROGRAM_ID, data=encode_claim_pack_v2(nonce), accounts=accounts)
 
 
-def build_claim_batch3_ix(
+def build_claim_pack_v2_tagged_ix(
     user: Pubkey,
     vault_state: Pubkey,
     pack_session: Pubkey,
@@ -559,9 +1140,31 @@ def build_claim_batch3_ix(
     vault_treasury: Pubkey,
     card_records: List[Pubkey],
     core_assets: List[Pubkey],
+    nonce: int,
 ) -> Instruction:
-    if len(card_records) != 3 or len(core_assets) != 3:
-        raise ValueError("claim_batch3 requires exactly 3 card_records/core_assets")
+    """Same accounts as claim_pack_v2, but cards are paired with assets in the data, not by position."""
+    ix = build_claim_pack_v2_ix(
+        user, vault_state, pack_session, vault_authority, vault_treasury, card_records, core_assets, nonce
+    )
+    return Instruction(
+        program_id=PROGRAM_ID,
+        data=encode_claim_pack_v2_tagged(card_records, core_assets, nonce),
+        accounts=ix.accounts,
+    )
+
+
+def build_claim_batch_ix(
+    user: Pubkey,
+    vault_state: Pubkey,
+    pack_session: Pubkey,
+    vault_authority: Pubkey,
+    vault_treasury: Pubkey,
+    card_records: List[Pubkey],
+    core_assets: List[Pubkey],
+    nonce: int,
+) -> Instruction:
+    if len(card_records) != len(core_assets):
+        raise ValueError("card_records/core_assets length mismatch")
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=user, is_signer=True, is_writable=True),
         AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
@@ -574,11 +1177,30 @@ def build_claim_batch3_ix(
             AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+            # Claims never touch stock counts, so the inventory slot is always empty.
+            _optional_rarity_inventory_meta(vault_state, False),
         ]
     )
     accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
     accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
-    return Instruction(program_id=PROGRAM_ID, data=encode_claim_pack_batch3(), accounts=accounts)
+    return Instruction(program_id=PROGRAM_ID, data=encode_claim_pack_batch(nonce), accounts=accounts)
+
+
+def build_claim_all_rares_v2_ix(
+    user: Pubkey,
+    vault_state: Pubkey,
+    pack_session: Pubkey,
+    vault_authority: Pubkey,
+    vault_treasury: Pubkey,
+    card_records: List[Pubkey],
+    core_assets: List[Pubkey],
+    nonce: int,
+) -> Instruction:
+    """Claim every rare of a (non-gift) V2 session; records/assets must follow session order."""
+    ix = build_claim_pack_v2_ix(
+        user, vault_state, pack_session, vault_authority, vault_treasury, card_records, core_assets, nonce
+    )
+    return Instruction(program_id=PROGRAM_ID, data=encode_claim_all_rares_v2(nonce), accounts=ix.accounts)
 
 
 def build_finalize_claim_ix(
@@ -586,6 +1208,7 @@ def build_finalize_claim_ix(
     vault_state: Pubkey,
     pack_session: Pubkey,
     vault_authority: Pubkey,
+    nonce: int,
 ) -> Instruction:
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=user, is_signer=True, is_writable=True),
@@ -593,7 +1216,7 @@ def build_finalize_claim_ix(
         AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
         AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
     ]
-    return Instruction(program_id=PROGRAM_ID, data=encode_finalize_claim(), accounts=accounts)
+    return Instruction(program_id=PROGRAM_ID, data=encode_finalize_claim(nonce), accounts=accounts)
 
 
 def build_sellback_pack_ix(
@@ -604,8 +1227,10 @@ def build_sellback_pack_ix(
     vault_treasury: Pubkey,
     card_records: List[Pubkey],
     core_assets: List[Pubkey],
+    nonce: int,
     user_currency_token: Optional[Pubkey] = None,
     vault_currency_token: Optional[Pubkey] = None,
+    track_inventory: bool = False,
 ) -> Instruction:
     if len(card_records) != len(core_assets):
         raise ValueError("card_records/core_assets length mismatch")
@@ -621,6 +1246,7 @@ def build_sellback_pack_ix(
             AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+            _optional_rarity_inventory_meta(vault_state, track_inventory),
         ]
     )
     accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
@@ -628,7 +1254,7 @@ def build_sellback_pack_ix(
     if user_currency_token and vault_currency_token:
         accounts.append(AccountMeta(pubkey=user_currency_token, is_signer=False, is_writable=True))
         accounts.append(AccountMeta(pubkey=vault_currency_token, is_signer=False, is_writable=True))
-    return Instruction(program_id=PROGRAM_ID, data=encode_sellback_pack(), accounts=accounts)
+    return Instruction(program_id=PROGRAM_ID, data=encode_sellback_pack(nonce), accounts=accounts)
 
 def build_sellback_pack_v2_ix(
     user: Pubkey,
@@ -638,8 +1264,12 @@ def build_sellback_pack_v2_ix(
     vault_treasury: Pubkey,
     card_records: List[Pubkey],
     core_assets: List[Pubkey],
+    nonce: int,
     user_currency_token: Optional[Pubkey] = None,
     vault_currency_token: Optional[Pubkey] = None,
+    burn: bool = False,
+    track_inventory: bool = False,
+    fee_recipients: Optional[List[Pubkey]] = None,
 ) -> Instruction:
     if len(card_records) != len(core_assets):
         raise ValueError("card_records/core_assets length mismatch")
@@ -652,13 +1282,19 @@ def build_sellback_pack_v2_ix(
         AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
     ]
     accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
     accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
     if user_currency_token and vault_currency_token:
         accounts.append(AccountMeta(pubkey=user_currency_token, is_signer=False, is_writable=True))
         accounts.append(AccountMeta(pubkey=vault_currency_token, is_signer=False, is_writable=True))
-    return Instruction(program_id=PROGRAM_ID, data=encode_sellback_pack_v2(), accounts=accounts)
+    # A SOL sellback fee settles through the fee split when one is configured.
+    if fee_recipients:
+        accounts.append(AccountMeta(pubkey=fee_split_pda(vault_state), is_signer=False, is_writable=False))
+        accounts.extend([AccountMeta(pubkey=r, is_signer=False, is_writable=True) for r in fee_recipients])
+    data = encode_sellback_burn_v2(nonce) if burn else encode_sellback_pack_v2(nonce)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
 
 
 def build_expire_session_ix(
@@ -669,7 +1305,11 @@ def build_expire_session_ix(
     vault_treasury: Pubkey,
     card_records: List[Pubkey],
     core_assets: Optional[List[Pubkey]] = None,
+    user_currency_token: Optional[Pubkey] = None,
+    vault_currency_token: Optional[Pubkey] = None,
+    track_inventory: bool = False,
 ) -> Instruction:
+    """Token sessions that were partly batch-claimed need both token accounts for the refund."""
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=user, is_signer=True, is_writable=True),
         AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
@@ -682,11 +1322,15 @@ def build_expire_session_ix(
             AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
             AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+            _optional_rarity_inventory_meta(vault_state, track_inventory),
         ]
     )
     accounts.extend(
         [AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records]
     )
+    if user_currency_token and vault_currency_token:
+        accounts.append(AccountMeta(pubkey=user_currency_token, is_signer=False, is_writable=True))
+        accounts.append(AccountMeta(pubkey=vault_currency_token, is_signer=False, is_writable=True))
     return Instruction(program_id=PROGRAM_ID, data=encode_expire_session(), accounts=accounts)
 
 def build_expire_session_v2_ix(
@@ -696,6 +1340,7 @@ def build_expire_session_v2_ix(
     vault_authority: Pubkey,
     vault_treasury: Pubkey,
     card_records: List[Pubkey],
+    track_inventory: bool = False,
 ) -> Instruction:
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=user, is_signer=True, is_writable=True),
@@ -706,6 +1351,7 @@ def build_expire_session_v2_ix(
         AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
     ]
     accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records])
     return Instruction(program_id=PROGRAM_ID, data=encode_expire_session_v2(), accounts=accounts)
@@ -720,6 +1366,7 @@ def build_admin_force_expire_ix(
     vault_treasury: Pubkey,
     card_records: List[Pubkey],
     core_assets: Optional[List[Pubkey]] = None,
+    track_inventory: bool = False,
 ) -> Instruction:
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
@@ -729,6 +1376,7 @@ def build_admin_force_expire_ix(
         AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
         AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
     ]
     accounts.extend(
         [AccountMeta(pubkey=cr, is_signer=False, is_writable=True) for cr in card_records]
@@ -747,6 +1395,7 @@ def build_admin_reset_session_ix(
     pack_session: Pubkey,
     vault_authority: Pubkey,
     card_records: Optional[List[Pubkey]] = None,
+    track_inventory: bool = False,
 ) -> Instruction:
     accounts: List[AccountMeta] = [
         AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
@@ -754,6 +1403,7 @@ def build_admin_reset_session_ix(
         AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
         AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
         AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
     ]
     if card_records:
         accounts.extend(
@@ -769,6 +1419,7 @@ def build_admin_force_close_session_ix(
     pack_session: Pubkey,
     vault_authority: Pubkey,
     card_records: list[Pubkey],
+    track_inventory: bool = False,
 ) -> Instruction:
     accounts = [
         AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
@@ -776,6 +1427,7 @@ def build_admin_force_close_session_ix(
         AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
         AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
         AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
     ]
     for cr in card_records:
         accounts.append(AccountMeta(pubkey=cr, is_signer=False, is_writable=True))
@@ -788,19 +1440,42 @@ def build_admin_force_close_v2_ix(
     pack_session: Pubkey,
     vault_authority: Pubkey,
     card_records: list[Pubkey],
+    track_inventory: bool = False,
+    requested: bool = False,
 ) -> Instruction:
+    """`requested`: pass the force-close request PDA (needed while the vault sets a delay)."""
+    request_meta = (
+        AccountMeta(pubkey=force_close_request_pda(vault_state, user), is_signer=False, is_writable=True)
+        if requested
+        else AccountMeta(pubkey=PROGRAM_ID, is_signer=False, is_writable=False)
+    )
     accounts = [
         AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
-        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=user, is_signer=False, is_writable=True),
         AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
         AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
         AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+        request_meta,
     ]
     for cr in card_records:
         accounts.append(AccountMeta(pubkey=cr, is_signer=False, is_writable=True))
     return Instruction(program_id=PROGRAM_ID, data=encode_admin_force_close_v2(), accounts=accounts)
 
 
+def build_admin_request_force_close_ix(admin: Pubkey, user: Pubkey, vault_state: Pubkey) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=force_close_request_pda(vault_state, user), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("admin_request_force_close"), accounts=accounts)
+
+
 def build_set_reward_config_ix(
     admin: Pubkey,
     vault_state: Pubkey,
@@ -817,6 +1492,91 @@ def build_set_reward_config_ix(
     data = encode_set_reward_config(mochi_mint, reward_per_pack)
     return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
 
+def build_set_rarity_inventory_ix(admin: Pubkey, vault_state: Pubkey, available_count: List[int]) -> Instruction:
+    if len(available_count) != len(RARITY_ORDER):
+        raise ValueError("available_count needs one entry per rarity")
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=rarity_inventory_pda(vault_state), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    data = sighash("set_rarity_inventory") + b"".join(int(c).to_bytes(4, "little") for c in available_count)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+def build_recompute_vault_counters_ix(
+    admin: Pubkey,
+    vault_state: Pubkey,
+    accounts_batch: List[Pubkey],
+    reset: bool,
+    track_inventory: bool = False,
+) -> Instruction:
+    """`accounts_batch` mixes CardRecords and PackSessionV2s; pass `reset=True` on the first batch only."""
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+    ]
+    accounts.extend(AccountMeta(pubkey=key, is_signer=False, is_writable=False) for key in accounts_batch)
+    data = sighash("recompute_vault_counters") + Bool.build(reset)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+def build_inventory_counts_ix(vault_state: Pubkey) -> Instruction:
+    """Simulate to read Available counts per rarity (9 x u32 return data, RARITY_ORDER order)."""
+    accounts = [
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=rarity_inventory_pda(vault_state), is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("inventory_counts"), accounts=accounts)
+
+def build_set_mochi_price_ix(admin: Pubkey, vault_state: Pubkey, pack_price_mochi: int) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    data = encode_set_mochi_price(pack_price_mochi)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+def build_set_reward_mode_ix(
+    admin: Pubkey,
+    vault_state: Pubkey,
+    proportional: bool,
+    reward_rate_bps: int = 0,
+    mochi_per_sol: int = 0,
+    mochi_per_usdc: int = 0,
+    max_reward_per_pack: int = 0,
+) -> Instruction:
+    """Proportional rewards pay `reward_rate_bps` of the price valued at MOCHI raw units per whole SOL / USDC."""
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    data = (
+        sighash("set_reward_mode")
+        + U8.build(1 if proportional else 0)
+        + U16.build(reward_rate_bps)
+        + U64.build(mochi_per_sol)
+        + U64.build(mochi_per_usdc)
+        + U64.build(max_reward_per_pack)
+    )
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+def build_set_partial_claim_refund_ix(admin: Pubkey, vault_state: Pubkey, refund_bps: int) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    data = sighash("set_partial_claim_refund") + U16.build(refund_bps)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+def build_set_max_manifest_len_ix(admin: Pubkey, vault_state: Pubkey, max_manifest_len: int) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    data = sighash("set_max_manifest_len") + U16.build(max_manifest_len)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
 def build_admin_force_cancel_listing_ix(
     admin: Pubkey,
     vault_state: Pubkey,
@@ -836,10 +1596,230 @@ def build_admin_force_cancel_listing_ix(
         AccountMeta(pubkey=seller, is_signer=False, is_writable=True),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _seller_index_meta(vault_state, seller),
     ]
     return Instruction(program_id=PROGRAM_ID, data=encode_admin_force_cancel_listing(), accounts=accounts)
 
 
+def build_burn_vault_mochi_ix(
+    admin: Pubkey, vault_state: Pubkey, mochi_mint: Pubkey, vault_mochi: Pubkey, amount: int
+) -> Instruction:
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=mochi_mint, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_mochi, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    data = sighash("burn_vault_mochi") + int(amount).to_bytes(8, "little")
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_rotate_payment_mint_ix(
+    admin: Pubkey,
+    vault_state: Pubkey,
+    new_mint: Pubkey,
+    old_vault_token: Optional[Pubkey] = None,
+    sweep_destination: Optional[Pubkey] = None,
+) -> Instruction:
+    """Pass both token accounts to sweep the old mint's vault balance, or neither."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=old_vault_token or PROGRAM_ID, is_signer=False, is_writable=old_vault_token is not None),
+        AccountMeta(
+            pubkey=sweep_destination or PROGRAM_ID, is_signer=False, is_writable=sweep_destination is not None
+        ),
+        AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    data = sighash("rotate_payment_mint") + bytes(new_mint)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_admin_burn_cards_batch_ix(
+    admin: Pubkey,
+    vault_state: Pubkey,
+    core_assets: List[Pubkey],
+    track_inventory: bool = False,
+) -> Instruction:
+    """Burn Available vault cards; records first, then assets, in matching order."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+    ]
+    accounts.extend(
+        [AccountMeta(pubkey=card_record_pda(vault_state, asset), is_signer=False, is_writable=True) for asset in core_assets]
+    )
+    accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
+    return Instruction(program_id=PROGRAM_ID, data=sighash("admin_burn_cards_batch"), accounts=accounts)
+
+
+def build_relayer_claim_batch_ix(
+    relayer: Pubkey,
+    vault_state: Pubkey,
+    vault_treasury: Pubkey,
+    groups: List[Tuple[Pubkey, Pubkey, List[Pubkey]]],
+) -> Instruction:
+    """Claim several users' sessions; each group is (user, beneficiary, core_assets in session order)."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=relayer, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    for user, beneficiary, core_assets in groups:
+        accounts.append(AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=True))
+        accounts.append(AccountMeta(pubkey=beneficiary, is_signer=False, is_writable=True))
+        accounts.extend(
+            [AccountMeta(pubkey=card_record_pda(vault_state, asset), is_signer=False, is_writable=True) for asset in core_assets]
+        )
+        accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in core_assets])
+    return Instruction(program_id=PROGRAM_ID, data=sighash("relayer_claim_batch"), accounts=accounts)
+
+
+def build_create_template_set_ix(admin: Pubkey, vault_state: Pubkey, set_id: int, template_ids: List[int]) -> Instruction:
+    data = sighash("create_template_set") + int(set_id).to_bytes(4, "little") + Vec(U32).build(template_ids)
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=template_set_pda(vault_state, set_id), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_claim_set_bonus_ix(
+    user: Pubkey,
+    vault_state: Pubkey,
+    set_id: int,
+    reward_mint: Pubkey,
+    user_token_account: Pubkey,
+    core_assets: List[Pubkey],
+) -> Instruction:
+    """`core_assets`: one user-owned card per set template, in set order."""
+    template_set = template_set_pda(vault_state, set_id)
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=template_set, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=set_bonus_claim_pda(template_set, user), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=reward_mint, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=user_token_account, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=TOKEN_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    accounts.extend(
+        [AccountMeta(pubkey=card_record_pda(vault_state, asset), is_signer=False, is_writable=False) for asset in core_assets]
+    )
+    return Instruction(program_id=PROGRAM_ID, data=sighash("claim_set_bonus"), accounts=accounts)
+
+
+def build_create_fusion_recipe_ix(
+    admin: Pubkey,
+    vault_state: Pubkey,
+    recipe_id: int,
+    input_rarity: str,
+    input_count: int,
+    same_template: bool,
+    output_rarity: str,
+) -> Instruction:
+    data = (
+        sighash("create_fusion_recipe")
+        + int(recipe_id).to_bytes(4, "little")
+        + bytes([encode_rarity_tag(input_rarity), input_count, 1 if same_template else 0, encode_rarity_tag(output_rarity)])
+    )
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=fusion_recipe_pda(vault_state, recipe_id), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_fuse_cards_ix(
+    user: Pubkey,
+    vault_state: Pubkey,
+    recipe_id: int,
+    input_assets: List[Pubkey],
+    output_asset: Pubkey,
+    track_inventory: bool = False,
+) -> Instruction:
+    """Burn `input_assets` per the recipe and receive the vault's `output_asset`."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=fusion_recipe_pda(vault_state, recipe_id), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+    ]
+    accounts.extend(
+        [AccountMeta(pubkey=card_record_pda(vault_state, asset), is_signer=False, is_writable=True) for asset in input_assets]
+    )
+    accounts.extend([AccountMeta(pubkey=asset, is_signer=False, is_writable=True) for asset in input_assets])
+    accounts.append(AccountMeta(pubkey=card_record_pda(vault_state, output_asset), is_signer=False, is_writable=True))
+    accounts.append(AccountMeta(pubkey=output_asset, is_signer=False, is_writable=True))
+    return Instruction(program_id=PROGRAM_ID, data=sighash("fuse_cards"), accounts=accounts)
+
+
+def build_set_pity_threshold_ix(admin: Pubkey, vault_state: Pubkey, pity_threshold: int) -> Instruction:
+    """After `pity_threshold` V2 opens without a Rare+, the next open must reserve one (0 disables)."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    return Instruction(
+        program_id=PROGRAM_ID, data=sighash("set_pity_threshold") + U16.build(pity_threshold), accounts=accounts
+    )
+
+
+def build_pity_status_ix(vault_state: Pubkey, user: Pubkey) -> Instruction:
+    """Simulate to read (current_counter u32, threshold u16, guaranteed_next bool) from return data."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=user_stats_pda(vault_state, user), is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("pity_status"), accounts=accounts)
+
+
+def build_set_spend_limit_ix(user: Pubkey, vault_state: Pubkey, limit_lamports: int, period_seconds: int) -> Instruction:
+    """Tighter limits apply at once; looser ones (including 0 = none) wait out the cooldown."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=user_stats_pda(vault_state, user), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    data = (
+        sighash("set_spend_limit")
+        + int(limit_lamports).to_bytes(8, "little")
+        + int(period_seconds).to_bytes(8, "little", signed=True)
+    )
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_close_card_record_ix(admin: Pubkey, vault_state: Pubkey, core_asset: Pubkey) -> Instruction:
+    """Return a Burned/Deprecated card record's rent to the admin."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=card_record_pda(vault_state, core_asset), is_signer=False, is_writable=True),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("close_card_record"), accounts=accounts)
+
+
 def build_admin_prune_listing_ix(admin: Pubkey, vault_state: Pubkey, listing: Pubkey) -> Instruction:
     accounts = [
         AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
@@ -869,6 +1849,349 @@ def build_user_reset_session_ix(
     return Instruction(program_id=PROGRAM_ID, data=encode_user_reset_session(), accounts=accounts)
 
 
+def build_admin_extend_session_ix(admin: Pubkey, user: Pubkey, vault_state: Pubkey, new_expires_at: int) -> Instruction:
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=True),
+    ]
+    data = sighash("admin_extend_session") + int(new_expires_at).to_bytes(8, "little", signed=True)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_close_session_v2_ix(user: Pubkey, vault_state: Pubkey, pack_session: Pubkey) -> Instruction:
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session, is_signer=False, is_writable=True),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=encode_close_session_v2(), accounts=accounts)
+
+
+def build_peek_pack_ix(user: Pubkey, vault_state: Pubkey, rare_card_records: List[Pubkey]) -> Instruction:
+    """Simulate to read (rare_templates, rare_rarities, manifest_hash, expires_at) from return data."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=False),
+    ]
+    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=False) for cr in rare_card_records])
+    return Instruction(program_id=PROGRAM_ID, data=sighash("peek_pack"), accounts=accounts)
+
+
+def build_reveal_pack_v2_ix(user: Pubkey, vault_state: Pubkey, rare_card_records: List[Pubkey]) -> Instruction:
+    """User-signed peek_pack; locks the session against sellback on commit-to-keep vaults."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=True),
+    ]
+    accounts.extend([AccountMeta(pubkey=cr, is_signer=False, is_writable=False) for cr in rare_card_records])
+    return Instruction(program_id=PROGRAM_ID, data=sighash("reveal_pack_v2"), accounts=accounts)
+
+
+def build_user_pending_summary_ix(user: Pubkey, vault_state: Pubkey) -> Instruction:
+    """Simulate to read (rare_count, expires_at, state, claimable) from return data."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("user_pending_summary"), accounts=accounts)
+
+
+def build_assert_session_valid_ix(user: Pubkey, vault_state: Pubkey) -> Instruction:
+    """Simulate to check a V2 session's invariants; a failure names the broken one."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("assert_session_valid"), accounts=accounts)
+
+
+def build_verify_pack_fairness_ix(
+    user: Pubkey, vault_state: Pubkey, candidate_records: List[Pubkey], claimed_templates: List[int]
+) -> Instruction:
+    """Simulate to replay an open_pack_random draw; `candidate_records` in ascending key order, as the open sent them."""
+    data = sighash("verify_pack_fairness") + Vec(U32).build(claimed_templates)
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, user), is_signer=False, is_writable=False),
+    ]
+    accounts.extend(
+        AccountMeta(pubkey=rec, is_signer=False, is_writable=False) for rec in sorted(candidate_records, key=bytes)
+    )
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_card_availability_ix(
+    vault_state: Pubkey, card_record: Pubkey, pack_session: Optional[Pubkey] = None
+) -> Instruction:
+    """Simulate to read (status, expires_at) from return data; pass the owner's V2 session for expiry."""
+    session_meta = (
+        AccountMeta(pubkey=PROGRAM_ID, is_signer=False, is_writable=False)
+        if pack_session is None
+        else AccountMeta(pubkey=pack_session, is_signer=False, is_writable=False)
+    )
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=card_record, is_signer=False, is_writable=False),
+        session_meta,
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=encode_card_availability(), accounts=accounts)
+
+
+def build_release_orphaned_card_ix(
+    caller: Pubkey,
+    vault_state: Pubkey,
+    core_asset: Pubkey,
+    owner: Pubkey,
+    track_inventory: bool = False,
+) -> Instruction:
+    """`owner` is the card record's current owner (the wallet its stale reservation names)."""
+    card_record = card_record_pda(vault_state, core_asset)
+    reservation = Pubkey.find_program_address([b"card_reservation", bytes(card_record)], PROGRAM_ID)[0]
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=caller, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=card_record, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=pack_session_v2_pda(vault_state, owner), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=pack_session_pda(vault_state, owner), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=reservation, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("release_orphaned_card"), accounts=accounts)
+
+
+def build_swap_cards_ix(
+    user_a: Pubkey,
+    user_b: Pubkey,
+    vault_state_a: Pubkey,
+    core_asset_a: Pubkey,
+    vault_state_b: Pubkey,
+    core_asset_b: Pubkey,
+    sweetener_lamports: int = 0,
+    sweetener_from_b: bool = False,
+) -> Instruction:
+    """Both users must sign; `vault_state_*` is the vault each card's record lives under."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=user_a, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=user_b, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=card_record_pda(vault_state_a, core_asset_a), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=core_asset_a, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=card_record_pda(vault_state_b, core_asset_b), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=core_asset_b, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    data = (
+        sighash("swap_cards")
+        + int(sweetener_lamports).to_bytes(8, "little")
+        + (b"\x01" if sweetener_from_b else b"\x00")
+    )
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_treasury_list_batch_ix(
+    admin: Pubkey, vault_state: Pubkey, entries: List[Tuple[Pubkey, int, str, int]]
+) -> Instruction:
+    """`entries` is (core_asset, template_id, rarity, price_lamports) per asset the vault authority holds (max 5)."""
+    data = (
+        sighash("treasury_list_batch")
+        + Vec(U32).build([template for _, template, _, _ in entries])
+        + Vec(U8).build([encode_rarity_tag(rarity) for _, _, rarity, _ in entries])
+        + Vec(U64).build([price for _, _, _, price in entries])
+    )
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=market_vault_authority_pda(vault_state), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    for core_asset, _, _, _ in entries:
+        accounts.extend(
+            [
+                AccountMeta(pubkey=card_record_pda(vault_state, core_asset), is_signer=False, is_writable=True),
+                AccountMeta(pubkey=core_asset, is_signer=False, is_writable=True),
+                AccountMeta(pubkey=listing_pda(vault_state, core_asset), is_signer=False, is_writable=True),
+            ]
+        )
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_set_listing_fee_override_ix(
+    admin: Pubkey, vault_state: Pubkey, core_asset: Pubkey, fee_bps_override: Optional[int]
+) -> Instruction:
+    """`fee_bps_override=None` returns the listing to the vault-wide marketplace fee."""
+    data = sighash("set_listing_fee_override") + Option(U16).build(fee_bps_override)
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=core_asset, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=listing_pda(vault_state, core_asset), is_signer=False, is_writable=True),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_set_royalty_collections_ix(
+    admin: Pubkey, vault_state: Pubkey, collections: List[Tuple[Pubkey, int, Pubkey]]
+) -> Instruction:
+    """`collections` is (collection, royalty_bps, recipient) per verified collection; empty disables royalties."""
+    entries = [
+        {"collection": list(bytes(collection)), "royalty_bps": bps, "recipient": list(bytes(recipient))}
+        for collection, bps, recipient in collections
+    ]
+    data = sighash("set_royalty_collections") + Vec(VerifiedCollectionLayout).build(entries)
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_set_maker_taker_fees_ix(
+    admin: Pubkey, vault_state: Pubkey, maker_fee_bps: int, taker_fee_bps: int
+) -> Instruction:
+    """Both fees at 0 return the marketplace to the single `marketplace_fee_bps` charged to the seller."""
+    data = sighash("set_maker_taker_fees") + U16.build(maker_fee_bps) + U16.build(taker_fee_bps)
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_verify_vault_authority_bump_ix(vault_state: Pubkey) -> Instruction:
+    """Simulate to check the stored vault authority bump matches the canonical PDA (gacha or marketplace)."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("verify_vault_authority_bump"), accounts=accounts)
+
+
+def build_verify_listing_backing_ix(vault_state: Pubkey, core_asset: Pubkey) -> Instruction:
+    """Simulate to check the listed asset is still held by the marketplace vault authority."""
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=core_asset, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=listing_pda(vault_state, core_asset), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=market_vault_authority_pda(vault_state), is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("verify_listing_backing"), accounts=accounts)
+
+
+def build_invalidate_unbacked_listing_ix(
+    admin: Pubkey, vault_state: Pubkey, core_asset: Pubkey, seller: Pubkey
+) -> Instruction:
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=core_asset, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=listing_pda(vault_state, core_asset), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=market_vault_authority_pda(vault_state), is_signer=False, is_writable=False),
+        _seller_index_meta(vault_state, seller),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("invalidate_unbacked_listing"), accounts=accounts)
+
+
+def build_migrate_listing_to_canonical_ix(
+    admin: Pubkey,
+    legacy_vault_state: Pubkey,
+    legacy_vault_authority: Pubkey,
+    core_asset: Pubkey,
+    seller: Pubkey,
+) -> Instruction:
+    """`legacy_vault_authority` is the legacy vault's marketplace or gacha authority holding the asset."""
+    market = market_vault_state_pda()
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=market, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=market_vault_authority_pda(market), is_signer=False, is_writable=False),
+        AccountMeta(pubkey=legacy_vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=legacy_vault_authority, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=card_record_pda(legacy_vault_state, core_asset), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=listing_pda(legacy_vault_state, core_asset), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=core_asset, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=card_record_pda(market, core_asset), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=listing_pda(market, core_asset), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=seller, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=seller_listing_index_pda(market, seller), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=sighash("migrate_listing_to_canonical"), accounts=accounts)
+
+
+def build_reassign_listing_seller_ix(
+    admin: Pubkey,
+    vault_state: Pubkey,
+    core_asset: Pubkey,
+    listing: Pubkey,
+    old_seller: Pubkey,
+    new_seller: Pubkey,
+) -> Instruction:
+    accounts: List[AccountMeta] = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=core_asset, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=listing, is_signer=False, is_writable=True),
+        _seller_index_meta(vault_state, old_seller),
+        _seller_index_meta(vault_state, new_seller),
+    ]
+    data = sighash("reassign_listing_seller") + bytes(new_seller)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def _seller_index_meta(vault_state: Pubkey, seller: Pubkey) -> AccountMeta:
+    # Always required; the program skips it when the seller has no index yet.
+    return AccountMeta(pubkey=seller_listing_index_pda(vault_state, seller), is_signer=False, is_writable=True)
+
+
+def _optional_rarity_inventory_meta(vault_state: Pubkey, tracked: bool) -> AccountMeta:
+    # Vaults that ran set_rarity_inventory must pass their RarityInventory PDA.
+    if not tracked:
+        return AccountMeta(pubkey=PROGRAM_ID, is_signer=False, is_writable=False)
+    return AccountMeta(pubkey=rarity_inventory_pda(vault_state), is_signer=False, is_writable=True)
+
+
+def _optional_instructions_sysvar_meta(gated: bool) -> AccountMeta:
+    # Vaults with an open_gate_authority read the preceding ed25519 verify from this sysvar.
+    if not gated:
+        return AccountMeta(pubkey=PROGRAM_ID, is_signer=False, is_writable=False)
+    return AccountMeta(pubkey=SYSVAR_INSTRUCTIONS_ID, is_signer=False, is_writable=False)
+
+
+def open_gate_message(user: Pubkey, vault_state: Pubkey, nonce: int, expires_at: int) -> bytes:
+    """Bytes the open gate authority signs; `nonce` is the session's current `created_at`."""
+    return (
+        bytes(user)
+        + bytes(vault_state)
+        + nonce.to_bytes(8, "little", signed=True)
+        + expires_at.to_bytes(8, "little", signed=True)
+    )
+
+
+def build_open_gate_verify_ix(gate_authority: Pubkey, signature: bytes, message: bytes) -> Instruction:
+    """Ed25519 verify that must sit immediately before a gated open in the same transaction."""
+    if len(signature) != 64:
+        raise ValueError("Ed25519 signature must be 64 bytes")
+    pubkey_offset = 16
+    signature_offset = pubkey_offset + 32
+    message_offset = signature_offset + 64
+    own_ix = 0xFFFF
+    offsets = b"".join(
+        v.to_bytes(2, "little")
+        for v in (signature_offset, own_ix, pubkey_offset, own_ix, message_offset, len(message), own_ix)
+    )
+    data = bytes([1, 0]) + offsets + bytes(gate_authority) + signature + message
+    return Instruction(program_id=ED25519_PROGRAM_ID, data=data, accounts=[])
+
+
 def build_list_card_ix(
     seller: Pubkey,
     vault_state: Pubkey,
@@ -890,11 +2213,78 @@ def build_list_card_ix(
         AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=seller_listing_index_pda(vault_state, seller), is_signer=False, is_writable=True),
     ]
     data = encode_list_card(price_lamports, currency_mint, template_id, rarity_tag)
     return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
 
 
+def build_relist_ix(
+    seller: Pubkey,
+    vault_state: Pubkey,
+    card_record: Pubkey,
+    core_asset: Pubkey,
+    listing: Pubkey,
+    vault_authority: Pubkey,
+    new_price_lamports: int,
+    new_currency_mint: Optional[str],
+) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=seller, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=card_record, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=core_asset, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=listing, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=False),
+    ]
+    data = encode_relist(new_price_lamports, new_currency_mint)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_manage_admin_ix(admin: Pubkey, vault_state: Pubkey, target: Pubkey, add: bool = True) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    data = encode_add_admin(target) if add else encode_remove_admin(target)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
+def build_set_airdrop_root_ix(admin: Pubkey, vault_state: Pubkey, root: bytes) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=airdrop_bitmap_pda(vault_state, root), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+    ]
+    return Instruction(program_id=PROGRAM_ID, data=encode_set_airdrop_root(root), accounts=accounts)
+
+
+def build_claim_airdrop_ix(
+    claimer: Pubkey,
+    vault_state: Pubkey,
+    root: bytes,
+    core_asset: Pubkey,
+    proof: List[bytes],
+    leaf_index: int,
+    template_id: int,
+    track_inventory: bool = False,
+) -> Instruction:
+    accounts = [
+        AccountMeta(pubkey=claimer, is_signer=True, is_writable=True),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=airdrop_bitmap_pda(vault_state, root), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=card_record_pda(vault_state, core_asset), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=core_asset, is_signer=False, is_writable=True),
+        AccountMeta(pubkey=vault_authority_pda(vault_state), is_signer=False, is_writable=True),
+        AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
+        AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _optional_rarity_inventory_meta(vault_state, track_inventory),
+    ]
+    data = encode_claim_airdrop(proof, leaf_index, template_id, core_asset)
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
+
+
 def build_cancel_listing_ix(
     seller: Pubkey,
     vault_state: Pubkey,
@@ -912,6 +2302,7 @@ def build_cancel_listing_ix(
         AccountMeta(pubkey=vault_authority, is_signer=False, is_writable=True),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _seller_index_meta(vault_state, seller),
     ]
     return Instruction(program_id=PROGRAM_ID, data=encode_cancel_listing(), accounts=accounts)
 
@@ -934,7 +2325,16 @@ def build_fill_listing_ix(
     listing: Pubkey,
     vault_authority: Pubkey,
     vault_treasury: Pubkey,
+    fee_recipients: Optional[List[Pubkey]] = None,
+    cosigner: Optional[Pubkey] = None,
+    royalty_recipient: Optional[Pubkey] = None,
 ) -> Instruction:
+    # The cosigner slot is optional; fills at or above the vault's threshold must sign with it.
+    cosigner_meta = (
+        AccountMeta(pubkey=PROGRAM_ID, is_signer=False, is_writable=False)
+        if cosigner is None
+        else AccountMeta(pubkey=cosigner, is_signer=True, is_writable=False)
+    )
     accounts = [
         AccountMeta(pubkey=buyer, is_signer=True, is_writable=True),
         AccountMeta(pubkey=seller, is_signer=False, is_writable=True),
@@ -946,7 +2346,15 @@ def build_fill_listing_ix(
         AccountMeta(pubkey=vault_treasury, is_signer=False, is_writable=True),
         AccountMeta(pubkey=SYS_PROGRAM_ID, is_signer=False, is_writable=False),
         AccountMeta(pubkey=MPL_CORE_PROGRAM_ID, is_signer=False, is_writable=False),
+        _seller_index_meta(vault_state, seller),
+        cosigner_meta,
+        # Required when the asset's collection is verified (see quote_fill's royalty_recipient).
+        AccountMeta(pubkey=royalty_recipient or PROGRAM_ID, is_signer=False, is_writable=royalty_recipient is not None),
     ]
+    # Vaults with a fee split expect [fee_split, recipients...] as remaining accounts.
+    if fee_recipients:
+        accounts.append(AccountMeta(pubkey=fee_split_pda(vault_state), is_signer=False, is_writable=False))
+        accounts.extend([AccountMeta(pubkey=r, is_signer=False, is_writable=True) for r in fee_recipients])
     return Instruction(program_id=PROGRAM_ID, data=encode_fill_listing(), accounts=accounts)
 
 
@@ -973,3 +2381,17 @@ def message_from_instructions(ixs: List[Instruction], payer: Pubkey, blockhash:
 def versioned_tx_b64(payer: Pubkey, blockhash: str, ixs: List[Instruction]) -> str:
     message = MessageV0.try_compile(payer, ixs, [], Hash.from_string(blockhash))
     return base64.b64encode(bytes(message)).decode()
+
+
+def build_set_open_gate_authority_ix(
+    admin: Pubkey, vault_state: Pubkey, open_gate_authority: Optional[Pubkey]
+) -> Instruction:
+    """`open_gate_authority=None` lifts the signed-open requirement."""
+    accounts = [
+        AccountMeta(pubkey=admin, is_signer=True, is_writable=False),
+        AccountMeta(pubkey=vault_state, is_signer=False, is_writable=True),
+    ]
+    data = sighash("set_open_gate_authority") + Option(U8[32]).build(
+        list(bytes(open_gate_authority)) if open_gate_authority else None
+    )
+    return Instruction(program_id=PROGRAM_ID, data=data, accounts=accounts)
@@ -24,6 +24,10 @@ async function main() {
   const program = new Program(idl as any, programId, provider as any);
 
   const [vaultState] = PublicKey.findProgramAddressSync([Buffer.from('vault_state')], programId);
+  const [vaultAuthority] = PublicKey.findProgramAddressSync(
+    [Buffer.from('vault_authority'), vaultState.toBuffer()],
+    programId,
+  );
 
   const packPriceSolLamports = new BN(120_000_000); // 0.12 SOL
   const packPriceUsdc = new BN(12_000_000); // 12 USDC (6 decimals)
@@ -33,6 +37,7 @@ async function main() {
   const usdcMint = new PublicKey('GWRsfsckjMn2vRZjUf3756AdZiNJULG6E6oTvbK6SvRu');
   const mochiMint = new PublicKey('2iL86tZQkt3MB4iVbFwNefEdTeR3Dh5QNNxDfuF16yjT');
   const rewardPerPack = new BN(100_000_000); // 100 MOCHI @ 6 decimals
+  const treasury = vaultAuthority; // SOL payments and buybacks flow through the vault authority PDA
 
   console.log('Migrating vault state', vaultState.toBase58());
 
@@ -46,6 +51,7 @@ async function main() {
       usdcMint,
       mochiMint,
       rewardPerPack,
+      treasury,
     )
     .accounts({
       admin: admin.publicKey,